@@ -0,0 +1,34 @@
+// Run with `cargo bench --bench par --features rayon`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rql_parser::parser::Parser;
+use serde_json::json;
+use std::hint::black_box;
+
+fn filter_1m(c: &mut Criterion) {
+    let query = Parser::new_from_string(
+        "and(eq(name,\"user7\"),or(gt(speed.max,100),lt(speed.min,60.0)),ne(active,false))"
+            .to_owned(),
+    )
+    .parse_query()
+    .unwrap();
+    let docs: Vec<serde_json::Value> = (0..1_000_000)
+        .map(|i| {
+            json!({
+                "name": format!("user{}", i % 10),
+                "speed": {"max": i % 200, "min": (i % 100) as f64},
+                "active": i % 3 != 0,
+            })
+        })
+        .collect();
+    let mut group = c.benchmark_group("filter 1M docs");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| {
+        b.iter(|| docs.iter().filter(|d| query.matches(black_box(d))).count())
+    });
+    group.bench_function("parallel", |b| b.iter(|| query.par_matches(black_box(&docs))));
+    group.finish();
+}
+
+criterion_group!(benches, filter_1m);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rql_parser::lexer::Lexer;
+use rql_parser::token::Token;
+
+fuzz_target!(|data: &[u8]| {
+    let input = String::from_utf8_lossy(data).into_owned();
+    let len = input.len();
+    let mut lexer = Lexer::new(input);
+    // Every token consumes at least one char, so Eof must come within len + 1 tokens.
+    for _ in 0..=len {
+        let (token, span) = lexer.next_token_with_span();
+        assert!(span.start <= span.end && span.end <= len);
+        if token == Token::Eof {
+            return;
+        }
+    }
+    panic!("lexer did not reach Eof");
+});
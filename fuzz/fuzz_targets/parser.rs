@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rql_parser::parser::Parser;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(input) = std::str::from_utf8(data) {
+        // Must return Ok or Err, never panic.
+        let _ = Parser::new_from_string(input.to_owned()).parse_query();
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rql_parser::ast::Query;
+use rql_parser::parser::{Parser, ParserOptions};
+
+fuzz_target!(|query: Query| {
+    let rql = query.to_rql();
+    let options = ParserOptions {
+        variables: true,
+        ..ParserOptions::default()
+    };
+    let parsed = Parser::new_from_string(rql.clone())
+        .with_options(options)
+        .parse_query();
+    match parsed {
+        Ok(parsed) => assert_eq!(parsed, query, "{}", rql),
+        Err(e) => panic!("{} failed: {:?}", rql, e),
+    }
+});
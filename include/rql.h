@@ -0,0 +1,36 @@
+/* C interface of rql-parser. Build the crate with `--features ffi`. */
+
+#ifndef RQL_H
+#define RQL_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque handle to a parsed query. */
+typedef struct RqlQuery RqlQuery;
+
+/* Parses a NUL-terminated RQL string. Returns NULL on failure. */
+RqlQuery *rql_parse(const char *input);
+
+/* Canonical RQL form of the query. Release with rql_string_free. */
+char *rql_to_string(const RqlQuery *query);
+
+/* 1 when the JSON document matches, 0 when it doesn't, -1 on error. */
+int rql_matches_json(const RqlQuery *query, const char *json);
+
+/* Error message of the last failed call on this thread, or NULL.
+ * Valid until the next rql_* call on the same thread. */
+const char *rql_last_error_message(void);
+
+/* Releases a query returned by rql_parse. NULL is ignored. */
+void rql_free(RqlQuery *query);
+
+/* Releases a string returned by rql_to_string. NULL is ignored. */
+void rql_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RQL_H */
@@ -0,0 +1,97 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, GenericParam, LitStr};
+
+pub(crate) fn derive(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unit => return Ok(implement(&input, &[])),
+            Fields::Unnamed(_) => return Err(unsupported(&input)),
+        },
+        _ => return Err(unsupported(&input)),
+    };
+    let mut named = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+        let mut name = ident.to_string();
+        // r#type のようなフィールドは type として読む
+        if let Some(raw) = name.strip_prefix("r#") {
+            name = raw.to_owned();
+        }
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("filterable"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `rename = \"...\"`"))
+                }
+            })?;
+        }
+        // 型のスパンにすれば、Filterable でない型のエラーがフィールドを指す
+        let visit = quote_spanned! {field.ty.span()=>
+            ::rql_parser::eval::Filterable::visit_field(&self.#ident, rest, found)
+        };
+        named.push((name, visit));
+    }
+    Ok(implement(&input, &named))
+}
+
+fn implement(input: &DeriveInput, fields: &[(String, TokenStream2)]) -> TokenStream2 {
+    let ident = &input.ident;
+    let mut generics = input.generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param
+                .bounds
+                .push(syn::parse_quote!(::rql_parser::eval::Filterable));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let names = fields.iter().map(|(name, _)| name);
+    let visits = fields.iter().map(|(_, visit)| visit);
+    let all = fields.iter().map(|(_, visit)| visit);
+    quote! {
+        impl #impl_generics ::rql_parser::eval::Filterable for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn visit_field<'a>(
+                &'a self,
+                segments: &[::rql_parser::ast::Segment],
+                found: &mut ::std::vec::Vec<::rql_parser::eval::DocValue<'a>>,
+            ) {
+                match segments.split_first() {
+                    ::core::option::Option::None => {
+                        found.push(::rql_parser::eval::DocValue::Object)
+                    }
+                    ::core::option::Option::Some((
+                        ::rql_parser::ast::Segment::Key(key),
+                        rest,
+                    )) => match key.as_str() {
+                        #(#names => #visits,)*
+                        _ => {}
+                    },
+                    ::core::option::Option::Some((
+                        ::rql_parser::ast::Segment::Wildcard,
+                        rest,
+                    )) => {
+                        #(#all;)*
+                    }
+                    ::core::option::Option::Some(_) => {}
+                }
+            }
+        }
+    }
+}
+
+fn unsupported(input: &DeriveInput) -> syn::Error {
+    syn::Error::new(
+        input.ident.span(),
+        "Filterable can only be derived for structs with named fields",
+    )
+}
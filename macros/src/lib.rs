@@ -0,0 +1,380 @@
+//! `rql!`: RQL queries written in Rust code, parsed and checked at compile
+//! time.
+//!
+//! ```
+//! use rql_parser::ast::Query;
+//! use rql_parser_macros::rql;
+//!
+//! let open: Query = rql!("and(eq(status,\"open\"),gt(age,18))");
+//!
+//! let user_id = 42;
+//! let mine: Query = rql!("and(eq(owner,{}),sort(-created))", user_id);
+//! ```
+//!
+//! The macro expands to the [`Query`] itself, so nothing is parsed at run
+//! time. A syntax error is a compile error with the position in the query.
+//! The parser is strict: an unquoted identifier in value position
+//! (`eq(status,open)`) is an error rather than a warning.
+//!
+//! With arguments after the query, each `{}` stands for a filter value and
+//! takes the next argument, converted with `Into<rql_parser::ast::Value>`.
+//! Strings always become string literals, never paths or RQL syntax. Without
+//! arguments, `{` and `}` have no special meaning.
+//!
+//! `#[derive(Filterable)]` lets queries read a struct's fields directly,
+//! without serializing it to JSON:
+//!
+//! ```
+//! use rql_parser_macros::{rql, Filterable};
+//!
+//! #[derive(Filterable)]
+//! struct Address {
+//!     city: String,
+//! }
+//!
+//! #[derive(Filterable)]
+//! struct User {
+//!     #[filterable(rename = "userName")]
+//!     name: String,
+//!     address: Address,
+//!     nickname: Option<String>,
+//!     tags: Vec<String>,
+//! }
+//!
+//! let user = User {
+//!     name: "alice".to_owned(),
+//!     address: Address { city: "Tokyo".to_owned() },
+//!     nickname: None,
+//!     tags: vec!["admin".to_owned()],
+//! };
+//! assert!(rql!("and(eq(userName,\"alice\"),eq(address.city,\"Tokyo\"))").matches_struct(&user));
+//! assert!(rql!("eq(tags.*,\"admin\")").matches_struct(&user));
+//! assert!(rql!("missing(nickname)").matches_struct(&user));
+//! ```
+//!
+//! See `rql_parser::eval::Filterable` for the field types it reads.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::quote;
+use rql_parser_core::ast::{Path, Query, Value};
+use rql_parser_core::parser::{Parser, ParserOptions};
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, LitStr, Token};
+
+mod filterable;
+
+// {} の代わりに入れる変数。{} と同じ2文字なので、エラー位置がずれない
+const PLACEHOLDER: &str = "$_";
+
+struct Input {
+    query: LitStr,
+    args: Vec<Expr>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let query = input.parse()?;
+        let mut args = vec![];
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            args.push(input.parse()?);
+        }
+        Ok(Input { query, args })
+    }
+}
+
+/// Parses an RQL query at compile time and expands to the
+/// `rql_parser::ast::Query` it stands for. See the [crate](crate) docs.
+#[proc_macro]
+pub fn rql(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as Input);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `rql_parser::eval::Filterable` for a struct with named fields.
+/// `#[filterable(rename = "...")]` on a field sets the name queries use for
+/// it. See the [crate](crate) docs.
+#[proc_macro_derive(Filterable, attributes(filterable))]
+pub fn derive_filterable(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    filterable::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: Input) -> syn::Result<TokenStream2> {
+    let span = input.query.span();
+    let error = |message: String| syn::Error::new(span, message);
+    let mut text = input.query.value();
+    let templated = !input.args.is_empty();
+    if templated {
+        if text.contains(PLACEHOLDER) {
+            return Err(error(format!(
+                "{} is reserved in rql! queries with arguments",
+                PLACEHOLDER
+            )));
+        }
+        let placeholders = text.matches("{}").count();
+        if placeholders != input.args.len() {
+            return Err(error(format!(
+                "the query has {} {{}} placeholders but {} arguments were given",
+                placeholders,
+                input.args.len()
+            )));
+        }
+        text = text.replace("{}", PLACEHOLDER);
+    }
+    // {} は変数 $_ として読むので、$name は常に変数
+    #[allow(clippy::needless_update)]
+    let options = ParserOptions {
+        strict: true,
+        variables: true,
+        ..ParserOptions::default()
+    };
+    let query = Parser::new_from_string(text)
+        .with_options(options)
+        .parse_query()
+        .map_err(|e| {
+            let message = e.to_string();
+            let message = match templated {
+                true => message.replace(PLACEHOLDER, "{}"),
+                false => message,
+            };
+            error(format!("invalid RQL: {}", message))
+        })?;
+    let mut expander = Expander {
+        args: input.args.into_iter(),
+        span,
+    };
+    let tokens = expander.query(&query)?;
+    // 値以外の位置の {} (eq(a,x{}) など) はプレースホルダーとして読まれていない
+    if expander.args.len() > 0 {
+        return Err(error(
+            "each {} must stand for a whole filter value".to_owned(),
+        ));
+    }
+    Ok(tokens)
+}
+
+struct Expander {
+    args: std::vec::IntoIter<Expr>,
+    span: Span,
+}
+
+impl Expander {
+    fn query(&mut self, query: &Query) -> syn::Result<TokenStream2> {
+        let tokens = match query {
+            Query::And(queries) | Query::Or(queries) => {
+                let variant = match query {
+                    Query::And(_) => quote!(And),
+                    _ => quote!(Or),
+                };
+                let queries = queries
+                    .iter()
+                    .map(|q| self.query(q))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                quote!(::rql_parser::ast::Query::#variant(::std::vec![#(#queries),*]))
+            }
+            Query::Not(q) => {
+                let q = self.query(q)?;
+                quote!(::rql_parser::ast::Query::Not(::std::boxed::Box::new(#q)))
+            }
+            Query::In(p, values) | Query::Out(p, values) => {
+                let variant = match query {
+                    Query::In(..) => quote!(In),
+                    _ => quote!(Out),
+                };
+                let p = path(p);
+                let values = values
+                    .iter()
+                    .map(|v| self.value(v))
+                    .collect::<syn::Result<Vec<_>>>()?;
+                quote!(::rql_parser::ast::Query::#variant(#p, ::std::vec![#(#values),*]))
+            }
+            Query::Contains(p, value) => {
+                let p = path(p);
+                let value = self.value(value)?;
+                quote!(::rql_parser::ast::Query::Contains(#p, #value))
+            }
+            Query::Sort(keys) => {
+                let keys = keys
+                    .iter()
+                    .map(|(prefix, value)| {
+                        let prefix = variant(prefix);
+                        let value = self.plain_value(value)?;
+                        Ok(quote!((::rql_parser::ast::Prefix::#prefix, #value)))
+                    })
+                    .collect::<syn::Result<Vec<_>>>()?;
+                quote!(::rql_parser::ast::Query::Sort(::std::vec![#(#keys),*]))
+            }
+            Query::Limit(count, offset) => {
+                quote!(::rql_parser::ast::Query::Limit(#count, #offset))
+            }
+            Query::Select(paths) => {
+                let paths = paths.iter().map(path);
+                quote!(::rql_parser::ast::Query::Select(::std::vec![#(#paths),*]))
+            }
+            Query::Distinct(None) => quote!(::rql_parser::ast::Query::Distinct(None)),
+            Query::Distinct(Some(p)) => {
+                let p = path(p);
+                quote!(::rql_parser::ast::Query::Distinct(Some(#p)))
+            }
+            Query::Aggregate(keys, functions) => {
+                let keys = keys.iter().map(path);
+                let functions = functions.iter().map(|(function, p)| {
+                    let function = variant(function);
+                    let p = path(p);
+                    quote!((::rql_parser::ast::Aggregation::#function, #p))
+                });
+                quote!(::rql_parser::ast::Query::Aggregate(
+                    ::std::vec![#(#keys),*],
+                    ::std::vec![#(#functions),*]
+                ))
+            }
+            Query::First => quote!(::rql_parser::ast::Query::First),
+            Query::One => quote!(::rql_parser::ast::Query::One),
+            Query::Count => quote!(::rql_parser::ast::Query::Count),
+            Query::Reduce(function, p) => {
+                let function = variant(function);
+                let p = path(p);
+                quote!(::rql_parser::ast::Query::Reduce(
+                    ::rql_parser::ast::Aggregation::#function,
+                    #p
+                ))
+            }
+            Query::Filter(infix, ident, value) => {
+                let infix = variant(infix);
+                let ident = self.plain_value(ident)?;
+                let value = self.value(value)?;
+                quote!(::rql_parser::ast::Query::Filter(
+                    ::rql_parser::ast::Infix::#infix,
+                    #ident,
+                    #value
+                ))
+            }
+            Query::Exists(p) => {
+                let p = path(p);
+                quote!(::rql_parser::ast::Query::Exists(#p))
+            }
+            Query::Missing(p) => {
+                let p = path(p);
+                quote!(::rql_parser::ast::Query::Missing(#p))
+            }
+            Query::Search(fields, term) => {
+                let fields = fields.iter().map(path);
+                let term = string(term);
+                quote!(::rql_parser::ast::Query::Search(
+                    ::std::vec![#(#fields),*],
+                    #term
+                ))
+            }
+            Query::Near(p, lat, lon, radius) => {
+                let p = path(p);
+                quote!(::rql_parser::ast::Query::Near(#p, #lat, #lon, #radius))
+            }
+            Query::None => quote!(::rql_parser::ast::Query::None),
+        };
+        Ok(tokens)
+    }
+
+    // フィルターの値。{} は次の引数になる
+    fn value(&mut self, value: &Value) -> syn::Result<TokenStream2> {
+        match value {
+            Value::Variable(name) if format!("${}", name) == PLACEHOLDER => {
+                match self.args.next() {
+                    Some(arg) => Ok(quote!(::core::convert::Into::<
+                        ::rql_parser::ast::Value,
+                    >::into(#arg))),
+                    None => Err(syn::Error::new(self.span, "missing argument for {}")),
+                }
+            }
+            _ => self.plain_value(value),
+        }
+    }
+
+    fn plain_value(&self, value: &Value) -> syn::Result<TokenStream2> {
+        let value = match value {
+            Value::Identifier(p) => {
+                let p = path(p);
+                quote!(Identifier(#p))
+            }
+            Value::IntegerLiteral(i) => quote!(IntegerLiteral(#i)),
+            Value::UnsignedLiteral(u) => quote!(UnsignedLiteral(#u)),
+            // 桁の多いリテラルは inf になり、リテラルとしては書けない
+            Value::FloatLiteral(f) if f.is_infinite() && *f > 0.0 => {
+                quote!(FloatLiteral(::core::f64::INFINITY))
+            }
+            Value::FloatLiteral(f) if f.is_infinite() => {
+                quote!(FloatLiteral(::core::f64::NEG_INFINITY))
+            }
+            Value::FloatLiteral(f) => quote!(FloatLiteral(#f)),
+            Value::StringLiteral(s) => {
+                let s = string(s);
+                quote!(StringLiteral(#s))
+            }
+            Value::Boolean(b) => quote!(Boolean(#b)),
+            Value::Call(name) => {
+                let name = string(name);
+                quote!(Call(#name))
+            }
+            Value::Variable(name) if format!("${}", name) == PLACEHOLDER => {
+                return Err(syn::Error::new(
+                    self.span,
+                    "{} can only stand for a filter value",
+                ))
+            }
+            Value::Variable(name) => {
+                let name = string(name);
+                quote!(Variable(#name))
+            }
+            // rql-parser の decimal はマクロとは別に有効になるので、Decimal にするか
+            // f64 にするかは展開先でパーサと同じように決める
+            Value::DecimalLiteral(d) => {
+                let d = d.to_string();
+                quote!(__float_literal(#d))
+            }
+            #[cfg(feature = "chrono")]
+            Value::DateTime(datetime) => {
+                let datetime = datetime.to_rfc3339();
+                quote!(DateTime(::core::str::FromStr::from_str(#datetime).unwrap()))
+            }
+            #[cfg(feature = "chrono")]
+            Value::RelativeTime(amount, unit) => {
+                let unit = variant(unit);
+                quote!(RelativeTime(#amount, ::rql_parser::datetime::TimeUnit::#unit))
+            }
+            // rql-parser だけでフィーチャが有効になっている場合
+            #[allow(unreachable_patterns)]
+            _ => {
+                return Err(syn::Error::new(
+                    self.span,
+                    format!(
+                        "rql! can't expand the literal {}; enable the same feature on rql-parser-macros or pass it with {{}}",
+                        value
+                    ),
+                ))
+            }
+        };
+        Ok(quote!(::rql_parser::ast::Value::#value))
+    }
+}
+
+// Infix::Eq などのバリアント名。Debug 出力がそのまま名前になる
+fn variant(v: impl std::fmt::Debug) -> Ident {
+    Ident::new(&format!("{:?}", v), Span::call_site())
+}
+
+fn string(s: &str) -> TokenStream2 {
+    quote!(::std::string::String::from(#s))
+}
+
+fn path(p: &Path) -> TokenStream2 {
+    let p = p.to_string();
+    quote!(::rql_parser::ast::Path::from(#p))
+}
@@ -0,0 +1,182 @@
+use rql_parser::ast::Path;
+use rql_parser::eval::{DocValue, EvalOptions, Filterable};
+use rql_parser::parser::Parser;
+use rql_parser_macros::Filterable;
+use std::borrow::Cow;
+
+#[derive(Filterable)]
+struct Address {
+    city: String,
+    #[filterable(rename = "zip")]
+    postal_code: Option<String>,
+}
+
+#[derive(Filterable)]
+struct Item {
+    sku: &'static str,
+    quantity: u32,
+}
+
+#[derive(Filterable)]
+struct Order {
+    id: u64,
+    #[filterable(rename = "customerName")]
+    customer_name: String,
+    total: f64,
+    paid: bool,
+    note: Option<String>,
+    address: Address,
+    billing: Option<Box<Address>>,
+    tags: Vec<String>,
+    items: Vec<Item>,
+    r#type: char,
+}
+
+fn order() -> Order {
+    Order {
+        id: u64::MAX,
+        customer_name: "alice".to_owned(),
+        total: 120.5,
+        paid: true,
+        note: None,
+        address: Address {
+            city: "Tokyo".to_owned(),
+            postal_code: Some("100-0001".to_owned()),
+        },
+        billing: None,
+        tags: vec!["gift".to_owned(), "express".to_owned()],
+        items: vec![
+            Item {
+                sku: "A-1",
+                quantity: 2,
+            },
+            Item {
+                sku: "B-7",
+                quantity: 10,
+            },
+        ],
+        r#type: 'x',
+    }
+}
+
+fn matches(input: &str) -> bool {
+    Parser::from_str(input)
+        .parse_query()
+        .unwrap()
+        .matches_struct(&order())
+}
+
+#[test]
+fn fields() {
+    assert!(matches(
+        "and(eq(customerName,\"alice\"),gt(total,100.0),eq(paid,true))"
+    ));
+    assert!(matches("eq(id,18446744073709551615)"));
+    assert!(matches("eq(type,\"x\")"));
+    // 名前を変えたフィールドは元の名前では読めない
+    assert!(!matches("eq(customer_name,\"alice\")"));
+    assert!(matches("missing(customer_name)"));
+    assert!(matches("eq(address.city,\"Tokyo\")"));
+    assert!(matches("startswith(address.zip,\"100\")"));
+    assert!(matches("missing(address.postal_code)"));
+}
+
+#[test]
+fn optional() {
+    assert!(matches("missing(note)"));
+    assert!(!matches("exists(note)"));
+    assert!(matches("missing(billing.city)"));
+    assert!(matches("exists(address)"));
+    let mut order = order();
+    order.note = Some("leave at the door".to_owned());
+    order.billing = Some(Box::new(Address {
+        city: "Osaka".to_owned(),
+        postal_code: None,
+    }));
+    let query =
+        Parser::from_str("and(exists(note),eq(billing.city,\"Osaka\"),missing(billing.zip))")
+            .parse_query()
+            .unwrap();
+    assert!(query.matches_struct(&order));
+}
+
+#[test]
+fn vecs() {
+    assert!(matches("eq(tags.*,\"express\")"));
+    assert!(!matches("eq(tags.*,\"fragile\")"));
+    assert!(matches("eq(tags.0,\"gift\")"));
+    assert!(matches("missing(tags.2)"));
+    assert!(matches(
+        "and(eq(items.*.sku,\"B-7\"),gt(items.*.quantity,5))"
+    ));
+    assert!(matches("eq(items.1.quantity,10)"));
+    assert!(matches("search((tags.*),\"expr\")"));
+
+    let order = order();
+    let get = |path: &str| order.get_field(&Path::from(path));
+    assert_eq!(
+        get("tags"),
+        Some(DocValue::Array(vec![
+            DocValue::String(Cow::Borrowed("gift")),
+            DocValue::String(Cow::Borrowed("express")),
+        ]))
+    );
+    assert_eq!(
+        get("items.*.quantity"),
+        Some(DocValue::Array(vec![
+            DocValue::Integer(2),
+            DocValue::Integer(10)
+        ]))
+    );
+    assert_eq!(get("items.0"), Some(DocValue::Object));
+    assert_eq!(get("items.sku"), None);
+}
+
+// JSON にしたものと同じ結果になる
+#[test]
+fn same_as_json() {
+    let json = serde_json::json!({
+        "id": u64::MAX,
+        "customerName": "alice",
+        "total": 120.5,
+        "paid": true,
+        "address": {"city": "Tokyo", "zip": "100-0001"},
+        "tags": ["gift", "express"],
+        "items": [{"sku": "A-1", "quantity": 2}, {"sku": "B-7", "quantity": 10}],
+        "type": "x",
+    });
+    let options = EvalOptions {
+        null_is_present: true,
+        ..EvalOptions::default()
+    };
+    for input in [
+        "or(lt(total,100),eq(address.*,\"Tokyo\"))",
+        "and(ne(paid,false),le(items.*.quantity,2))",
+        "eq(items.*.sku,address.city)",
+        "and(exists(note),exists(billing))",
+        "eq(tags,\"gift\")",
+    ] {
+        let query = Parser::from_str(input).parse_query().unwrap();
+        assert_eq!(
+            query.matches_struct_with(&order(), &options),
+            query.matches_with(&json, &options),
+            "{}",
+            input
+        );
+    }
+}
+
+#[derive(Filterable)]
+struct Wrapper<T> {
+    value: T,
+}
+
+#[test]
+fn generic() {
+    let query = Parser::from_str("eq(value.city,\"Tokyo\")")
+        .parse_query()
+        .unwrap();
+    assert!(query.matches_struct(&Wrapper {
+        value: order().address
+    }));
+}
@@ -0,0 +1,65 @@
+use rql_parser::ast::{Infix, Query, Value};
+use rql_parser::parser::{Parser, ParserOptions};
+use rql_parser_macros::rql;
+
+// rql! と同じく $name を変数として読む
+fn parse(input: &str) -> Query {
+    Parser::new_from_string(input.to_owned())
+        .with_options(ParserOptions {
+            variables: true,
+            ..ParserOptions::default()
+        })
+        .parse_query()
+        .unwrap()
+}
+
+#[test]
+fn same_as_parser() {
+    macro_rules! check {
+        ($($query:literal),* $(,)?) => {
+            $(assert_eq!(rql!($query), parse($query), "{}", $query);)*
+        };
+    }
+    check!(
+        "and(eq(status,\"open\"),gt(age,18))",
+        "or(eq(a,1),le(b,2.5),ge(c,0.125),ne(d,true),eq(e,18446744073709551615))",
+        "and(startswith(name,\"a\\\"b\"),endswith(`odd.key`.x,\"z\"))",
+        "and(sort(+a,-b.c),limit(10,20),select(a,b.c),distinct(a))",
+        "and(distinct(),first(),one(),count())",
+        "and(aggregate(city,sum(price),count(id)),mean(price))",
+        "and(exists(tags.*),missing(deleted_at),eq(created,now()),eq(owner,$me))",
+    );
+}
+
+#[test]
+fn arguments() {
+    let owner = String::from("alice \"ally\"");
+    let age = 18;
+    assert_eq!(
+        rql!("and(eq(owner,{}),gt(age,{}),eq(active,{}))", &owner, age, true),
+        Query::And(vec![
+            Query::Filter(
+                Infix::Eq,
+                Value::Identifier("owner".into()),
+                Value::StringLiteral(owner.clone()),
+            ),
+            Query::Filter(
+                Infix::Gt,
+                Value::Identifier("age".into()),
+                Value::IntegerLiteral(18),
+            ),
+            Query::Filter(
+                Infix::Eq,
+                Value::Identifier("active".into()),
+                Value::Boolean(true),
+            ),
+        ])
+    );
+    // 文字列はそのままリテラルになり、RQL として読まれない
+    let injected = "x\"),eq(admin,true";
+    assert_eq!(
+        rql!("eq(name,{})", injected).to_rql(),
+        r#"eq(name,"x\"),eq(admin,true")"#
+    );
+    assert_eq!(rql!("eq(n,{})", u64::MAX), parse("eq(n,18446744073709551615)"));
+}
@@ -0,0 +1,8 @@
+use rql_parser_macros::rql;
+
+fn main() {
+    let id = 1;
+    let _ = rql!("eq(owner,{})", id, id);
+    let _ = rql!("eq({},1)", id);
+    let _ = rql!("sort(+{})", id);
+}
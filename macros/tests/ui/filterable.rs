@@ -0,0 +1,22 @@
+use rql_parser_macros::Filterable;
+use std::collections::HashMap;
+
+#[derive(Filterable)]
+struct Settings {
+    name: String,
+    limits: HashMap<String, u32>,
+}
+
+#[derive(Filterable)]
+struct Renamed {
+    #[filterable(alias = "n")]
+    name: String,
+}
+
+#[derive(Filterable)]
+enum Status {
+    Open,
+    Closed,
+}
+
+fn main() {}
@@ -0,0 +1,5 @@
+use rql_parser_macros::rql;
+
+fn main() {
+    let _ = rql!("eq(status,open)");
+}
@@ -0,0 +1,252 @@
+//! `arbitrary::Arbitrary` implementations for structured fuzzing.
+//!
+//! Generated queries are always printable with `Query::to_rql` and parse back
+//! to the same tree with `ParserOptions::variables`: `None` isn't generated, shaping nodes such as `sort()`
+//! never sit inside `or()` or `not()`, floats are finite, strings contain no
+//! `"`, and identifiers are never keywords.
+
+use crate::ast::{Aggregation, Infix, Path, Prefix, Query, Value};
+use crate::token::{lookup_ident, Token};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+// 木が大きくなりすぎないように深さと子の数を制限する
+const MAX_DEPTH: usize = 4;
+const MAX_CHILDREN: usize = 4;
+const MAX_IDENT_LEN: usize = 8;
+const MAX_VALUES: usize = 3;
+
+impl<'a> Arbitrary<'a> for Query {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_query(u, MAX_DEPTH, true)
+    }
+}
+
+// shaping が false なら or() や not() の中なので、sort() などは作らない
+fn arbitrary_query(u: &mut Unstructured, depth: usize, shaping: bool) -> Result<Query> {
+    let kind = if depth == 0 {
+        u.int_in_range(3..=24)?
+    } else {
+        u.int_in_range(0..=24)?
+    };
+    Ok(match kind {
+        0 | 1 => {
+            let len = u.int_in_range(0..=MAX_CHILDREN)?;
+            let mut queries = Vec::with_capacity(len);
+            for _ in 0..len {
+                queries.push(arbitrary_query(u, depth - 1, shaping && kind == 0)?);
+            }
+            if kind == 0 {
+                Query::And(queries)
+            } else {
+                Query::Or(queries)
+            }
+        }
+        2 => Query::Not(Box::new(arbitrary_query(u, depth - 1, false)?)),
+        3 => Query::In(arbitrary_path(u)?, arbitrary_values(u)?),
+        4 => Query::Out(arbitrary_path(u)?, arbitrary_values(u)?),
+        5 => Query::Contains(arbitrary_path(u)?, u.arbitrary()?),
+        6 => Query::Exists(arbitrary_path(u)?),
+        7 => Query::Missing(arbitrary_path(u)?),
+        8 => {
+            let len = u.int_in_range(0..=2)?;
+            let fields = (0..len).map(|_| arbitrary_path(u)).collect::<Result<_>>()?;
+            Query::Search(fields, arbitrary_string(u)?)
+        }
+        9 => Query::Near(
+            arbitrary_path(u)?,
+            u.int_in_range(-90_000_000..=90_000_000)? as f64 / 1e6,
+            u.int_in_range(-180_000_000..=180_000_000)? as f64 / 1e6,
+            u.arbitrary::<u32>()? as f64 / 10.0,
+        ),
+        10 if shaping => {
+            let len = u.int_in_range(1..=3)?;
+            let keys = (0..len)
+                .map(|_| Ok((u.arbitrary()?, Value::Identifier(arbitrary_path(u)?))))
+                .collect::<Result<_>>()?;
+            Query::Sort(keys)
+        }
+        11 if shaping => Query::Limit(
+            u.arbitrary::<u32>()? as usize,
+            u.arbitrary::<u32>()? as usize,
+        ),
+        12 if shaping => {
+            let len = u.int_in_range(1..=3)?;
+            Query::Select((0..len).map(|_| arbitrary_path(u)).collect::<Result<_>>()?)
+        }
+        13 if shaping => Query::Distinct(match u.arbitrary()? {
+            true => Some(arbitrary_path(u)?),
+            false => None,
+        }),
+        14 if shaping => {
+            // 引数が1つも無い aggregate() は書けない
+            let keys: Vec<Path> = (0..u.int_in_range(0..=2)?)
+                .map(|_| arbitrary_path(u))
+                .collect::<Result<_>>()?;
+            let min = if keys.is_empty() { 1 } else { 0 };
+            let functions = (0..u.int_in_range(min..=2)?)
+                .map(|_| Ok((u.arbitrary()?, arbitrary_path(u)?)))
+                .collect::<Result<_>>()?;
+            Query::Aggregate(keys, functions)
+        }
+        15 if shaping => Query::First,
+        16 if shaping => Query::One,
+        17 if shaping => Query::Count,
+        18 if shaping => {
+            // count(path) は無いので、単独の集計は sum, min, max, mean だけ
+            let function = *u.choose(&[
+                Aggregation::Sum,
+                Aggregation::Min,
+                Aggregation::Max,
+                Aggregation::Mean,
+            ])?;
+            Query::Reduce(function, arbitrary_path(u)?)
+        }
+        _ => {
+            let infix: Infix = u.arbitrary()?;
+            // startswith や大文字小文字を区別しないものは文字列しか取らない
+            let value = if infix == Infix::StartsWith || infix == Infix::EndsWith || infix.ignores_case() {
+                Value::StringLiteral(arbitrary_string(u)?)
+            } else {
+                u.arbitrary()?
+            };
+            Query::Filter(infix, Value::Identifier(arbitrary_path(u)?), value)
+        }
+    })
+}
+
+fn arbitrary_values(u: &mut Unstructured) -> Result<Vec<Value>> {
+    let len = u.int_in_range(1..=MAX_VALUES)?;
+    (0..len).map(|_| u.arbitrary()).collect()
+}
+
+fn arbitrary_path(u: &mut Unstructured) -> Result<Path> {
+    Ok(arbitrary_identifier(u)?.into())
+}
+
+fn arbitrary_string(u: &mut Unstructured) -> Result<String> {
+    let s: String = u.arbitrary()?;
+    Ok(s.replace('"', ""))
+}
+
+fn arbitrary_identifier(u: &mut Unstructured) -> Result<String> {
+    const FIRST: &[char] = &['a', 'b', 'x', 'y', '_', '.', '$', 'é', '漢'];
+    const REST: &[char] = &['a', 'b', 'x', 'y', '_', '.', '$', 'é', '漢', '0', '1', '9'];
+    let mut ident = u.choose(FIRST)?.to_string();
+    for _ in 0..u.int_in_range(0..=MAX_IDENT_LEN)? {
+        ident.push(*u.choose(REST)?);
+    }
+    if let Token::Ident(_) = lookup_ident(&ident) {
+        Ok(ident)
+    } else {
+        Ok(ident + "_")
+    }
+}
+
+// 変数名と関数名。ドットや $ を含まず、キーワードにもならない
+fn arbitrary_name(u: &mut Unstructured) -> Result<String> {
+    const FIRST: &[char] = &['a', 'b', 'x', 'y', '_', 'é'];
+    const REST: &[char] = &['a', 'b', 'x', 'y', '_', 'é', '0', '1', '9'];
+    let mut name = u.choose(FIRST)?.to_string();
+    for _ in 0..u.int_in_range(0..=MAX_IDENT_LEN)? {
+        name.push(*u.choose(REST)?);
+    }
+    if let Token::Ident(_) = lookup_ident(&name) {
+        Ok(name)
+    } else {
+        Ok(name + "_")
+    }
+}
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=8)? {
+            // 右辺の識別子は別のフィールドとの比較
+            0 => Value::Identifier(arbitrary_path(u)?),
+            1 => match (u.arbitrary::<u64>()?, u.arbitrary::<bool>()?) {
+                (n, _) if n > i64::MAX as u64 => Value::UnsignedLiteral(n),
+                (n, true) => Value::IntegerLiteral(-(n as i64)),
+                (n, false) => Value::IntegerLiteral(n as i64),
+            },
+            2 => {
+                let f: f64 = u.arbitrary()?;
+                let value = Value::FloatLiteral(if f.is_finite() { f } else { 0.0 });
+                // パーサと同じく、Decimal で表せるものは Decimal にする
+                #[cfg(feature = "decimal")]
+                if let Some(d) = crate::decimal::parse(&value.to_string()) {
+                    return Ok(Value::DecimalLiteral(d));
+                }
+                value
+            }
+            3 => Value::StringLiteral(arbitrary_string(u)?),
+            4 => Value::Call(arbitrary_name(u)?),
+            5 => Value::Variable(arbitrary_name(u)?),
+            #[cfg(feature = "chrono")]
+            6 => {
+                use chrono::{DateTime, FixedOffset};
+                let seconds = u.int_in_range(0..=4_102_444_800)?;
+                let offset = FixedOffset::east_opt(u.int_in_range(-12..=14)? * 3600).unwrap();
+                Value::DateTime(DateTime::from_timestamp(seconds, 0).unwrap().with_timezone(&offset))
+            }
+            #[cfg(feature = "chrono")]
+            7 => {
+                use crate::datetime::TimeUnit;
+                // now+0d は now として書かれ、秒の 0 として読まれる
+                match u.int_in_range(-1000..=1000)? {
+                    0 => Value::RelativeTime(0, TimeUnit::Second),
+                    amount => Value::RelativeTime(
+                        amount,
+                        *u.choose(&[
+                            TimeUnit::Second,
+                            TimeUnit::Minute,
+                            TimeUnit::Hour,
+                            TimeUnit::Day,
+                            TimeUnit::Week,
+                        ])?,
+                    ),
+                }
+            }
+            _ => Value::Boolean(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Infix {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(u.choose(&[
+            Infix::Eq,
+            Infix::NotEq,
+            Infix::Le,
+            Infix::Ge,
+            Infix::Lt,
+            Infix::Gt,
+            Infix::StartsWith,
+            Infix::EndsWith,
+            Infix::IEq,
+            Infix::IContains,
+            Infix::IStartsWith,
+        ])?
+        .clone())
+    }
+}
+
+impl<'a> Arbitrary<'a> for Prefix {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? {
+            Prefix::Plus
+        } else {
+            Prefix::Minus
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Aggregation {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(*u.choose(&[
+            Aggregation::Count,
+            Aggregation::Sum,
+            Aggregation::Min,
+            Aggregation::Max,
+            Aggregation::Mean,
+        ])?)
+    }
+}
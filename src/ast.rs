@@ -1,40 +1,155 @@
-use log::debug;
+use crate::eval::DocValue;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt;
 
-#[derive(Debug, PartialEq, Clone)]
+mod constraints;
+mod describe;
+mod diff;
+mod dot;
+mod equivalent;
+mod filters;
+mod fingerprint;
+mod from_map;
+mod implies;
+mod path;
+mod pretty;
+mod wire;
+
+pub use constraints::Constraint;
+pub use diff::DiffEntry;
+pub use filters::Filters;
+pub use from_map::FromValueError;
+pub use implies::Implication;
+pub(crate) use implies::number;
+pub(crate) use path::JSONPATH_SUPPORTED;
+pub use path::{Path, Segment};
+pub use pretty::PrettyOptions;
+pub use wire::{WireError, WIRE_VERSION};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Query {
     And(Vec<Query>),
     Or(Vec<Query>),
-    Sort(Prefix, Value),
+    // not(条件)。条件に一致しないドキュメントに一致する
+    Not(Box<Query>),
+    // sort(+a,-b) のキー。左から順に優先される
+    Sort(Vec<(Prefix, Value)>),
+    // limit(件数, 開始位置)
+    Limit(usize, usize),
+    Select(Vec<Path>),
+    // 引数無しならドキュメント全体で重複を除く
+    Distinct(Option<Path>),
+    // aggregate(グループキー..., 集計関数(path)...)
+    Aggregate(Vec<Path>, Vec<(Aggregation, Path)>),
+    // 最初の1件だけを返す
+    First,
+    // ちょうど1件でなければエラー
+    One,
+    // 結果を件数にする
+    Count,
+    // sum(price) などの単独の集計。結果は1つの値になる
+    Reduce(Aggregation, Path),
     Filter(Infix, Value, Value),
+    // in(path,(値,...))。or(eq(path,値),...) と同じ
+    In(Path, Vec<Value>),
+    // out(path,(値,...))。and(ne(path,値),...) と同じ
+    Out(Path, Vec<Value>),
+    // contains(path,値)。配列ならどれかの要素が値と等しく、文字列なら値を部分文字列として含む
+    Contains(Path, Value),
+    // フィールドがあるか (既定では null は無いものとして扱う)
+    Exists(Path),
+    Missing(Path),
+    // search((フィールド...),"語 語")。フィールドが空ならドキュメント中のすべての文字列を探す
+    Search(Vec<Path>, String),
+    // near(path, 緯度, 経度, 半径 (メートル))
+    Near(Path, f64, f64, f64),
     None,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Value {
-    Identifier(String),
+    Identifier(Path),
     IntegerLiteral(i64),
+    // i64 に収まらない正の整数。それ以下は IntegerLiteral になる
+    UnsignedLiteral(u64),
     FloatLiteral(f64),
+    // decimal フィーチャでは小数点を含むリテラルはこちらになる
+    #[cfg(feature = "decimal")]
+    DecimalLiteral(rust_decimal::Decimal),
     StringLiteral(String),
+    // date:2024-01-01T00:00:00Z
+    #[cfg(feature = "chrono")]
+    DateTime(chrono::DateTime<chrono::FixedOffset>),
+    // now-7d。評価時 (変換時) の時刻からの相対時刻
+    #[cfg(feature = "chrono")]
+    RelativeTime(i64, crate::datetime::TimeUnit),
     Boolean(bool),
+    // now() のように評価時に EvalContext から値を得る
+    Call(String),
+    // $name
+    Variable(String),
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Infix {
     Eq,
+    /// `ne()`. Like every comparison it needs a value to compare, so it
+    /// doesn't match documents where the field is missing or `null`; the
+    /// converters add the check where the target's negation would match them.
     NotEq,
     Le,
     Ge,
     Lt,
     Gt,
+    // 文字列の前方・後方一致
+    StartsWith,
+    EndsWith,
+    // 大文字小文字を区別しない (Unicode の case folding で比べる)
+    IEq,
+    IContains,
+    IStartsWith,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Prefix {
     Plus,
     Minus,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Aggregation {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Mean,
+}
+
+impl Aggregation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregation::Count => "count",
+            Aggregation::Sum => "sum",
+            Aggregation::Min => "min",
+            Aggregation::Max => "max",
+            Aggregation::Mean => "mean",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Aggregation::Count),
+            "sum" => Some(Aggregation::Sum),
+            "min" => Some(Aggregation::Min),
+            "max" => Some(Aggregation::Max),
+            "mean" => Some(Aggregation::Mean),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for Query {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -44,179 +159,605 @@ impl Query {
     pub fn is_none(&self) -> bool {
         self == &Query::None
     }
+
+    /// Combines queries with `and`. `Query::None` entries are dropped, a single
+    /// remaining query is returned as is, and nothing at all gives `Query::None`.
+    pub fn and_all<I: IntoIterator<Item = Query>>(queries: I) -> Query {
+        combine(queries, Query::And)
+    }
+
+    /// Same as [`Query::and_all`], combining with `or`.
+    pub fn or_all<I: IntoIterator<Item = Query>>(queries: I) -> Query {
+        combine(queries, Query::Or)
+    }
+
+    // in() を or(eq(),...) に、out() を and(ne(),...) にした同じ意味のクエリ。
+    // 他のノードはそのまま
+    pub(crate) fn expand_in(&self) -> Query {
+        let (infix, path, values) = match self {
+            Query::In(path, values) => (Infix::Eq, path, values),
+            Query::Out(path, values) => (Infix::NotEq, path, values),
+            _ => return self.clone(),
+        };
+        let filters = values
+            .iter()
+            .map(|v| Query::Filter(infix.clone(), Value::Identifier(path.clone()), v.clone()))
+            .collect();
+        match infix {
+            Infix::Eq => Query::Or(filters),
+            _ => Query::And(filters),
+        }
+    }
+
+    /// Renders the query back into RQL text that the parser accepts.
+    pub fn to_rql(&self) -> String {
+        match self {
+            Query::And(queries) => format!("and({})", join_rql(queries)),
+            Query::Or(queries) => format!("or({})", join_rql(queries)),
+            Query::Not(query) => format!("not({})", query.to_rql()),
+            Query::Sort(keys) => {
+                let keys: Vec<String> = keys
+                    .iter()
+                    .map(|(prefix, value)| match prefix {
+                        Prefix::Plus => format!("+{}", value),
+                        Prefix::Minus => format!("-{}", value),
+                    })
+                    .collect();
+                format!("sort({})", keys.join(","))
+            }
+            Query::Limit(count, 0) => format!("limit({})", count),
+            Query::Limit(count, offset) => format!("limit({},{})", count, offset),
+            Query::Select(paths) => format!("select({})", join_paths(paths)),
+            Query::Distinct(Some(path)) => format!("distinct({})", path),
+            Query::Distinct(None) => "distinct()".to_owned(),
+            Query::Aggregate(keys, functions) => {
+                let args: Vec<String> = keys
+                    .iter()
+                    .map(Path::to_string)
+                    .chain(
+                        functions
+                            .iter()
+                            .map(|(function, path)| format!("{}({})", function.name(), path)),
+                    )
+                    .collect();
+                format!("aggregate({})", args.join(","))
+            }
+            Query::First => "first()".to_owned(),
+            Query::One => "one()".to_owned(),
+            Query::Count => "count()".to_owned(),
+            Query::Reduce(function, path) => format!("{}({})", function.name(), path),
+            Query::Filter(infix, ident, value) => {
+                format!("{}({},{})", infix.keyword(), ident, value)
+            }
+            Query::In(path, values) => format!("in({},({}))", path, join_values(values)),
+            Query::Out(path, values) => format!("out({},({}))", path, join_values(values)),
+            Query::Contains(path, value) => format!("contains({},{})", path, value),
+            Query::Exists(path) => format!("exists({})", path),
+            Query::Missing(path) => format!("missing({})", path),
+            Query::Search(fields, term) => {
+                let term = Value::StringLiteral(term.clone());
+                match fields.is_empty() {
+                    true => format!("search({})", term),
+                    false => format!("search(({}),{})", join_paths(fields), term),
+                }
+            }
+            Query::Near(path, lat, lon, radius) => {
+                format!("near({},{},{},{})", path, lat, lon, radius)
+            }
+            Query::None => String::new(),
+        }
+    }
+}
+
+fn combine<I>(queries: I, group: fn(Vec<Query>) -> Query) -> Query
+where
+    I: IntoIterator<Item = Query>,
+{
+    let mut queries: Vec<Query> = queries.into_iter().filter(|q| !q.is_none()).collect();
+    match queries.len() {
+        0 => Query::None,
+        1 => queries.pop().unwrap(),
+        _ => group(queries),
+    }
+}
+
+fn join_rql(queries: &[Query]) -> String {
+    queries
+        .iter()
+        .map(Query::to_rql)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn join_values(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn join_paths(paths: &[Path]) -> String {
+    paths
+        .iter()
+        .map(Path::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Value::StringLiteral(s) => write!(f, "\"{}\"", s),
+            Value::StringLiteral(s) => crate::token::write_quoted(f, s),
             Value::Identifier(s) => write!(f, "{}", s),
             Value::IntegerLiteral(i) => write!(f, "{}", i),
+            Value::UnsignedLiteral(u) => write!(f, "{}", u),
+            // 60.0 を 60 と出力すると整数として再パースされてしまう
+            Value::FloatLiteral(i) if i.is_finite() && i.fract() == 0.0 => write!(f, "{:.1}", i),
             Value::FloatLiteral(i) => write!(f, "{}", i),
+            // Decimal は書かれた桁数を保つ
+            #[cfg(feature = "decimal")]
+            Value::DecimalLiteral(d) => write!(f, "{}", d),
             Value::Boolean(b) => write!(f, "{}", b),
+            Value::Call(name) => write!(f, "{}()", name),
+            Value::Variable(name) => write!(f, "${}", name),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(datetime) => write!(f, "date:{}", crate::datetime::format(datetime)),
+            #[cfg(feature = "chrono")]
+            Value::RelativeTime(0, _) => write!(f, "now"),
+            #[cfg(feature = "chrono")]
+            Value::RelativeTime(amount, unit) => write!(f, "now{:+}{}", amount, unit.suffix()),
         }
     }
 }
 
-// serde_json::Value個別の値との比較
+// 実行時の値をリテラルとして埋め込む (rql! の {} など)。文字列は識別子にならない
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::IntegerLiteral(i)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::IntegerLiteral(i.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(u: u32) -> Self {
+        Value::IntegerLiteral(u.into())
+    }
+}
+
+// パーサと同じく、i64 に収まるものは IntegerLiteral にする
+impl From<u64> for Value {
+    fn from(u: u64) -> Self {
+        match i64::try_from(u) {
+            Ok(i) => Value::IntegerLiteral(i),
+            Err(_) => Value::UnsignedLiteral(u),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        Value::FloatLiteral(f)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::StringLiteral(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::StringLiteral(s)
+    }
+}
+
+impl From<&String> for Value {
+    fn from(s: &String) -> Self {
+        Value::StringLiteral(s.clone())
+    }
+}
+
 impl Value {
-    pub fn eq(&self, comparison: serde_json::Value) -> bool {
+    /// The string of a string literal.
+    pub fn as_str(&self) -> Option<&str> {
         match self {
-            Value::StringLiteral(s) => {
-                if let Some(v) = comparison.as_str() {
-                    return v == s.as_str();
-                }
-            }
-            // identiferは入ってこないが、一応文字列としても評価できるようにしておく
-            Value::Identifier(s) => {
-                if let Some(v) = comparison.as_str() {
-                    return v == s.as_str();
-                }
-            }
-            Value::IntegerLiteral(i) => {
-                if let Some(v) = comparison.as_i64() {
-                    return &v == i;
-                }
-            }
-            Value::FloatLiteral(i) => {
-                if let Some(v) = comparison.as_f64() {
-                    return &v == i;
-                }
-            }
-            Value::Boolean(b) => {
-                if let Some(v) = comparison.as_bool() {
-                    return &v == b;
-                }
-            }
+            Value::StringLiteral(s) => Some(s),
+            _ => None,
         }
-        false
     }
 
-    pub fn ne(&self, comparison: serde_json::Value) -> bool {
+    /// An integer literal that fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
         match self {
-            Value::StringLiteral(s) => {
-                if let Some(v) = comparison.as_str() {
-                    debug!("ne {}, {}", v, s);
-                    return v != s.as_str();
-                }
-            }
-            Value::Identifier(s) => {
-                if let Some(v) = comparison.as_str() {
-                    return v != s.as_str();
-                }
-            }
-            Value::IntegerLiteral(i) => {
-                if let Some(v) = comparison.as_i64() {
-                    return &v != i;
-                }
-            }
-            Value::FloatLiteral(i) => {
-                if let Some(v) = comparison.as_f64() {
-                    return &v != i;
-                }
-            }
-            Value::Boolean(b) => {
-                if let Some(v) = comparison.as_bool() {
-                    return &v != b;
-                }
-            }
+            Value::IntegerLiteral(i) => Some(*i),
+            Value::UnsignedLiteral(u) => i64::try_from(*u).ok(),
+            _ => None,
         }
-        false
     }
 
-    pub fn lt(&self, comparison: serde_json::Value) -> bool {
+    /// Any number literal as a float, like `serde_json::Value::as_f64`:
+    /// integers widen, possibly losing precision past 2^53.
+    pub fn as_f64(&self) -> Option<f64> {
         match self {
-            Value::IntegerLiteral(i) => {
-                if let Some(v) = comparison.as_i64() {
-                    return i > &v;
-                }
-                false
-            }
-            Value::FloatLiteral(i) => {
-                if let Some(v) = comparison.as_f64() {
-                    return i > &v;
-                }
-                false
-            }
-            // eq,ne以外の演算子が使えない
-            _ => false,
+            Value::IntegerLiteral(i) => Some(*i as f64),
+            Value::UnsignedLiteral(u) => Some(*u as f64),
+            Value::FloatLiteral(f) => Some(*f),
+            #[cfg(feature = "decimal")]
+            Value::DecimalLiteral(d) => rust_decimal::prelude::ToPrimitive::to_f64(d),
+            _ => None,
         }
     }
 
-    pub fn le(&self, comparison: serde_json::Value) -> bool {
+    pub fn as_bool(&self) -> Option<bool> {
         match self {
-            Value::IntegerLiteral(i) => {
-                if let Some(v) = comparison.as_i64() {
-                    return i >= &v;
-                }
-                false
-            }
-            Value::FloatLiteral(i) => {
-                if let Some(v) = comparison.as_f64() {
-                    return i >= &v;
-                }
-                false
-            }
-            // eq,ne以外の演算子が使えない
-            _ => false,
+            Value::Boolean(b) => Some(*b),
+            _ => None,
         }
     }
 
-    pub fn gt(&self, comparison: serde_json::Value) -> bool {
+    pub fn is_identifier(&self) -> bool {
+        matches!(self, Value::Identifier(_))
+    }
+
+    /// The kind of value, for messages: `"integer"` for both integer
+    /// variants, `"float"`, `"string"`, `"identifier"`...
+    pub fn type_name(&self) -> &'static str {
         match self {
-            Value::IntegerLiteral(i) => {
-                debug!("gt {} val {:?}", i, comparison);
-                if let Some(v) = comparison.as_i64() {
-                    return i < &v;
-                }
-                false
+            Value::Identifier(_) => "identifier",
+            Value::IntegerLiteral(_) | Value::UnsignedLiteral(_) => "integer",
+            Value::FloatLiteral(_) => "float",
+            #[cfg(feature = "decimal")]
+            Value::DecimalLiteral(_) => "decimal",
+            Value::StringLiteral(_) => "string",
+            #[cfg(feature = "chrono")]
+            Value::DateTime(_) => "datetime",
+            #[cfg(feature = "chrono")]
+            Value::RelativeTime(..) => "relative_time",
+            Value::Boolean(_) => "boolean",
+            Value::Call(_) => "call",
+            Value::Variable(_) => "variable",
+        }
+    }
+
+    /// Whether `json` has a type the literal can be compared with: a number
+    /// for a number literal, a string for a string literal, a string or a
+    /// number (an epoch timestamp) for a date, a boolean for a boolean.
+    /// `null`, arrays, objects and values read from elsewhere (another
+    /// field, a call or a variable) never match.
+    pub fn json_type_matches(&self, json: &serde_json::Value) -> bool {
+        match self {
+            Value::IntegerLiteral(_) | Value::UnsignedLiteral(_) | Value::FloatLiteral(_) => {
+                json.is_number()
             }
-            Value::FloatLiteral(i) => {
-                if let Some(v) = comparison.as_f64() {
-                    return i < &v;
-                }
-                false
+            #[cfg(feature = "decimal")]
+            Value::DecimalLiteral(_) => json.is_number(),
+            Value::StringLiteral(_) => json.is_string(),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(_) | Value::RelativeTime(..) => {
+                json.is_string() || json.is_number()
             }
-            // eq,ne以外の演算子が使えない
-            _ => false,
+            Value::Boolean(_) => json.is_boolean(),
+            Value::Identifier(_) | Value::Call(_) | Value::Variable(_) => false,
         }
     }
 
-    pub fn ge(&self, comparison: serde_json::Value) -> bool {
+    // rql! が小数のリテラルを展開するのに使う。パーサと同じく、decimal フィーチャが
+    // あれば Decimal に、無ければ f64 にする
+    #[doc(hidden)]
+    pub fn __float_literal(s: &str) -> Value {
+        #[cfg(feature = "decimal")]
+        if let Some(d) = crate::decimal::parse(s) {
+            return Value::DecimalLiteral(d);
+        }
+        Value::FloatLiteral(s.parse().unwrap())
+    }
+
+    // JSON の整数は i64 か u64 のどちらかで取り出せるので、i128 に揃えて比較する
+    fn as_i128(&self) -> Option<i128> {
         match self {
-            Value::IntegerLiteral(i) => {
-                if let Some(v) = comparison.as_i64() {
-                    return i <= &v;
-                }
-                false
+            Value::IntegerLiteral(i) => Some(i128::from(*i)),
+            Value::UnsignedLiteral(u) => Some(i128::from(*u)),
+            _ => None,
+        }
+    }
+}
+
+// ドキュメントの値 (&serde_json::Value や DocValue) との比較
+//
+// JSON の数値は常に有限なので、JSON では非有限になりうるのはリテラル側だけ。
+// パーサは NaN を受け付けないが、桁の多いリテラルは inf になり、Value を直接作れば
+// どちらも作れる。inf はどの有限値より大きく (-inf は小さく) 、eq は自分自身とだけ
+// 一致するので JSON のどの値とも一致しない。NaN はどの比較にも、ne にも一致しない
+#[allow(clippy::should_implement_trait)]
+impl Value {
+    pub fn eq<'a>(&self, comparison: impl Into<DocValue<'a>>) -> bool {
+        self.equals(&comparison.into()) == Some(true)
+    }
+
+    pub fn ne<'a>(&self, comparison: impl Into<DocValue<'a>>) -> bool {
+        self.equals(&comparison.into()) == Some(false)
+    }
+
+    pub fn lt<'a>(&self, comparison: impl Into<DocValue<'a>>) -> bool {
+        self.order(&comparison.into()) == Some(Ordering::Greater)
+    }
+
+    pub fn le<'a>(&self, comparison: impl Into<DocValue<'a>>) -> bool {
+        matches!(
+            self.order(&comparison.into()),
+            Some(Ordering::Greater | Ordering::Equal)
+        )
+    }
+
+    pub fn gt<'a>(&self, comparison: impl Into<DocValue<'a>>) -> bool {
+        self.order(&comparison.into()) == Some(Ordering::Less)
+    }
+
+    pub fn ge<'a>(&self, comparison: impl Into<DocValue<'a>>) -> bool {
+        matches!(
+            self.order(&comparison.into()),
+            Some(Ordering::Less | Ordering::Equal)
+        )
+    }
+
+    // 比べられるなら等しいかどうか。解決前の値と NaN は何とも比べられない。
+    // 配列やオブジェクトはどのリテラルとも等しくない (eq は不一致、ne は一致)
+    fn equals(&self, comparison: &DocValue) -> Option<bool> {
+        match (self, comparison) {
+            (Value::Identifier(_) | Value::Call(_) | Value::Variable(_), _) => return None,
+            (Value::FloatLiteral(f), _) if f.is_nan() => return None,
+            (_, DocValue::Array(_) | DocValue::Object) => return Some(false),
+            _ => {}
+        }
+        if let Some(s) = self.as_str() {
+            Some(comparison.as_str()? == s)
+        } else if let Some(b) = self.as_bool() {
+            Some(comparison.as_bool()? == b)
+        } else {
+            self.order(comparison).map(|o| o == Ordering::Equal)
+        }
+    }
+
+    // ドキュメントの値に対するリテラルの順序。大小を比べられるのは数値と日時だけ。
+    // 整数リテラルは整数とだけ i128 で正確に比べ、それ以外の数値は as_f64 で比べる
+    fn order(&self, comparison: &DocValue) -> Option<Ordering> {
+        match self {
+            Value::IntegerLiteral(_) | Value::UnsignedLiteral(_) => {
+                Some(self.as_i128()?.cmp(&comparison.as_i128()?))
             }
-            Value::FloatLiteral(i) => {
-                if let Some(v) = comparison.as_f64() {
-                    return i <= &v;
-                }
-                false
+            #[cfg(feature = "decimal")]
+            Value::DecimalLiteral(d) => crate::decimal::compare(d, comparison),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(_) | Value::RelativeTime(..) => {
+                let d = crate::datetime::instant(self, None)?;
+                Some(d.cmp(&crate::datetime::from_doc(comparison)?))
             }
-            // eq,ne以外の演算子が使えない
-            _ => false,
+            _ => self.as_f64()?.partial_cmp(&comparison.as_f64()?),
         }
     }
 }
 
-impl fmt::Display for Infix {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Infix {
+    pub fn keyword(&self) -> &'static str {
         match self {
-            Infix::NotEq => write!(f, "!="),
-            Infix::Eq => write!(f, "="),
-            Infix::Le => write!(f, "<="),
-            Infix::Ge => write!(f, ">="),
-            Infix::Lt => write!(f, "<"),
-            Infix::Gt => write!(f, ">"),
+            Infix::Eq => "eq",
+            Infix::NotEq => "ne",
+            Infix::Le => "le",
+            Infix::Ge => "ge",
+            Infix::Lt => "lt",
+            Infix::Gt => "gt",
+            Infix::StartsWith => "startswith",
+            Infix::EndsWith => "endswith",
+            Infix::IEq => "ieq",
+            Infix::IContains => "icontains",
+            Infix::IStartsWith => "istartswith",
+        }
+    }
+
+    /// `ieq`, `icontains` and `istartswith`, which compare strings ignoring
+    /// case.
+    pub fn ignores_case(&self) -> bool {
+        matches!(self, Infix::IEq | Infix::IContains | Infix::IStartsWith)
+    }
+
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "eq" => Some(Infix::Eq),
+            "ne" => Some(Infix::NotEq),
+            "le" => Some(Infix::Le),
+            "ge" => Some(Infix::Ge),
+            "lt" => Some(Infix::Lt),
+            "gt" => Some(Infix::Gt),
+            "startswith" => Some(Infix::StartsWith),
+            "endswith" => Some(Infix::EndsWith),
+            "ieq" => Some(Infix::IEq),
+            "icontains" => Some(Infix::IContains),
+            "istartswith" => Some(Infix::IStartsWith),
+            _ => None,
         }
     }
 }
 
+impl fmt::Display for Infix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.keyword())
+    }
+}
+
 impl fmt::Display for Prefix {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Infix, Query, Value};
+    use serde_json::json;
+
+    fn eq(name: &str, value: i64) -> Query {
+        Query::Filter(
+            Infix::Eq,
+            Value::Identifier(name.into()),
+            Value::IntegerLiteral(value),
+        )
+    }
+
+    #[test]
+    fn and_all() {
+        assert_eq!(Query::and_all(vec![]), Query::None);
+        assert_eq!(Query::and_all(vec![Query::None, eq("a", 1)]), eq("a", 1));
+        assert_eq!(
+            Query::and_all(vec![eq("a", 1), eq("b", 2)]),
+            Query::And(vec![eq("a", 1), eq("b", 2)])
+        );
+        assert_eq!(
+            Query::or_all(vec![eq("a", 1), Query::None, eq("b", 2)]),
+            Query::Or(vec![eq("a", 1), eq("b", 2)])
+        );
+    }
+
+    #[test]
+    fn string_escapes() {
+        for s in &["say \"hi\"", "C:\\dir\\", "two\nlines\r\n", "tab\there", "\u{7f}"] {
+            let query = Query::Filter(
+                Infix::Eq,
+                Value::Identifier("a".into()),
+                Value::StringLiteral(s.to_string()),
+            );
+            let reparsed = crate::parser::Parser::new_from_string(query.to_rql()).parse_query();
+            assert_eq!(reparsed.unwrap(), query);
+        }
+        assert_eq!(
+            Value::StringLiteral("a\"b\\".to_owned()).to_string(),
+            r#""a\"b\\""#
+        );
+    }
+
+    #[test]
+    fn non_finite_literals() {
+        let numbers = [json!(-1e308), json!(0), json!(1.5), json!(u64::MAX)];
+        for v in &numbers {
+            // lt(a,inf) は a < inf
+            let inf = Value::FloatLiteral(f64::INFINITY);
+            assert!(inf.lt(v) && inf.le(v) && !inf.gt(v) && !inf.ge(v));
+            assert!(!inf.eq(v) && inf.ne(v));
+            let neg = Value::FloatLiteral(f64::NEG_INFINITY);
+            assert!(neg.gt(v) && !neg.lt(v) && !neg.eq(v));
+            let nan = Value::FloatLiteral(f64::NAN);
+            assert!(!nan.eq(v) && !nan.ne(v) && !nan.lt(v) && !nan.le(v));
+            assert!(!nan.gt(v) && !nan.ge(v));
+        }
+        // 数値以外とはどれも一致しない
+        assert!(!Value::FloatLiteral(f64::INFINITY).ne(&json!("inf")));
+    }
+
+    #[test]
+    fn integer_boundaries() {
+        let max = Value::UnsignedLiteral(u64::MAX);
+        assert!(max.eq(&json!(u64::MAX)));
+        assert!(!max.eq(&json!(i64::MAX)) && max.ne(&json!(i64::MAX)));
+        // lt(a,max) は a < max
+        assert!(max.lt(&json!(i64::MAX)) && max.lt(&json!(-1)));
+        assert!(max.le(&json!(u64::MAX)) && !max.gt(&json!(u64::MAX)));
+
+        let i = Value::IntegerLiteral(i64::MAX);
+        assert!(i.gt(&json!(i64::MAX as u64 + 1)));
+        assert!(i.ne(&json!(u64::MAX)) && !i.eq(&json!(u64::MAX)));
+        assert!(Value::IntegerLiteral(i64::MIN).eq(&json!(i64::MIN)));
+        assert!(Value::IntegerLiteral(-1).lt(&json!(i64::MIN)));
+    }
+
+    // 表示は RQL の名前。SQL の演算子は convert::sql が決める
+    #[test]
+    fn infix_display() {
+        assert_eq!(Infix::Eq.to_string(), "eq");
+        assert_eq!(Infix::NotEq.to_string(), "ne");
+        assert_eq!(Infix::IStartsWith.to_string(), "istartswith");
+        assert_eq!(Infix::EndsWith.to_string(), Infix::EndsWith.keyword());
+    }
+
+    #[test]
+    fn accessors() {
+        let s = Value::StringLiteral("京都".to_owned());
+        assert_eq!(s.as_str(), Some("京都"));
+        assert_eq!(s.as_i64(), None);
+        assert_eq!(s.as_f64(), None);
+        assert_eq!(Value::Identifier("a".into()).as_str(), None);
+
+        assert_eq!(Value::IntegerLiteral(-3).as_i64(), Some(-3));
+        assert_eq!(Value::UnsignedLiteral(u64::MAX).as_i64(), None);
+        assert_eq!(Value::UnsignedLiteral(7).as_i64(), Some(7));
+        // 小数は整数にしない
+        assert_eq!(Value::FloatLiteral(2.0).as_i64(), None);
+
+        assert_eq!(Value::Boolean(true).as_bool(), Some(true));
+        assert_eq!(Value::IntegerLiteral(1).as_bool(), None);
+        assert!(Value::Identifier("a.b".into()).is_identifier());
+        assert!(!Value::StringLiteral("a.b".to_owned()).is_identifier());
+    }
+
+    #[test]
+    fn as_f64_widens() {
+        assert_eq!(Value::FloatLiteral(1.5).as_f64(), Some(1.5));
+        assert_eq!(Value::IntegerLiteral(-42).as_f64(), Some(-42.0));
+        assert_eq!(Value::UnsignedLiteral(u64::MAX).as_f64(), Some(u64::MAX as f64));
+        // 2^53 を超えると丸められる
+        assert_eq!(
+            Value::IntegerLiteral((1 << 53) + 1).as_f64(),
+            Some((1u64 << 53) as f64)
+        );
+        assert_eq!(Value::Boolean(true).as_f64(), None);
+        #[cfg(feature = "decimal")]
+        assert_eq!(
+            Value::DecimalLiteral("0.25".parse().unwrap()).as_f64(),
+            Some(0.25)
+        );
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(Value::IntegerLiteral(1).type_name(), "integer");
+        assert_eq!(Value::UnsignedLiteral(u64::MAX).type_name(), "integer");
+        assert_eq!(Value::FloatLiteral(1.0).type_name(), "float");
+        assert_eq!(Value::StringLiteral(String::new()).type_name(), "string");
+        assert_eq!(Value::Identifier("a".into()).type_name(), "identifier");
+        assert_eq!(Value::Boolean(false).type_name(), "boolean");
+        assert_eq!(Value::Variable("x".to_owned()).type_name(), "variable");
+        assert_eq!(Value::Call("now".to_owned()).type_name(), "call");
+    }
+
+    #[test]
+    fn json_type_matches() {
+        let int = Value::IntegerLiteral(1);
+        assert!(int.json_type_matches(&json!(1)) && int.json_type_matches(&json!(1.5)));
+        assert!(!int.json_type_matches(&json!("1")) && !int.json_type_matches(&json!(null)));
+        assert!(Value::FloatLiteral(0.5).json_type_matches(&json!(u64::MAX)));
+        let s = Value::StringLiteral("a".to_owned());
+        assert!(s.json_type_matches(&json!("b")) && !s.json_type_matches(&json!(["a"])));
+        assert!(!Value::Identifier("a".into()).json_type_matches(&json!("a")));
+        assert!(Value::Boolean(true).json_type_matches(&json!(false)));
+        assert!(!Value::Boolean(true).json_type_matches(&json!(1)));
+        assert!(!Value::Variable("x".to_owned()).json_type_matches(&json!(1)));
+        #[cfg(feature = "chrono")]
+        {
+            let date = Value::DateTime("2024-01-01T00:00:00Z".parse().unwrap());
+            assert!(date.json_type_matches(&json!("2024-01-01")));
+            assert!(date.json_type_matches(&json!(1704067200)));
+            assert!(!date.json_type_matches(&json!(true)));
+        }
+    }
+}
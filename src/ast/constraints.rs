@@ -0,0 +1,178 @@
+//! The equality and range constraints every matching document satisfies, for
+//! choosing an index.
+
+use crate::ast::{Infix, Path, Query, Value};
+
+/// A filter comparing a field with a literal, `eq(field,value)` or one of
+/// `lt`, `le`, `gt`, `ge`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Constraint {
+    pub field: Path,
+    pub operator: Infix,
+    pub value: Value,
+}
+
+impl Constraint {
+    /// The constraint as a filter.
+    pub fn to_query(&self) -> Query {
+        Query::Filter(
+            self.operator.clone(),
+            Value::Identifier(self.field.clone()),
+            self.value.clone(),
+        )
+    }
+}
+
+impl Query {
+    /// The `eq()`, `lt()`, `le()`, `gt()` and `ge()` filters with a literal
+    /// value that hold for every document the query matches, in the order
+    /// they appear, for picking an index to look them up with.
+    ///
+    /// Filters of an `and()` (nested or not) are all required. One under an
+    /// `or()` is only required when every branch implies it (see
+    /// [`Query::implies`]): `or(and(eq(type,"a"),gt(n,5)),and(eq(type,"a"),eq(n,7)))`
+    /// requires `eq(type,"a")` and `gt(n,5)`. Nothing under a `not()` is
+    /// required: `not(eq(a,1))` holds exactly where `eq(a,1)` doesn't.
+    /// `ne()`, `startswith()` and the like, comparisons with other fields,
+    /// `$variables` and calls such as `now()` aren't constraints.
+    ///
+    /// ```
+    /// use rql_parser::ast::Infix;
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let query = Parser::new_from_string(
+    ///     "and(eq(tenant,3),or(ge(age,18),eq(guardian,true)))".to_owned(),
+    /// )
+    /// .parse_query()
+    /// .unwrap();
+    /// let constraints = query.required_constraints();
+    /// assert_eq!(constraints.len(), 1);
+    /// assert_eq!(constraints[0].field.to_string(), "tenant");
+    /// assert_eq!(constraints[0].operator, Infix::Eq);
+    /// ```
+    pub fn required_constraints(&self) -> Vec<Constraint> {
+        let mut constraints = vec![];
+        match self {
+            Query::And(queries) => {
+                for query in queries {
+                    for c in query.required_constraints() {
+                        if !constraints.contains(&c) {
+                            constraints.push(c);
+                        }
+                    }
+                }
+            }
+            // どれかの枝の制約のうち、すべての枝が含意するもの
+            Query::Or(branches) => {
+                for branch in branches {
+                    for c in branch.required_constraints() {
+                        if !constraints.contains(&c)
+                            && branches
+                                .iter()
+                                .all(|b| b.implies(&c.to_query()).is_implied())
+                        {
+                            constraints.push(c);
+                        }
+                    }
+                }
+            }
+            Query::Filter(
+                operator @ (Infix::Eq | Infix::Lt | Infix::Le | Infix::Gt | Infix::Ge),
+                Value::Identifier(field),
+                value,
+            ) if !matches!(
+                value,
+                Value::Identifier(_) | Value::Variable(_) | Value::Call(_)
+            ) =>
+            {
+                constraints.push(Constraint {
+                    field: field.clone(),
+                    operator: operator.clone(),
+                    value: value.clone(),
+                })
+            }
+            // not() の中の条件は、一致するドキュメントでは成り立たないことがある
+            Query::Not(_) => {}
+            _ => {}
+        }
+        constraints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+
+    fn required(input: &str) -> Vec<String> {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .required_constraints()
+            .iter()
+            .map(|c| c.to_query().to_rql())
+            .collect()
+    }
+
+    #[test]
+    fn and() {
+        assert_eq!(
+            required("and(eq(status,\"open\"),ge(age,18),and(lt(age,65),eq(status,\"open\")))"),
+            vec!["eq(status,\"open\")", "ge(age,18)", "lt(age,65)"]
+        );
+        assert_eq!(required("eq(a,1)"), vec!["eq(a,1)"]);
+        assert_eq!(
+            required("and(eq(a,1),sort(+a),limit(10),exists(b))"),
+            vec!["eq(a,1)"]
+        );
+    }
+
+    #[test]
+    fn not_constraints() {
+        assert!(required(
+            "and(ne(a,1),startswith(b,\"x\"),eq(c,d),eq(e,$user),gt(f,now()),missing(g))"
+        )
+        .is_empty());
+        assert!(required("and()").is_empty());
+    }
+
+    #[test]
+    fn not() {
+        assert!(required("not(eq(a,1))").is_empty());
+        assert!(required("not(and(eq(a,1),ge(b,2)))").is_empty());
+        assert!(required("not(or(eq(a,1),eq(a,2)))").is_empty());
+        assert_eq!(
+            required("and(eq(tenant,3),not(eq(status,\"closed\")),not(not(eq(a,1))))"),
+            vec!["eq(tenant,3)"]
+        );
+        assert_eq!(
+            required("or(and(eq(a,1),not(eq(b,1))),and(eq(a,1),eq(b,2)))"),
+            vec!["eq(a,1)"]
+        );
+    }
+
+    #[test]
+    fn or() {
+        assert_eq!(
+            required("or(and(eq(type,\"a\"),eq(n,1)),and(eq(n,2),eq(type,\"a\")))"),
+            vec!["eq(type,\"a\")"]
+        );
+        assert!(required("or(eq(a,1),eq(b,1))").is_empty());
+        // eq(n,7) は gt(n,5) を含意する
+        assert_eq!(
+            required("or(and(eq(type,\"a\"),gt(n,5)),and(eq(type,\"a\"),eq(n,7)))"),
+            vec!["eq(type,\"a\")", "gt(n,5)"]
+        );
+        assert_eq!(
+            required("and(eq(tenant,3),or(ge(age,18),eq(guardian,true)))"),
+            vec!["eq(tenant,3)"]
+        );
+        assert_eq!(
+            required("or(and(eq(a,1),or(eq(b,1),and(eq(b,1),eq(c,2)))),eq(a,1))"),
+            vec!["eq(a,1)"]
+        );
+        assert_eq!(
+            required("and(eq(a,1),or(and(eq(b,1),gt(c,0)),and(eq(b,1),lt(c,0))))"),
+            vec!["eq(a,1)", "eq(b,1)"]
+        );
+    }
+}
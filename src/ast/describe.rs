@@ -0,0 +1,249 @@
+//! Plain English descriptions of queries, for people who don't read RQL.
+
+use crate::ast::{Aggregation, Infix, Path, Prefix, Query, Value};
+use std::collections::HashMap;
+
+impl Query {
+    /// Describes the query in English, e.g. `name equals "test" AND
+    /// (speed.max is greater than 100 OR speed.min is less than 60)`.
+    ///
+    /// Nested `and()`/`or()` are parenthesized like in the query. Filters come
+    /// first in a group; `sort()`, `limit()` and the like follow after a comma.
+    pub fn describe(&self) -> String {
+        self.describe_with(&HashMap::new())
+    }
+
+    /// Same as [`Query::describe`], showing fields found in `labels` by their
+    /// label, e.g. `speed.max` as "maximum speed".
+    pub fn describe_with(&self, labels: &HashMap<String, String>) -> String {
+        describe(self, labels)
+    }
+}
+
+fn describe(query: &Query, labels: &HashMap<String, String>) -> String {
+    let fields = |paths: &[Path]| {
+        paths
+            .iter()
+            .map(|p| field(p, labels))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let values = |values: &[Value]| {
+        values
+            .iter()
+            .map(|v| operand(v, labels))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match query {
+        Query::And(queries) if queries.is_empty() => "everything".to_owned(),
+        Query::Or(queries) if queries.is_empty() => "nothing".to_owned(),
+        Query::And(queries) => group(queries, " AND ", labels),
+        Query::Or(queries) => group(queries, " OR ", labels),
+        Query::Not(query) => match query.as_ref() {
+            Query::And(qs) | Query::Or(qs) if qs.len() > 1 => {
+                format!("NOT ({})", describe(query, labels))
+            }
+            _ => format!("NOT {}", describe(query, labels)),
+        },
+        Query::Filter(infix, ident, value) => {
+            let phrase = match infix {
+                Infix::Eq => "equals",
+                Infix::NotEq => "does not equal",
+                Infix::Lt => "is less than",
+                Infix::Le => "is at most",
+                Infix::Gt => "is greater than",
+                Infix::Ge => "is at least",
+                Infix::StartsWith => "starts with",
+                Infix::EndsWith => "ends with",
+                Infix::IEq => "equals (ignoring case)",
+                Infix::IContains => "contains (ignoring case)",
+                Infix::IStartsWith => "starts with (ignoring case)",
+            };
+            format!(
+                "{} {} {}",
+                operand(ident, labels),
+                phrase,
+                operand(value, labels)
+            )
+        }
+        Query::In(path, list) => format!("{} is one of ({})", field(path, labels), values(list)),
+        Query::Out(path, list) => {
+            format!("{} is none of ({})", field(path, labels), values(list))
+        }
+        Query::Contains(path, value) => {
+            format!("{} contains {}", field(path, labels), operand(value, labels))
+        }
+        Query::Exists(path) => format!("{} is present", field(path, labels)),
+        Query::Missing(path) => format!("{} is missing", field(path, labels)),
+        Query::Search(paths, term) => {
+            let term = operand(&Value::StringLiteral(term.clone()), labels);
+            match paths.is_empty() {
+                true => format!("any text contains the words {}", term),
+                false => format!("{} contains the words {}", fields(paths), term),
+            }
+        }
+        Query::Near(path, lat, lon, radius) => {
+            format!("{} is within {} m of ({}, {})", field(path, labels), radius, lat, lon)
+        }
+        Query::Sort(keys) => {
+            let keys: Vec<String> = keys
+                .iter()
+                .map(|(prefix, value)| {
+                    let order = match prefix {
+                        Prefix::Plus => "ascending",
+                        Prefix::Minus => "descending",
+                    };
+                    format!("{} {}", operand(value, labels), order)
+                })
+                .collect();
+            format!("sorted by {}", keys.join(", then "))
+        }
+        Query::Limit(count, 0) => format!("at most {} results", count),
+        Query::Limit(count, offset) => {
+            format!("at most {} results after skipping {}", count, offset)
+        }
+        Query::Select(paths) => format!("showing only {}", fields(paths)),
+        Query::Distinct(Some(path)) => format!("one result per {}", field(path, labels)),
+        Query::Distinct(None) => "without duplicates".to_owned(),
+        Query::Aggregate(keys, functions) => {
+            let functions: Vec<String> = functions
+                .iter()
+                .map(|(function, path)| aggregation(*function, &field(path, labels)))
+                .collect();
+            format!(
+                "grouped by {} with {}",
+                fields(keys),
+                functions.join(" and ")
+            )
+        }
+        Query::First => "only the first result".to_owned(),
+        Query::One => "exactly one result".to_owned(),
+        Query::Count => "the number of results".to_owned(),
+        Query::Reduce(function, path) => aggregation(*function, &field(path, labels)),
+        Query::None => String::new(),
+    }
+}
+
+fn group(queries: &[Query], separator: &str, labels: &HashMap<String, String>) -> String {
+    let (shaping, filters): (Vec<&Query>, Vec<&Query>) = queries.iter().partition(|q| {
+        !matches!(
+            q,
+            Query::And(_)
+                | Query::Or(_)
+                | Query::Not(_)
+                | Query::Filter(..)
+                | Query::In(..)
+                | Query::Out(..)
+                | Query::Contains(..)
+                | Query::Exists(_)
+                | Query::Missing(_)
+                | Query::Search(..)
+                | Query::Near(..)
+        )
+    });
+    let filters: Vec<String> = filters
+        .into_iter()
+        .map(|q| match q {
+            Query::And(qs) | Query::Or(qs) if qs.len() > 1 => format!("({})", describe(q, labels)),
+            _ => describe(q, labels),
+        })
+        .collect();
+    let shaping: Vec<String> = shaping
+        .into_iter()
+        .map(|q| describe(q, labels))
+        .filter(|s| !s.is_empty())
+        .collect();
+    match (filters.is_empty(), shaping.is_empty()) {
+        (_, true) => filters.join(separator),
+        (true, false) => shaping.join(", "),
+        (false, false) => format!("{}, {}", filters.join(separator), shaping.join(", ")),
+    }
+}
+
+fn operand(value: &Value, labels: &HashMap<String, String>) -> String {
+    match value {
+        Value::Identifier(path) => field(path, labels),
+        _ => value.to_string(),
+    }
+}
+
+fn field(path: &Path, labels: &HashMap<String, String>) -> String {
+    let path = path.to_string();
+    labels.get(&path).cloned().unwrap_or(path)
+}
+
+fn aggregation(function: Aggregation, field: &str) -> String {
+    match function {
+        Aggregation::Count => format!("the count of {}", field),
+        Aggregation::Sum => format!("the sum of {}", field),
+        Aggregation::Min => format!("the lowest {}", field),
+        Aggregation::Max => format!("the highest {}", field),
+        Aggregation::Mean => format!("the average {}", field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Query;
+    use crate::parser::Parser;
+    use std::collections::HashMap;
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn operators() {
+        let cases = [
+            ("eq(name,\"test\")", "name equals \"test\""),
+            ("ne(status,\"closed\")", "status does not equal \"closed\""),
+            ("lt(age,18)", "age is less than 18"),
+            ("le(age,18)", "age is at most 18"),
+            ("gt(score,1.5)", "score is greater than 1.5"),
+            ("ge(score,2)", "score is at least 2"),
+            ("startswith(name,\"Dr\")", "name starts with \"Dr\""),
+            ("endswith(email,\".jp\")", "email ends with \".jp\""),
+            ("exists(phone)", "phone is present"),
+            ("missing(phone)", "phone is missing"),
+            ("in(status,(\"new\",\"open\"))", "status is one of (\"new\", \"open\")"),
+            ("out(age,(1,2))", "age is none of (1, 2)"),
+            ("contains(tags,\"rust\")", "tags contains \"rust\""),
+            ("not(eq(a,1))", "NOT a equals 1"),
+            ("not(or(eq(a,1),eq(b,2)))", "NOT (a equals 1 OR b equals 2)"),
+            ("eq(active,true)", "active equals true"),
+            ("and()", "everything"),
+            ("or()", "nothing"),
+            (
+                "and(eq(a,1),sort(+a,-b),limit(10,20))",
+                "a equals 1, sorted by a ascending, then b descending, at most 10 results after skipping 20",
+            ),
+            ("aggregate(city,sum(price),count(id))", "grouped by city with the sum of price and the count of id"),
+        ];
+        for (input, expected) in &cases {
+            assert_eq!(parse(input).describe(), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn nested_with_labels() {
+        let query = parse("and(eq(name,\"test\"),or(gt(speed.max,100),lt(speed.min,60)))");
+        assert_eq!(
+            query.describe(),
+            "name equals \"test\" AND (speed.max is greater than 100 OR speed.min is less than 60)"
+        );
+        let mut labels = HashMap::new();
+        labels.insert("speed.max".to_owned(), "maximum speed".to_owned());
+        labels.insert("speed.min".to_owned(), "minimum speed".to_owned());
+        assert_eq!(
+            query.describe_with(&labels),
+            "name equals \"test\" AND (maximum speed is greater than 100 OR minimum speed is less than 60)"
+        );
+        assert_eq!(
+            parse("or(and(eq(a,1),eq(b,2)),and(eq(c,3)))").describe(),
+            "(a equals 1 AND b equals 2) OR c equals 3"
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Differences between two versions of a query, for audit logs of edited
+//! saved searches.
+
+use crate::ast::Query;
+use serde::Serialize;
+use std::fmt;
+
+/// One difference found by [`Query::diff`]. `path` locates the node in the
+/// tree, like `And[1].Or[0]` for the first child of the `or()` that is the
+/// second child of the top-level `and()`, and `Not` for the child of a
+/// `not()`; it is empty for the root.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum DiffEntry {
+    Added {
+        path: String,
+        query: String,
+    },
+    Removed {
+        path: String,
+        query: String,
+    },
+    // part は field, operator, literal または query (ノード全体)
+    Changed {
+        path: String,
+        part: &'static str,
+        old: String,
+        new: String,
+    },
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = match self {
+            DiffEntry::Added { path, .. }
+            | DiffEntry::Removed { path, .. }
+            | DiffEntry::Changed { path, .. } => path,
+        };
+        if !path.is_empty() {
+            write!(f, "{}: ", path)?;
+        }
+        match self {
+            DiffEntry::Added { query, .. } => write!(f, "added {}", query),
+            DiffEntry::Removed { query, .. } => write!(f, "removed {}", query),
+            DiffEntry::Changed { part, old, new, .. } => {
+                write!(f, "{} changed {} → {}", part, old, new)
+            }
+        }
+    }
+}
+
+impl Query {
+    /// Lists what changed from `self` to `new`. Children of `and()` and `or()`
+    /// are aligned on the ones left unchanged, so inserting a condition
+    /// reports one addition rather than a change of every following child;
+    /// the remaining children are paired in order and compared part by part.
+    /// Identical queries give an empty list.
+    pub fn diff(&self, new: &Query) -> Vec<DiffEntry> {
+        let mut entries = vec![];
+        diff(self, new, "", &mut entries);
+        entries
+    }
+}
+
+fn diff(old: &Query, new: &Query, path: &str, entries: &mut Vec<DiffEntry>) {
+    match (old, new) {
+        (Query::And(a), Query::And(b)) => diff_children(a, b, &child_path(path, "And"), entries),
+        (Query::Or(a), Query::Or(b)) => diff_children(a, b, &child_path(path, "Or"), entries),
+        (Query::Not(a), Query::Not(b)) => diff(a, b, &child_path(path, "Not"), entries),
+        (Query::Filter(infix_a, ident_a, value_a), Query::Filter(infix_b, ident_b, value_b)) => {
+            let mut change = |part, old: String, new: String| {
+                if old != new {
+                    entries.push(DiffEntry::Changed {
+                        path: path.to_owned(),
+                        part,
+                        old,
+                        new,
+                    });
+                }
+            };
+            change("field", ident_a.to_string(), ident_b.to_string());
+            change(
+                "operator",
+                infix_a.keyword().to_owned(),
+                infix_b.keyword().to_owned(),
+            );
+            change("literal", value_a.to_string(), value_b.to_string());
+        }
+        _ if old == new => {}
+        _ => entries.push(DiffEntry::Changed {
+            path: path.to_owned(),
+            part: "query",
+            old: old.to_rql(),
+            new: new.to_rql(),
+        }),
+    }
+}
+
+// 変更の無い子 (最長共通部分列) で揃え、その間の子を順に対応付ける
+fn diff_children(a: &[Query], b: &[Query], prefix: &str, entries: &mut Vec<DiffEntry>) {
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let (mut removed, mut added) = (vec![], vec![]);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            pair(a, b, &removed, &added, prefix, entries);
+            removed.clear();
+            added.clear();
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(j);
+            j += 1;
+        } else {
+            removed.push(i);
+            i += 1;
+        }
+    }
+    pair(a, b, &removed, &added, prefix, entries);
+}
+
+fn pair(
+    a: &[Query],
+    b: &[Query],
+    removed: &[usize],
+    added: &[usize],
+    prefix: &str,
+    entries: &mut Vec<DiffEntry>,
+) {
+    for (&i, &j) in removed.iter().zip(added) {
+        diff(&a[i], &b[j], &format!("{}[{}]", prefix, j), entries);
+    }
+    for &i in removed.iter().skip(added.len()) {
+        entries.push(DiffEntry::Removed {
+            path: format!("{}[{}]", prefix, i),
+            query: a[i].to_rql(),
+        });
+    }
+    for &j in added.iter().skip(removed.len()) {
+        entries.push(DiffEntry::Added {
+            path: format!("{}[{}]", prefix, j),
+            query: b[j].to_rql(),
+        });
+    }
+}
+
+fn child_path(path: &str, node: &str) -> String {
+    if path.is_empty() {
+        node.to_owned()
+    } else {
+        format!("{}.{}", path, node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::DiffEntry;
+    use crate::parser::Parser;
+
+    fn diff(old: &str, new: &str) -> Vec<String> {
+        let parse = |s: &str| Parser::new_from_string(s.to_owned()).parse_query().unwrap();
+        parse(old)
+            .diff(&parse(new))
+            .iter()
+            .map(DiffEntry::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn identical() {
+        let query = "and(eq(a,1),or(gt(b,100),lt(c,2)))";
+        assert_eq!(diff(query, query), Vec::<String>::new());
+    }
+
+    #[test]
+    fn changes() {
+        assert_eq!(
+            diff(
+                "and(eq(a,1),or(gt(b,100),lt(c,2)))",
+                "and(eq(a,1),or(gt(b,200),lt(c,2)))"
+            ),
+            ["And[1].Or[0]: literal changed 100 → 200"]
+        );
+        assert_eq!(
+            diff("and(eq(a,1),eq(b,2))", "and(eq(a,1),ne(b,2))"),
+            ["And[1]: operator changed eq → ne"]
+        );
+        assert_eq!(
+            diff("eq(a,1)", "gt(b,\"1\")"),
+            [
+                "field changed a → b",
+                "operator changed eq → gt",
+                "literal changed 1 → \"1\""
+            ]
+        );
+        assert_eq!(
+            diff("and(eq(a,1),eq(b,2))", "or(eq(a,1),eq(b,2))"),
+            ["query changed and(eq(a,1),eq(b,2)) → or(eq(a,1),eq(b,2))"]
+        );
+        assert_eq!(
+            diff("and(eq(a,1),not(eq(b,2)))", "and(eq(a,1),not(lt(b,2)))"),
+            ["And[1].Not: operator changed eq → lt"]
+        );
+    }
+
+    #[test]
+    fn added_and_removed() {
+        assert_eq!(
+            diff("and(eq(a,1),eq(b,2))", "and(eq(z,0),eq(a,1),eq(b,2))"),
+            ["And[0]: added eq(z,0)"]
+        );
+        assert_eq!(
+            diff("and(eq(a,1),eq(b,2),eq(c,3))", "and(eq(a,1),eq(c,3))"),
+            ["And[1]: removed eq(b,2)"]
+        );
+        let parse = |s: &str| Parser::new_from_string(s.to_owned()).parse_query().unwrap();
+        let entries = parse("and(eq(a,1))").diff(&parse("and(eq(a,1),sort(-a))"));
+        assert_eq!(
+            serde_json::to_value(&entries).unwrap(),
+            serde_json::json!([{"change": "added", "path": "And[1]", "query": "sort(-a)"}])
+        );
+    }
+}
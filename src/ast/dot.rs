@@ -0,0 +1,101 @@
+//! Graphviz rendering of the query tree, for documentation and debugging.
+
+use crate::ast::{Infix, Query};
+
+impl Query {
+    /// Renders the query as a Graphviz digraph: `and()`/`or()` are boxes with
+    /// an edge to each child in order, filters are leaves labeled
+    /// `field op literal`, and other nodes are leaves labeled with their RQL.
+    /// Render it with `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph query {\n");
+        let mut next = 0;
+        write_node(self, &mut next, &mut out);
+        out.push_str("}\n");
+        out
+    }
+}
+
+// ノードを先行順に n0, n1, ... と番号付けし、自分の番号を返す
+fn write_node(query: &Query, next: &mut usize, out: &mut String) -> usize {
+    let id = *next;
+    *next += 1;
+    let (shape, label, children) = match query {
+        Query::And(children) => ("box", "and".to_owned(), &children[..]),
+        Query::Or(children) => ("box", "or".to_owned(), &children[..]),
+        Query::Not(child) => ("box", "not".to_owned(), std::slice::from_ref(&**child)),
+        Query::Filter(infix, ident, value) => {
+            let op = match infix {
+                Infix::Eq => "=",
+                Infix::NotEq => "!=",
+                Infix::Le => "<=",
+                Infix::Ge => ">=",
+                Infix::Lt => "<",
+                Infix::Gt => ">",
+                _ => infix.keyword(),
+            };
+            ("ellipse", format!("{} {} {}", ident, op, value), &[][..])
+        }
+        _ => ("ellipse", query.to_rql(), &[][..]),
+    };
+    out.push_str(&format!(
+        "  n{} [shape={}, label=\"{}\"];\n",
+        id,
+        shape,
+        escape(&label)
+    ));
+    for child in children {
+        let child_id = write_node(child, next, out);
+        out.push_str(&format!("  n{} -> n{};\n", id, child_id));
+    }
+    id
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+
+    fn to_dot(input: &str) -> String {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .to_dot()
+    }
+
+    #[test]
+    fn nested() {
+        let dot = to_dot(
+            "and(eq(name,\"say \\\"hi\\\"\"),or(gt(speed.max,100),startswith(id,\"a\")),sort(+id))",
+        );
+        assert_eq!(
+            dot,
+            r#"digraph query {
+  n0 [shape=box, label="and"];
+  n1 [shape=ellipse, label="name = \"say \\\"hi\\\"\""];
+  n0 -> n1;
+  n2 [shape=box, label="or"];
+  n3 [shape=ellipse, label="speed.max > 100"];
+  n2 -> n3;
+  n4 [shape=ellipse, label="id startswith \"a\""];
+  n2 -> n4;
+  n0 -> n2;
+  n5 [shape=ellipse, label="sort(+id)"];
+  n0 -> n5;
+}
+"#
+        );
+        assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+        assert!(to_dot("eq(a,1)").starts_with("digraph query {\n"));
+        assert!(to_dot("eq(a,1)").ends_with("}\n"));
+        assert!(to_dot("not(eq(a,1))").contains(
+            "  n0 [shape=box, label=\"not\"];\n  n1 [shape=ellipse, label=\"a = 1\"];\n  n0 -> n1;\n"
+        ));
+    }
+}
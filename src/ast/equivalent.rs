@@ -0,0 +1,127 @@
+//! Comparison of queries that ignores the order of `and()`/`or()` children.
+
+use crate::ast::Query;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+impl Query {
+    /// Returns true when both queries are equal once the children of every
+    /// `and()` and `or()` are treated as unordered multisets, so
+    /// `and(eq(a,1),eq(b,2))` is equivalent to `and(eq(b,2),eq(a,1))` but not
+    /// to `and(eq(a,1),eq(a,1),eq(b,2))`. Other nodes, filters included, must be
+    /// equal. Neither tree is cloned or rewritten.
+    pub fn equivalent(&self, other: &Query) -> bool {
+        match (self, other) {
+            (Query::And(a), Query::And(b)) | (Query::Or(a), Query::Or(b)) => same_children(a, b),
+            (Query::Not(a), Query::Not(b)) => a.equivalent(b),
+            _ => self == other,
+        }
+    }
+}
+
+// 子を fingerprint 順に並べ、同じ fingerprint の中だけで対応を探す。
+// equivalent は同値関係なので、貪欲に対応付けても取りこぼさない
+fn same_children(a: &[Query], b: &[Query]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let (a, b) = (sorted(a), sorted(b));
+    if a.iter()
+        .map(|(key, _)| key)
+        .ne(b.iter().map(|(key, _)| key))
+    {
+        return false;
+    }
+    let mut start = 0;
+    while start < a.len() {
+        let end = start
+            + a[start..]
+                .iter()
+                .take_while(|(key, _)| *key == a[start].0)
+                .count();
+        let mut used = vec![false; end - start];
+        for (_, x) in &a[start..end] {
+            let found = (start..end).find(|&j| !used[j - start] && x.equivalent(b[j].1));
+            match found {
+                Some(j) => used[j - start] = true,
+                None => return false,
+            }
+        }
+        start = end;
+    }
+    true
+}
+
+fn sorted(queries: &[Query]) -> Vec<(u64, &Query)> {
+    let mut keyed: Vec<(u64, &Query)> = queries.iter().map(|q| (fingerprint(q), q)).collect();
+    keyed.sort_by_key(|(key, _)| *key);
+    keyed
+}
+
+// 子の順序に依存しないハッシュ。等価なクエリは同じ値になる
+fn fingerprint(query: &Query) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match query {
+        Query::And(children) | Query::Or(children) => {
+            matches!(query, Query::And(_)).hash(&mut hasher);
+            let mut keys: Vec<u64> = children.iter().map(fingerprint).collect();
+            keys.sort_unstable();
+            keys.hash(&mut hasher);
+        }
+        Query::Not(query) => {
+            "not".hash(&mut hasher);
+            fingerprint(query).hash(&mut hasher);
+        }
+        _ => query.to_rql().hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+
+    fn equivalent(a: &str, b: &str) -> bool {
+        let parse = |s: &str| Parser::new_from_string(s.to_owned()).parse_query().unwrap();
+        parse(a).equivalent(&parse(b))
+    }
+
+    #[test]
+    fn permutations() {
+        assert!(equivalent("and(eq(a,1),eq(b,2))", "and(eq(b,2),eq(a,1))"));
+        assert!(equivalent(
+            "or(and(eq(a,1),lt(b,2),gt(c,3)),eq(d,\"x\"))",
+            "or(eq(d,\"x\"),and(gt(c,3),eq(a,1),lt(b,2)))"
+        ));
+        assert!(equivalent(
+            "and(or(eq(a,1),eq(a,1),eq(b,2)),sort(+a))",
+            "and(sort(+a),or(eq(b,2),eq(a,1),eq(a,1)))"
+        ));
+        assert!(equivalent(
+            "and(not(or(eq(a,1),eq(b,2))),eq(c,3))",
+            "and(eq(c,3),not(or(eq(b,2),eq(a,1))))"
+        ));
+    }
+
+    #[test]
+    fn differences() {
+        assert!(!equivalent("and(eq(a,1),eq(b,2))", "and(eq(a,1),eq(b,3))"));
+        assert!(!equivalent("and(eq(a,1),eq(b,2))", "or(eq(a,1),eq(b,2))"));
+        assert!(!equivalent(
+            "and(eq(a,1),eq(b,2))",
+            "and(eq(a,1),eq(b,2),eq(b,2))"
+        ));
+        assert!(!equivalent(
+            "and(eq(a,1),eq(a,1),eq(b,2))",
+            "and(eq(a,1),eq(b,2),eq(b,2))"
+        ));
+        assert!(!equivalent("eq(a,1)", "eq(a,1.0)"));
+        assert!(!equivalent("not(eq(a,1))", "eq(a,1)"));
+        assert!(!equivalent(
+            "or(and(eq(a,1),eq(b,2)))",
+            "or(and(eq(a,1),eq(b,1)))"
+        ));
+        // sort のキーの順序は意味を持つ
+        assert!(!equivalent("sort(+a,-b)", "sort(-b,+a)"));
+    }
+}
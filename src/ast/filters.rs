@@ -0,0 +1,124 @@
+//! Walking the comparisons of a query without writing a visitor.
+
+use crate::ast::{Infix, Query, Value};
+use std::slice;
+
+impl Query {
+    /// The `Filter` leaves of the query, as `(operator, identifier, value)`,
+    /// depth first and left to right: in the order they're written in RQL.
+    /// `and()`, `or()` and `not()` are walked into; every other node,
+    /// `sort()`, `limit()`, `exists()`, `in()` and the like, is skipped.
+    ///
+    /// Only groups nested inside another group allocate,
+    /// for the stack of parents to come back to.
+    ///
+    /// ```
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let query = Parser::from_str("and(eq(a,1),or(gt(b,2),lt(c,3)),sort(+a))")
+    ///     .parse_query()
+    ///     .unwrap();
+    /// let paths: Vec<String> = query.filters().map(|(_, path, _)| path.to_string()).collect();
+    /// assert_eq!(paths, ["a", "b", "c"]);
+    /// ```
+    pub fn filters(&self) -> Filters<'_> {
+        Filters {
+            current: slice::from_ref(self).iter(),
+            parents: vec![],
+        }
+    }
+}
+
+/// The iterator returned by [`Query::filters`].
+#[derive(Debug, Clone)]
+pub struct Filters<'a> {
+    // 今見ている兄弟たちの残り
+    current: slice::Iter<'a, Query>,
+    // 親の兄弟たちの残り。current を読み終えたら戻る
+    parents: Vec<slice::Iter<'a, Query>>,
+}
+
+impl<'a> Iterator for Filters<'a> {
+    type Item = (&'a Infix, &'a Value, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let query = match self.current.next() {
+                Some(query) => query,
+                None => {
+                    self.current = self.parents.pop()?;
+                    continue;
+                }
+            };
+            match query {
+                Query::Filter(infix, ident, value) => return Some((infix, ident, value)),
+                Query::And(_) | Query::Or(_) | Query::Not(_) => {
+                    let queries = match query {
+                        Query::And(queries) | Query::Or(queries) => &queries[..],
+                        Query::Not(query) => slice::from_ref(&**query),
+                        _ => unreachable!(),
+                    };
+                    let parent = std::mem::replace(&mut self.current, queries.iter());
+                    // 親の兄弟が残っていなければ戻る必要はない
+                    if parent.len() > 0 {
+                        self.parents.push(parent);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Infix, Query, Value};
+    use crate::parser::Parser;
+
+    fn leaves(input: &str) -> Vec<String> {
+        Parser::from_str(input)
+            .parse_query()
+            .unwrap()
+            .filters()
+            .map(|(infix, ident, value)| format!("{}({},{})", infix.keyword(), ident, value))
+            .collect()
+    }
+
+    #[test]
+    fn nested() {
+        let input = concat!(
+            "and(eq(a,1),sort(-a),",
+            "or(and(gt(b,2),or(lt(c,3))),exists(d),startswith(e,\"x\")),",
+            "or(),and(and(ne(f,true))),limit(10),le(g,4.5))",
+        );
+        assert_eq!(
+            leaves(input),
+            [
+                "eq(a,1)",
+                "gt(b,2)",
+                "lt(c,3)",
+                "startswith(e,\"x\")",
+                "ne(f,true)",
+                "le(g,4.5)",
+            ]
+        );
+        assert_eq!(leaves("ge(a,1)"), ["ge(a,1)"]);
+        assert_eq!(
+            leaves("and(not(or(eq(a,1),in(b,(2)))),not(gt(c,3)))"),
+            ["eq(a,1)", "gt(c,3)"]
+        );
+        assert!(leaves("sort(+a)").is_empty());
+        assert_eq!(Query::None.filters().count(), 0);
+
+        let query = Query::Filter(
+            Infix::Eq,
+            Value::Identifier("a".into()),
+            Value::IntegerLiteral(1),
+        );
+        let (a, one) = (Value::Identifier("a".into()), Value::IntegerLiteral(1));
+        assert_eq!(
+            query.filters().collect::<Vec<_>>(),
+            [(&Infix::Eq, &a, &one)]
+        );
+    }
+}
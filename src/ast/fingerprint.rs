@@ -0,0 +1,405 @@
+//! A canonical byte encoding of queries, for hashing them and using them as
+//! cache keys despite the floats they hold.
+
+use crate::ast::{Path, Query, Segment, Value};
+use crate::eval::is_shaping;
+use std::hash::{Hash, Hasher};
+
+impl Query {
+    /// A 64-bit hash of the query, the same across process runs, platforms
+    /// and versions of Rust, to key a cache of translated queries with.
+    ///
+    /// Queries that compare equal have the same fingerprint. The order of the
+    /// children of `and()` and `or()` counts, so call
+    /// [`Query::canonicalize`] first and cache on the fingerprint of the
+    /// result: `and(eq(a,1),eq(b,2))` and `and(eq(b,2),eq(a,1))` then share
+    /// an entry. Number literals of different types differ: `eq(a,1)` isn't
+    /// `eq(a,1.0)`, as they don't match the same documents.
+    ///
+    /// ```
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let parse = |s: &str| Parser::new_from_string(s.to_owned()).parse_query().unwrap();
+    /// let a = parse("and(eq(a,1),gt(b,2.5))");
+    /// let b = parse("and(gt(b,2.5),eq(a,1))");
+    /// assert_ne!(a.fingerprint(), b.fingerprint());
+    /// assert_eq!(a.canonicalize().fingerprint(), b.canonicalize().fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        let mut encoded = vec![];
+        self.encode(&mut encoded);
+        fnv1a(&encoded)
+    }
+
+    /// The query with the filters under every `and()` and `or()`, and the
+    /// values of every `in()` and `out()`, in a fixed order, so that queries
+    /// differing only in that order become equal.
+    /// Pipeline stages such as `sort()` and `limit()` go after the filters,
+    /// in their original order, since two of them can depend on it.
+    /// Equal results are [`Query::equivalent`], and match the same documents.
+    pub fn canonicalize(&self) -> Query {
+        match self {
+            Query::And(queries) => Query::And(canonical_children(queries)),
+            Query::Or(queries) => Query::Or(canonical_children(queries)),
+            Query::Not(query) => Query::Not(Box::new(query.canonicalize())),
+            Query::In(path, values) => Query::In(path.clone(), canonical_values(values)),
+            Query::Out(path, values) => Query::Out(path.clone(), canonical_values(values)),
+            query => query.clone(),
+        }
+    }
+
+    // ノードごとに 1 バイトの種類、続けて中身。長さや数値はリトルエンディアン
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Query::And(queries) | Query::Or(queries) => {
+                out.push(if matches!(self, Query::And(_)) { 0 } else { 1 });
+                encode_len(queries.len(), out);
+                queries.iter().for_each(|q| q.encode(out));
+            }
+            Query::Sort(keys) => {
+                out.push(2);
+                encode_len(keys.len(), out);
+                for (prefix, value) in keys {
+                    out.push(prefix.clone() as u8);
+                    value.encode(out);
+                }
+            }
+            Query::Limit(count, offset) => {
+                out.push(3);
+                encode_len(*count, out);
+                encode_len(*offset, out);
+            }
+            Query::Select(paths) => {
+                out.push(4);
+                encode_len(paths.len(), out);
+                paths.iter().for_each(|p| p.encode(out));
+            }
+            Query::Distinct(path) => {
+                out.push(5);
+                match path {
+                    Some(path) => {
+                        out.push(1);
+                        path.encode(out);
+                    }
+                    None => out.push(0),
+                }
+            }
+            Query::Aggregate(keys, functions) => {
+                out.push(6);
+                encode_len(keys.len(), out);
+                keys.iter().for_each(|k| k.encode(out));
+                encode_len(functions.len(), out);
+                for (function, path) in functions {
+                    out.push(*function as u8);
+                    path.encode(out);
+                }
+            }
+            Query::First => out.push(7),
+            Query::One => out.push(8),
+            Query::Count => out.push(9),
+            Query::Reduce(function, path) => {
+                out.push(10);
+                out.push(*function as u8);
+                path.encode(out);
+            }
+            Query::Filter(infix, ident, value) => {
+                out.push(11);
+                out.push(infix.clone() as u8);
+                ident.encode(out);
+                value.encode(out);
+            }
+            Query::Exists(path) => {
+                out.push(12);
+                path.encode(out);
+            }
+            Query::Missing(path) => {
+                out.push(13);
+                path.encode(out);
+            }
+            Query::None => out.push(14),
+            Query::Search(fields, term) => {
+                out.push(15);
+                encode_len(fields.len(), out);
+                fields.iter().for_each(|f| f.encode(out));
+                encode_str(term, out);
+            }
+            Query::Near(path, lat, lon, radius) => {
+                out.push(16);
+                path.encode(out);
+                for n in [lat, lon, radius] {
+                    let n = if *n == 0.0 { 0.0 } else { *n };
+                    out.extend_from_slice(&n.to_bits().to_le_bytes());
+                }
+            }
+            Query::Not(query) => {
+                out.push(17);
+                query.encode(out);
+            }
+            Query::In(path, values) | Query::Out(path, values) => {
+                out.push(if matches!(self, Query::In(..)) { 18 } else { 19 });
+                path.encode(out);
+                encode_len(values.len(), out);
+                values.iter().for_each(|v| v.encode(out));
+            }
+            Query::Contains(path, value) => {
+                out.push(20);
+                path.encode(out);
+                value.encode(out);
+            }
+        }
+    }
+}
+
+impl Value {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Identifier(path) => {
+                out.push(0);
+                path.encode(out);
+            }
+            Value::IntegerLiteral(i) => {
+                out.push(1);
+                out.extend_from_slice(&i.to_le_bytes());
+            }
+            Value::UnsignedLiteral(u) => {
+                out.push(2);
+                out.extend_from_slice(&u.to_le_bytes());
+            }
+            Value::FloatLiteral(f) => {
+                out.push(3);
+                // 0.0 == -0.0 なので同じにする
+                let f = if *f == 0.0 { 0.0 } else { *f };
+                out.extend_from_slice(&f.to_bits().to_le_bytes());
+            }
+            // 1.0 == 1.00 なので正規化する
+            #[cfg(feature = "decimal")]
+            Value::DecimalLiteral(d) => {
+                out.push(4);
+                out.extend_from_slice(&d.normalize().serialize());
+            }
+            Value::StringLiteral(s) => {
+                out.push(5);
+                encode_str(s, out);
+            }
+            // オフセットが違っても同じ時刻なら等しい
+            #[cfg(feature = "chrono")]
+            Value::DateTime(t) => {
+                out.push(6);
+                out.extend_from_slice(&t.timestamp().to_le_bytes());
+                out.extend_from_slice(&t.timestamp_subsec_nanos().to_le_bytes());
+            }
+            #[cfg(feature = "chrono")]
+            Value::RelativeTime(n, unit) => {
+                out.push(7);
+                out.extend_from_slice(&n.to_le_bytes());
+                out.push(unit.suffix() as u8);
+            }
+            Value::Boolean(b) => {
+                out.push(8);
+                out.push(*b as u8);
+            }
+            Value::Call(name) => {
+                out.push(9);
+                encode_str(name, out);
+            }
+            Value::Variable(name) => {
+                out.push(10);
+                encode_str(name, out);
+            }
+        }
+    }
+}
+
+impl Path {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_len(self.segments().len(), out);
+        for segment in self.segments() {
+            match segment {
+                Segment::Key(key) => {
+                    out.push(0);
+                    encode_str(key, out);
+                }
+                Segment::Index(i) => {
+                    out.push(1);
+                    encode_len(*i, out);
+                }
+                Segment::Wildcard => out.push(2),
+            }
+        }
+    }
+}
+
+/// Hashes the same bytes as [`Query::fingerprint`].
+impl Hash for Query {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut encoded = vec![];
+        self.encode(&mut encoded);
+        state.write(&encoded);
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut encoded = vec![];
+        self.encode(&mut encoded);
+        state.write(&encoded);
+    }
+}
+
+// 子を正規化してからエンコードしたバイト列の順に並べる
+// 並べ替えるのは条件だけ。limit() や sort() が 2 つあれば順序で結果が変わる
+fn canonical_children(queries: &[Query]) -> Vec<Query> {
+    let (stages, filters): (Vec<&Query>, Vec<&Query>) =
+        queries.iter().partition(|q| is_shaping(q));
+    let mut keyed: Vec<(Vec<u8>, Query)> = filters
+        .into_iter()
+        .map(|q| {
+            let q = q.canonicalize();
+            let mut encoded = vec![];
+            q.encode(&mut encoded);
+            (encoded, q)
+        })
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    keyed
+        .into_iter()
+        .map(|(_, q)| q)
+        .chain(stages.into_iter().cloned())
+        .collect()
+}
+
+// in() と out() の値は順序によらないので、エンコードしたバイト列の順に並べる
+fn canonical_values(values: &[Value]) -> Vec<Value> {
+    let mut keyed: Vec<(Vec<u8>, &Value)> = values
+        .iter()
+        .map(|v| {
+            let mut encoded = vec![];
+            v.encode(&mut encoded);
+            (encoded, v)
+        })
+        .collect();
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    keyed.into_iter().map(|(_, v)| v.clone()).collect()
+}
+
+// usize の幅はプラットフォームで違うので u64 にする
+fn encode_len(n: usize, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(n as u64).to_le_bytes());
+}
+
+fn encode_str(s: &str, out: &mut Vec<u8>) {
+    encode_len(s.len(), out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+// FNV-1a。DefaultHasher は Rust のバージョンで値が変わりうる
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Query, Value};
+    use crate::parser::Parser;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    fn fingerprint(input: &str) -> u64 {
+        parse(input).fingerprint()
+    }
+
+    #[test]
+    fn equal_queries() {
+        let input = "and(eq(a,1),or(gt(b,2.5),startswith(c,\"x\")),sort(+a,-b),limit(10,5))";
+        assert_eq!(fingerprint(input), fingerprint(input));
+        assert_eq!(fingerprint(input), parse(input).clone().fingerprint());
+        // プロセスや環境が変わっても同じ値
+        assert_eq!(fingerprint("eq(a,1)"), 0xbdeb_3e54_6acb_6ed7);
+
+        let hash = |q: &Query| {
+            let mut hasher = DefaultHasher::new();
+            q.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&parse(input)), hash(&parse(input)));
+        let zero = |f: f64| {
+            Query::Filter(
+                crate::ast::Infix::Eq,
+                Value::Identifier("a".into()),
+                Value::FloatLiteral(f),
+            )
+        };
+        assert_eq!(zero(0.0), zero(-0.0));
+        assert_eq!(zero(0.0).fingerprint(), zero(-0.0).fingerprint());
+    }
+
+    #[test]
+    fn permuted() {
+        let a = parse("and(eq(a,1),or(eq(b,2),eq(c,3)))");
+        let b = parse("and(or(eq(c,3),eq(b,2)),eq(a,1))");
+        assert_ne!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.canonicalize(), b.canonicalize());
+        assert_eq!(
+            a.canonicalize().fingerprint(),
+            b.canonicalize().fingerprint()
+        );
+        assert!(a.canonicalize().equivalent(&a));
+        assert_ne!(
+            parse("and(eq(a,1),eq(b,2))").canonicalize().fingerprint(),
+            parse("or(eq(a,1),eq(b,2))").canonicalize().fingerprint()
+        );
+    }
+
+    #[test]
+    fn permuted_values() {
+        let a = parse("not(and(in(a,(3,1,2)),out(b,(\"y\",\"x\"))))");
+        let b = parse("not(and(out(b,(\"x\",\"y\")),in(a,(1,2,3))))");
+        assert_eq!(a.canonicalize(), b.canonicalize());
+        assert_eq!(
+            a.canonicalize().to_rql(),
+            "not(and(in(a,(1,2,3)),out(b,(\"x\",\"y\"))))"
+        );
+        assert_ne!(
+            parse("in(a,(1,2))").fingerprint(),
+            parse("out(a,(1,2))").fingerprint()
+        );
+    }
+
+    #[test]
+    fn stages_keep_their_order() {
+        let query = parse("and(limit(2),eq(b,1),limit(1),sort(-a),eq(a,1),sort(+b))");
+        assert_eq!(
+            query.canonicalize().to_rql(),
+            "and(eq(a,1),eq(b,1),limit(2),limit(1),sort(-a),sort(+b))"
+        );
+        let docs: Vec<serde_json::Value> =
+            (0..4).map(|i| serde_json::json!({"a": 1, "b": 1, "i": i})).collect();
+        assert_eq!(
+            crate::eval::apply(&query.canonicalize(), docs.clone()).unwrap().documents,
+            crate::eval::apply(&query, docs).unwrap().documents
+        );
+        assert_ne!(
+            parse("and(limit(2),limit(1))").canonicalize(),
+            parse("and(limit(1),limit(2))").canonicalize()
+        );
+    }
+
+    #[test]
+    fn differences() {
+        assert_ne!(fingerprint("eq(a,1)"), fingerprint("eq(a,1.0)"));
+        assert_ne!(fingerprint("eq(a,1)"), fingerprint("eq(a,\"1\")"));
+        assert_ne!(fingerprint("eq(a,1)"), fingerprint("eq(a.b,1)"));
+        assert_ne!(fingerprint("eq(`a.b`,1)"), fingerprint("eq(a.b,1)"));
+        assert_ne!(fingerprint("eq(items.0,1)"), fingerprint("eq(items.`0`,1)"));
+        assert_ne!(fingerprint("sort(+a,-b)"), fingerprint("sort(-b,+a)"));
+        assert_ne!(fingerprint("select(ab)"), fingerprint("select(a,b)"));
+    }
+}
@@ -0,0 +1,189 @@
+//! Building exact-match queries from key-value criteria.
+
+use crate::ast::{Infix, Path, Query, Value};
+use std::convert::{Infallible, TryFrom, TryInto};
+use std::fmt;
+
+/// A value with no RQL literal to compare with, such as a JSON `null`, array or
+/// object.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FromValueError {
+    /// The criterion's key, when the value came through [`Query::from_map`].
+    pub key: Option<String>,
+    /// The value as JSON, or a description of it.
+    pub value: String,
+}
+
+impl fmt::Display for FromValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{} can't be compared with {}", key, self.value),
+            None => write!(f, "{} can't be used as an RQL literal", self.value),
+        }
+    }
+}
+
+impl std::error::Error for FromValueError {}
+
+// From<i64> などの変換は失敗しない
+impl From<Infallible> for FromValueError {
+    fn from(never: Infallible) -> Self {
+        match never {}
+    }
+}
+
+impl TryFrom<serde_json::Value> for Value {
+    type Error = FromValueError;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, FromValueError> {
+        match value {
+            serde_json::Value::String(s) => Ok(Value::StringLiteral(s)),
+            serde_json::Value::Bool(b) => Ok(Value::Boolean(b)),
+            serde_json::Value::Number(n) => Ok(match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => Value::IntegerLiteral(i),
+                (None, Some(u)) => Value::UnsignedLiteral(u),
+                _ => Value::FloatLiteral(n.as_f64().unwrap_or_default()),
+            }),
+            value => Err(FromValueError {
+                key: None,
+                value: value.to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<&serde_json::Value> for Value {
+    type Error = FromValueError;
+
+    fn try_from(value: &serde_json::Value) -> Result<Self, FromValueError> {
+        Value::try_from(value.clone())
+    }
+}
+
+impl Query {
+    /// Turns exact-match criteria, such as a `HashMap<String, serde_json::Value>`
+    /// of request parameters, into `and(eq(k1,v1),eq(k2,v2),...)`. Keys are
+    /// paths (`address.country`) and are sorted, so the query doesn't depend on
+    /// the map's iteration order. One entry gives the single filter and none
+    /// gives [`Query::None`], like [`Query::and_all`].
+    ///
+    /// ```
+    /// use rql_parser::ast::Query;
+    /// use serde_json::json;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut criteria = HashMap::new();
+    /// criteria.insert("status", json!("open"));
+    /// criteria.insert("owner.id", json!(42));
+    /// let query = Query::from_map(criteria).unwrap();
+    /// assert_eq!(query.to_rql(), "and(eq(owner.id,42),eq(status,\"open\"))");
+    /// ```
+    pub fn from_map<K, V, I>(criteria: I) -> Result<Query, FromValueError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: TryInto<Value>,
+        V::Error: Into<FromValueError>,
+    {
+        let mut entries: Vec<(String, V)> = criteria
+            .into_iter()
+            .map(|(key, value)| (key.into(), value))
+            .collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut filters = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let value = value.try_into().map_err(|e| FromValueError {
+                key: Some(key.clone()),
+                ..e.into()
+            })?;
+            filters.push(Query::Filter(
+                Infix::Eq,
+                Value::Identifier(Path::from(key)),
+                value,
+            ));
+        }
+        Ok(Query::and_all(filters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{FromValueError, Infix, Query, Value};
+    use serde_json::json;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn empty() {
+        let criteria: HashMap<String, serde_json::Value> = HashMap::new();
+        assert_eq!(Query::from_map(criteria), Ok(Query::None));
+    }
+
+    #[test]
+    fn single() {
+        assert_eq!(
+            Query::from_map(vec![("age", 18)]),
+            Ok(Query::Filter(
+                Infix::Eq,
+                Value::Identifier("age".into()),
+                Value::IntegerLiteral(18),
+            ))
+        );
+    }
+
+    #[test]
+    fn multiple() {
+        let criteria: HashMap<String, serde_json::Value> = [
+            ("status", json!("open")),
+            ("address.country", json!("JP")),
+            ("score", json!(1.5)),
+            ("active", json!(true)),
+            ("big", json!(u64::MAX)),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+        let query = Query::from_map(criteria).unwrap();
+        assert_eq!(
+            query.to_rql(),
+            "and(eq(active,true),eq(address.country,\"JP\"),eq(big,18446744073709551615),\
+             eq(score,1.5),eq(status,\"open\"))"
+        );
+        assert!(query.matches(&json!({
+            "status": "open",
+            "address": {"country": "JP"},
+            "score": 1.5,
+            "active": true,
+            "big": u64::MAX,
+        })));
+
+        let mut strings = BTreeMap::new();
+        strings.insert("b".to_owned(), "x");
+        strings.insert("a".to_owned(), "y");
+        assert_eq!(
+            Query::from_map(strings).unwrap().to_rql(),
+            "and(eq(a,\"y\"),eq(b,\"x\"))"
+        );
+    }
+
+    #[test]
+    fn unsupported_value() {
+        let mut criteria = HashMap::new();
+        criteria.insert("name", json!("a"));
+        criteria.insert("tags", json!(["x", "y"]));
+        let err = Query::from_map(criteria).unwrap_err();
+        assert_eq!(
+            err,
+            FromValueError {
+                key: Some("tags".to_owned()),
+                value: "[\"x\",\"y\"]".to_owned(),
+            }
+        );
+        assert_eq!(err.to_string(), "tags can't be compared with [\"x\",\"y\"]");
+        assert_eq!(
+            Query::from_map(vec![("deleted_at", json!(null))])
+                .unwrap_err()
+                .to_string(),
+            "deleted_at can't be compared with null"
+        );
+    }
+}
@@ -0,0 +1,882 @@
+//! Whether every document matching one query also matches another, for
+//! deciding if a cached result set can serve a narrower query, and whether a
+//! query can match anything at all.
+
+use crate::ast::{Infix, Path, Query, Segment, Value};
+use std::collections::BTreeMap;
+
+/// The answer of [`Query::implies`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Implication {
+    /// Every document matching the first query matches the second.
+    Implied,
+    /// Some document matches the first query but not the second.
+    NotImplied,
+    /// Neither could be shown.
+    Unknown,
+}
+
+impl Implication {
+    pub fn is_implied(self) -> bool {
+        self == Implication::Implied
+    }
+}
+
+// and() の分配などで調べる組み合わせの上限
+const BUDGET: usize = 10_000;
+// 反例を探すときに試すドキュメントの上限
+const MAX_DOCUMENTS: usize = 4096;
+// 2^53 までの整数は f64 で正確に表せる
+const EXACT: f64 = 9_007_199_254_740_992.0;
+
+impl Query {
+    /// Whether every document [`Query::matches`] accepts is also accepted by
+    /// `other`, with the default evaluation options.
+    ///
+    /// [`Implication::Implied`] is only returned when it's certain. It's
+    /// decided for `and()`/`or()` structure, `exists()`/`missing()`, and
+    /// comparisons of one field with number, string and boolean literals,
+    /// `in()` and `out()` included as the `or()` of `eq()`s and the `and()` of
+    /// `ne()`s they stand for:
+    /// `and(ge(age,18),lt(age,30))` implies `gt(age,10)`, and `eq(a,5)`
+    /// implies `le(a,5)`. Comparisons are typed like in evaluation, so
+    /// `gt(a,5)` (integers only) implies `gt(a,4.5)` but not the other way
+    /// round. [`Implication::NotImplied`] comes with a document matching `self`
+    /// but not `other`, found among values built from the queries' literals.
+    /// Anything else, such as paths with wildcards or a field compared with
+    /// another, is [`Implication::Unknown`].
+    ///
+    /// `sort()`, `limit()` and the other non-filter nodes match every document,
+    /// as in [`Query::matches`].
+    ///
+    /// ```
+    /// use rql_parser::ast::Implication;
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let parse = |s: &str| Parser::new_from_string(s.to_owned()).parse_query().unwrap();
+    /// let narrow = parse("and(eq(status,\"open\"),ge(age,21))");
+    /// let cached = parse("and(eq(status,\"open\"),gt(age,18))");
+    /// assert_eq!(narrow.implies(&cached), Implication::Implied);
+    /// assert_eq!(cached.implies(&narrow), Implication::NotImplied);
+    /// ```
+    pub fn implies(&self, other: &Query) -> Implication {
+        let (a, b) = (expand(self), expand(other));
+        if prove(&a, &b, &mut BUDGET.clone()) {
+            Implication::Implied
+        } else if counterexample(&a, &b).is_some() {
+            Implication::NotImplied
+        } else {
+            Implication::Unknown
+        }
+    }
+
+    /// Whether no document can match, such as
+    /// `and(eq(status,"open"),eq(status,"closed"))` or
+    /// `and(gt(x,10),lt(x,5))`.
+    ///
+    /// `true` is certain; `false` means no conflict was found. Conflicts are
+    /// found between conditions on the same field: different `eq()` values,
+    /// an empty range (`eq()` counts as both bounds, and integer literals only
+    /// match integers, so `and(gt(x,1),lt(x,2))` is empty), `ne()` excluding
+    /// the only value left, values of different types, and `exists()` with
+    /// `missing()`. An `or()` is unsatisfiable when all its branches are.
+    ///
+    /// ```
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let parse = |s: &str| Parser::new_from_string(s.to_owned()).parse_query().unwrap();
+    /// assert!(parse("and(ge(x,10),or(lt(x,5),eq(x,3)))").is_unsatisfiable());
+    /// assert!(!parse("and(ge(x,10),or(lt(x,5),eq(x,30)))").is_unsatisfiable());
+    /// ```
+    pub fn is_unsatisfiable(&self) -> bool {
+        // 何にも一致しない or() を含意するなら、一致するドキュメントは無い
+        prove(&expand(self), &Query::Or(vec![]), &mut BUDGET.clone())
+    }
+}
+
+// in() と out() を eq() の or() と ne() の and() に開く
+fn expand(query: &Query) -> Query {
+    match query {
+        Query::And(queries) => Query::And(queries.iter().map(expand).collect()),
+        Query::Or(queries) => Query::Or(queries.iter().map(expand).collect()),
+        Query::Not(query) => Query::Not(Box::new(expand(query))),
+        Query::In(..) | Query::Out(..) => query.expand_in(),
+        _ => query.clone(),
+    }
+}
+
+// a に一致するものがすべて b に一致することを示せたら true。
+// false は「示せなかった」で、成り立たないとは限らない
+fn prove(a: &Query, b: &Query, budget: &mut usize) -> bool {
+    if *budget == 0 {
+        return false;
+    }
+    *budget -= 1;
+    if let Query::Or(branches) = a {
+        return branches.iter().all(|x| prove(x, b, budget));
+    }
+    match b {
+        Query::And(queries) => return queries.iter().all(|y| prove(a, y, budget)),
+        Query::Or(queries) if queries.iter().any(|y| prove(a, y, budget)) => return true,
+        Query::Or(_)
+        | Query::Not(_)
+        | Query::In(..)
+        | Query::Out(..)
+        | Query::Contains(..)
+        | Query::Filter(..)
+        | Query::Exists(_)
+        | Query::Missing(_)
+        | Query::Search(..)
+        | Query::Near(..) => {}
+        // sort() などはすべてに一致する
+        _ => return true,
+    }
+    let mut conjuncts = vec![];
+    flatten(a, &mut conjuncts);
+    if conjuncts.iter().any(|c| c.equivalent(b)) {
+        return true;
+    }
+    // or() を無視した条件は a より広いので、それで示せれば十分
+    let fields = constraints(&conjuncts);
+    if fields.values().any(Field::is_empty) {
+        return true;
+    }
+    if let Some((path, atom)) = atom(b) {
+        if implied(&fields, &path, &atom) {
+            return true;
+        }
+    }
+    // 最初の or() を分配し、枝ごとに示す
+    let i = match conjuncts.iter().position(|c| matches!(c, Query::Or(_))) {
+        Some(i) => i,
+        None => return false,
+    };
+    let branches = match conjuncts[i] {
+        Query::Or(branches) => branches,
+        _ => unreachable!(),
+    };
+    branches.iter().all(|branch| {
+        let mut rest: Vec<Query> = conjuncts
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, c)| (*c).clone())
+            .collect();
+        rest.push(branch.clone());
+        prove(&Query::And(rest), b, budget)
+    })
+}
+
+fn flatten<'a>(query: &'a Query, out: &mut Vec<&'a Query>) {
+    match query {
+        Query::And(queries) => queries.iter().for_each(|q| flatten(q, out)),
+        q => out.push(q),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Literal {
+    // (値, 整数リテラルか)。整数リテラルは整数の値にしか一致しない
+    Number(f64, bool),
+    String(String),
+    Bool(bool),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Kind {
+    Number,
+    String,
+    Bool,
+}
+
+impl Literal {
+    fn kind(&self) -> Kind {
+        match self {
+            Literal::Number(..) => Kind::Number,
+            Literal::String(_) => Kind::String,
+            Literal::Bool(_) => Kind::Bool,
+        }
+    }
+}
+
+enum Atom {
+    Exists,
+    Missing,
+    Compare(Infix, Literal),
+}
+
+// キーだけからなるパスの単純な条件。それ以外は扱わない
+fn atom(query: &Query) -> Option<(Vec<String>, Atom)> {
+    match query {
+        Query::Exists(path) => Some((keys(path)?, Atom::Exists)),
+        Query::Missing(path) => Some((keys(path)?, Atom::Missing)),
+        Query::Filter(infix, Value::Identifier(path), value) => {
+            Some((keys(path)?, Atom::Compare(infix.clone(), literal(value)?)))
+        }
+        _ => None,
+    }
+}
+
+fn keys(path: &Path) -> Option<Vec<String>> {
+    path.segments()
+        .iter()
+        .map(|segment| match segment {
+            Segment::Key(key) => Some(key.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn literal(value: &Value) -> Option<Literal> {
+    match value {
+        Value::StringLiteral(s) => Some(Literal::String(s.clone())),
+        Value::Boolean(b) => Some(Literal::Bool(*b)),
+        value => number(value).map(|(n, integer)| Literal::Number(n, integer)),
+    }
+}
+
+// 数値リテラルを (値, 整数リテラルか) にする。区間の比較は f64 で行うので、
+// f64 で正確に表せるものだけ扱う
+pub(crate) fn number(value: &Value) -> Option<(f64, bool)> {
+    match value {
+        Value::IntegerLiteral(i) if (*i as f64).abs() <= EXACT => Some((*i as f64, true)),
+        Value::UnsignedLiteral(u) if (*u as f64) <= EXACT => Some((*u as f64, true)),
+        Value::FloatLiteral(f) if !f.is_nan() => Some((*f, false)),
+        #[cfg(feature = "decimal")]
+        Value::DecimalLiteral(d) => {
+            use rust_decimal::prelude::ToPrimitive;
+            let f = d.to_f64()?;
+            match f.to_string().parse::<rust_decimal::Decimal>() {
+                Ok(exact) if exact == *d => Some((f, false)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn holds(infix: &Infix, x: f64, n: f64) -> bool {
+    match infix {
+        Infix::Eq => x == n,
+        Infix::NotEq => x != n,
+        Infix::Lt => x < n,
+        Infix::Le => x <= n,
+        Infix::Gt => x > n,
+        Infix::Ge => x >= n,
+        Infix::StartsWith
+        | Infix::EndsWith
+        | Infix::IEq
+        | Infix::IContains
+        | Infix::IStartsWith => false,
+    }
+}
+
+// 1つのフィールドに対する条件の積
+#[derive(Debug, Default, Clone)]
+struct Field {
+    // exists() か比較がある (null でない値がある)
+    present: bool,
+    missing: bool,
+    kind: Option<Kind>,
+    integer: bool,
+    // (値, 含むか)
+    lower: Option<(f64, bool)>,
+    upper: Option<(f64, bool)>,
+    excluded: Vec<Literal>,
+    string: Option<String>,
+    boolean: Option<bool>,
+    prefixes: Vec<String>,
+    suffixes: Vec<String>,
+    // lt(a,"x") のように何にも一致しない条件がある
+    nothing: bool,
+}
+
+fn constraints(conjuncts: &[&Query]) -> BTreeMap<Vec<String>, Field> {
+    let mut fields: BTreeMap<Vec<String>, Field> = BTreeMap::new();
+    for (path, atom) in conjuncts.iter().filter_map(|c| atom(c)) {
+        fields.entry(path).or_default().add(&atom);
+    }
+    fields
+}
+
+impl Field {
+    fn add(&mut self, atom: &Atom) {
+        let (infix, literal) = match atom {
+            Atom::Exists => return self.present = true,
+            Atom::Missing => return self.missing = true,
+            Atom::Compare(infix, literal) => (infix, literal),
+        };
+        self.present = true;
+        if self.kind.is_some_and(|kind| kind != literal.kind()) {
+            self.nothing = true;
+            return;
+        }
+        self.kind = Some(literal.kind());
+        match (infix, literal) {
+            (Infix::NotEq, _) => self.excluded.push(literal.clone()),
+            (
+                Infix::StartsWith
+                | Infix::EndsWith
+                | Infix::IEq
+                | Infix::IContains
+                | Infix::IStartsWith,
+                Literal::Number(..),
+            ) => self.nothing = true,
+            // 大文字小文字を区別しないものは、文字列であることしか使わない
+            (Infix::IEq | Infix::IContains | Infix::IStartsWith, Literal::String(_)) => {}
+            (_, Literal::Number(n, integer)) => {
+                self.integer |= *integer;
+                let n = *n;
+                match infix {
+                    Infix::Eq => {
+                        self.raise((n, true));
+                        self.lower_to((n, true));
+                    }
+                    Infix::Lt => self.lower_to((n, false)),
+                    Infix::Le => self.lower_to((n, true)),
+                    Infix::Gt => self.raise((n, false)),
+                    _ => self.raise((n, true)),
+                }
+            }
+            (Infix::Eq, Literal::String(s)) => {
+                if self.string.as_ref().is_some_and(|x| x != s) {
+                    self.nothing = true;
+                }
+                self.string = Some(s.clone());
+            }
+            (Infix::StartsWith, Literal::String(s)) => self.prefixes.push(s.clone()),
+            (Infix::EndsWith, Literal::String(s)) => self.suffixes.push(s.clone()),
+            (Infix::Eq, Literal::Bool(b)) => {
+                if self.boolean.is_some_and(|x| x != *b) {
+                    self.nothing = true;
+                }
+                self.boolean = Some(*b);
+            }
+            // 文字列や真偽値の大小比較は何にも一致しない
+            _ => self.nothing = true,
+        }
+    }
+
+    fn raise(&mut self, bound: (f64, bool)) {
+        self.lower = match self.lower {
+            Some(lower) if lower.0 > bound.0 || (lower.0 == bound.0 && !lower.1) => Some(lower),
+            _ => Some(bound),
+        };
+    }
+
+    fn lower_to(&mut self, bound: (f64, bool)) {
+        self.upper = match self.upper {
+            Some(upper) if upper.0 < bound.0 || (upper.0 == bound.0 && !upper.1) => Some(upper),
+            _ => Some(bound),
+        };
+    }
+
+    // 整数しか取らないときの、両端を含む範囲
+    fn integer_range(&self) -> (f64, f64) {
+        let lower = match self.lower {
+            Some((v, true)) => v.ceil(),
+            Some((v, false)) => v.floor() + 1.0,
+            None => f64::NEG_INFINITY,
+        };
+        let upper = match self.upper {
+            Some((v, true)) => v.floor(),
+            Some((v, false)) => v.ceil() - 1.0,
+            None => f64::INFINITY,
+        };
+        (lower, upper)
+    }
+
+    fn excludes(&self, x: f64) -> bool {
+        self.excluded
+            .iter()
+            .any(|e| matches!(e, Literal::Number(n, _) if *n == x))
+    }
+
+    // 取りうる数値が少なければ列挙する。実数では1点に決まるときだけ
+    fn elements(&self) -> Option<Vec<f64>> {
+        let (lower, upper) = if self.integer {
+            self.integer_range()
+        } else {
+            match (self.lower, self.upper) {
+                (Some((l, true)), Some((u, true))) if l == u => (l, u),
+                _ => return None,
+            }
+        };
+        if !lower.is_finite() || !upper.is_finite() || upper - lower > 64.0 {
+            return None;
+        }
+        let mut elements = vec![];
+        let mut x = lower;
+        while x <= upper {
+            if !self.excludes(x) {
+                elements.push(x);
+            }
+            x += 1.0;
+        }
+        Some(elements)
+    }
+
+    // どのドキュメントも満たせないことが確かなら true
+    fn is_empty(&self) -> bool {
+        if self.nothing || (self.missing && self.present) {
+            return true;
+        }
+        match self.kind {
+            Some(Kind::Number) => {
+                if let Some(elements) = self.elements() {
+                    return elements.is_empty();
+                }
+                let (lower, upper) = if self.integer {
+                    let (lower, upper) = self.integer_range();
+                    ((lower, true), (upper, true))
+                } else {
+                    (
+                        self.lower.unwrap_or((f64::NEG_INFINITY, true)),
+                        self.upper.unwrap_or((f64::INFINITY, true)),
+                    )
+                };
+                // JSON の数値は有限
+                lower.0 > upper.0
+                    || (lower.0 == upper.0 && !(lower.1 && upper.1))
+                    || lower.0 == f64::INFINITY
+                    || upper.0 == f64::NEG_INFINITY
+            }
+            Some(Kind::String) => match &self.string {
+                Some(s) => {
+                    self.excluded.contains(&Literal::String(s.clone()))
+                        || self.prefixes.iter().any(|p| !s.starts_with(p.as_str()))
+                        || self.suffixes.iter().any(|p| !s.ends_with(p.as_str()))
+                }
+                None => false,
+            },
+            Some(Kind::Bool) => match self.boolean {
+                Some(b) => self.excluded.contains(&Literal::Bool(b)),
+                None => {
+                    self.excluded.contains(&Literal::Bool(true))
+                        && self.excluded.contains(&Literal::Bool(false))
+                }
+            },
+            None => false,
+        }
+    }
+
+    // このフィールドの条件を満たす値がすべて比較を満たすか
+    fn implies(&self, infix: &Infix, literal: &Literal) -> bool {
+        if self.kind != Some(literal.kind()) {
+            return false;
+        }
+        match literal {
+            Literal::Number(n, integer) => {
+                let n = *n;
+                if *integer && !self.integer {
+                    return false;
+                }
+                if let Some(elements) = self.elements() {
+                    return elements.iter().all(|x| holds(infix, *x, n));
+                }
+                if self.integer {
+                    let (lower, upper) = self.integer_range();
+                    return match infix {
+                        Infix::NotEq => {
+                            n < lower || n > upper || n.fract() != 0.0 || self.excludes(n)
+                        }
+                        Infix::Lt => upper < n,
+                        Infix::Le => upper <= n,
+                        Infix::Gt => lower > n,
+                        Infix::Ge => lower >= n,
+                        _ => false,
+                    };
+                }
+                match (infix, self.lower, self.upper) {
+                    (Infix::NotEq, lower, upper) => {
+                        self.excludes(n)
+                            || lower.is_some_and(|(l, inclusive)| n < l || (n == l && !inclusive))
+                            || upper.is_some_and(|(u, inclusive)| n > u || (n == u && !inclusive))
+                    }
+                    (Infix::Lt, _, Some((u, inclusive))) => u < n || (u == n && !inclusive),
+                    (Infix::Le, _, Some((u, _))) => u <= n,
+                    (Infix::Gt, Some((l, inclusive)), _) => l > n || (l == n && !inclusive),
+                    (Infix::Ge, Some((l, _)), _) => l >= n,
+                    _ => false,
+                }
+            }
+            Literal::String(s) => match infix {
+                Infix::Eq => self.string.as_ref() == Some(s),
+                Infix::NotEq => {
+                    self.string.as_ref().is_some_and(|x| x != s)
+                        || self.excluded.contains(literal)
+                        || self.prefixes.iter().any(|p| !s.starts_with(p.as_str()))
+                        || self.suffixes.iter().any(|p| !s.ends_with(p.as_str()))
+                }
+                Infix::StartsWith => {
+                    self.string
+                        .as_ref()
+                        .is_some_and(|x| x.starts_with(s.as_str()))
+                        || self.prefixes.iter().any(|p| p.starts_with(s.as_str()))
+                }
+                Infix::EndsWith => {
+                    self.string
+                        .as_ref()
+                        .is_some_and(|x| x.ends_with(s.as_str()))
+                        || self.suffixes.iter().any(|p| p.ends_with(s.as_str()))
+                }
+                _ => false,
+            },
+            Literal::Bool(b) => match infix {
+                Infix::Eq => self.boolean == Some(*b) || self.excluded.contains(&Literal::Bool(!b)),
+                Infix::NotEq => {
+                    self.boolean == Some(!b) || self.excluded.contains(&Literal::Bool(*b))
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+fn implied(fields: &BTreeMap<Vec<String>, Field>, path: &[String], atom: &Atom) -> bool {
+    match atom {
+        // a.b に値があれば a はオブジェクト
+        Atom::Exists => fields.iter().any(|(p, f)| f.present && p.starts_with(path)),
+        // a が無いか、オブジェクト以外の値なら a.b も無い
+        Atom::Missing => fields.iter().any(|(p, f)| {
+            path.starts_with(p) && (f.missing || (p.len() < path.len() && f.kind.is_some()))
+        }),
+        Atom::Compare(infix, literal) => {
+            fields.get(path).is_some_and(|f| f.implies(infix, literal))
+        }
+    }
+}
+
+// a に一致し b に一致しないドキュメントを、クエリ中のリテラルから作った値で探す
+fn counterexample(a: &Query, b: &Query) -> Option<serde_json::Value> {
+    let mut literals: BTreeMap<Vec<String>, Vec<Literal>> = BTreeMap::new();
+    collect(a, &mut literals);
+    collect(b, &mut literals);
+    let mut conjuncts = vec![];
+    flatten(a, &mut conjuncts);
+    let mut candidates: Vec<(Vec<String>, Vec<Option<serde_json::Value>>)> = vec![];
+    for (path, literals) in literals {
+        // a の (最上位の) 条件でこのフィールドだけで満たせないものは除く
+        let checks: Vec<&Query> = conjuncts
+            .iter()
+            .filter(|c| atom(c).is_some_and(|(p, _)| p == path))
+            .copied()
+            .collect();
+        let values: Vec<Option<serde_json::Value>> = values(&literals)
+            .into_iter()
+            .filter(|value| {
+                let doc = document(&[(&path, value)]).unwrap_or_default();
+                checks.iter().all(|c| c.matches(&doc))
+            })
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        candidates.push((path, values));
+    }
+    // 各フィールドの候補の組み合わせを順に試す
+    let mut choice = vec![0; candidates.len()];
+    for _ in 0..MAX_DOCUMENTS {
+        let fields: Vec<(&Vec<String>, &Option<serde_json::Value>)> = candidates
+            .iter()
+            .zip(&choice)
+            .map(|((path, values), &i)| (path, &values[i]))
+            .collect();
+        if let Some(doc) = document(&fields) {
+            if a.matches(&doc) && !b.matches(&doc) {
+                return Some(doc);
+            }
+        }
+        let mut i = 0;
+        loop {
+            if i == choice.len() {
+                return None;
+            }
+            choice[i] += 1;
+            if choice[i] < candidates[i].1.len() {
+                break;
+            }
+            choice[i] = 0;
+            i += 1;
+        }
+    }
+    None
+}
+
+fn collect(query: &Query, literals: &mut BTreeMap<Vec<String>, Vec<Literal>>) {
+    match query {
+        Query::And(queries) | Query::Or(queries) => {
+            queries.iter().for_each(|q| collect(q, literals))
+        }
+        Query::Not(q) => collect(q, literals),
+        q => {
+            if let Some((path, atom)) = atom(q) {
+                let entry = literals.entry(path).or_default();
+                if let Atom::Compare(_, literal) = atom {
+                    entry.push(literal);
+                }
+            }
+        }
+    }
+}
+
+// 無い (None)、null、リテラルそのものと、その前後の値
+fn values(literals: &[Literal]) -> Vec<Option<serde_json::Value>> {
+    let mut values = vec![None, Some(serde_json::Value::Null)];
+    let mut push = |value: serde_json::Value| {
+        if !values.contains(&Some(value.clone())) {
+            values.push(Some(value));
+        }
+    };
+    for literal in literals {
+        match literal {
+            Literal::Number(n, _) => {
+                for x in &[*n, n - 1.0, n + 1.0, n - 0.5, n + 0.5] {
+                    if x.fract() == 0.0 && x.abs() <= EXACT {
+                        push(serde_json::json!(*x as i64));
+                    }
+                    if let Some(f) = serde_json::Number::from_f64(*x) {
+                        push(serde_json::Value::Number(f));
+                    }
+                }
+            }
+            Literal::String(s) => {
+                push(serde_json::json!(s));
+                push(serde_json::json!(format!("{}~", s)));
+                push(serde_json::json!(format!("~{}", s)));
+            }
+            Literal::Bool(_) => {
+                push(serde_json::json!(true));
+                push(serde_json::json!(false));
+            }
+        }
+    }
+    if literals.is_empty() {
+        push(serde_json::json!(0));
+    }
+    values
+}
+
+// パスに値を置いたドキュメント。a に値があるのに a.b にも置こうとすると None
+fn document(fields: &[(&Vec<String>, &Option<serde_json::Value>)]) -> Option<serde_json::Value> {
+    let mut doc = serde_json::Map::new();
+    for (path, value) in fields {
+        let value = match value {
+            Some(value) => value,
+            None => continue,
+        };
+        let (last, parents) = path.split_last()?;
+        let mut object = &mut doc;
+        for key in parents {
+            let next = object
+                .entry(key.clone())
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+            object = next.as_object_mut()?;
+        }
+        if object.contains_key(last) {
+            return None;
+        }
+        object.insert(last.clone(), value.clone());
+    }
+    Some(serde_json::Value::Object(doc))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::implies::counterexample;
+    use crate::ast::{Implication, Query};
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    fn implies(a: &str, b: &str) -> Implication {
+        parse(a).implies(&parse(b))
+    }
+
+    #[test]
+    fn intervals() {
+        use Implication::*;
+        let cases = [
+            ("gt(a,5)", "gt(a,3)", Implied),
+            ("gt(a,5)", "ge(a,5)", Implied),
+            ("ge(a,5)", "gt(a,5)", NotImplied),
+            ("gt(a,3)", "gt(a,5)", NotImplied),
+            ("lt(a,3)", "le(a,3)", Implied),
+            ("le(a,3)", "lt(a,3)", NotImplied),
+            ("and(ge(a,18),lt(a,30))", "and(gt(a,10),le(a,30))", Implied),
+            ("and(ge(a,18),lt(a,30))", "lt(a,29)", NotImplied),
+            // 整数だけなら lt(a,30) は le(a,29) と同じ
+            ("and(ge(a,18),lt(a,30))", "le(a,29)", Implied),
+            ("and(gt(a,1),lt(a,3))", "eq(a,2)", Implied),
+            ("and(gt(a,1.0),lt(a,3.0))", "eq(a,2.0)", NotImplied),
+            ("and(ge(a,1),le(a,3),ne(a,2),ne(a,3))", "eq(a,1)", Implied),
+            ("eq(a,5)", "ge(a,5)", Implied),
+            ("eq(a,5)", "le(a,5)", Implied),
+            ("eq(a,5)", "ne(a,4)", Implied),
+            ("eq(a,5)", "ne(a,5)", NotImplied),
+            ("eq(a,5)", "eq(a,5.0)", Implied),
+            // 5.0 のような浮動小数点数は整数の比較に一致しない
+            ("eq(a,5.0)", "eq(a,5)", NotImplied),
+            ("gt(a,5)", "gt(a,4.5)", Implied),
+            ("gt(a,4.5)", "gt(a,4)", NotImplied),
+            ("gt(a,4.5)", "gt(a,4.0)", Implied),
+            ("lt(a,3)", "ne(a,2.5)", Implied),
+            ("gt(a,5)", "gt(b,5)", NotImplied),
+            ("gt(a,5)", "exists(a)", Implied),
+            ("exists(a)", "gt(a,5)", NotImplied),
+        ];
+        for (a, b, expected) in &cases {
+            assert_eq!(implies(a, b), *expected, "{} => {}", a, b);
+        }
+    }
+
+    #[test]
+    fn strings_and_booleans() {
+        use Implication::*;
+        let cases = [
+            ("eq(name,\"alice\")", "ne(name,\"bob\")", Implied),
+            ("eq(name,\"alice\")", "startswith(name,\"al\")", Implied),
+            (
+                "startswith(name,\"alice\")",
+                "startswith(name,\"al\")",
+                Implied,
+            ),
+            (
+                "startswith(name,\"al\")",
+                "startswith(name,\"alice\")",
+                NotImplied,
+            ),
+            (
+                "endswith(mail,\".co.jp\")",
+                "endswith(mail,\".jp\")",
+                Implied,
+            ),
+            ("ne(name,\"bob\")", "eq(name,\"alice\")", NotImplied),
+            ("eq(active,true)", "ne(active,false)", Implied),
+            ("ne(active,false)", "eq(active,true)", Implied),
+            ("eq(active,true)", "eq(active,false)", NotImplied),
+            // 型の違う条件は同時に満たせない
+            ("and(eq(a,1),eq(a,\"x\"))", "eq(b,2)", Implied),
+            ("eq(a,\"x\")", "lt(a,\"y\")", NotImplied),
+        ];
+        for (a, b, expected) in &cases {
+            assert_eq!(implies(a, b), *expected, "{} => {}", a, b);
+        }
+    }
+
+    #[test]
+    fn structure() {
+        use Implication::*;
+        let cases = [
+            ("and(eq(a,1),eq(b,2))", "eq(a,1)", Implied),
+            ("eq(a,1)", "and(eq(a,1),eq(b,2))", NotImplied),
+            ("eq(a,1)", "or(eq(a,1),eq(b,2))", Implied),
+            ("or(eq(a,1),eq(a,2))", "le(a,2)", Implied),
+            ("or(eq(a,1),eq(a,3))", "le(a,2)", NotImplied),
+            ("in(a,(1,2))", "le(a,2)", Implied),
+            ("eq(a,3)", "out(a,(1,2))", Implied),
+            ("out(a,(1))", "in(a,(2))", NotImplied),
+            ("not(eq(a,1))", "ne(a,1)", NotImplied),
+            ("eq(a,1)", "not(eq(a,2))", Unknown),
+            ("and(gt(a,0),or(lt(a,5),eq(b,1)))", "ge(a,1)", Implied),
+            (
+                "and(or(eq(a,1),eq(a,2)),or(eq(b,1),eq(b,2)))",
+                "and(le(a,2),ge(b,1))",
+                Implied,
+            ),
+            ("and(eq(a,1),missing(a))", "eq(z,\"anything\")", Implied),
+            ("or()", "eq(a,1)", Implied),
+            ("eq(a,1)", "and()", Implied),
+            ("eq(a,1)", "sort(+a)", Implied),
+            ("and(sort(+a),gt(a,2),limit(10))", "gt(a,1)", Implied),
+            ("missing(a)", "missing(a.b)", Implied),
+            ("eq(a,1)", "missing(a.b)", Implied),
+            ("exists(a.b)", "exists(a)", Implied),
+            ("exists(a)", "exists(a.b)", NotImplied),
+            ("and(eq(a,b),eq(c,1))", "and(eq(c,1),eq(a,b))", Implied),
+            // 右辺の識別子やワイルドカードは解析しない
+            ("eq(a,b)", "exists(a)", Unknown),
+            ("gt(tags.*,5)", "gt(tags.*,3)", Unknown),
+        ];
+        for (a, b, expected) in &cases {
+            assert_eq!(implies(a, b), *expected, "{} => {}", a, b);
+        }
+    }
+
+    #[test]
+    fn unsatisfiable() {
+        for query in &[
+            "and(eq(status,\"open\"),eq(status,\"closed\"))",
+            "and(gt(x,10),lt(x,5))",
+            "and(ge(x,5),lt(x,5))",
+            "and(gt(x,1),lt(x,2))",
+            "and(gt(x,1.5),lt(x,1.5))",
+            "and(eq(x,3),gt(x,3))",
+            "and(eq(x,3),eq(x,4))",
+            "and(eq(x,3),ne(x,3))",
+            "and(ge(x,1),le(x,2),ne(x,1),ne(x,2))",
+            "and(eq(x,1),eq(x,\"1\"))",
+            "and(eq(done,true),ne(done,true))",
+            "and(ne(done,true),ne(done,false))",
+            "and(startswith(name,\"a\"),eq(name,\"bob\"))",
+            "and(exists(x),missing(x))",
+            "and(missing(x),gt(x,1))",
+            "lt(name,\"x\")",
+            "or(and(eq(x,1),eq(x,2)),and(gt(y,5),lt(y,0)))",
+            "and(or(eq(x,1),eq(x,2)),gt(x,3))",
+            "and(eq(y,1),and(lt(x,0),gt(x,0)))",
+            "and(in(x,(1,2)),gt(x,3))",
+            "and(in(x,(1)),out(x,(1,2)))",
+            "or()",
+        ] {
+            assert!(parse(query).is_unsatisfiable(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn satisfiable() {
+        for query in &[
+            "eq(status,\"open\")",
+            "and(eq(status,\"open\"),eq(kind,\"closed\"))",
+            "and(gt(x,5),lt(x,10))",
+            "and(ge(x,5),le(x,5))",
+            "and(gt(x,1.0),lt(x,2.0))",
+            "and(eq(x,3),ge(x,3))",
+            "and(eq(x,3),eq(x,3.0))",
+            "and(ge(x,1),le(x,2),ne(x,1))",
+            "and(ne(done,true),ne(done,true))",
+            "and(startswith(name,\"a\"),eq(name,\"alice\"))",
+            "and(exists(x),ne(x,1))",
+            "and(missing(x),exists(y))",
+            "or(and(eq(x,1),eq(x,2)),eq(y,3))",
+            "and(or(eq(x,1),eq(x,5)),gt(x,3))",
+            "and(in(x,(1,5)),gt(x,3))",
+            "and(not(eq(x,1)),eq(x,1))",
+            // 別のフィールドを参照する比較は解析しない
+            "and(gt(x,y),lt(x,y))",
+            "and()",
+            "sort(+x)",
+        ] {
+            assert!(!parse(query).is_unsatisfiable(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn counterexamples() {
+        for (a, b) in &[
+            ("ge(a,5)", "gt(a,5)"),
+            ("eq(a,5.0)", "eq(a,5)"),
+            ("exists(a)", "exists(a.b)"),
+            ("startswith(name,\"al\")", "startswith(name,\"alice\")"),
+            ("in(a,(1,2))", "lt(a,2)"),
+        ] {
+            let (a, b) = (parse(a), parse(b));
+            let doc = counterexample(&a, &b).unwrap();
+            assert!(a.matches(&doc) && !b.matches(&doc), "{}", doc);
+        }
+    }
+}
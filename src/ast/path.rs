@@ -0,0 +1,315 @@
+//! Property paths such as `speed.max`, `items.0.name` or `tags.*`, split into
+//! segments once when the query is parsed.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+mod jsonpath;
+
+pub(crate) use jsonpath::SUPPORTED as JSONPATH_SUPPORTED;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Segment {
+    Key(String),
+    // 配列の位置。数字だけのキーを持つオブジェクトではそのキー
+    Index(usize),
+    // 配列の全要素、またはオブジェクトの全ての値
+    Wildcard,
+}
+
+/// A dotted property path split into segments.
+///
+/// A segment of digits (`items.0`) is an index and `*` a wildcard. Keys
+/// containing dots or other characters identifiers can't have are quoted with
+/// backticks, doubling any backtick inside: ``meta.`content.type` ``.
+///
+/// A filter on a path with wildcards matches when any of the values it reaches
+/// does: `eq(tags.*,"rust")` matches `{"tags": ["go", "rust"]}`.
+///
+/// ```
+/// use rql_parser::ast::{Path, Segment};
+///
+/// let path = Path::from("items.0.`unit.price`");
+/// assert_eq!(
+///     path.segments(),
+///     &[
+///         Segment::Key("items".to_owned()),
+///         Segment::Index(0),
+///         Segment::Key("unit.price".to_owned()),
+///     ]
+/// );
+/// let doc = serde_json::json!({"items": [{"unit.price": 3}]});
+/// assert_eq!(path.resolve(&doc), Some(&serde_json::json!(3)));
+/// assert_eq!(path.to_string(), "items.0.`unit.price`");
+/// ```
+#[derive(PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct Path {
+    segments: Vec<Segment>,
+}
+
+impl Path {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        Path { segments }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn has_wildcard(&self) -> bool {
+        self.segments.contains(&Segment::Wildcard)
+    }
+
+    /// The value at this path in `doc`; the first one in document order when
+    /// the path has wildcards.
+    pub fn resolve<'a>(&self, doc: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        let mut found = None;
+        self.visit(doc, &mut |v| {
+            found = Some(v);
+            true
+        });
+        found
+    }
+
+    /// Every value this path reaches in `doc`, in document order.
+    pub fn resolve_all<'a>(&self, doc: &'a serde_json::Value) -> Vec<&'a serde_json::Value> {
+        let mut found = vec![];
+        self.visit(doc, &mut |v| {
+            found.push(v);
+            false
+        });
+        found
+    }
+
+    // 見つかった値ごとに f を呼び、f が true を返したらそこでやめる
+    pub(crate) fn visit<'a>(
+        &self,
+        doc: &'a serde_json::Value,
+        f: &mut dyn FnMut(&'a serde_json::Value) -> bool,
+    ) -> bool {
+        visit(doc, &self.segments, f)
+    }
+}
+
+fn visit<'a>(
+    v: &'a serde_json::Value,
+    segments: &[Segment],
+    f: &mut dyn FnMut(&'a serde_json::Value) -> bool,
+) -> bool {
+    use serde_json::Value::{Array, Object};
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return f(v),
+    };
+    let child = match (segment, v) {
+        (Segment::Key(key), _) => v.get(key.as_str()),
+        (Segment::Index(i), Array(items)) => items.get(*i),
+        (Segment::Index(i), Object(map)) => map.get(&i.to_string()),
+        (Segment::Wildcard, Array(items)) => return items.iter().any(|v| visit(v, rest, f)),
+        (Segment::Wildcard, Object(map)) => return map.values().any(|v| visit(v, rest, f)),
+        _ => None,
+    };
+    child.is_some_and(|child| visit(child, rest, f))
+}
+
+fn segment(key: String, quoted: bool) -> Segment {
+    if quoted {
+        return Segment::Key(key);
+    }
+    match index(&key) {
+        Some(i) => Segment::Index(i),
+        None if key == "*" => Segment::Wildcard,
+        None => Segment::Key(key),
+    }
+}
+
+// 01 のように書き戻すと変わってしまうものはキーのまま
+fn index(key: &str) -> Option<usize> {
+    key.parse()
+        .ok()
+        .filter(|i: &usize| i.to_string() == key)
+}
+
+fn needs_quotes(key: &str, first: bool) -> bool {
+    // 先頭の $ は変数、数字は数値として読まれてしまう
+    (first && key.starts_with(|c: char| c == '$' || c.is_ascii_digit()))
+        || key == "*"
+        || index(key).is_some()
+        || key
+            .chars()
+            .any(|c| !(c.is_alphabetic() || c.is_ascii_digit() || matches!(c, '_' | '$' | '*')))
+}
+
+impl From<&str> for Path {
+    fn from(s: &str) -> Self {
+        let mut segments = vec![];
+        let mut key = String::new();
+        let mut quoted = false;
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '.' => {
+                    segments.push(segment(std::mem::take(&mut key), quoted));
+                    quoted = false;
+                }
+                '`' => {
+                    quoted = true;
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '`' if chars.peek() == Some(&'`') => {
+                                chars.next();
+                                key.push('`');
+                            }
+                            '`' => break,
+                            c => key.push(c),
+                        }
+                    }
+                }
+                c => key.push(c),
+            }
+        }
+        segments.push(segment(key, quoted));
+        Path { segments }
+    }
+}
+
+impl From<String> for Path {
+    fn from(s: String) -> Self {
+        Path::from(s.as_str())
+    }
+}
+
+impl From<Path> for String {
+    fn from(path: Path) -> Self {
+        path.to_string()
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            match segment {
+                Segment::Key(key) if needs_quotes(key, i == 0) => {
+                    write!(f, "`{}`", key.replace('`', "``"))?
+                }
+                Segment::Key(key) => f.write_str(key)?,
+                Segment::Index(i) => write!(f, "{}", i)?,
+                Segment::Wildcard => f.write_str("*")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares with the path as written in RQL (`path == "meta.id"`), without
+/// building the text.
+impl PartialEq<str> for Path {
+    fn eq(&self, other: &str) -> bool {
+        let mut rest = Rest(other);
+        fmt::write(&mut rest, format_args!("{}", self)).is_ok() && rest.0.is_empty()
+    }
+}
+
+impl PartialEq<&str> for Path {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+// 書き出された部分を先頭から削り、食い違ったらそこでやめる
+struct Rest<'a>(&'a str);
+
+impl fmt::Write for Rest<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 = self.0.strip_prefix(s).ok_or(fmt::Error)?;
+        Ok(())
+    }
+}
+
+// 従来どおり Identifier("a.b") と表示する
+impl fmt::Debug for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Path, Segment};
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    #[test]
+    fn segments() {
+        let key = |k: &str| Segment::Key(k.to_owned());
+        assert_eq!(Path::from("a.b").segments(), &[key("a"), key("b")]);
+        assert_eq!(
+            Path::from("items.*.0.01").segments(),
+            &[key("items"), Segment::Wildcard, Segment::Index(0), key("01")]
+        );
+        assert_eq!(
+            Path::from("`a.b`.`c``d`.`1`").segments(),
+            &[key("a.b"), key("c`d"), key("1")]
+        );
+        for text in &["a.b", "items.*.0.01", "`a.b`.`c``d`.`1`", "`$a`.`x y`", "a..b"] {
+            assert_eq!(&Path::from(*text).to_string(), text);
+        }
+        assert_eq!(
+            serde_json::to_value(Path::from("a.`b.c`")).unwrap(),
+            json!("a.`b.c`")
+        );
+    }
+
+    #[test]
+    fn eq_str() {
+        let cases = [
+            ("a.`b.c`", "a.`b.c`", true),
+            ("items.0", "items.0", true),
+            ("a.b", "a", false),
+            ("a", "a.b", false),
+            ("`a.b`", "a.b", false),
+        ];
+        for (path, text, equal) in cases {
+            let path = Path::from(path);
+            assert_eq!(path == text, equal, "{:?} == {:?}", path, text);
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let doc = json!({
+            "items": [{"name": "a", "tags": ["x"]}, {"name": "b", "tags": ["y", "z"]}],
+            "meta": {"content.type": "json", "0": "zero"},
+        });
+        assert_eq!(Path::from("items.1.name").resolve(&doc), Some(&json!("b")));
+        assert_eq!(Path::from("items.2.name").resolve(&doc), None);
+        assert_eq!(Path::from("meta.0").resolve(&doc), Some(&json!("zero")));
+        assert_eq!(
+            Path::from("meta.`content.type`").resolve(&doc),
+            Some(&json!("json"))
+        );
+        assert_eq!(Path::from("meta.content.type").resolve(&doc), None);
+        assert_eq!(
+            Path::from("items.*.tags.*").resolve_all(&doc),
+            vec![&json!("x"), &json!("y"), &json!("z")]
+        );
+
+        let parse = |s: &str| {
+            Parser::new_from_string(s.to_owned())
+                .parse_query()
+                .unwrap()
+        };
+        assert!(parse("eq(items.*.name,\"b\")").matches(&doc));
+        assert!(!parse("eq(items.*.name,\"c\")").matches(&doc));
+        assert!(parse("eq(meta.`content.type`,\"json\")").matches(&doc));
+        assert!(parse("exists(items.*.tags.1)").matches(&doc));
+        assert_eq!(
+            parse("eq(meta.`content.type`,1)").to_rql(),
+            "eq(meta.`content.type`,1)"
+        );
+    }
+}
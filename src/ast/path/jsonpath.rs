@@ -0,0 +1,201 @@
+//! The subset of JSONPath ([RFC 9535]) that maps onto [`Path`]: `$.name`,
+//! `$['name']`, `$[0]`, `$.*` and `$[*]`.
+//!
+//! [RFC 9535]: https://www.rfc-editor.org/rfc/rfc9535
+
+use crate::ast::{Path, Segment};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The supported subset, for error messages.
+pub(crate) const SUPPORTED: &str =
+    "child (.name, ['name']), index ([0]) and wildcard (.*, [*]) selectors";
+
+impl Path {
+    /// Parses a JSONPath such as `$.store.book[0]['unit price']` into the path
+    /// ``store.book.0.`unit price` ``. On failure, returns the feature it ran
+    /// into that isn't supported, such as `"recursive descent (..)"`.
+    pub(crate) fn from_jsonpath(s: &str) -> Result<Path, &'static str> {
+        let rest = s.strip_prefix('$').ok_or("path not starting with $")?;
+        let mut segments = vec![];
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '.' if chars.peek() == Some(&'.') => return Err("recursive descent (..)"),
+                '.' => {
+                    let mut name = String::new();
+                    while let Some(c) = chars.next_if(|&c| c != '.' && c != '[') {
+                        name.push(c);
+                    }
+                    segments.push(match name.as_str() {
+                        "" => return Err("empty member name"),
+                        "*" => Segment::Wildcard,
+                        // ドット記法の数字はインデックスではなくメンバー名
+                        _ => Segment::Key(name),
+                    });
+                }
+                '[' => segments.push(bracket(&mut chars)?),
+                _ => return Err("segment not starting with . or ["),
+            }
+        }
+        Ok(Path::new(segments))
+    }
+}
+
+// [ の後ろから ] までを読む
+fn bracket(chars: &mut Peekable<Chars>) -> Result<Segment, &'static str> {
+    let mut selector = String::new();
+    loop {
+        match chars.next().ok_or("unclosed bracket")? {
+            ']' => break,
+            q @ ('\'' | '"') if selector.trim().is_empty() => {
+                let name = quoted(chars, q)?;
+                return match chars.find(|c| !c.is_whitespace()) {
+                    Some(']') => Ok(Segment::Key(name)),
+                    Some(',') => Err("union ([a,b])"),
+                    _ => Err("unclosed bracket"),
+                };
+            }
+            c => selector.push(c),
+        }
+    }
+    let selector = selector.trim();
+    if selector == "*" {
+        return Ok(Segment::Wildcard);
+    }
+    if selector.starts_with('?') {
+        return Err("filter expression ([?...])");
+    }
+    if selector.starts_with('(') {
+        return Err("script expression ([(...)])");
+    }
+    if selector.contains(':') {
+        return Err("array slice ([start:end])");
+    }
+    if selector.contains(',') {
+        return Err("union ([a,b])");
+    }
+    if selector.starts_with('-') {
+        return Err("negative index");
+    }
+    if selector.is_empty() || !selector.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("bracket selector that isn't an index, * or a quoted name");
+    }
+    selector
+        .parse()
+        .map(Segment::Index)
+        .map_err(|_| "index out of range")
+}
+
+// 引用符の中。\' や \\ はエスケープ
+fn quoted(chars: &mut Peekable<Chars>, quote: char) -> Result<String, &'static str> {
+    let mut name = String::new();
+    loop {
+        match chars.next().ok_or("unclosed quote")? {
+            c if c == quote => return Ok(name),
+            '\\' => name.push(chars.next().ok_or("unclosed quote")?),
+            c => name.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Query;
+    use crate::parser::{Parser, ParserError, ParserOptions};
+    use serde_json::json;
+
+    fn parse(input: &str) -> Result<Query, ParserError> {
+        Parser::new_from_string(input.to_owned())
+            .with_options(ParserOptions {
+                jsonpath: true,
+                variables: true,
+                ..ParserOptions::default()
+            })
+            .parse_query()
+    }
+
+    #[test]
+    fn same_as_dotted() {
+        let docs = [
+            json!({"store": {"book": [{"title": "a", "price": 8}, {"title": "b", "price": 22}]}}),
+            json!({"store": {"book": [{"title": "c", "price": 30}]}, "unit price": 5}),
+            json!({"store": {"book": {"0": {"price": 1}}}, "tags": ["x", "y"]}),
+            json!({"a.b": {"c": true}, "tags": {"k": "y"}}),
+        ];
+        let pairs = [
+            ("lt($.store.book[0].price,10)", "lt(store.book.0.price,10)"),
+            (
+                "gt($['store']['book'][1]['price'],20)",
+                "gt(store.book.1.price,20)",
+            ),
+            ("gt($.store.book[*].price,25)", "gt(store.book.*.price,25)"),
+            ("eq($.tags.*,\"y\")", "eq(tags.*,\"y\")"),
+            ("eq($.tags[*],\"y\")", "eq(tags.*,\"y\")"),
+            ("eq($[\"a.b\"].c,true)", "eq(`a.b`.c,true)"),
+            ("exists($['unit price'])", "exists(`unit price`)"),
+            (
+                "and(exists($.store), sort(-$.store.book[0].price))",
+                "and(exists(store),sort(-store.book.0.price))",
+            ),
+        ];
+        for (jsonpath, dotted) in pairs.iter() {
+            let (jsonpath, dotted) = (parse(jsonpath).unwrap(), parse(dotted).unwrap());
+            assert_eq!(jsonpath, dotted);
+            for doc in docs.iter() {
+                assert_eq!(jsonpath.matches(doc), dotted.matches(doc), "{}", doc);
+            }
+        }
+        // 引用符の中の区切り文字とエスケープ
+        assert_eq!(
+            parse("eq($['a, (b)']['it\\'s'],1)").unwrap().to_rql(),
+            "eq(`a, (b)`.`it's`,1)"
+        );
+        // 値の位置では変数のまま
+        assert_eq!(parse("eq($.a,$user)").unwrap().to_rql(), "eq(a,$user)");
+    }
+
+    #[test]
+    fn unsupported() {
+        let err = |input: &str| parse(input).unwrap_err();
+        let e = err("eq($..price,1)");
+        assert_eq!(e.code(), "unsupported_jsonpath");
+        assert_eq!(e.position(), Some(3));
+        assert_eq!(
+            e.to_string(),
+            "unsupported JSONPath recursive descent (..) in $..price at position 3; only child \
+             (.name, ['name']), index ([0]) and wildcard (.*, [*]) selectors are supported"
+        );
+        let feature = |input: &str| match err(input) {
+            ParserError::UnsupportedJsonPath(_, feature, _) => feature,
+            e => panic!("unexpected error {:?}", e),
+        };
+        assert_eq!(
+            feature("eq($.book[?(@.price < 10)],1)"),
+            "filter expression ([?...])"
+        );
+        assert_eq!(
+            feature("eq($.book[0:2].price,1)"),
+            "array slice ([start:end])"
+        );
+        assert_eq!(feature("eq($.book[0,1],1)"), "union ([a,b])");
+        assert_eq!(feature("eq($['a','b'],1)"), "union ([a,b])");
+        assert_eq!(feature("eq($.book[-1],1)"), "negative index");
+        assert_eq!(
+            feature("eq($.book[(@.length-1)],1)"),
+            "script expression ([(...)])"
+        );
+        assert_eq!(feature("eq($.book[0,1],1)"), "union ([a,b])");
+        assert_eq!(feature("eq($.a.,1)"), "empty member name");
+        assert_eq!(feature("eq($.a[0,1)"), "unclosed bracket");
+
+        // 既定では JSONPath として読まず、$ というフィールドの下の a になる
+        assert_eq!(
+            Parser::new_from_string("eq($.a,1)".to_owned())
+                .parse_query()
+                .unwrap()
+                .to_rql(),
+            "eq(`$`.a,1)"
+        );
+    }
+}
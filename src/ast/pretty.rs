@@ -0,0 +1,262 @@
+//! Multi-line rendering of queries for logs and code review.
+
+use crate::ast::{Path, Query, Value};
+
+/// Layout of [`Query::to_pretty_string_with`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct PrettyOptions {
+    /// Spaces per nesting level.
+    pub indent: usize,
+    /// An `and()`/`or()`/`not()`, or an `in()`/`out()` value list, whose
+    /// one-line form fits in this many columns, indentation and trailing
+    /// comma included, stays on one line. With `0` every non-empty group is
+    /// broken up.
+    pub width: usize,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        PrettyOptions {
+            indent: 2,
+            width: 80,
+        }
+    }
+}
+
+impl Query {
+    /// Renders the query over several lines with the default [`PrettyOptions`].
+    /// The output is still RQL and parses back to an equal query.
+    pub fn to_pretty_string(&self) -> String {
+        self.to_pretty_string_with(&PrettyOptions::default())
+    }
+
+    /// Same as [`Query::to_pretty_string`] with explicit options: `and()`,
+    /// `or()` and `not()` that don't fit get one child per line, indented one level
+    /// deeper, and so do the path and the value list of `in()` and `out()`,
+    /// whose values are then broken up the same way. Filters and other nodes
+    /// always stay on one line.
+    pub fn to_pretty_string_with(&self, options: &PrettyOptions) -> String {
+        let mut out = String::new();
+        write_pretty(self, 0, 0, options, &mut out);
+        out
+    }
+}
+
+// trailing は同じ行に続く文字数 (区切りのカンマ)
+fn write_pretty(
+    query: &Query,
+    depth: usize,
+    trailing: usize,
+    options: &PrettyOptions,
+    out: &mut String,
+) {
+    let line = query.to_rql();
+    if fits(&line, depth, trailing, options) {
+        out.push_str(&line);
+        return;
+    }
+    let (name, children) = match query {
+        Query::And(children) if !children.is_empty() => ("and", &children[..]),
+        Query::Or(children) if !children.is_empty() => ("or", &children[..]),
+        Query::Not(child) => ("not", std::slice::from_ref(&**child)),
+        Query::In(path, values) => {
+            return write_membership("in", path, values, depth, options, out)
+        }
+        Query::Out(path, values) => {
+            return write_membership("out", path, values, depth, options, out)
+        }
+        _ => {
+            out.push_str(&line);
+            return;
+        }
+    };
+    out.push_str(name);
+    out.push_str("(\n");
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        out.push_str(&" ".repeat((depth + 1) * options.indent));
+        write_pretty(child, depth + 1, if last { 0 } else { 1 }, options, out);
+        if !last {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str(&" ".repeat(depth * options.indent));
+    out.push(')');
+}
+
+// in(path,(値...)) の path と値のリストを1行ずつに分け、リストも入らなければ値ごとに分ける
+fn write_membership(
+    name: &str,
+    path: &Path,
+    values: &[Value],
+    depth: usize,
+    options: &PrettyOptions,
+    out: &mut String,
+) {
+    let indent = " ".repeat((depth + 1) * options.indent);
+    out.push_str(name);
+    out.push_str("(\n");
+    out.push_str(&indent);
+    out.push_str(&path.to_string());
+    out.push_str(",\n");
+    out.push_str(&indent);
+    let values: Vec<String> = values.iter().map(Value::to_string).collect();
+    let line = format!("({})", values.join(","));
+    if fits(&line, depth + 1, 0, options) {
+        out.push_str(&line);
+    } else {
+        out.push_str("(\n");
+        for (i, value) in values.iter().enumerate() {
+            out.push_str(&" ".repeat((depth + 2) * options.indent));
+            out.push_str(value);
+            if i + 1 < values.len() {
+                out.push(',');
+            }
+            out.push('\n');
+        }
+        out.push_str(&indent);
+        out.push(')');
+    }
+    out.push('\n');
+    out.push_str(&" ".repeat(depth * options.indent));
+    out.push(')');
+}
+
+fn fits(line: &str, depth: usize, trailing: usize, options: &PrettyOptions) -> bool {
+    depth * options.indent + line.chars().count() + trailing <= options.width
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::PrettyOptions;
+    use crate::parser::Parser;
+
+    const QUERY: &str = "and(eq(status,\"open\"),or(gt(priority,3),and(le(due.days,1.5),\
+                         ne(owner,\"bob\"))),eq(team,\"core\"))";
+
+    fn parse(input: &str) -> crate::ast::Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn nested() {
+        let query = parse(QUERY);
+        let broken = PrettyOptions {
+            indent: 4,
+            width: 0,
+        };
+        let expected = "\
+and(
+    eq(status,\"open\"),
+    or(
+        gt(priority,3),
+        and(
+            le(due.days,1.5),
+            ne(owner,\"bob\")
+        )
+    ),
+    eq(team,\"core\")
+)";
+        assert_eq!(query.to_pretty_string_with(&broken), expected);
+        assert_eq!(parse(expected), query);
+    }
+
+    #[test]
+    fn width() {
+        let query = parse(QUERY);
+        let expected = "\
+and(
+  eq(status,\"open\"),
+  or(gt(priority,3),and(le(due.days,1.5),ne(owner,\"bob\"))),
+  eq(team,\"core\")
+)";
+        assert_eq!(query.to_pretty_string(), expected);
+        assert_eq!(parse(expected), query);
+        let narrow = PrettyOptions {
+            indent: 2,
+            width: 44,
+        };
+        let expected = "\
+and(
+  eq(status,\"open\"),
+  or(
+    gt(priority,3),
+    and(le(due.days,1.5),ne(owner,\"bob\"))
+  ),
+  eq(team,\"core\")
+)";
+        assert_eq!(query.to_pretty_string_with(&narrow), expected);
+        assert_eq!(parse(expected), query);
+        assert_eq!(parse("and(eq(a,1))").to_pretty_string(), "and(eq(a,1))");
+    }
+
+    #[test]
+    fn negation() {
+        let query = parse("not(or(eq(a,1),eq(b,2)))");
+        let broken = PrettyOptions {
+            indent: 2,
+            width: 0,
+        };
+        let expected = "\
+not(
+  or(
+    eq(a,1),
+    eq(b,2)
+  )
+)";
+        assert_eq!(query.to_pretty_string_with(&broken), expected);
+        assert_eq!(parse(expected), query);
+        assert_eq!(query.to_pretty_string(), "not(or(eq(a,1),eq(b,2)))");
+    }
+
+    #[test]
+    fn membership() {
+        let query = parse("and(in(status,(\"new\",\"open\")),out(id,(1,2,3)))");
+        let broken = PrettyOptions {
+            indent: 2,
+            width: 0,
+        };
+        let expected = "\
+and(
+  in(
+    status,
+    (
+      \"new\",
+      \"open\"
+    )
+  ),
+  out(
+    id,
+    (
+      1,
+      2,
+      3
+    )
+  )
+)";
+        assert_eq!(query.to_pretty_string_with(&broken), expected);
+        assert_eq!(parse(expected), query);
+        // 入るだけの幅があれば、値のリストは1行のまま
+        let narrow = PrettyOptions {
+            indent: 2,
+            width: 22,
+        };
+        let expected = "\
+and(
+  in(
+    status,
+    (\"new\",\"open\")
+  ),
+  out(id,(1,2,3))
+)";
+        assert_eq!(query.to_pretty_string_with(&narrow), expected);
+        assert_eq!(parse(expected), query);
+        assert_eq!(
+            query.to_pretty_string(),
+            "and(in(status,(\"new\",\"open\")),out(id,(1,2,3)))"
+        );
+    }
+}
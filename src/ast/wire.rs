@@ -0,0 +1,486 @@
+//! A versioned JSON encoding of queries, to store them and send them between
+//! services. Unlike the serde derives on [`Query`], its shape doesn't follow
+//! the Rust types, so it stays the same when they change; a new shape gets a
+//! new version, and older versions keep being read.
+//!
+//! # Version 1
+//!
+//! The query is a JSON object, a node, holding `"v": 1`. Each node has an
+//! `op`, the RQL keyword of the operator, and the operator's operands:
+//!
+//! | Query | Node |
+//! |---|---|
+//! | `and(...)`, `or(...)` | `{"op":"and","args":[node...]}` |
+//! | `not(...)` | `{"op":"not","arg":node}` |
+//! | `eq(a,1)`, and the other comparisons | `{"op":"eq","field":"a","value":1}` |
+//! | `in(a,(1,2))`, `out(a,(1,2))` | `{"op":"in","field":"a","values":[1,2]}` |
+//! | `contains(a,1)` | `{"op":"contains","field":"a","value":1}` |
+//! | `exists(a)`, `missing(a)` | `{"op":"exists","field":"a"}` |
+//! | `search((a,b),"x y")`, `search("x")` | `{"op":"search","fields":["a","b"],"term":"x y"}` |
+//! | `near(a,35.6,139.7,500)` | `{"op":"near","field":"a","lat":35.6,"lon":139.7,"radius":500.0}` |
+//! | `sort(+a,-b)` | `{"op":"sort","keys":[{"field":"a","order":"asc"},...]}`, `"desc"` for `-` |
+//! | `limit(10,20)` | `{"op":"limit","count":10,"offset":20}` |
+//! | `select(a,b)` | `{"op":"select","fields":["a","b"]}` |
+//! | `distinct(a)`, `distinct()` | `{"op":"distinct","field":"a"}`, `{"op":"distinct"}` |
+//! | `aggregate(a,sum(b))` | `{"op":"aggregate","keys":["a"],"functions":[{"op":"sum",...}]}` |
+//! | `sum(a)`, `min(a)`... | `{"op":"sum","field":"a"}` |
+//! | `first()`, `one()`, `count()` | `{"op":"first"}` |
+//! | the empty query | `{"op":"none"}` |
+//!
+//! Fields are paths written as in RQL: `address.city`, `` `a.b` ``.
+//!
+//! Strings, booleans and numbers are written as JSON values: an integer
+//! literal is a JSON integer and a float literal a JSON number with a
+//! fractional part or an exponent, `1.0` rather than `1`. Other values are
+//! objects with a single key:
+//!
+//! | Value | JSON |
+//! |---|---|
+//! | another field, `eq(a,b)` | `{"field":"b"}` |
+//! | an infinite or NaN float | `{"float":"inf"}`, `{"float":"-inf"}`, `{"float":"nan"}` |
+//! | a decimal | `{"decimal":"1.50"}` |
+//! | a date | `{"date":"2024-01-01T00:00:00Z"}` |
+//! | a relative time, `now-7d` | `{"now":"-7d"}` |
+//! | `now()` | `{"call":"now"}` |
+//! | `$name` | `{"var":"name"}` |
+//!
+//! Keys a node doesn't use are ignored.
+
+use crate::ast::{Aggregation, Infix, Path, Prefix, Query, Value};
+use serde_json::{json, Map, Value as Json};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// The version [`Query::to_wire_json`] writes.
+pub const WIRE_VERSION: u64 = 1;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum WireError {
+    /// The `v` of the root, or `null` without one: a version this release
+    /// doesn't read.
+    UnsupportedVersion(Json),
+    /// A node or value doesn't have the shape of the format: where, as a
+    /// JSON Pointer, and what's wrong.
+    Invalid(String, String),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WireError::UnsupportedVersion(Json::Null) => f.write_str("no wire format version"),
+            WireError::UnsupportedVersion(v) => {
+                write!(f, "unsupported wire format version {}", v)
+            }
+            WireError::Invalid(at, what) if at.is_empty() => write!(f, "{} at the root", what),
+            WireError::Invalid(at, what) => write!(f, "{} at {}", what, at),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+impl Query {
+    /// The query in the wire format, at [`WIRE_VERSION`].
+    ///
+    /// ```
+    /// use rql_parser::ast::Query;
+    /// use rql_parser::parser::Parser;
+    /// use serde_json::json;
+    ///
+    /// let query = Parser::from_str("and(eq(a,1),ne(b,\"x\"))").parse_query().unwrap();
+    /// let wire = query.to_wire_json();
+    /// assert_eq!(
+    ///     wire,
+    ///     json!({"v": 1, "op": "and", "args": [
+    ///         {"op": "eq", "field": "a", "value": 1},
+    ///         {"op": "ne", "field": "b", "value": "x"},
+    ///     ]})
+    /// );
+    /// assert_eq!(Query::from_wire_json(&wire).unwrap(), query);
+    /// ```
+    pub fn to_wire_json(&self) -> Json {
+        let mut node = encode(self);
+        node["v"] = WIRE_VERSION.into();
+        node
+    }
+
+    /// Reads a query written by [`Query::to_wire_json`], by this release or
+    /// an earlier one.
+    pub fn from_wire_json(json: &Json) -> Result<Query, WireError> {
+        match json.get("v") {
+            Some(v) if v.as_u64() == Some(1) => decode(json, ""),
+            Some(v) => Err(WireError::UnsupportedVersion(v.clone())),
+            None if json.is_object() => Err(WireError::UnsupportedVersion(Json::Null)),
+            None => Err(invalid("", "expected an object")),
+        }
+    }
+}
+
+fn encode(query: &Query) -> Json {
+    match query {
+        Query::And(queries) | Query::Or(queries) => {
+            let op = if matches!(query, Query::And(_)) {
+                "and"
+            } else {
+                "or"
+            };
+            let args: Vec<Json> = queries.iter().map(encode).collect();
+            json!({"op": op, "args": args})
+        }
+        Query::Not(query) => json!({"op": "not", "arg": encode(query)}),
+        Query::Filter(infix, ident, value) => json!({
+            "op": infix.keyword(),
+            // 左辺はパスのはず。パーサを通さずに作られた他の値は RQL の表記をパスとする
+            "field": ident.to_string(),
+            "value": encode_value(value),
+        }),
+        Query::In(path, values) | Query::Out(path, values) => {
+            let op = if matches!(query, Query::In(..)) {
+                "in"
+            } else {
+                "out"
+            };
+            let values: Vec<Json> = values.iter().map(encode_value).collect();
+            json!({"op": op, "field": path, "values": values})
+        }
+        Query::Contains(path, value) => {
+            json!({"op": "contains", "field": path, "value": encode_value(value)})
+        }
+        Query::Exists(path) => json!({"op": "exists", "field": path}),
+        Query::Missing(path) => json!({"op": "missing", "field": path}),
+        Query::Sort(keys) => {
+            let keys: Vec<Json> = keys
+                .iter()
+                .map(|(prefix, value)| {
+                    let order = match prefix {
+                        Prefix::Plus => "asc",
+                        Prefix::Minus => "desc",
+                    };
+                    json!({"field": value.to_string(), "order": order})
+                })
+                .collect();
+            json!({"op": "sort", "keys": keys})
+        }
+        Query::Limit(count, offset) => json!({"op": "limit", "count": count, "offset": offset}),
+        Query::Select(paths) => json!({"op": "select", "fields": paths}),
+        Query::Search(fields, term) => json!({"op": "search", "fields": fields, "term": term}),
+        Query::Near(path, lat, lon, radius) => json!({
+            "op": "near",
+            "field": path,
+            "lat": lat,
+            "lon": lon,
+            "radius": radius,
+        }),
+        Query::Distinct(Some(path)) => json!({"op": "distinct", "field": path}),
+        Query::Distinct(None) => json!({"op": "distinct"}),
+        Query::Aggregate(keys, functions) => {
+            let functions: Vec<Json> = functions
+                .iter()
+                .map(|(function, path)| json!({"op": function.name(), "field": path}))
+                .collect();
+            json!({"op": "aggregate", "keys": keys, "functions": functions})
+        }
+        Query::First => json!({"op": "first"}),
+        Query::One => json!({"op": "one"}),
+        Query::Count => json!({"op": "count"}),
+        Query::Reduce(function, path) => json!({"op": function.name(), "field": path}),
+        Query::None => json!({"op": "none"}),
+    }
+}
+
+fn encode_value(value: &Value) -> Json {
+    match value {
+        Value::Identifier(path) => json!({"field": path.to_string()}),
+        Value::IntegerLiteral(i) => (*i).into(),
+        Value::UnsignedLiteral(u) => (*u).into(),
+        Value::FloatLiteral(f) => match serde_json::Number::from_f64(*f) {
+            Some(n) => Json::Number(n),
+            None if f.is_nan() => json!({"float": "nan"}),
+            None if *f > 0.0 => json!({"float": "inf"}),
+            None => json!({"float": "-inf"}),
+        },
+        #[cfg(feature = "decimal")]
+        Value::DecimalLiteral(d) => json!({"decimal": d.to_string()}),
+        Value::StringLiteral(s) => s.as_str().into(),
+        #[cfg(feature = "chrono")]
+        Value::DateTime(datetime) => json!({"date": crate::datetime::format(datetime)}),
+        #[cfg(feature = "chrono")]
+        Value::RelativeTime(amount, unit) => {
+            json!({"now": format!("{:+}{}", amount, unit.suffix())})
+        }
+        Value::Boolean(b) => (*b).into(),
+        Value::Call(name) => json!({"call": name}),
+        Value::Variable(name) => json!({"var": name}),
+    }
+}
+
+fn decode(json: &Json, at: &str) -> Result<Query, WireError> {
+    let node = json
+        .as_object()
+        .ok_or_else(|| invalid(at, "expected an object"))?;
+    let op = string(node, "op", at)?;
+    let field = || string(node, "field", at).map(Path::from);
+    let value = || {
+        let value = node
+            .get("value")
+            .ok_or_else(|| invalid(at, "missing value"))?;
+        decode_value(value, &format!("{}/value", at))
+    };
+    if let Some(infix) = Infix::from_keyword(op) {
+        let ident = Value::Identifier(field()?);
+        return Ok(Query::Filter(infix, ident, value()?));
+    }
+    if let Some(function) = Aggregation::from_name(op) {
+        // count だけは field の無い count() もある
+        if function == Aggregation::Count && !node.contains_key("field") {
+            return Ok(Query::Count);
+        }
+        return Ok(Query::Reduce(function, field()?));
+    }
+    Ok(match op {
+        "and" | "or" => {
+            let args = array(node, "args", at)?
+                .iter()
+                .enumerate()
+                .map(|(i, arg)| decode(arg, &format!("{}/args/{}", at, i)))
+                .collect::<Result<_, _>>()?;
+            if op == "and" {
+                Query::And(args)
+            } else {
+                Query::Or(args)
+            }
+        }
+        "not" => {
+            let arg = node.get("arg").ok_or_else(|| invalid(at, "missing arg"))?;
+            Query::Not(Box::new(decode(arg, &format!("{}/arg", at))?))
+        }
+        "in" | "out" => {
+            let values = array(node, "values", at)?
+                .iter()
+                .enumerate()
+                .map(|(i, value)| decode_value(value, &format!("{}/values/{}", at, i)))
+                .collect::<Result<_, _>>()?;
+            if op == "in" {
+                Query::In(field()?, values)
+            } else {
+                Query::Out(field()?, values)
+            }
+        }
+        "contains" => Query::Contains(field()?, value()?),
+        "exists" => Query::Exists(field()?),
+        "missing" => Query::Missing(field()?),
+        "sort" => {
+            let mut keys = vec![];
+            for (i, key) in array(node, "keys", at)?.iter().enumerate() {
+                let at = format!("{}/keys/{}", at, i);
+                let key = key
+                    .as_object()
+                    .ok_or_else(|| invalid(&at, "expected an object"))?;
+                let prefix = match string(key, "order", &at)? {
+                    "asc" => Prefix::Plus,
+                    "desc" => Prefix::Minus,
+                    _ => return Err(invalid(&at, "order should be \"asc\" or \"desc\"")),
+                };
+                let path = Path::from(string(key, "field", &at)?);
+                keys.push((prefix, Value::Identifier(path)));
+            }
+            Query::Sort(keys)
+        }
+        "limit" => Query::Limit(size(node, "count", at)?, size(node, "offset", at)?),
+        "select" => Query::Select(paths(node, "fields", at)?),
+        "near" => Query::Near(
+            field()?,
+            number(node, "lat", at)?,
+            number(node, "lon", at)?,
+            number(node, "radius", at)?,
+        ),
+        "search" => Query::Search(
+            paths(node, "fields", at)?,
+            string(node, "term", at)?.to_owned(),
+        ),
+        "distinct" if node.contains_key("field") => Query::Distinct(Some(field()?)),
+        "distinct" => Query::Distinct(None),
+        "aggregate" => {
+            let mut functions = vec![];
+            for (i, function) in array(node, "functions", at)?.iter().enumerate() {
+                let at = format!("{}/functions/{}", at, i);
+                let function = function
+                    .as_object()
+                    .ok_or_else(|| invalid(&at, "expected an object"))?;
+                let name = string(function, "op", &at)?;
+                let aggregation = Aggregation::from_name(name)
+                    .ok_or_else(|| invalid(&at, &format!("unknown function {:?}", name)))?;
+                functions.push((aggregation, Path::from(string(function, "field", &at)?)));
+            }
+            Query::Aggregate(paths(node, "keys", at)?, functions)
+        }
+        "first" => Query::First,
+        "one" => Query::One,
+        "none" => Query::None,
+        _ => return Err(invalid(at, &format!("unknown op {:?}", op))),
+    })
+}
+
+fn decode_value(json: &Json, at: &str) -> Result<Value, WireError> {
+    let tagged = match json {
+        Json::String(s) => return Ok(Value::StringLiteral(s.clone())),
+        Json::Bool(b) => return Ok(Value::Boolean(*b)),
+        Json::Number(n) => {
+            return Ok(match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => Value::IntegerLiteral(i),
+                (None, Some(u)) => Value::UnsignedLiteral(u),
+                _ => Value::FloatLiteral(n.as_f64().unwrap_or_default()),
+            })
+        }
+        Json::Object(object) => object.iter().next().filter(|_| object.len() == 1),
+        _ => None,
+    };
+    let (tag, s) = match tagged {
+        Some((tag, Json::String(s))) => (tag.as_str(), s.as_str()),
+        Some((tag, _)) => return Err(invalid(at, &format!("{} should be a string", tag))),
+        None => {
+            return Err(invalid(
+                at,
+                "expected a string, number, boolean or tagged value",
+            ))
+        }
+    };
+    let bad = || invalid(at, &format!("invalid {}: {:?}", tag, s));
+    Ok(match tag {
+        "field" => Value::Identifier(Path::from(s)),
+        "float" => match s {
+            "inf" => Value::FloatLiteral(f64::INFINITY),
+            "-inf" => Value::FloatLiteral(f64::NEG_INFINITY),
+            "nan" => Value::FloatLiteral(f64::NAN),
+            _ => return Err(bad()),
+        },
+        #[cfg(feature = "decimal")]
+        "decimal" => Value::DecimalLiteral(s.parse().map_err(|_| bad())?),
+        #[cfg(feature = "chrono")]
+        "date" => Value::DateTime(crate::datetime::parse(s).ok_or_else(bad)?),
+        #[cfg(feature = "chrono")]
+        "now" => {
+            let (amount, unit) = crate::datetime::parse_offset(s).ok_or_else(bad)?;
+            Value::RelativeTime(amount, unit)
+        }
+        #[cfg(not(feature = "decimal"))]
+        "decimal" => return Err(invalid(at, "decimals need the decimal feature")),
+        #[cfg(not(feature = "chrono"))]
+        "date" | "now" => return Err(invalid(at, "dates need the chrono feature")),
+        "call" => Value::Call(s.to_owned()),
+        "var" => Value::Variable(s.to_owned()),
+        _ => return Err(invalid(at, &format!("unknown value tag {:?}", tag))),
+    })
+}
+
+fn string<'a>(node: &'a Map<String, Json>, key: &str, at: &str) -> Result<&'a str, WireError> {
+    node.get(key)
+        .and_then(Json::as_str)
+        .ok_or_else(|| invalid(at, &format!("{} should be a string", key)))
+}
+
+fn array<'a>(node: &'a Map<String, Json>, key: &str, at: &str) -> Result<&'a [Json], WireError> {
+    node.get(key)
+        .and_then(Json::as_array)
+        .map(Vec::as_slice)
+        .ok_or_else(|| invalid(at, &format!("{} should be an array", key)))
+}
+
+fn paths(node: &Map<String, Json>, key: &str, at: &str) -> Result<Vec<Path>, WireError> {
+    array(node, key, at)?
+        .iter()
+        .map(|s| s.as_str().map(Path::from))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid(at, &format!("{} should be an array of strings", key)))
+}
+
+fn size(node: &Map<String, Json>, key: &str, at: &str) -> Result<usize, WireError> {
+    node.get(key)
+        .and_then(Json::as_u64)
+        .and_then(|n| usize::try_from(n).ok())
+        .ok_or_else(|| invalid(at, &format!("{} should be a non-negative integer", key)))
+}
+
+fn number(node: &Map<String, Json>, key: &str, at: &str) -> Result<f64, WireError> {
+    node.get(key)
+        .and_then(Json::as_f64)
+        .ok_or_else(|| invalid(at, &format!("{} should be a number", key)))
+}
+
+fn invalid(at: &str, what: &str) -> WireError {
+    WireError::Invalid(at.to_owned(), what.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Query, Value, WireError};
+    use serde_json::json;
+
+    #[test]
+    fn errors() {
+        let err = |json: serde_json::Value| Query::from_wire_json(&json).unwrap_err();
+        assert_eq!(
+            err(json!({"v": 2, "op": "none"})),
+            WireError::UnsupportedVersion(json!(2))
+        );
+        assert_eq!(
+            err(json!({"op": "none"})).to_string(),
+            "no wire format version"
+        );
+        assert_eq!(err(json!([])).to_string(), "expected an object at the root");
+        assert_eq!(
+            err(json!({"v": 1, "op": "and", "args": [{"op": "eq", "field": "a"}]})).to_string(),
+            "missing value at /args/0"
+        );
+        assert_eq!(
+            err(json!({"v": 1, "op": "or", "args": [
+                {"op": "and", "args": [{"op": "like", "field": "a", "value": "x"}]}
+            ]}))
+            .to_string(),
+            "unknown op \"like\" at /args/0/args/0"
+        );
+        assert_eq!(
+            err(json!({"v": 1, "op": "eq", "field": "a", "value": {"float": "1e400"}})).to_string(),
+            "invalid float: \"1e400\" at /value"
+        );
+        assert_eq!(
+            err(json!({"v": 1, "op": "eq", "field": "a", "value": [1]})).to_string(),
+            "expected a string, number, boolean or tagged value at /value"
+        );
+        assert_eq!(
+            err(json!({"v": 1, "op": "not", "arg": {"op": "in", "field": "a", "values": [[1]]}}))
+                .to_string(),
+            "expected a string, number, boolean or tagged value at /arg/values/0"
+        );
+        assert_eq!(
+            err(json!({"v": 1, "op": "not"})).to_string(),
+            "missing arg at the root"
+        );
+        assert_eq!(
+            err(json!({"v": 1, "op": "limit", "count": -1, "offset": 0})).to_string(),
+            "count should be a non-negative integer at the root"
+        );
+    }
+
+    #[test]
+    fn non_finite() {
+        for f in [f64::INFINITY, f64::NEG_INFINITY] {
+            let query = Query::Filter(
+                crate::ast::Infix::Lt,
+                Value::Identifier("a".into()),
+                Value::FloatLiteral(f),
+            );
+            assert_eq!(Query::from_wire_json(&query.to_wire_json()).unwrap(), query);
+        }
+        let nan = Query::Filter(
+            crate::ast::Infix::Eq,
+            Value::Identifier("a".into()),
+            Value::FloatLiteral(f64::NAN),
+        );
+        assert_eq!(nan.to_wire_json()["value"], json!({"float": "nan"}));
+        match Query::from_wire_json(&nan.to_wire_json()).unwrap() {
+            Query::Filter(_, _, Value::FloatLiteral(f)) => assert!(f.is_nan()),
+            query => panic!("{:?}", query),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{parse_queries, read_queries, single_query, Error, Options};
+use rql_parser::ast::{PrettyOptions, Query};
+use std::process::ExitCode;
+
+/// Renders and/or with one child per line; filters and empty groups stay on one line.
+pub fn pretty(query: &Query) -> String {
+    query.to_pretty_string_with(&PrettyOptions {
+        indent: 2,
+        width: 0,
+    })
+}
+
+pub fn format(query: &Query, pretty_print: bool) -> String {
+    if pretty_print {
+        pretty(query)
+    } else {
+        query.to_rql()
+    }
+}
+
+// --check は整形済みかどうかだけを終了コードで返す
+pub fn run(options: &Options) -> Result<ExitCode, Error> {
+    let inputs = read_queries(options, false)?;
+    if !options.check {
+        let query = parse_queries(inputs, options.input.or)?;
+        println!("{}", format(&query, options.pretty));
+        return Ok(ExitCode::SUCCESS);
+    }
+    let input = single_query(inputs, "--check")?;
+    let formatted = format(&parse_queries(vec![input.clone()], false)?, options.pretty);
+    if input.trim_end() == formatted {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!("query is not formatted; expected:\n{}", formatted);
+        Ok(ExitCode::from(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rql_parser::parser::Parser;
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn pretty_nested() {
+        let query = parse("and( eq(a,1) ,or(eq(b,2),eq(c,3)), and() )");
+        let expected = "and(\n  eq(a,1),\n  or(\n    eq(b,2),\n    eq(c,3)\n  ),\n  and()\n)";
+        assert_eq!(pretty(&query), expected);
+        assert_eq!(parse(expected), query);
+        assert_eq!(pretty(&parse(expected)), expected);
+    }
+
+    #[test]
+    fn compact_is_idempotent() {
+        let compact = format(&parse("or( eq(a,\"x\") , gt(b,1.0))"), false);
+        assert_eq!(compact, "or(eq(a,\"x\"),gt(b,1.0))");
+        assert_eq!(format(&parse(&compact), false), compact);
+    }
+}
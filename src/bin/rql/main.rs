@@ -0,0 +1,894 @@
+use clap::{ArgGroup, Args, ColorChoice, CommandFactory, FromArgMatches, Subcommand};
+use rql_parser::ast::Query;
+use rql_parser::convert::sql::Dialect;
+use rql_parser::convert::ConvertError;
+use rql_parser::diagnostic;
+use rql_parser::eval::{self, EvalError};
+use rql_parser::lexer::tokenize;
+use rql_parser::parser::{Parser, ParserError};
+use rql_parser::token::Token;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::{env, fmt, fs};
+
+mod canonical;
+mod repl;
+mod stats;
+
+/// Parse RQL queries, filter documents with them and translate them for
+/// databases.
+///
+/// Without a subcommand, rql works like `rql parse`.
+#[derive(clap::Parser)]
+#[command(name = "rql", args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    options: Options,
+    /// Don't color error messages (also NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Parse the query and print it (the default)
+    Parse(Options),
+    /// Filter JSON, newline-delimited JSON or CSV documents with the query
+    Eval(EvalArgs),
+    /// Print the query in canonical form
+    Fmt(FmtArgs),
+    /// Print the lexer output, one token per line
+    Tokens(TokensArgs),
+    /// Print the query translated for a backend, or as a Graphviz graph
+    Emit(EmitArgs),
+    /// Read queries interactively
+    Repl(ReplArgs),
+}
+
+// 終了コード: 0 成功, 1 パース失敗 (または単一ドキュメントが不一致), 2 使い方・入力の誤り
+enum Error {
+    Usage(String),
+    Io(String, io::Error),
+    Input(String),
+    // 位置引数が複数ある場合は何番目か (1始まり) も持つ
+    Parse(Option<usize>, String, ParserError),
+    Convert(ConvertError),
+    Eval(EvalError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Usage(message) => write!(
+                f,
+                "error: {}\n\n{}\n\nFor more information, try '--help'.",
+                message,
+                Cli::command().render_usage()
+            ),
+            Error::Io(source, e) => write!(f, "error: can't read {}: {}", source, e),
+            Error::Input(message) => write!(f, "error: {}", message),
+            Error::Parse(..) => write!(f, "{}", self.render(false)),
+            Error::Convert(e) => write!(f, "error: {}", e),
+            Error::Eval(e) => write!(f, "error: {}", e),
+        }
+    }
+}
+
+impl Error {
+    // パースエラーはクエリの該当行とキャレットを付け、必要なら色を付ける
+    fn render(&self, color: bool) -> String {
+        match self {
+            Error::Parse(None, input, e) => diagnostic::render(input, e, color),
+            Error::Parse(Some(index), input, e) => {
+                let message = format!("argument {}: {}", index, e);
+                diagnostic::render_message(input, &message, diagnostic::span(input, e), color)
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            Error::Parse(..) | Error::Convert(..) | Error::Eval(..) => ExitCode::from(1),
+            _ => ExitCode::from(2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Debug,
+    Json,
+    Rql,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "debug" => Ok(Format::Debug),
+            "json" => Ok(Format::Json),
+            "rql" => Ok(Format::Rql),
+            _ => Err(format!("unknown format {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Output {
+    Csv,
+    Json,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "csv" => Ok(Output::Csv),
+            "json" => Ok(Output::Json),
+            _ => Err(format!("unknown output {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Emit {
+    Sql,
+    Mongo,
+    Es,
+    Dot,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "sql" => Ok(Emit::Sql),
+            "mongo" => Ok(Emit::Mongo),
+            "es" => Ok(Emit::Es),
+            "dot" => Ok(Emit::Dot),
+            _ => Err(format!("unknown backend {}", s)),
+        }
+    }
+}
+
+/// Where the query comes from.
+#[derive(Args, Default)]
+struct Input {
+    /// The query; several are combined with and (or with --or). Without one,
+    /// or with -, it's read from stdin
+    #[arg(value_name = "QUERY")]
+    queries: Vec<String>,
+    /// Read the query from a file (- for stdin)
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    file: Option<String>,
+    /// Combine several queries with or instead of and
+    #[arg(long)]
+    or: bool,
+}
+
+// 従来のサブコマンド無しの呼び出しで使えるすべてのオプション。
+// サブコマンドの引数もこれに直して実行する
+#[derive(Args)]
+struct Options {
+    #[command(flatten)]
+    input: Input,
+    /// Output format of the parsed query
+    #[arg(long, value_name = "debug|json|rql", default_value = "debug")]
+    format: Format,
+    /// Indent JSON output
+    #[arg(long)]
+    pretty: bool,
+    /// Print the lexer output, one token per line
+    #[arg(long)]
+    tokens: bool,
+    /// Validate every line of the input as a separate query; blank lines and
+    /// lines starting with # are skipped
+    #[arg(long)]
+    check: bool,
+    /// JSON array (or single object) to filter
+    #[arg(long, value_name = "FILE")]
+    data: Option<String>,
+    /// Filter newline-delimited JSON from stdin
+    #[arg(long)]
+    ndjson: bool,
+    /// With --ndjson, exit 1 if any line is malformed
+    #[arg(long)]
+    strict: bool,
+    /// Print only the number of matching records
+    #[arg(long)]
+    count: bool,
+    /// Exit 1 if no record matched
+    #[arg(long)]
+    fail_empty: bool,
+    /// With --data, show how each node of the query evaluated for every
+    /// document (as JSON with --format json)
+    #[arg(long)]
+    explain: bool,
+    /// With --data, print how many documents matched, out of how many, and
+    /// min/max/mean of the field of the first aggregate in the query
+    #[arg(long)]
+    stats: bool,
+    /// With --stats, the field to give min/max/mean of
+    #[arg(long, value_name = "PATH")]
+    stats_field: Option<String>,
+    /// CSV file to filter; cells are coerced to the literal's type
+    #[arg(long, value_name = "FILE")]
+    csv: Option<String>,
+    /// The CSV has no header row; columns are named $1, $2, ...
+    #[arg(long)]
+    no_header: bool,
+    /// Output format of matching CSV rows
+    #[arg(long, value_name = "csv|json", default_value = "csv")]
+    output: Output,
+    /// Print the query translated for a backend, or as a Graphviz graph
+    #[arg(long, value_name = "sql|mongo|es|dot")]
+    emit: Option<Emit>,
+    /// SQL dialect for --emit sql
+    #[arg(long, value_name = "postgres|mysql|sqlite", default_value = "postgres")]
+    dialect: Dialect,
+    /// With --emit sql, use placeholders and print the bind values
+    #[arg(long)]
+    params: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            input: Input::default(),
+            format: Format::Debug,
+            pretty: false,
+            tokens: false,
+            check: false,
+            data: None,
+            ndjson: false,
+            strict: false,
+            count: false,
+            fail_empty: false,
+            explain: false,
+            stats: false,
+            stats_field: None,
+            csv: None,
+            no_header: false,
+            output: Output::Csv,
+            emit: None,
+            dialect: Dialect::Postgres,
+            params: false,
+        }
+    }
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("source").required(true).args(["data", "ndjson", "csv"])))]
+struct EvalArgs {
+    #[command(flatten)]
+    input: Input,
+    /// JSON array (or single object) to filter
+    #[arg(long, value_name = "FILE")]
+    data: Option<String>,
+    /// Read one JSON document per line from stdin
+    #[arg(long)]
+    ndjson: bool,
+    /// CSV file to filter; cells are coerced to the literal's type
+    #[arg(long, value_name = "FILE")]
+    csv: Option<String>,
+    /// Exit 1 if any line of the NDJSON is malformed
+    #[arg(long)]
+    strict: bool,
+    /// Print only the number of matching records
+    #[arg(long)]
+    count: bool,
+    /// Exit 1 if no record matched
+    #[arg(long)]
+    fail_empty: bool,
+    /// Show how each node of the query evaluated for every document
+    #[arg(long, conflicts_with_all = ["count", "stats"])]
+    explain: bool,
+    /// Print how many documents matched, out of how many, and min/max/mean of
+    /// the field of the first aggregate in the query
+    #[arg(long)]
+    stats: bool,
+    /// The field to give min/max/mean of
+    #[arg(long, value_name = "PATH", requires = "stats")]
+    stats_field: Option<String>,
+    /// The CSV has no header row; columns are named $1, $2, ...
+    #[arg(long)]
+    no_header: bool,
+    /// Output format of matching CSV rows [default: csv]
+    #[arg(long, value_name = "csv|json")]
+    output: Option<Output>,
+    /// Output format of --explain and --stats
+    #[arg(long, value_name = "debug|json", default_value = "debug")]
+    format: Format,
+    /// Indent JSON output
+    #[arg(long)]
+    pretty: bool,
+}
+
+#[derive(Args)]
+struct FmtArgs {
+    #[command(flatten)]
+    input: Input,
+    /// Put each child of and() and or() on its own line
+    #[arg(long)]
+    pretty: bool,
+    /// Exit 1 if the query isn't already formatted, printing nothing
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(Args)]
+struct TokensArgs {
+    /// The query; without one, or with -, it's read from stdin
+    #[arg(value_name = "QUERY")]
+    query: Option<String>,
+    /// Read the query from a file (- for stdin)
+    #[arg(short = 'f', long = "file", value_name = "FILE", conflicts_with = "query")]
+    file: Option<String>,
+}
+
+#[derive(Args)]
+struct EmitArgs {
+    /// The backend to translate for
+    #[arg(value_name = "sql|mongo|es|dot")]
+    backend: Emit,
+    #[command(flatten)]
+    input: Input,
+    /// SQL dialect
+    #[arg(long, value_name = "postgres|mysql|sqlite", default_value = "postgres")]
+    dialect: Dialect,
+    /// Use placeholders and print the bind values
+    #[arg(long)]
+    params: bool,
+    /// Indent JSON output
+    #[arg(long)]
+    pretty: bool,
+}
+
+#[derive(Args)]
+struct ReplArgs {
+    /// JSON array (or single object) to run the queries on
+    #[arg(long, value_name = "FILE")]
+    data: Option<String>,
+    /// Output format of the parsed queries
+    #[arg(long, value_name = "debug|json|rql", default_value = "debug")]
+    format: Format,
+}
+
+impl Command {
+    fn run(self) -> Result<ExitCode, Error> {
+        match self {
+            Command::Parse(options) => run(&options),
+            Command::Eval(args) => {
+                // clap の requires は同じグループの別の入力があると無視されるので、ここで確かめる
+                if args.strict && !args.ndjson {
+                    return Err(Error::Usage("--strict needs --ndjson".to_owned()));
+                }
+                if args.csv.is_none() && (args.no_header || args.output.is_some()) {
+                    return Err(Error::Usage(
+                        "--no-header and --output need --csv".to_owned(),
+                    ));
+                }
+                run(&Options {
+                    input: args.input,
+                    data: args.data,
+                    ndjson: args.ndjson,
+                    csv: args.csv,
+                    strict: args.strict,
+                    count: args.count,
+                    fail_empty: args.fail_empty,
+                    explain: args.explain,
+                    stats: args.stats,
+                    stats_field: args.stats_field,
+                    no_header: args.no_header,
+                    output: args.output.unwrap_or(Output::Csv),
+                    format: args.format,
+                    pretty: args.pretty,
+                    ..Options::default()
+                })
+            }
+            Command::Fmt(args) => canonical::run(&Options {
+                input: args.input,
+                pretty: args.pretty,
+                check: args.check,
+                ..Options::default()
+            }),
+            Command::Tokens(args) => run(&Options {
+                input: Input {
+                    queries: args.query.into_iter().collect(),
+                    file: args.file,
+                    or: false,
+                },
+                tokens: true,
+                ..Options::default()
+            }),
+            Command::Emit(args) => {
+                if args.params && args.backend != Emit::Sql {
+                    return Err(Error::Usage("--params needs the sql backend".to_owned()));
+                }
+                run(&Options {
+                    input: args.input,
+                    emit: Some(args.backend),
+                    dialect: args.dialect,
+                    params: args.params,
+                    pretty: args.pretty,
+                    ..Options::default()
+                })
+            }
+            Command::Repl(args) => repl::run(&Options {
+                data: args.data,
+                format: args.format,
+                ..Options::default()
+            }),
+        }
+    }
+}
+
+// "-" は標準入力
+fn read_source(source: &str) -> Result<String, Error> {
+    if source == "-" {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| Error::Io("stdin".to_owned(), e))?;
+        Ok(input)
+    } else {
+        fs::read_to_string(source).map_err(|e| Error::Io(source.to_owned(), e))
+    }
+}
+
+// 位置引数、-f のファイル、標準入力のいずれかからクエリを読む。
+// 標準入力と -f は単一のクエリとして扱う
+fn read_queries(options: &Options, stdin_taken: bool) -> Result<Vec<String>, Error> {
+    let both_stdin = || Error::Usage("query and data can't both be read from stdin".to_owned());
+    let input = match (options.input.queries.as_slice(), &options.input.file) {
+        ([], Some(file)) if stdin_taken && file == "-" => return Err(both_stdin()),
+        ([], Some(file)) => read_source(file)?,
+        (_, Some(_)) => return Err(Error::Usage("-f can't be combined with a query".to_owned())),
+        ([], None) if stdin_taken || io::stdin().is_terminal() => {
+            return Err(Error::Usage("missing query".to_owned()))
+        }
+        ([arg], None) if stdin_taken && (arg.is_empty() || arg == "-") => return Err(both_stdin()),
+        ([], None) => read_source("-")?,
+        ([arg], None) if arg.is_empty() || arg == "-" => read_source("-")?,
+        (args, None) if args.iter().any(|arg| arg.is_empty() || arg == "-") => {
+            return Err(Error::Usage(
+                "stdin can't be combined with other queries".to_owned(),
+            ))
+        }
+        (args, None) => return Ok(args.to_vec()),
+    };
+    Ok(vec![input])
+}
+
+// --tokens や --check など、入力をそのまま扱うモード用
+fn single_query(mut inputs: Vec<String>, flag: &str) -> Result<String, Error> {
+    match inputs.len() {
+        1 => Ok(inputs.remove(0)),
+        _ => Err(Error::Usage(format!("{} takes a single query", flag))),
+    }
+}
+
+// 各引数を個別にパースし、and (--or なら or) でまとめる
+fn parse_queries(inputs: Vec<String>, or: bool) -> Result<Query, Error> {
+    let indexed = inputs.len() > 1;
+    let mut queries = Vec::with_capacity(inputs.len());
+    for (i, input) in inputs.into_iter().enumerate() {
+        let mut parser = Parser::new_from_string(input.clone());
+        let index = if indexed { Some(i + 1) } else { None };
+        queries.push(
+            parser
+                .parse_query()
+                .map_err(|e| Error::Parse(index, input, e))?,
+        );
+    }
+    Ok(if or {
+        Query::or_all(queries)
+    } else {
+        Query::and_all(queries)
+    })
+}
+
+// 配列なら一致した要素を1行ずつ出力し、件数を標準エラーに出す。
+// それ以外は単一のドキュメントとして扱い、一致したかどうかを終了コードで返す
+fn read_json(source: &str) -> Result<serde_json::Value, Error> {
+    serde_json::from_str(&read_source(source)?)
+        .map_err(|e| Error::Input(format!("invalid JSON in {}: {}", source, e)))
+}
+
+// --count なら件数だけを出力し、--fail-empty なら0件のとき終了コード1を返す
+fn finish(matched: usize, summary: &str, options: &Options) -> ExitCode {
+    if options.count {
+        println!("{}", matched);
+    } else {
+        eprintln!("{}", summary);
+    }
+    if options.fail_empty && matched == 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn filter_data(query: &Query, source: &str, options: &Options) -> Result<ExitCode, Error> {
+    match read_json(source)? {
+        serde_json::Value::Array(docs) => {
+            let total = docs.len();
+            let page = eval::apply(query, docs).map_err(Error::Eval)?;
+            // count() などは値だけを出力する
+            let reduced = eval::reduce(query, &page.documents, &eval::EvalOptions::default());
+            if let Some(value) = reduced.map_err(Error::Eval)? {
+                print_json(&value, false);
+                return Ok(ExitCode::SUCCESS);
+            }
+            if !options.count {
+                for doc in &page.documents {
+                    print_json(doc, false);
+                }
+            }
+            let summary = format!("{} of {} documents matched", page.total, total);
+            Ok(finish(page.total, &summary, options))
+        }
+        doc => {
+            let matched = query.matches(&doc);
+            if options.count {
+                println!("{}", matched as usize);
+            } else if matched {
+                print_json(&doc, false);
+            }
+            Ok(if matched {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(1)
+            })
+        }
+    }
+}
+
+// 単一ドキュメントなら一致したかどうかを終了コードでも返す
+fn explain_data(query: &Query, source: &str, options: &Options) -> Result<ExitCode, Error> {
+    let print = |doc: &serde_json::Value| {
+        let explain = query.explain(doc);
+        match options.format {
+            Format::Json => print_json(&serde_json::to_value(&explain).unwrap(), options.pretty),
+            _ => println!("{}", explain),
+        }
+        explain.matched()
+    };
+    match read_json(source)? {
+        serde_json::Value::Array(docs) => {
+            for (i, doc) in docs.iter().enumerate() {
+                if options.format != Format::Json {
+                    println!("document {}:", i + 1);
+                }
+                print(doc);
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        doc if print(&doc) => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::from(1)),
+    }
+}
+
+// 1行ずつ読み、一致した行をそのまま出力する。メモリ使用量は最長の行で決まる
+fn filter_ndjson(query: &Query, options: &Options) -> Result<ExitCode, Error> {
+    let stdin = io::stdin();
+    let mut reader = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let mut line = Vec::new();
+    let (mut total, mut matched, mut malformed) = (0, 0, 0);
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| Error::Io("stdin".to_owned(), e))?;
+        if read == 0 {
+            break;
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        total += 1;
+        match serde_json::from_slice::<serde_json::Value>(&line) {
+            Ok(doc) if query.matches(&doc) => {
+                matched += 1;
+                if options.count {
+                    continue;
+                }
+                let written = out.write_all(&line).and_then(|_| {
+                    if line.ends_with(b"\n") {
+                        Ok(())
+                    } else {
+                        out.write_all(b"\n")
+                    }
+                });
+                // 出力先が閉じられた (head など) 場合はそこで終える
+                if written.is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(_) => malformed += 1,
+        }
+    }
+    let _ = out.flush();
+    drop(out);
+    let summary = format!("{} of {} documents matched", matched, total - malformed);
+    let code = finish(matched, &summary, options);
+    if malformed > 0 {
+        eprintln!("{} malformed lines", malformed);
+        if options.strict {
+            return Ok(ExitCode::from(1));
+        }
+    }
+    Ok(code)
+}
+
+#[cfg(feature = "csv")]
+fn filter_csv(query: &Query, source: &str, options: &Options) -> Result<ExitCode, Error> {
+    use rql_parser::csv::{infer, positional_headers};
+
+    let input: Box<dyn Read> = if source == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(fs::File::open(source).map_err(|e| Error::Io(source.to_owned(), e))?)
+    };
+    let invalid = |e: csv::Error| Error::Input(format!("invalid CSV in {}: {}", source, e));
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(!options.no_header)
+        .flexible(true)
+        .from_reader(input);
+    // ヘッダー無しの場合 headers() は先頭行を返すので、列数だけを使う
+    let mut headers = reader.headers().map_err(invalid)?.clone();
+    if options.no_header {
+        headers = positional_headers(headers.len());
+    }
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    if options.output == Output::Csv && !options.no_header && !options.count {
+        writer.write_record(&headers).map_err(invalid)?;
+    }
+    let (mut total, mut matched) = (0, 0);
+    for record in reader.records() {
+        let record = record.map_err(invalid)?;
+        total += 1;
+        if options.no_header && headers.len() < record.len() {
+            headers = positional_headers(record.len());
+        }
+        if !query.matches_csv(&headers, &record) {
+            continue;
+        }
+        matched += 1;
+        if options.count {
+            continue;
+        }
+        match options.output {
+            Output::Csv => writer.write_record(&record).map_err(invalid)?,
+            Output::Json => {
+                let object: serde_json::Map<String, serde_json::Value> = headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(header, cell)| (header.to_owned(), infer(cell)))
+                    .collect();
+                writer
+                    .flush()
+                    .map_err(|e| Error::Io("stdout".to_owned(), e))?;
+                print_json(&serde_json::Value::Object(object), false);
+            }
+        }
+    }
+    writer
+        .flush()
+        .map_err(|e| Error::Io("stdout".to_owned(), e))?;
+    Ok(finish(
+        matched,
+        &format!("{} of {} rows matched", matched, total),
+        options,
+    ))
+}
+
+#[cfg(not(feature = "csv"))]
+fn filter_csv(_: &Query, _: &str, _: &Options) -> Result<ExitCode, Error> {
+    Err(Error::Usage(
+        "rql was built without the csv feature".to_owned(),
+    ))
+}
+
+// 1行1クエリとして検証し、失敗が1つでもあれば終了コード1を返す
+fn check(input: &str) -> ExitCode {
+    let mut failed = false;
+    let mut offset = 0;
+    for (i, raw) in input.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += raw.len();
+        let line = raw.trim_end_matches(&['\n', '\r'][..]);
+        let query = line.trim();
+        if query.is_empty() || query.starts_with('#') {
+            continue;
+        }
+        match Parser::from_str(line).parse_query() {
+            Ok(_) => println!("line {}: OK", i + 1),
+            Err(e) => {
+                failed = true;
+                // 行番号がファイルと一致するよう、入力全体に対して表示する
+                let span = diagnostic::span(line, &e)
+                    .map(|span| span.start + line_start..span.end + line_start);
+                let message = format!("line {}: {}", i + 1, e);
+                println!(
+                    "{}",
+                    diagnostic::render_message(input, &message, span, false)
+                );
+            }
+        }
+    }
+    if failed {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn format_tokens(input: &str) -> String {
+    let mut out = String::new();
+    for (token, span) in tokenize(input) {
+        let line = match token {
+            Token::Illegal => format!("{:?} Illegal {:?}\n", span, &input[span.clone()]),
+            _ => format!("{:?} {:?}\n", span, token),
+        };
+        out.push_str(&line);
+    }
+    out
+}
+
+// 出力するキーは常に辞書順に並べる。bson などの feature で serde_json の preserve_order が
+// 有効になると Map が挿入順になり、同じ入力でも出力が変わってしまうため
+fn sort_keys(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(items) => items.iter().map(sort_keys).collect(),
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.clone(), sort_keys(value)))
+                .collect()
+        }
+        _ => value.clone(),
+    }
+}
+
+fn print_json(value: &serde_json::Value, pretty: bool) {
+    let value = sort_keys(value);
+    if pretty {
+        println!("{:#}", value);
+    } else {
+        println!("{}", value);
+    }
+}
+
+fn emit(query: &Query, backend: Emit, options: &Options) -> Result<(), Error> {
+    match backend {
+        Emit::Sql if options.params => {
+            let (sql, params) = query
+                .to_sql_params(options.dialect)
+                .map_err(Error::Convert)?;
+            println!("{}", sql);
+            print_json(&params.into(), options.pretty);
+        }
+        Emit::Sql => println!("{}", query.to_sql(options.dialect).map_err(Error::Convert)?),
+        Emit::Mongo => print_json(&query.to_mongo().map_err(Error::Convert)?, options.pretty),
+        Emit::Es => print_json(&query.to_es().map_err(Error::Convert)?, options.pretty),
+        Emit::Dot => print!("{}", query.to_dot()),
+    }
+    Ok(())
+}
+
+fn format_query(query: &Query, format: Format, pretty: bool) -> String {
+    match format {
+        Format::Debug => format!("out: {}", query),
+        Format::Json if pretty => serde_json::to_string_pretty(query).unwrap(),
+        Format::Json => serde_json::to_string(query).unwrap(),
+        Format::Rql => query.to_rql(),
+    }
+}
+
+fn run(options: &Options) -> Result<ExitCode, Error> {
+    let modes = [
+        options.data.is_some(),
+        options.ndjson,
+        options.csv.is_some(),
+    ];
+    if modes.iter().filter(|&&m| m).count() > 1 {
+        return Err(Error::Usage(
+            "--data, --ndjson and --csv can't be combined".to_owned(),
+        ));
+    }
+    if (options.count || options.fail_empty) && !modes.contains(&true) {
+        return Err(Error::Usage(
+            "--count and --fail-empty need --data, --ndjson or --csv".to_owned(),
+        ));
+    }
+    let stdin_taken = options.ndjson
+        || options.data.as_deref() == Some("-")
+        || options.csv.as_deref() == Some("-");
+    let inputs = read_queries(options, stdin_taken)?;
+    if options.check {
+        return Ok(check(&single_query(inputs, "--check")?));
+    }
+    if options.tokens {
+        print!("{}", format_tokens(&single_query(inputs, "--tokens")?));
+        return Ok(ExitCode::SUCCESS);
+    }
+    let query = parse_queries(inputs, options.input.or)?;
+    if options.explain {
+        return match &options.data {
+            Some(data) => explain_data(&query, data, options),
+            None => Err(Error::Usage("--explain needs --data".to_owned())),
+        };
+    }
+    if options.stats {
+        return match &options.data {
+            Some(data) => stats::run(&query, data, options),
+            None => Err(Error::Usage("--stats needs --data".to_owned())),
+        };
+    }
+    if options.stats_field.is_some() {
+        return Err(Error::Usage("--stats-field needs --stats".to_owned()));
+    }
+    if let Some(data) = &options.data {
+        return filter_data(&query, data, options);
+    }
+    if let Some(backend) = options.emit {
+        emit(&query, backend, options)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if options.ndjson {
+        return filter_ndjson(&query, options);
+    }
+    if let Some(csv) = &options.csv {
+        return filter_csv(&query, csv, options);
+    }
+    println!("{}", format_query(&query, options.format, options.pretty));
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let color = io::stderr().is_terminal()
+        && !args.iter().any(|arg| arg == "--no-color")
+        && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+    let choice = if color {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    };
+    let cli = match Cli::command().color(choice).try_get_matches_from(&args) {
+        Ok(matches) => Cli::from_arg_matches(&matches),
+        Err(e) => Err(e),
+    };
+    let cli = match cli {
+        Ok(cli) => cli,
+        // --help もここで表示する
+        Err(e) => {
+            let _ = e.print();
+            return ExitCode::from(e.exit_code() as u8);
+        }
+    };
+    let result = match cli.command {
+        Some(command) => command.run(),
+        None => run(&cli.options),
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e.render(color));
+            e.exit_code()
+        }
+    }
+}
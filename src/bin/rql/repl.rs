@@ -0,0 +1,207 @@
+use crate::{format_query, format_tokens, read_json, Error, Format, Options};
+use rql_parser::parser::Parser;
+use std::io::{self, BufRead, Write};
+use std::process::ExitCode;
+
+const HELP: &str = "enter a query, or one of:
+  :tokens <query>              print the lexer output
+  :format <debug|json|rql>     change the output format
+  :help                        print this help
+  :quit                        exit";
+
+// --data がある場合に表示する一致レコードの件数
+const PREVIEW: usize = 5;
+
+pub struct Repl {
+    format: Format,
+    data: Option<Vec<serde_json::Value>>,
+}
+
+pub enum Reply {
+    Print(String),
+    Quit,
+}
+
+impl Repl {
+    pub fn new(format: Format, data: Option<serde_json::Value>) -> Self {
+        let data = data.map(|data| match data {
+            serde_json::Value::Array(docs) => docs,
+            doc => vec![doc],
+        });
+        Repl { format, data }
+    }
+
+    /// Processes one input line. Errors are part of the reply; the session goes on.
+    pub fn eval_line(&mut self, line: &str) -> Reply {
+        let line = line.trim();
+        if let Some(command) = line.strip_prefix(':') {
+            let (name, arg) = match command.find(char::is_whitespace) {
+                Some(i) => (&command[..i], command[i..].trim()),
+                None => (command, ""),
+            };
+            return match name {
+                "q" | "quit" | "exit" => Reply::Quit,
+                "h" | "help" => Reply::Print(HELP.to_owned()),
+                "tokens" => Reply::Print(format_tokens(arg).trim_end().to_owned()),
+                "format" => match arg.parse::<Format>() {
+                    Ok(format) => {
+                        self.format = format;
+                        Reply::Print(format!("format: {}", arg))
+                    }
+                    Err(_) => Reply::Print(format!("error: unknown format {}", arg)),
+                },
+                _ => Reply::Print(format!("error: unknown command :{} (try :help)", name)),
+            };
+        }
+        if line.is_empty() {
+            return Reply::Print(String::new());
+        }
+        let query = match Parser::from_str(line).parse_query() {
+            Ok(query) => query,
+            Err(e) => return Reply::Print(e.render(line)),
+        };
+        let docs = match &self.data {
+            Some(docs) => docs,
+            None => return Reply::Print(format_query(&query, self.format, false)),
+        };
+        let matched: Vec<_> = docs.iter().filter(|doc| query.matches(doc)).collect();
+        let mut out = format!("{} of {} documents matched", matched.len(), docs.len());
+        for doc in matched.iter().take(PREVIEW) {
+            out.push_str(&format!("\n{}", doc));
+        }
+        if matched.len() > PREVIEW {
+            out.push_str(&format!("\n... {} more", matched.len() - PREVIEW));
+        }
+        Reply::Print(out)
+    }
+}
+
+pub fn run(options: &Options) -> Result<ExitCode, Error> {
+    if !options.input.queries.is_empty() {
+        return Err(Error::Usage("repl doesn't take a query".to_owned()));
+    }
+    let data = match &options.data {
+        Some(source) if source == "-" => {
+            return Err(Error::Usage(
+                "repl reads queries from stdin; use a data file".to_owned(),
+            ))
+        }
+        Some(source) => Some(read_json(source)?),
+        None => None,
+    };
+    let mut repl = Repl::new(options.format, data);
+    let stdin = io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("rql> ");
+        io::stdout()
+            .flush()
+            .map_err(|e| Error::Io("stdout".to_owned(), e))?;
+        line.clear();
+        let read = stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| Error::Io("stdin".to_owned(), e))?;
+        if read == 0 {
+            println!();
+            return Ok(ExitCode::SUCCESS);
+        }
+        match repl.eval_line(&line) {
+            Reply::Print(out) if out.is_empty() => {}
+            Reply::Print(out) => println!("{}", out),
+            Reply::Quit => return Ok(ExitCode::SUCCESS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repl::{Repl, Reply};
+    use crate::Format;
+    use serde_json::json;
+
+    fn eval(repl: &mut Repl, line: &str) -> String {
+        match repl.eval_line(line) {
+            Reply::Print(out) => out,
+            Reply::Quit => panic!("unexpected quit"),
+        }
+    }
+
+    #[test]
+    fn query_output_and_format() {
+        let mut repl = Repl::new(Format::Rql, None);
+        assert_eq!(
+            eval(&mut repl, "and( eq(a,1) ,gt(b,2))\n"),
+            "and(eq(a,1),gt(b,2))"
+        );
+        assert_eq!(eval(&mut repl, ":format json"), "format: json");
+        assert_eq!(
+            eval(&mut repl, "eq(a,1)"),
+            "{\"Filter\":[\"Eq\",{\"Identifier\":\"a\"},{\"IntegerLiteral\":1}]}"
+        );
+        assert_eq!(
+            eval(&mut repl, ":format yaml"),
+            "error: unknown format yaml"
+        );
+        assert_eq!(eval(&mut repl, ""), "");
+    }
+
+    #[test]
+    fn parse_error_continues() {
+        let mut repl = Repl::new(Format::Rql, None);
+        assert_eq!(
+            eval(&mut repl, "eq(a 1)"),
+            "error: expected ',', found '1' at position 5\n  |\n1 | eq(a 1)\n  |      ^"
+        );
+        assert_eq!(eval(&mut repl, "eq(a,1)"), "eq(a,1)");
+    }
+
+    #[test]
+    fn meta_commands() {
+        let mut repl = Repl::new(Format::Debug, None);
+        assert_eq!(
+            eval(&mut repl, ":tokens eq(a,1)"),
+            "0..2 Eq\n2..3 Lparen\n3..4 Ident(\"a\")\n4..5 Comma\n5..6 Int(\"1\")\n6..7 Rparen\n7..7 Eof"
+        );
+        assert!(eval(&mut repl, ":help").contains(":quit"));
+        assert!(eval(&mut repl, ":nope").starts_with("error: unknown command :nope"));
+        assert!(matches!(repl.eval_line(":quit"), Reply::Quit));
+    }
+
+    // 一致レコードの行は値として比べる。bson などの feature で serde_json の
+    // preserve_order が有効になると、キーの順序が変わるため
+    fn lines(reply: &str) -> Vec<serde_json::Value> {
+        reply
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap_or_else(|_| json!(line)))
+            .collect()
+    }
+
+    #[test]
+    fn data() {
+        let docs: Vec<_> = (0..8)
+            .map(|i| json!({"id": i, "even": i % 2 == 0}))
+            .collect();
+        let mut repl = Repl::new(Format::Debug, Some(docs.into()));
+        assert_eq!(
+            lines(&eval(&mut repl, "gt(id,5)")),
+            vec![
+                json!("2 of 8 documents matched"),
+                json!({"even": true, "id": 6}),
+                json!({"even": false, "id": 7}),
+            ]
+        );
+        assert_eq!(
+            lines(&eval(&mut repl, "ge(id,1)")),
+            vec![
+                json!("7 of 8 documents matched"),
+                json!({"even": false, "id": 1}),
+                json!({"even": true, "id": 2}),
+                json!({"even": false, "id": 3}),
+                json!({"even": true, "id": 4}),
+                json!({"even": false, "id": 5}),
+                json!("... 2 more"),
+            ]
+        );
+    }
+}
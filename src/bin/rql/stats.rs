@@ -0,0 +1,83 @@
+use crate::{print_json, read_json, Error, Format, Options};
+use rql_parser::ast::{Aggregation, Path, Query};
+use rql_parser::eval::{self, EvalOptions};
+use serde_json::{json, Value};
+use std::process::ExitCode;
+
+// --stats-field が無ければ、クエリ中の最初の sum(price) などのフィールドを使う
+fn field(query: &Query, options: &Options) -> Option<Path> {
+    if let Some(field) = &options.stats_field {
+        return Some(Path::from(field.as_str()));
+    }
+    match query {
+        Query::Reduce(_, path) => Some(path.clone()),
+        Query::Aggregate(_, functions) => functions
+            .iter()
+            .find(|(function, _)| *function != Aggregation::Count)
+            .map(|(_, path)| path.clone()),
+        Query::And(queries) => queries.iter().find_map(|q| field(q, options)),
+        _ => None,
+    }
+}
+
+// 一致件数と全件数、一致率、フィールドがあればその最小・最大・平均を出力する。
+// sort() や limit() などは無視し、フィルターだけで数える
+pub fn run(query: &Query, source: &str, options: &Options) -> Result<ExitCode, Error> {
+    let docs = match read_json(source)? {
+        Value::Array(docs) => docs,
+        doc => vec![doc],
+    };
+    let total = docs.len();
+    let matched: Vec<Value> = docs.into_iter().filter(|doc| query.matches(doc)).collect();
+    let mut stats = json!({
+        "matched": matched.len(),
+        "total": total,
+        "percent": if total == 0 {
+            Value::Null
+        } else {
+            json!(matched.len() as f64 * 100.0 / total as f64)
+        },
+    });
+    if let Some(path) = field(query, options) {
+        stats["field"] = path.to_string().into();
+        for function in [Aggregation::Min, Aggregation::Max, Aggregation::Mean] {
+            let reduce = Query::Reduce(function, path.clone());
+            let value =
+                eval::reduce(&reduce, &matched, &EvalOptions::default()).map_err(Error::Eval)?;
+            stats[function.name()] = value.unwrap_or(Value::Null);
+        }
+    }
+    match options.format {
+        Format::Json => print_json(&stats, options.pretty),
+        _ => print!("{}", table(&stats)),
+    }
+    Ok(if options.fail_empty && matched.is_empty() {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn table(stats: &Value) -> String {
+    let rows = ["matched", "total", "percent", "field", "min", "max", "mean"];
+    let mut out = String::new();
+    for name in &rows {
+        let cell = match (stats.get(name), *name) {
+            (None, _) => continue,
+            (Some(Value::Null), _) => "-".to_owned(),
+            (Some(Value::Number(n)), "percent") => {
+                format!("{:.1}%", n.as_f64().unwrap_or_default())
+            }
+            (Some(Value::Number(n)), "mean") => {
+                format!(
+                    "{}",
+                    (n.as_f64().unwrap_or_default() * 1000.0).round() / 1000.0
+                )
+            }
+            (Some(Value::String(s)), _) => s.clone(),
+            (Some(value), _) => value.to_string(),
+        };
+        out.push_str(&format!("{:<8} {}\n", name, cell));
+    }
+    out
+}
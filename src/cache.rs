@@ -0,0 +1,200 @@
+//! A cache of parsed queries, for servers that see the same handful of
+//! filter strings on every request.
+
+use crate::ast::Query;
+use crate::parser::{Parser, ParserError, ParserOptions};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A least-recently-used map from input strings to their parsed queries,
+/// shareable between threads.
+///
+/// ```
+/// use rql_parser::cache::QueryCache;
+/// use std::sync::Arc;
+///
+/// let cache = QueryCache::new(100);
+/// let a = cache.get_or_parse("eq(status,\"open\")").unwrap();
+/// let b = cache.get_or_parse("eq(status,\"open\")").unwrap();
+/// assert!(Arc::ptr_eq(&a, &b));
+/// ```
+#[derive(Debug)]
+pub struct QueryCache {
+    capacity: usize,
+    options: ParserOptions,
+    inner: Mutex<Lru>,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    // 入力 -> (クエリ, 最後に使った時刻)
+    entries: HashMap<String, (Arc<Query>, u64)>,
+    // 最後に使った時刻 -> 入力。先頭が最も古い
+    order: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl QueryCache {
+    /// A cache holding up to `capacity` queries. With 0, nothing is kept.
+    pub fn new(capacity: usize) -> Self {
+        QueryCache {
+            capacity,
+            options: ParserOptions::default(),
+            inner: Mutex::new(Lru::default()),
+        }
+    }
+
+    /// Parses with `options` instead of the defaults.
+    pub fn with_options(mut self, options: ParserOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The query `input` parses to, parsing it only if it isn't cached.
+    /// Errors aren't cached. Parsing happens outside the lock, so two threads
+    /// missing the same input at once may both parse it; they get the same
+    /// query either way.
+    pub fn get_or_parse(&self, input: &str) -> Result<Arc<Query>, ParserError> {
+        if let Some(query) = self.lock().get(input) {
+            return Ok(query);
+        }
+        let query = Arc::new(
+            Parser::from_str(input)
+                .with_options(self.options.clone())
+                .parse_query()?,
+        );
+        if self.capacity == 0 {
+            return Ok(query);
+        }
+        let mut lru = self.lock();
+        // 別のスレッドが先に入れていればそちらを使う
+        if let Some(query) = lru.get(input) {
+            return Ok(query);
+        }
+        lru.insert(input.to_owned(), query.clone(), self.capacity);
+        Ok(query)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, input: &str) -> bool {
+        self.lock().entries.contains_key(input)
+    }
+
+    pub fn clear(&self) {
+        *self.lock() = Lru::default();
+    }
+
+    // パースでパニックしてもキャッシュの中身は壊れないので、毒は無視する
+    fn lock(&self) -> MutexGuard<'_, Lru> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Lru {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn get(&mut self, input: &str) -> Option<Arc<Query>> {
+        let now = self.tick();
+        let (query, used) = self.entries.get_mut(input)?;
+        let key = self.order.remove(used)?;
+        *used = now;
+        self.order.insert(now, key);
+        Some(query.clone())
+    }
+
+    fn insert(&mut self, input: String, query: Arc<Query>, capacity: usize) {
+        while self.entries.len() >= capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+        let now = self.tick();
+        self.order.insert(now, input.clone());
+        self.entries.insert(input, (query, now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::QueryCache;
+    use crate::parser::ParserOptions;
+    use std::sync::Arc;
+
+    #[test]
+    fn eviction() {
+        let cache = QueryCache::new(2);
+        let a = cache.get_or_parse("eq(a,1)").unwrap();
+        cache.get_or_parse("eq(b,1)").unwrap();
+        // a を使ったので、次に追い出されるのは b
+        assert!(Arc::ptr_eq(&a, &cache.get_or_parse("eq(a,1)").unwrap()));
+        cache.get_or_parse("eq(c,1)").unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains("eq(a,1)"));
+        assert!(!cache.contains("eq(b,1)"));
+        assert!(cache.contains("eq(c,1)"));
+
+        cache.get_or_parse("eq(d,1)").unwrap();
+        assert!(!cache.contains("eq(a,1)"));
+        assert!(!Arc::ptr_eq(&a, &cache.get_or_parse("eq(a,1)").unwrap()));
+
+        assert!(cache.get_or_parse("eq(a,").is_err());
+        assert!(!cache.contains("eq(a,"));
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert!(cache.is_empty());
+
+        let none = QueryCache::new(0);
+        assert_eq!(none.get_or_parse("eq(a,1)").unwrap().to_rql(), "eq(a,1)");
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn options() {
+        let cache = QueryCache::new(10).with_options(ParserOptions {
+            strict: true,
+            ..ParserOptions::default()
+        });
+        assert!(cache.get_or_parse("eq(a,b)").is_err());
+        assert!(cache.get_or_parse("eq(a,\"b\")").is_ok());
+    }
+
+    #[test]
+    fn threads() {
+        let cache = QueryCache::new(8);
+        let inputs: Vec<String> = (0..16).map(|i| format!("eq(f{},{})", i % 12, i)).collect();
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let (cache, inputs) = (&cache, &inputs);
+                s.spawn(move || {
+                    for i in 0..2000 {
+                        let input = &inputs[(i * 7 + t) % inputs.len()];
+                        assert_eq!(&cache.get_or_parse(input).unwrap().to_rql(), input);
+                        assert!(cache.len() <= 8);
+                    }
+                });
+            }
+        });
+        assert_eq!(cache.len(), 8);
+        let lru = cache.lock();
+        assert_eq!(lru.order.len(), 8);
+        assert!(lru
+            .order
+            .values()
+            .all(|input| lru.entries.contains_key(input)));
+    }
+}
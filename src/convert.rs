@@ -0,0 +1,71 @@
+//! Translation of queries into backend query languages.
+
+use crate::ast::{Path, Segment, Value};
+use std::fmt;
+
+mod backend;
+pub mod cypher;
+pub mod es;
+pub mod graphql;
+pub mod mongo;
+pub mod redisearch;
+pub mod sql;
+
+pub use backend::Backend;
+
+#[derive(Debug, PartialEq)]
+pub enum ConvertError {
+    // (ノード, 変換先)
+    Unsupported(String, &'static str),
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConvertError::Unsupported(node, target) => {
+                write!(f, "{} can't be translated to {}", node, target)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+pub(crate) fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::StringLiteral(s) => s.as_str().into(),
+        Value::IntegerLiteral(i) => (*i).into(),
+        Value::UnsignedLiteral(u) => (*u).into(),
+        Value::FloatLiteral(f) => (*f).into(),
+        #[cfg(feature = "decimal")]
+        Value::DecimalLiteral(d) => crate::decimal::to_json(d),
+        Value::Boolean(b) => (*b).into(),
+        #[cfg(feature = "chrono")]
+        Value::DateTime(datetime) => crate::datetime::format(datetime).into(),
+        // 変換した時点の時刻で絶対時刻にする
+        #[cfg(feature = "chrono")]
+        Value::RelativeTime(..) => match crate::datetime::instant(value, None) {
+            Some(datetime) => crate::datetime::format(&datetime).into(),
+            None => value.to_string().into(),
+        },
+        // 変換先では値にならないので、呼び出し側で弾くかフィールドの参照にする
+        Value::Identifier(_) | Value::Call(_) | Value::Variable(_) => value.to_string().into(),
+    }
+}
+
+// ドキュメントストアのドット区切りのフィールド名。配列は暗黙に展開されるので * は省く。
+// ドットを含むキーはドット区切りでは書けない
+pub(crate) fn dotted(path: &Path, target: &'static str) -> Result<String, ConvertError> {
+    let mut parts = vec![];
+    for segment in path.segments() {
+        match segment {
+            Segment::Key(key) if key.contains('.') => {
+                return Err(ConvertError::Unsupported(path.to_string(), target))
+            }
+            Segment::Key(key) => parts.push(key.clone()),
+            Segment::Index(i) => parts.push(i.to_string()),
+            Segment::Wildcard => {}
+        }
+    }
+    Ok(parts.join("."))
+}
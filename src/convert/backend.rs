@@ -0,0 +1,82 @@
+//! [`Backend`], the tree walk shared by the converters.
+
+use crate::ast::{Path, Query};
+
+/// A target query language, driven by [`Query::lower`].
+///
+/// `lower` walks the query and hands each node to the backend: `and()` and
+/// `or()` become [`combine_and`](Backend::combine_and) and
+/// [`combine_or`](Backend::combine_or) of their lowered children, `not()` the
+/// [`negate`](Backend::negate)d lowered child, everything
+/// else goes to [`visit_filter`](Backend::visit_filter), and the fragment for
+/// the whole query is passed to [`finish`](Backend::finish). An empty query
+/// (`Query::None`) is an empty `combine_and`.
+///
+/// `tests/backend.rs` has a backend printing S-expressions.
+pub trait Backend {
+    /// What a node lowers to, before the query is finished.
+    type Fragment;
+    /// What [`Query::lower`] returns.
+    type Output;
+    type Error;
+
+    /// Lowers a node that isn't `and()`, `or()` or `not()`: a filter,
+    /// `in()`, `contains()`, `exists()`, `search()`, `near()`, or a node like
+    /// `sort()` that isn't a condition, which most backends reject.
+    fn visit_filter(&mut self, query: &Query) -> Result<Self::Fragment, Self::Error>;
+
+    /// Lowers `and()`. `parts` may be empty, which matches everything.
+    fn combine_and(&mut self, parts: Vec<Self::Fragment>) -> Result<Self::Fragment, Self::Error>;
+
+    /// Lowers `or()`. `parts` may be empty, which matches nothing.
+    fn combine_or(&mut self, parts: Vec<Self::Fragment>) -> Result<Self::Fragment, Self::Error>;
+
+    /// The fragment matching what `part` doesn't.
+    fn negate(&mut self, part: Self::Fragment) -> Result<Self::Fragment, Self::Error>;
+
+    /// Turns the fragment for the whole query into the output.
+    fn finish(&mut self, part: Self::Fragment) -> Result<Self::Output, Self::Error>;
+
+    /// Lowers `missing(field)`. By default it's `exists(field)` negated;
+    /// override it when the target has a direct form.
+    fn visit_missing(&mut self, field: &Path) -> Result<Self::Fragment, Self::Error> {
+        let exists = self.visit_filter(&Query::Exists(field.clone()))?;
+        self.negate(exists)
+    }
+}
+
+impl Query {
+    /// Translates the query with `backend`. See [`Backend`].
+    pub fn lower<B: Backend>(&self, backend: &mut B) -> Result<B::Output, B::Error> {
+        let part = self.lower_part(backend)?;
+        backend.finish(part)
+    }
+
+    fn lower_part<B: Backend>(&self, backend: &mut B) -> Result<B::Fragment, B::Error> {
+        match self {
+            Query::And(queries) => {
+                let parts = lower_all(queries, backend)?;
+                backend.combine_and(parts)
+            }
+            Query::Or(queries) => {
+                let parts = lower_all(queries, backend)?;
+                backend.combine_or(parts)
+            }
+            Query::Not(query) => {
+                let part = query.lower_part(backend)?;
+                backend.negate(part)
+            }
+            Query::None => backend.combine_and(vec![]),
+            Query::Missing(field) => backend.visit_missing(field),
+            _ => backend.visit_filter(self),
+        }
+    }
+}
+
+fn lower_all<B: Backend>(queries: &[Query], backend: &mut B) -> Result<Vec<B::Fragment>, B::Error> {
+    let mut parts = Vec::with_capacity(queries.len());
+    for query in queries {
+        parts.push(query.lower_part(backend)?);
+    }
+    Ok(parts)
+}
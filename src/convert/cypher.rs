@@ -0,0 +1,353 @@
+use crate::ast::{Infix, Path, Query, Segment, Value};
+use crate::convert::{json_value, ConvertError};
+use serde_json::Map;
+
+const TARGET: &str = "Cypher";
+
+impl Query {
+    /// Renders the query as a Cypher `WHERE` expression on the node or
+    /// relationship bound to `var`, such as
+    /// `n.age > 30 AND (n.name = 'x' OR n.vip = true)`, with inlined, escaped
+    /// literals.
+    ///
+    /// Dotted paths become nested map access (`n.address.city`) and indexes
+    /// list access (`tags.0` as `n.tags[0]`); keys that aren't plain
+    /// identifiers are quoted with backticks. Paths with wildcards can't be
+    /// translated. Dates become `datetime('...')`. `in()` is `n.f IN [..]`,
+    /// and `contains()` is `n.f CONTAINS 'x'` for a string and list
+    /// membership (`1 IN n.f`) for other values.
+    ///
+    /// ```
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let query = Parser::new_from_string("and(gt(age,30),startswith(name,\"A\"))".to_owned())
+    ///     .parse_query()
+    ///     .unwrap();
+    /// assert_eq!(query.to_cypher("n").unwrap(), "n.age > 30 AND n.name STARTS WITH 'A'");
+    /// ```
+    pub fn to_cypher(&self, var: &str) -> Result<String, ConvertError> {
+        CypherWriter {
+            var: identifier(var),
+            params: None,
+        }
+        .write(self)
+    }
+
+    /// Same as [`Query::to_cypher`] with `$p0`, `$p1`... placeholders
+    /// instead of literals, returning the parameters to run it with.
+    pub fn to_cypher_params(
+        &self,
+        var: &str,
+    ) -> Result<(String, Map<String, serde_json::Value>), ConvertError> {
+        let mut writer = CypherWriter {
+            var: identifier(var),
+            params: Some(Map::new()),
+        };
+        let cypher = writer.write(self)?;
+        Ok((cypher, writer.params.unwrap_or_default()))
+    }
+}
+
+// 英字か _ で始まり、英数字と _ だけからなる名前以外はバッククォートで囲む
+fn identifier(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        name.to_owned()
+    } else {
+        format!("`{}`", name.replace('`', "``"))
+    }
+}
+
+fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+struct CypherWriter {
+    var: String,
+    params: Option<Map<String, serde_json::Value>>,
+}
+
+impl CypherWriter {
+    fn write(&mut self, query: &Query) -> Result<String, ConvertError> {
+        match query {
+            Query::And(queries) if queries.is_empty() => Ok("true".to_owned()),
+            Query::Or(queries) if queries.is_empty() => Ok("false".to_owned()),
+            Query::And(queries) => self.join(queries, " AND "),
+            Query::Or(queries) => self.join(queries, " OR "),
+            Query::Not(query) => Ok(format!("NOT ({})", self.write(query)?)),
+            Query::Filter(_, _, value @ (Value::Call(_) | Value::Variable(_))) => {
+                Err(ConvertError::Unsupported(value.to_string(), TARGET))
+            }
+            Query::Filter(infix, Value::Identifier(path), value) => {
+                let operator = match infix {
+                    Infix::Eq => "=",
+                    Infix::NotEq => "<>",
+                    Infix::Lt => "<",
+                    Infix::Le => "<=",
+                    Infix::Gt => ">",
+                    Infix::Ge => ">=",
+                    Infix::StartsWith | Infix::IStartsWith => "STARTS WITH",
+                    Infix::EndsWith => "ENDS WITH",
+                    Infix::IEq => "=",
+                    Infix::IContains => "CONTAINS",
+                };
+                let property = self.property(path)?;
+                let literal = self.operand(value)?;
+                Ok(match infix.ignores_case() {
+                    true => format!("toLower({}) {} toLower({})", property, operator, literal),
+                    false => format!("{} {} {}", property, operator, literal),
+                })
+            }
+            Query::Filter(..) => Err(ConvertError::Unsupported(query.to_rql(), TARGET)),
+            // ne() と同じく、out() は null のプロパティに一致しない (NOT null は null)
+            Query::In(path, values) | Query::Out(path, values) => {
+                let property = self.property(path)?;
+                let mut list = Vec::with_capacity(values.len());
+                for value in values {
+                    list.push(self.operand(value)?);
+                }
+                let not = if matches!(query, Query::Out(..)) { "NOT " } else { "" };
+                Ok(format!("{}{} IN [{}]", not, property, list.join(", ")))
+            }
+            Query::Contains(path, value) => {
+                let property = self.property(path)?;
+                let operand = self.operand(value)?;
+                Ok(match value {
+                    Value::StringLiteral(_) => format!("{} CONTAINS {}", property, operand),
+                    _ => format!("{} IN {}", operand, property),
+                })
+            }
+            Query::Exists(path) => Ok(format!(
+                "{} IS NOT NULL",
+                self.property(path)?
+            )),
+            Query::Missing(path) => Ok(format!(
+                "{} IS NULL",
+                self.property(path)?
+            )),
+            Query::Sort(..) => Err(ConvertError::Unsupported(
+                "sort()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::Limit(..) => Err(ConvertError::Unsupported(
+                "limit()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::Select(..) => Err(ConvertError::Unsupported(
+                "select()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::Distinct(..) => Err(ConvertError::Unsupported(
+                "distinct()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::Aggregate(..) => Err(ConvertError::Unsupported(
+                "aggregate()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::First => Err(ConvertError::Unsupported(
+                "first()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::One => Err(ConvertError::Unsupported(
+                "one()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::Count => Err(ConvertError::Unsupported(
+                "count()".to_owned(),
+                "a Cypher WHERE clause",
+            )),
+            Query::Reduce(..) | Query::Search(..) | Query::Near(..) => Err(
+                ConvertError::Unsupported(query.to_rql(), "a Cypher WHERE clause"),
+            ),
+            Query::None => Ok("true".to_owned()),
+        }
+    }
+
+    fn join(&mut self, queries: &[Query], separator: &str) -> Result<String, ConvertError> {
+        let mut parts = Vec::with_capacity(queries.len());
+        for query in queries {
+            let cypher = self.write(query)?;
+            match query {
+                Query::And(qs) | Query::Or(qs) if qs.len() > 1 => {
+                    parts.push(format!("({})", cypher))
+                }
+                _ => parts.push(cypher),
+            }
+        }
+        Ok(parts.join(separator))
+    }
+
+    // n.address.city、n.tags[0]
+    fn property(&self, path: &Path) -> Result<String, ConvertError> {
+        let mut out = self.var.clone();
+        for segment in path.segments() {
+            match segment {
+                Segment::Key(key) => {
+                    out.push('.');
+                    out.push_str(&identifier(key));
+                }
+                Segment::Index(i) => out.push_str(&format!("[{}]", i)),
+                Segment::Wildcard => {
+                    return Err(ConvertError::Unsupported(path.to_string(), TARGET))
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    // 比べる値。右辺の識別子は同じノードのプロパティ
+    fn operand(&mut self, value: &Value) -> Result<String, ConvertError> {
+        match value {
+            Value::Identifier(path) => self.property(path),
+            Value::Call(_) | Value::Variable(_) => {
+                Err(ConvertError::Unsupported(value.to_string(), TARGET))
+            }
+            _ => Ok(self.literal(value)),
+        }
+    }
+
+    fn literal(&mut self, value: &Value) -> String {
+        if let Some(params) = &mut self.params {
+            let name = format!("p{}", params.len());
+            params.insert(name.clone(), json_value(value));
+            return match value {
+                // 文字列で渡した日時を比較できる値にする
+                #[cfg(feature = "chrono")]
+                Value::DateTime(_) | Value::RelativeTime(..) => format!("datetime(${})", name),
+                _ => format!("${}", name),
+            };
+        }
+        match value {
+            Value::StringLiteral(s) => quote_string(s),
+            Value::FloatLiteral(f) if f.is_infinite() && *f > 0.0 => "Infinity".to_owned(),
+            Value::FloatLiteral(f) if f.is_infinite() => "-Infinity".to_owned(),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(_) | Value::RelativeTime(..) => match json_value(value) {
+                serde_json::Value::String(s) => format!("datetime({})", quote_string(&s)),
+                _ => value.to_string(),
+            },
+            _ => json_value(value).to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::ConvertError;
+    use crate::parser::{Parser, ParserOptions};
+    use serde_json::json;
+
+    const QUERY: &str =
+        "and(gt(age,30),or(eq(name,\"O'Hara\"),eq(vip,true)),ne(address.city,\"Kyoto\"),\
+                         le(score,2.5),exists(tags.0),endswith(email,\"@example.com\"))";
+
+    fn to_cypher(input: &str, var: &str) -> Result<String, ConvertError> {
+        Parser::new_from_string(input.to_owned())
+            .with_options(ParserOptions {
+                variables: true,
+                ..ParserOptions::default()
+            })
+            .parse_query()
+            .unwrap()
+            .to_cypher(var)
+    }
+
+    #[test]
+    fn inline() {
+        assert_eq!(
+            to_cypher(QUERY, "n").unwrap(),
+            "n.age > 30 AND (n.name = 'O\\'Hara' OR n.vip = true) AND n.address.city <> 'Kyoto' \
+             AND n.score <= 2.5 AND n.tags[0] IS NOT NULL AND n.email ENDS WITH '@example.com'"
+        );
+        assert_eq!(
+            to_cypher("and(eq(`first name`,\"a\\\\b\"),missing(x))", "my node").unwrap(),
+            "`my node`.`first name` = 'a\\\\b' AND `my node`.x IS NULL"
+        );
+        assert_eq!(
+            to_cypher("and(eq(a,1),or())", "n").unwrap(),
+            "n.a = 1 AND false"
+        );
+        assert_eq!(to_cypher("and()", "n").unwrap(), "true");
+    }
+
+    #[test]
+    fn field_to_field() {
+        assert_eq!(
+            to_cypher("and(gt(spent,budget),eq(a,meta.`b c`))", "n").unwrap(),
+            "n.spent > n.budget AND n.a = n.meta.`b c`"
+        );
+        let query = Parser::from_str("eq(a,b)").parse_query().unwrap();
+        let (cypher, params) = query.to_cypher_params("n").unwrap();
+        assert_eq!(cypher, "n.a = n.b");
+        assert!(params.is_empty());
+        assert!(to_cypher("eq(a,tags.*)", "n").is_err());
+    }
+
+    #[test]
+    fn membership() {
+        assert_eq!(
+            to_cypher("and(in(status,(\"new\",\"open\")),out(age,(1,2)))", "n").unwrap(),
+            "n.status IN ['new', 'open'] AND NOT n.age IN [1, 2]"
+        );
+        // パラメータにすると値ごとに1つ
+        let query = Parser::from_str("and(in(status,(\"new\",\"open\")),out(age,(1,2)))")
+            .parse_query()
+            .unwrap();
+        let (cypher, params) = query.to_cypher_params("n").unwrap();
+        assert_eq!(cypher, "n.status IN [$p0, $p1] AND NOT n.age IN [$p2, $p3]");
+        assert_eq!(
+            serde_json::Value::Object(params),
+            json!({"p0": "new", "p1": "open", "p2": 1, "p3": 2})
+        );
+        assert_eq!(
+            to_cypher("or(contains(name,\"an\"),contains(scores,3),not(eq(a,1)))", "n").unwrap(),
+            "n.name CONTAINS 'an' OR 3 IN n.scores OR NOT (n.a = 1)"
+        );
+    }
+
+    #[test]
+    fn params() {
+        let query = Parser::new_from_string(QUERY.to_owned())
+            .parse_query()
+            .unwrap();
+        let (cypher, params) = query.to_cypher_params("u").unwrap();
+        assert_eq!(
+            cypher,
+            "u.age > $p0 AND (u.name = $p1 OR u.vip = $p2) AND u.address.city <> $p3 \
+             AND u.score <= $p4 AND u.tags[0] IS NOT NULL AND u.email ENDS WITH $p5"
+        );
+        assert_eq!(
+            serde_json::Value::Object(params),
+            json!({
+                "p0": 30,
+                "p1": "O'Hara",
+                "p2": true,
+                "p3": "Kyoto",
+                "p4": 2.5,
+                "p5": "@example.com",
+            })
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let err = |input: &str| to_cypher(input, "n").unwrap_err().to_string();
+        assert_eq!(
+            err("eq(tags.*,\"x\")"),
+            "tags.* can't be translated to Cypher"
+        );
+        assert_eq!(
+            err("eq(owner,$user)"),
+            "$user can't be translated to Cypher"
+        );
+        assert_eq!(
+            err("and(eq(a,1),limit(10))"),
+            "limit() can't be translated to a Cypher WHERE clause"
+        );
+    }
+}
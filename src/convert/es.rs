@@ -0,0 +1,313 @@
+use crate::ast::{Infix, Query, Value};
+use crate::convert::{dotted, json_value, ConvertError};
+use serde_json::json;
+
+impl Query {
+    /// Builds an Elasticsearch query DSL object. Conditions are placed in filter
+    /// context, so they don't affect scoring.
+    ///
+    /// Bounds on the same field are merged first with [`Query::merge_ranges`]
+    /// and go into one `range` clause; bounds that leave nothing become
+    /// `match_none`.
+    ///
+    /// `in()` is a `terms` clause and `not()` a `must_not`. `contains()` is a
+    /// `term`, which matches any element of an array, or for a string a
+    /// `*value*` wildcard; on an array of strings, that also matches
+    /// elements that merely contain the value.
+    pub fn to_es(&self) -> Result<serde_json::Value, ConvertError> {
+        self.merge_ranges().es()
+    }
+
+    fn es(&self) -> Result<serde_json::Value, ConvertError> {
+        match self {
+            Query::And(queries) if queries.is_empty() => Ok(json!({"match_all": {}})),
+            Query::Or(queries) if queries.is_empty() => Ok(json!({"match_none": {}})),
+            Query::And(queries) => Ok(json!({
+                "bool": {"filter": merge_range_clauses(to_es_all(queries)?)}
+            })),
+            Query::Or(queries) => Ok(json!({
+                "bool": {"should": to_es_all(queries)?, "minimum_should_match": 1}
+            })),
+            Query::Not(query) => Ok(json!({"bool": {"must_not": [query.es()?]}})),
+            Query::In(ident, values) | Query::Out(ident, values) => {
+                let ident = dotted(ident, "Elasticsearch")?;
+                let mut terms = Vec::with_capacity(values.len());
+                for value in values {
+                    match value {
+                        Value::Identifier(_) | Value::Call(_) | Value::Variable(_) => {
+                            return Err(ConvertError::Unsupported(self.to_rql(), "Elasticsearch"))
+                        }
+                        _ => terms.push(json_value(value)),
+                    }
+                }
+                Ok(match self {
+                    Query::In(..) => json!({"terms": { ident: terms }}),
+                    // ne() と同じく、値の無いドキュメントは除く
+                    _ => json!({"bool": {
+                        "filter": [{"exists": {"field": ident}}],
+                        "must_not": [{"terms": { ident: terms }}],
+                    }}),
+                })
+            }
+            Query::Contains(_, value @ (Value::Call(_) | Value::Variable(_))) => {
+                Err(ConvertError::Unsupported(value.to_string(), "Elasticsearch"))
+            }
+            Query::Contains(_, Value::Identifier(_)) => {
+                Err(ConvertError::Unsupported(self.to_rql(), "Elasticsearch"))
+            }
+            Query::Contains(ident, Value::StringLiteral(s)) => {
+                let ident = dotted(ident, "Elasticsearch")?;
+                Ok(json!({"wildcard": { ident: {"value": format!("*{}*", escape_wildcard(s))} }}))
+            }
+            Query::Contains(ident, value) => {
+                let ident = dotted(ident, "Elasticsearch")?;
+                Ok(json!({"term": { ident: json_value(value) }}))
+            }
+            Query::Filter(_, _, value @ (Value::Call(_) | Value::Variable(_))) => {
+                Err(ConvertError::Unsupported(value.to_string(), "Elasticsearch"))
+            }
+            // フィールド同士の比較は term や range では書けない
+            Query::Filter(_, _, Value::Identifier(_)) => {
+                Err(ConvertError::Unsupported(self.to_rql(), "Elasticsearch"))
+            }
+            Query::Filter(Infix::StartsWith, Value::Identifier(ident), Value::StringLiteral(s)) => {
+                let ident = dotted(ident, "Elasticsearch")?;
+                Ok(json!({"prefix": { ident: s }}))
+            }
+            Query::Filter(Infix::EndsWith, Value::Identifier(ident), Value::StringLiteral(s)) => {
+                let ident = dotted(ident, "Elasticsearch")?;
+                Ok(json!({"wildcard": { ident: {"value": format!("*{}", escape_wildcard(s))} }}))
+            }
+            Query::Filter(infix, Value::Identifier(ident), Value::StringLiteral(s))
+                if infix.ignores_case() =>
+            {
+                let ident = dotted(ident, "Elasticsearch")?;
+                Ok(match infix {
+                    Infix::IEq => {
+                        json!({"term": { ident: {"value": s, "case_insensitive": true} }})
+                    }
+                    Infix::IStartsWith => {
+                        json!({"prefix": { ident: {"value": s, "case_insensitive": true} }})
+                    }
+                    _ => json!({"wildcard": { ident: {
+                        "value": format!("*{}*", escape_wildcard(s)),
+                        "case_insensitive": true,
+                    } }}),
+                })
+            }
+            Query::Filter(infix, Value::Identifier(ident), value) => {
+                let ident = dotted(ident, "Elasticsearch")?;
+                let value = json_value(value);
+                Ok(match infix {
+                    Infix::Eq => json!({"term": { ident: value }}),
+                    // must_not だけでは値の無いドキュメントにも一致するので、評価時と同じく除く
+                    Infix::NotEq => json!({"bool": {
+                        "filter": [{"exists": {"field": ident}}],
+                        "must_not": [{"term": { ident: value }}],
+                    }}),
+                    Infix::Lt => json!({"range": { ident: {"lt": value} }}),
+                    Infix::Le => json!({"range": { ident: {"lte": value} }}),
+                    Infix::Gt => json!({"range": { ident: {"gt": value} }}),
+                    Infix::Ge => json!({"range": { ident: {"gte": value} }}),
+                    Infix::StartsWith
+                    | Infix::EndsWith
+                    | Infix::IEq
+                    | Infix::IContains
+                    | Infix::IStartsWith => {
+                        return Err(ConvertError::Unsupported(self.to_rql(), "Elasticsearch"))
+                    }
+                })
+            }
+            Query::Filter(..) => Err(ConvertError::Unsupported(self.to_rql(), "Elasticsearch")),
+            Query::Exists(ident) => Ok(json!({"exists": {"field": dotted(ident, "Elasticsearch")?}})),
+            Query::Missing(ident) => Ok(json!({
+                "bool": {"must_not": [{"exists": {"field": dotted(ident, "Elasticsearch")?}}]}
+            })),
+            Query::Near(ident, lat, lon, radius) => Ok(json!({"geo_distance": {
+                "distance": format!("{}m", radius),
+                dotted(ident, "Elasticsearch")?: {"lat": lat, "lon": lon},
+            }})),
+            Query::Sort(..) => Err(ConvertError::Unsupported(
+                "sort()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::Limit(..) => Err(ConvertError::Unsupported(
+                "limit()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::Select(..) => Err(ConvertError::Unsupported(
+                "select()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::Distinct(..) => Err(ConvertError::Unsupported(
+                "distinct()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::Aggregate(..) => Err(ConvertError::Unsupported(
+                "aggregate()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::First => Err(ConvertError::Unsupported(
+                "first()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::One => Err(ConvertError::Unsupported(
+                "one()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::Count => Err(ConvertError::Unsupported(
+                "count()".to_owned(),
+                "an Elasticsearch query",
+            )),
+            Query::Reduce(..) | Query::Search(..) => Err(ConvertError::Unsupported(
+                self.to_rql(),
+                "an Elasticsearch query",
+            )),
+            Query::None => Ok(json!({"match_all": {}})),
+        }
+    }
+}
+
+// wildcard クエリの * と ? をエスケープする
+fn escape_wildcard(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('*', "\\*").replace('?', "\\?")
+}
+
+fn to_es_all(queries: &[Query]) -> Result<Vec<serde_json::Value>, ConvertError> {
+    queries.iter().map(Query::es).collect()
+}
+
+// 同じフィールドの range 節を1つにまとめる。gte が2つあるなど、キーが重なるものはそのまま
+fn merge_range_clauses(clauses: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+    let mut merged: Vec<serde_json::Value> = Vec::with_capacity(clauses.len());
+    for clause in clauses {
+        let (field, bounds) = match clause["range"].as_object().and_then(|r| r.iter().next()) {
+            Some((field, bounds)) => (field.clone(), bounds.as_object().cloned().unwrap_or_default()),
+            None => {
+                merged.push(clause);
+                continue;
+            }
+        };
+        let target = merged.iter_mut().find_map(|c| {
+            c.get_mut("range")
+                .and_then(|r| r.get_mut(&field))
+                .and_then(serde_json::Value::as_object_mut)
+                .filter(|existing| bounds.keys().all(|k| !existing.contains_key(k)))
+        });
+        match target {
+            Some(existing) => existing.extend(bounds),
+            None => merged.push(clause),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    #[test]
+    fn to_es() {
+        let query = Parser::new_from_string(
+            "and(eq(status,\"open\"),or(gt(priority,3),le(due.days,1.5)),ne(active,false))"
+                .to_owned(),
+        )
+        .parse_query()
+        .unwrap();
+        assert_eq!(
+            query.to_es().unwrap(),
+            json!({"bool": {"filter": [
+                {"term": {"status": "open"}},
+                {"bool": {
+                    "should": [
+                        {"range": {"priority": {"gt": 3}}},
+                        {"range": {"due.days": {"lte": 1.5}}},
+                    ],
+                    "minimum_should_match": 1,
+                }},
+                {"bool": {
+                    "filter": [{"exists": {"field": "active"}}],
+                    "must_not": [{"term": {"active": false}}],
+                }},
+            ]}})
+        );
+    }
+
+    #[test]
+    fn field_to_field() {
+        let query = Parser::from_str("gt(spent,budget)").parse_query().unwrap();
+        assert_eq!(
+            query.to_es().unwrap_err().to_string(),
+            "gt(spent,budget) can't be translated to Elasticsearch"
+        );
+    }
+
+    #[test]
+    fn membership() {
+        let query = Parser::from_str(
+            "and(in(status,(\"new\",\"open\")),out(age,(1,2)),not(contains(tags,\"a*\")),contains(scores,3))",
+        )
+        .parse_query()
+        .unwrap();
+        assert_eq!(
+            query.to_es().unwrap(),
+            json!({"bool": {"filter": [
+                {"terms": {"status": ["new", "open"]}},
+                {"bool": {
+                    "filter": [{"exists": {"field": "age"}}],
+                    "must_not": [{"terms": {"age": [1, 2]}}],
+                }},
+                {"bool": {"must_not": [{"wildcard": {"tags": {"value": "*a\\**"}}}]}},
+                {"term": {"scores": 3}},
+            ]}})
+        );
+    }
+
+    #[test]
+    fn near() {
+        let query = Parser::new_from_string("near(store.location,-33.87,151.21,500)".to_owned())
+            .parse_query()
+            .unwrap();
+        assert_eq!(
+            query.to_es().unwrap(),
+            json!({"geo_distance": {
+                "distance": "500m",
+                "store.location": {"lat": -33.87, "lon": 151.21},
+            }})
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        let to_es = |input: &str| {
+            Parser::new_from_string(input.to_owned())
+                .parse_query()
+                .unwrap()
+                .to_es()
+                .unwrap()
+        };
+        assert_eq!(
+            to_es("and(ge(price,10),eq(kind,\"a\"),le(price,20),ge(price,12))"),
+            json!({"bool": {"filter": [
+                {"range": {"price": {"gte": 12, "lte": 20}}},
+                {"term": {"kind": "a"}},
+            ]}})
+        );
+        // 整数の条件を残すために gt が2つある
+        assert_eq!(
+            to_es("and(gt(x,1),gt(x,1.5),lt(x,3.5))"),
+            json!({"bool": {"filter": [
+                {"range": {"x": {"gt": 1, "lt": 3.5}}},
+                {"range": {"x": {"gt": 1.5}}},
+            ]}})
+        );
+        assert_eq!(
+            to_es("or(and(gt(x,10),lt(x,5)),eq(y,1))"),
+            json!({"bool": {
+                "should": [{"match_none": {}}, {"term": {"y": 1}}],
+                "minimum_should_match": 1,
+            }})
+        );
+    }
+}
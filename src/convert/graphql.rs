@@ -0,0 +1,524 @@
+use crate::ast::{Infix, Path, Query, Segment, Value};
+use crate::convert::{json_value, ConvertError};
+use serde_json::json;
+use std::collections::HashMap;
+
+const TARGET: &str = "a GraphQL where input";
+
+/// The shape of a GraphQL `where` argument.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhereDialect {
+    /// `{_and: [{age: {_gt: 30}}, {name: {_eq: "x"}}]}`
+    Hasura,
+    /// `{AND: [{age: {gt: 30}}, {name: {equals: "x"}}]}`
+    Prisma,
+}
+
+/// What a Prisma field holds, to choose the filter `contains()` becomes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldKind {
+    /// `{name: {contains: "x"}}`
+    String,
+    /// A scalar list: `{tags: {has: "x"}}`
+    List,
+}
+
+impl WhereDialect {
+    fn and(&self) -> &'static str {
+        match self {
+            WhereDialect::Hasura => "_and",
+            WhereDialect::Prisma => "AND",
+        }
+    }
+
+    fn or(&self) -> &'static str {
+        match self {
+            WhereDialect::Hasura => "_or",
+            WhereDialect::Prisma => "OR",
+        }
+    }
+
+    fn not(&self) -> &'static str {
+        match self {
+            WhereDialect::Hasura => "_not",
+            WhereDialect::Prisma => "NOT",
+        }
+    }
+
+    fn in_list(&self, out: bool) -> &'static str {
+        match (self, out) {
+            (WhereDialect::Hasura, false) => "_in",
+            (WhereDialect::Hasura, true) => "_nin",
+            (WhereDialect::Prisma, false) => "in",
+            (WhereDialect::Prisma, true) => "notIn",
+        }
+    }
+
+    fn operator(&self, infix: &Infix) -> &'static str {
+        match (self, infix) {
+            (WhereDialect::Hasura, Infix::Eq) => "_eq",
+            (WhereDialect::Hasura, Infix::NotEq) => "_neq",
+            (WhereDialect::Hasura, Infix::Lt) => "_lt",
+            (WhereDialect::Hasura, Infix::Le) => "_lte",
+            (WhereDialect::Hasura, Infix::Gt) => "_gt",
+            (WhereDialect::Hasura, Infix::Ge) => "_gte",
+            (WhereDialect::Hasura, Infix::StartsWith | Infix::EndsWith) => "_like",
+            (WhereDialect::Hasura, Infix::IEq | Infix::IContains | Infix::IStartsWith) => "_ilike",
+            (WhereDialect::Prisma, Infix::Eq) => "equals",
+            (WhereDialect::Prisma, Infix::NotEq) => "not",
+            (WhereDialect::Prisma, Infix::Lt) => "lt",
+            (WhereDialect::Prisma, Infix::Le) => "lte",
+            (WhereDialect::Prisma, Infix::Gt) => "gt",
+            (WhereDialect::Prisma, Infix::Ge) => "gte",
+            (WhereDialect::Prisma, Infix::StartsWith | Infix::IStartsWith) => "startsWith",
+            (WhereDialect::Prisma, Infix::EndsWith) => "endsWith",
+            (WhereDialect::Prisma, Infix::IEq) => "equals",
+            (WhereDialect::Prisma, Infix::IContains) => "contains",
+        }
+    }
+}
+
+impl Query {
+    /// Builds a GraphQL `where` input object in the style of Hasura or Prisma.
+    ///
+    /// Dotted paths become nested objects: `eq(author.name,"x")` is
+    /// `{author: {name: {_eq: "x"}}}`. Paths with indexes or wildcards can't be
+    /// translated. `startswith()`/`endswith()` are `_like` patterns in Hasura.
+    /// `exists()`/`missing()` compare with null. `in()`/`out()` are
+    /// `_in`/`_nin` (`in`/`notIn`) and `not()` is `_not` (`NOT`).
+    /// `contains()` with a string is a substring match, `_like "%x%"` or
+    /// `contains`; with another value it's a Prisma list `has`.
+    ///
+    /// ```
+    /// use rql_parser::convert::graphql::WhereDialect;
+    /// use rql_parser::parser::Parser;
+    /// use serde_json::json;
+    ///
+    /// let query = Parser::new_from_string("and(gt(age,30),eq(name,\"x\"))".to_owned())
+    ///     .parse_query()
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     query.to_graphql_where(WhereDialect::Hasura).unwrap(),
+    ///     json!({"_and": [{"age": {"_gt": 30}}, {"name": {"_eq": "x"}}]})
+    /// );
+    /// ```
+    pub fn to_graphql_where(
+        &self,
+        dialect: WhereDialect,
+    ) -> Result<serde_json::Value, ConvertError> {
+        self.where_input(dialect, false, &HashMap::new())
+    }
+
+    /// Builds a Prisma `where` input, treating dotted paths as relations:
+    /// `eq(author.profile.country,"JP")` is
+    /// `{author: {is: {profile: {is: {country: {equals: "JP"}}}}}}`, and a
+    /// wildcard over a list relation, `eq(posts.*.title,"x")`, is
+    /// `{posts: {some: {title: {equals: "x"}}}}`. Otherwise the same as
+    /// [`Query::to_graphql_where`] with [`WhereDialect::Prisma`].
+    ///
+    /// `fields` tells string fields from scalar lists, keyed by path:
+    /// `contains(tags,"x")` is `{tags: {has: "x"}}` for a [`FieldKind::List`]
+    /// and `{tags: {contains: "x"}}` for a [`FieldKind::String`]. Fields not
+    /// in it are strings when compared with a string and lists otherwise.
+    ///
+    /// ```
+    /// use rql_parser::convert::graphql::FieldKind;
+    /// use rql_parser::parser::Parser;
+    /// use serde_json::json;
+    /// use std::collections::HashMap;
+    ///
+    /// let query = Parser::new_from_string("and(eq(author.name,\"x\"),contains(tags,\"rust\"))".to_owned())
+    ///     .parse_query()
+    ///     .unwrap();
+    /// let mut fields = HashMap::new();
+    /// fields.insert("tags".to_owned(), FieldKind::List);
+    /// assert_eq!(
+    ///     query.to_prisma(&fields).unwrap(),
+    ///     json!({"AND": [
+    ///         {"author": {"is": {"name": {"equals": "x"}}}},
+    ///         {"tags": {"has": "rust"}},
+    ///     ]})
+    /// );
+    /// ```
+    pub fn to_prisma(
+        &self,
+        fields: &HashMap<String, FieldKind>,
+    ) -> Result<serde_json::Value, ConvertError> {
+        self.where_input(WhereDialect::Prisma, true, fields)
+    }
+
+    fn where_input(
+        &self,
+        dialect: WhereDialect,
+        relations: bool,
+        fields: &HashMap<String, FieldKind>,
+    ) -> Result<serde_json::Value, ConvertError> {
+        match self {
+            Query::And(queries) if queries.is_empty() => Ok(json!({})),
+            // Hasura では空の _or は true になるので、{} の否定にする
+            Query::Or(queries) if queries.is_empty() && dialect == WhereDialect::Hasura => {
+                Ok(json!({"_not": {}}))
+            }
+            Query::And(queries) => {
+                Ok(json!({ dialect.and(): to_graphql_all(queries, dialect, relations, fields)? }))
+            }
+            Query::Or(queries) => {
+                Ok(json!({ dialect.or(): to_graphql_all(queries, dialect, relations, fields)? }))
+            }
+            Query::Not(query) => {
+                Ok(json!({ dialect.not(): query.where_input(dialect, relations, fields)? }))
+            }
+            Query::Filter(_, _, value @ (Value::Call(_) | Value::Variable(_))) => {
+                Err(ConvertError::Unsupported(value.to_string(), TARGET))
+            }
+            // where の入力はフィールド同士を比べられない
+            Query::Filter(_, _, Value::Identifier(_)) => {
+                Err(ConvertError::Unsupported(self.to_rql(), TARGET))
+            }
+            Query::Filter(infix, Value::Identifier(path), value) => {
+                let like = matches!(infix, Infix::StartsWith | Infix::EndsWith);
+                let value = match dialect {
+                    WhereDialect::Hasura if like || infix.ignores_case() => {
+                        let s = match value {
+                            Value::StringLiteral(s) => escape_like(s),
+                            _ => return Err(ConvertError::Unsupported(self.to_rql(), TARGET)),
+                        };
+                        match infix {
+                            Infix::StartsWith | Infix::IStartsWith => json!(format!("{}%", s)),
+                            Infix::EndsWith => json!(format!("%{}", s)),
+                            Infix::IContains => json!(format!("%{}%", s)),
+                            _ => json!(s),
+                        }
+                    }
+                    _ => json_value(value),
+                };
+                let mut condition = json!({ dialect.operator(infix): value });
+                // Prisma では mode で大文字小文字を区別しなくする
+                if dialect == WhereDialect::Prisma && infix.ignores_case() {
+                    condition["mode"] = json!("insensitive");
+                }
+                nest(path, condition, relations)
+            }
+            Query::Filter(..) => Err(ConvertError::Unsupported(self.to_rql(), TARGET)),
+            Query::In(path, values) | Query::Out(path, values) => {
+                let mut list = Vec::with_capacity(values.len());
+                for value in values {
+                    match value {
+                        Value::Identifier(_) | Value::Call(_) | Value::Variable(_) => {
+                            return Err(ConvertError::Unsupported(self.to_rql(), TARGET))
+                        }
+                        _ => list.push(json_value(value)),
+                    }
+                }
+                let operator = dialect.in_list(matches!(self, Query::Out(..)));
+                nest(path, json!({ operator: list }), relations)
+            }
+            Query::Contains(path, value) => {
+                let kind = match (fields.get(&path.to_string()), value) {
+                    (Some(kind), _) => *kind,
+                    (None, Value::StringLiteral(_)) => FieldKind::String,
+                    (None, _) => FieldKind::List,
+                };
+                let condition = match (dialect, kind, value) {
+                    (_, _, Value::Identifier(_) | Value::Call(_) | Value::Variable(_)) => {
+                        return Err(ConvertError::Unsupported(self.to_rql(), TARGET))
+                    }
+                    (WhereDialect::Hasura, FieldKind::String, Value::StringLiteral(s)) => {
+                        json!({"_like": format!("%{}%", escape_like(s))})
+                    }
+                    (WhereDialect::Prisma, FieldKind::String, Value::StringLiteral(s)) => {
+                        json!({"contains": s})
+                    }
+                    (WhereDialect::Prisma, FieldKind::List, _) => json!({"has": json_value(value)}),
+                    _ => return Err(ConvertError::Unsupported(self.to_rql(), TARGET)),
+                };
+                nest(path, condition, relations)
+            }
+            Query::Exists(path) | Query::Missing(path) => {
+                let missing = matches!(self, Query::Missing(_));
+                let condition = match dialect {
+                    WhereDialect::Hasura => json!({"_is_null": missing}),
+                    WhereDialect::Prisma if missing => json!({"equals": null}),
+                    WhereDialect::Prisma => json!({"not": null}),
+                };
+                nest(path, condition, relations)
+            }
+            Query::Sort(..) => Err(ConvertError::Unsupported("sort()".to_owned(), TARGET)),
+            Query::Limit(..) => Err(ConvertError::Unsupported("limit()".to_owned(), TARGET)),
+            Query::Select(..) => Err(ConvertError::Unsupported("select()".to_owned(), TARGET)),
+            Query::Distinct(..) => Err(ConvertError::Unsupported("distinct()".to_owned(), TARGET)),
+            Query::Aggregate(..) => {
+                Err(ConvertError::Unsupported("aggregate()".to_owned(), TARGET))
+            }
+            Query::First => Err(ConvertError::Unsupported("first()".to_owned(), TARGET)),
+            Query::One => Err(ConvertError::Unsupported("one()".to_owned(), TARGET)),
+            Query::Count => Err(ConvertError::Unsupported("count()".to_owned(), TARGET)),
+            Query::Reduce(..) | Query::Search(..) | Query::Near(..) => {
+                Err(ConvertError::Unsupported(self.to_rql(), TARGET))
+            }
+            Query::None => Ok(json!({})),
+        }
+    }
+}
+
+fn to_graphql_all(
+    queries: &[Query],
+    dialect: WhereDialect,
+    relations: bool,
+    fields: &HashMap<String, FieldKind>,
+) -> Result<Vec<serde_json::Value>, ConvertError> {
+    queries
+        .iter()
+        .map(|q| q.where_input(dialect, relations, fields))
+        .collect()
+}
+
+// author.name は {author: {name: 条件}} にする。relations なら
+// {author: {is: {name: 条件}}}、posts.*.title は {posts: {some: {title: 条件}}}
+fn nest(
+    path: &Path,
+    condition: serde_json::Value,
+    relations: bool,
+) -> Result<serde_json::Value, ConvertError> {
+    let unsupported = || ConvertError::Unsupported(path.to_string(), TARGET);
+    let segments = path.segments();
+    let mut out = condition;
+    let mut i = segments.len();
+    while i > 0 {
+        i -= 1;
+        let key = match &segments[i] {
+            Segment::Key(key) => key,
+            Segment::Wildcard if relations && i > 0 && i + 1 < segments.len() => {
+                i -= 1;
+                match &segments[i] {
+                    Segment::Key(key) => {
+                        out = json!({ key: {"some": out} });
+                        continue;
+                    }
+                    _ => return Err(unsupported()),
+                }
+            }
+            _ => return Err(unsupported()),
+        };
+        out = match relations && i + 1 < segments.len() {
+            true => json!({ key: {"is": out} }),
+            false => json!({ key: out }),
+        };
+    }
+    Ok(out)
+}
+
+// _like のパターンで % と _ をエスケープする
+fn escape_like(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::graphql::{FieldKind, WhereDialect};
+    use crate::parser::Parser;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    const QUERY: &str = "and(gt(age,30),or(eq(name,\"x\"),ne(author.profile.country,\"JP\")),\
+                         le(score,2.5),exists(email))";
+
+    fn to_graphql_where(input: &str, dialect: WhereDialect) -> serde_json::Value {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .to_graphql_where(dialect)
+            .unwrap()
+    }
+
+    #[test]
+    fn hasura() {
+        assert_eq!(
+            to_graphql_where(QUERY, WhereDialect::Hasura),
+            json!({"_and": [
+                {"age": {"_gt": 30}},
+                {"_or": [
+                    {"name": {"_eq": "x"}},
+                    {"author": {"profile": {"country": {"_neq": "JP"}}}},
+                ]},
+                {"score": {"_lte": 2.5}},
+                {"email": {"_is_null": false}},
+            ]})
+        );
+        assert_eq!(
+            to_graphql_where(
+                "and(startswith(code,\"10%_\"),endswith(name,\"son\"),missing(deleted_at))",
+                WhereDialect::Hasura
+            ),
+            json!({"_and": [
+                {"code": {"_like": "10\\%\\_%"}},
+                {"name": {"_like": "%son"}},
+                {"deleted_at": {"_is_null": true}},
+            ]})
+        );
+        assert_eq!(
+            to_graphql_where("or()", WhereDialect::Hasura),
+            json!({"_not": {}})
+        );
+    }
+
+    #[test]
+    fn prisma() {
+        assert_eq!(
+            to_graphql_where(QUERY, WhereDialect::Prisma),
+            json!({"AND": [
+                {"age": {"gt": 30}},
+                {"OR": [
+                    {"name": {"equals": "x"}},
+                    {"author": {"profile": {"country": {"not": "JP"}}}},
+                ]},
+                {"score": {"lte": 2.5}},
+                {"email": {"not": null}},
+            ]})
+        );
+        assert_eq!(
+            to_graphql_where(
+                "and(startswith(code,\"10%\"),missing(deleted_at))",
+                WhereDialect::Prisma
+            ),
+            json!({"AND": [
+                {"code": {"startsWith": "10%"}},
+                {"deleted_at": {"equals": null}},
+            ]})
+        );
+        assert_eq!(
+            to_graphql_where("or()", WhereDialect::Prisma),
+            json!({"OR": []})
+        );
+        assert_eq!(to_graphql_where("and()", WhereDialect::Prisma), json!({}));
+    }
+
+    #[test]
+    fn membership_and_negation() {
+        let input = "and(in(status,(\"new\",\"open\")),out(age,(1,2)),not(eq(vip,true)))";
+        assert_eq!(
+            to_graphql_where(input, WhereDialect::Hasura),
+            json!({"_and": [
+                {"status": {"_in": ["new", "open"]}},
+                {"age": {"_nin": [1, 2]}},
+                {"_not": {"vip": {"_eq": true}}},
+            ]})
+        );
+        assert_eq!(
+            to_graphql_where(input, WhereDialect::Prisma),
+            json!({"AND": [
+                {"status": {"in": ["new", "open"]}},
+                {"age": {"notIn": [1, 2]}},
+                {"NOT": {"vip": {"equals": true}}},
+            ]})
+        );
+        assert_eq!(
+            to_graphql_where("contains(name,\"50%\")", WhereDialect::Hasura),
+            json!({"name": {"_like": "%50\\%%"}})
+        );
+    }
+
+    #[test]
+    fn prisma_contains() {
+        let query = Parser::from_str("and(contains(tags,\"rust\"),contains(name,\"an\"),contains(scores,3))")
+            .parse_query()
+            .unwrap();
+        let mut fields = HashMap::new();
+        fields.insert("tags".to_owned(), FieldKind::List);
+        assert_eq!(
+            query.to_prisma(&fields).unwrap(),
+            json!({"AND": [
+                {"tags": {"has": "rust"}},
+                {"name": {"contains": "an"}},
+                {"scores": {"has": 3}},
+            ]})
+        );
+        fields.insert("tags".to_owned(), FieldKind::String);
+        fields.insert("author.aliases".to_owned(), FieldKind::List);
+        let query = Parser::from_str("or(contains(tags,\"rust\"),not(contains(author.aliases,\"x\")))")
+            .parse_query()
+            .unwrap();
+        assert_eq!(
+            query.to_prisma(&fields).unwrap(),
+            json!({"OR": [
+                {"tags": {"contains": "rust"}},
+                {"NOT": {"author": {"is": {"aliases": {"has": "x"}}}}},
+            ]})
+        );
+        // 文字列のフィールドに数値は含まれない
+        fields.insert("name".to_owned(), FieldKind::String);
+        assert!(Parser::from_str("contains(name,3)")
+            .parse_query()
+            .unwrap()
+            .to_prisma(&fields)
+            .is_err());
+    }
+
+    #[test]
+    fn prisma_relations() {
+        let to_prisma = |input: &str| {
+            Parser::new_from_string(input.to_owned())
+                .parse_query()
+                .unwrap()
+                .to_prisma(&HashMap::new())
+        };
+        assert_eq!(
+            to_prisma("and(eq(author.profile.country,\"JP\"),gt(views,10))").unwrap(),
+            json!({"AND": [
+                {"author": {"is": {"profile": {"is": {"country": {"equals": "JP"}}}}}},
+                {"views": {"gt": 10}},
+            ]})
+        );
+        assert_eq!(
+            to_prisma("or(startswith(posts.*.title,\"Re\"),missing(author.bio))").unwrap(),
+            json!({"OR": [
+                {"posts": {"some": {"title": {"startsWith": "Re"}}}},
+                {"author": {"is": {"bio": {"equals": null}}}},
+            ]})
+        );
+        assert_eq!(
+            to_prisma("ne(author.posts.*.tags.*.name,\"x\")").unwrap(),
+            json!({"author": {"is": {"posts": {"some": {"tags": {"some": {
+                "name": {"not": "x"}
+            }}}}}}})
+        );
+        // 末尾のワイルドカードや位置はリレーションとして表せない
+        assert!(to_prisma("eq(tags.*,\"x\")").is_err());
+        assert!(to_prisma("eq(posts.0.title,\"x\")").is_err());
+        assert_eq!(
+            to_prisma("out(author.country,(\"JP\",\"KR\"))").unwrap(),
+            json!({"author": {"is": {"country": {"notIn": ["JP", "KR"]}}}})
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let err = |input: &str| {
+            Parser::new_from_string(input.to_owned())
+                .parse_query()
+                .unwrap()
+                .to_graphql_where(WhereDialect::Hasura)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            err("and(eq(a,1),sort(+a))"),
+            "sort() can't be translated to a GraphQL where input"
+        );
+        assert_eq!(
+            err("eq(items.0.id,1)"),
+            "items.0.id can't be translated to a GraphQL where input"
+        );
+        assert_eq!(
+            err("gt(created,now())"),
+            "now() can't be translated to a GraphQL where input"
+        );
+    }
+}
@@ -0,0 +1,644 @@
+use crate::ast::{Infix, Path, Query, Segment, Value};
+use crate::convert::{dotted, json_value, Backend, ConvertError};
+use serde_json::json;
+
+impl Query {
+    /// Builds a MongoDB filter document.
+    ///
+    /// `in()` and `out()` are `$in` and `$nin`, and `not()` is a `$nor`.
+    /// `contains()` with a string is a `$regex` for the value, which on an
+    /// array of strings also matches elements that merely contain it; with
+    /// other values it's an `$elemMatch` on the array.
+    pub fn to_mongo(&self) -> Result<serde_json::Value, ConvertError> {
+        self.lower(&mut MongoBackend)
+    }
+
+    /// Same as [`Query::to_mongo`], building a `bson::Document` for the
+    /// mongodb driver directly. Dates become BSON datetimes and integers
+    /// beyond `i64` Decimal128.
+    #[cfg(feature = "bson")]
+    pub fn to_bson(&self) -> Result<bson::Document, ConvertError> {
+        self.lower(&mut BsonBackend)
+    }
+}
+
+#[cfg(feature = "mongodb")]
+impl crate::request::Request {
+    /// The filter document and `FindOptions` for `collection.find()`: the
+    /// filter from [`Query::to_bson`] (`{}` without one), the sort document
+    /// from the `+`/`-` keys, `limit` and `skip` from `limit()`, and a
+    /// projection of the `select()`ed paths. Options without a counterpart
+    /// in the request are left unset. MongoDB reads a limit of 0 as no
+    /// limit, so `limit(0)` gives a filter that matches nothing instead.
+    pub fn to_mongo_find(
+        &self,
+    ) -> Result<(bson::Document, mongodb::options::FindOptions), ConvertError> {
+        use crate::ast::Prefix;
+        use std::convert::TryFrom;
+
+        let mut filter = match &self.filter {
+            Some(filter) => filter.to_bson()?,
+            None => bson::Document::new(),
+        };
+        let mut options = mongodb::options::FindOptions::default();
+        if !self.sort.is_empty() {
+            let mut sort = bson::Document::new();
+            for (prefix, key) in &self.sort {
+                let direction = match prefix {
+                    Prefix::Plus => 1,
+                    Prefix::Minus => -1,
+                };
+                sort.insert(dotted(key, "MongoDB")?, direction);
+            }
+            options.sort = Some(sort);
+        }
+        if let Some((0, _)) = self.limit {
+            filter = bson::doc! {"$expr": false};
+        } else if let Some((count, offset)) = self.limit {
+            options.limit = Some(i64::try_from(count).unwrap_or(i64::MAX));
+            if offset > 0 {
+                options.skip = Some(offset);
+            }
+        }
+        if !self.select.is_empty() {
+            let mut projection = bson::Document::new();
+            for path in &self.select {
+                projection.insert(dotted(path, "MongoDB")?, 1);
+            }
+            options.projection = Some(projection);
+        }
+        Ok((filter, options))
+    }
+}
+
+// 1つのフィールドへの条件。JSON と BSON のどちらもここから組み立てる
+enum Condition<'a> {
+    // {field: {op: value}}
+    Compare(&'static str, &'a Value),
+    // 別のフィールドとの比較。{$expr: {op: ["$field", "$other"]}} の "$other"
+    Field(&'static str, String),
+    // {field: {$in: [値...]}} と $nin
+    In(&'static str, &'a [Value]),
+    // 配列のどれかの要素が等しい
+    ElemMatch(&'a Value),
+    // 正規表現と、大文字小文字を区別しないか
+    Regex(String, bool),
+    Exists,
+    Missing,
+    // (緯度, 経度, 半径)
+    Near(f64, f64, f64),
+}
+
+fn condition(query: &Query) -> Result<(String, Condition<'_>), ConvertError> {
+    match query {
+        Query::Filter(_, _, value @ (Value::Call(_) | Value::Variable(_))) => {
+            Err(ConvertError::Unsupported(value.to_string(), "MongoDB"))
+        }
+        Query::Filter(Infix::StartsWith, Value::Identifier(ident), Value::StringLiteral(s)) => {
+            let ident = dotted(ident, "MongoDB")?;
+            Ok((ident, Condition::Regex(format!("^{}", escape_regex(s)), false)))
+        }
+        Query::Filter(Infix::EndsWith, Value::Identifier(ident), Value::StringLiteral(s)) => {
+            let ident = dotted(ident, "MongoDB")?;
+            Ok((ident, Condition::Regex(format!("{}$", escape_regex(s)), false)))
+        }
+        Query::Filter(infix, Value::Identifier(ident), Value::StringLiteral(s))
+            if infix.ignores_case() =>
+        {
+            let ident = dotted(ident, "MongoDB")?;
+            let pattern = match infix {
+                Infix::IEq => format!("^{}$", escape_regex(s)),
+                Infix::IStartsWith => format!("^{}", escape_regex(s)),
+                _ => escape_regex(s),
+            };
+            Ok((ident, Condition::Regex(pattern, true)))
+        }
+        // $expr の中では配列の添字や展開は書けない
+        Query::Filter(infix, Value::Identifier(ident), Value::Identifier(other)) => {
+            match (operator(infix), field_path(ident), field_path(other)) {
+                (Some(op), Some(ident), Some(other)) => Ok((ident, Condition::Field(op, other))),
+                _ => Err(ConvertError::Unsupported(query.to_rql(), "MongoDB")),
+            }
+        }
+        Query::Filter(infix, Value::Identifier(ident), value) => {
+            let ident = dotted(ident, "MongoDB")?;
+            match operator(infix) {
+                Some(op) => Ok((ident, Condition::Compare(op, value))),
+                None => Err(ConvertError::Unsupported(query.to_rql(), "MongoDB")),
+            }
+        }
+        Query::Filter(..) => Err(ConvertError::Unsupported(query.to_rql(), "MongoDB")),
+        Query::In(ident, values) | Query::Out(ident, values) => {
+            if values
+                .iter()
+                .any(|v| matches!(v, Value::Identifier(_) | Value::Call(_) | Value::Variable(_)))
+            {
+                return Err(ConvertError::Unsupported(query.to_rql(), "MongoDB"));
+            }
+            let op = if matches!(query, Query::In(..)) { "$in" } else { "$nin" };
+            Ok((dotted(ident, "MongoDB")?, Condition::In(op, values)))
+        }
+        Query::Contains(ident, Value::StringLiteral(s)) => {
+            Ok((dotted(ident, "MongoDB")?, Condition::Regex(escape_regex(s), false)))
+        }
+        Query::Contains(_, Value::Identifier(_) | Value::Call(_) | Value::Variable(_)) => {
+            Err(ConvertError::Unsupported(query.to_rql(), "MongoDB"))
+        }
+        Query::Contains(ident, value) => {
+            Ok((dotted(ident, "MongoDB")?, Condition::ElemMatch(value)))
+        }
+        Query::Exists(ident) => Ok((dotted(ident, "MongoDB")?, Condition::Exists)),
+        Query::Missing(ident) => Ok((dotted(ident, "MongoDB")?, Condition::Missing)),
+        Query::Near(ident, lat, lon, radius) => {
+            Ok((dotted(ident, "MongoDB")?, Condition::Near(*lat, *lon, *radius)))
+        }
+        Query::Sort(..) => Err(ConvertError::Unsupported("sort()".to_owned(), "a MongoDB filter")),
+        Query::Limit(..) => Err(ConvertError::Unsupported("limit()".to_owned(), "a MongoDB filter")),
+        Query::Select(..) => Err(ConvertError::Unsupported("select()".to_owned(), "a MongoDB filter")),
+        Query::Distinct(..) => Err(ConvertError::Unsupported("distinct()".to_owned(), "a MongoDB filter")),
+        Query::Aggregate(..) => Err(ConvertError::Unsupported("aggregate()".to_owned(), "a MongoDB filter")),
+        Query::First => Err(ConvertError::Unsupported("first()".to_owned(), "a MongoDB filter")),
+        Query::One => Err(ConvertError::Unsupported("one()".to_owned(), "a MongoDB filter")),
+        Query::Count => Err(ConvertError::Unsupported("count()".to_owned(), "a MongoDB filter")),
+        Query::Reduce(..) | Query::Search(..) => {
+            Err(ConvertError::Unsupported(query.to_rql(), "a MongoDB filter"))
+        }
+        Query::And(..) | Query::Or(..) | Query::Not(..) | Query::None => {
+            unreachable!("lowered by Query::lower")
+        }
+    }
+}
+
+fn operator(infix: &Infix) -> Option<&'static str> {
+    match infix {
+        Infix::Eq => Some("$eq"),
+        Infix::NotEq => Some("$ne"),
+        Infix::Lt => Some("$lt"),
+        Infix::Le => Some("$lte"),
+        Infix::Gt => Some("$gt"),
+        Infix::Ge => Some("$gte"),
+        Infix::StartsWith | Infix::EndsWith | Infix::IEq | Infix::IContains | Infix::IStartsWith => {
+            None
+        }
+    }
+}
+
+// 集約式で参照する "$a.b"。キーだけのパスに限る
+fn field_path(path: &Path) -> Option<String> {
+    let mut keys = vec![];
+    for segment in path.segments() {
+        match segment {
+            Segment::Key(key) if !key.contains('.') && !key.starts_with('$') => keys.push(key.as_str()),
+            _ => return None,
+        }
+    }
+    Some(format!("${}", keys.join(".")))
+}
+
+struct MongoBackend;
+
+impl Backend for MongoBackend {
+    type Fragment = serde_json::Value;
+    type Output = serde_json::Value;
+    type Error = ConvertError;
+
+    fn visit_filter(&mut self, query: &Query) -> Result<serde_json::Value, ConvertError> {
+        let (ident, condition) = condition(query)?;
+        Ok(match condition {
+            // $ne は null と存在しないフィールドにも一致するので、評価時と同じく除く
+            Condition::Compare("$ne", value) => json!({ ident: {"$nin": [mongo_value(value), null]} }),
+            Condition::Compare(op, value) => json!({ ident: { op: mongo_value(value) } }),
+            Condition::In(op, values) => {
+                let mut values: Vec<serde_json::Value> = values.iter().map(mongo_value).collect();
+                // $ne と同じく、$nin からも null と存在しないフィールドを除く
+                if op == "$nin" {
+                    values.push(serde_json::Value::Null);
+                }
+                json!({ ident: { op: values } })
+            }
+            Condition::ElemMatch(value) => {
+                json!({ ident: {"$elemMatch": {"$eq": mongo_value(value)}} })
+            }
+            // 集約の比較なので、存在しないフィールドは null より小さい値として比べられる
+            Condition::Field(op, other) => json!({"$expr": { op: [ident, other] }}),
+            Condition::Regex(pattern, false) => json!({ ident: {"$regex": pattern} }),
+            Condition::Regex(pattern, true) => {
+                json!({ ident: {"$regex": pattern, "$options": "i"} })
+            }
+            // {field: null} は null と存在しないフィールドの両方に一致する
+            Condition::Exists => json!({ ident: {"$ne": null} }),
+            Condition::Missing => json!({ ident: null }),
+            // $nearSphere には 2dsphere インデックスが要る。結果は近い順になる
+            Condition::Near(lat, lon, radius) => json!({ ident: {"$nearSphere": {
+                "$geometry": {"type": "Point", "coordinates": [lon, lat]},
+                "$maxDistance": radius,
+            }} }),
+        })
+    }
+
+    fn combine_and(&mut self, parts: Vec<serde_json::Value>) -> Result<serde_json::Value, ConvertError> {
+        match parts.is_empty() {
+            true => Ok(json!({})),
+            false => Ok(json!({ "$and": parts })),
+        }
+    }
+
+    // $or に空配列は渡せないので、何にも一致しない条件にする
+    fn combine_or(&mut self, parts: Vec<serde_json::Value>) -> Result<serde_json::Value, ConvertError> {
+        match parts.is_empty() {
+            true => Ok(json!({"$expr": false})),
+            false => Ok(json!({ "$or": parts })),
+        }
+    }
+
+    fn negate(&mut self, part: serde_json::Value) -> Result<serde_json::Value, ConvertError> {
+        Ok(json!({ "$nor": [part] }))
+    }
+
+    fn finish(&mut self, part: serde_json::Value) -> Result<serde_json::Value, ConvertError> {
+        Ok(part)
+    }
+
+    fn visit_missing(&mut self, field: &Path) -> Result<serde_json::Value, ConvertError> {
+        self.visit_filter(&Query::Missing(field.clone()))
+    }
+}
+
+// MongoBackend と同じ条件を bson::Document で組み立てる
+#[cfg(feature = "bson")]
+struct BsonBackend;
+
+#[cfg(feature = "bson")]
+impl Backend for BsonBackend {
+    type Fragment = bson::Document;
+    type Output = bson::Document;
+    type Error = ConvertError;
+
+    fn visit_filter(&mut self, query: &Query) -> Result<bson::Document, ConvertError> {
+        use bson::{doc, Bson};
+        let (ident, condition) = condition(query)?;
+        let condition = match condition {
+            Condition::Compare("$ne", value) => {
+                Bson::Document(doc! {"$nin": [bson_value(value), Bson::Null]})
+            }
+            Condition::Compare(op, value) => Bson::Document(doc! { op: bson_value(value) }),
+            Condition::In(op, values) => {
+                let mut values: Vec<Bson> = values.iter().map(bson_value).collect();
+                if op == "$nin" {
+                    values.push(Bson::Null);
+                }
+                Bson::Document(doc! { op: values })
+            }
+            Condition::ElemMatch(value) => {
+                Bson::Document(doc! {"$elemMatch": {"$eq": bson_value(value)}})
+            }
+            Condition::Field(op, other) => return Ok(doc! {"$expr": { op: [ident, other] }}),
+            Condition::Regex(pattern, false) => Bson::Document(doc! {"$regex": pattern}),
+            Condition::Regex(pattern, true) => {
+                Bson::Document(doc! {"$regex": pattern, "$options": "i"})
+            }
+            Condition::Exists => Bson::Document(doc! {"$ne": Bson::Null}),
+            Condition::Missing => Bson::Null,
+            Condition::Near(lat, lon, radius) => Bson::Document(doc! {"$nearSphere": {
+                "$geometry": {"type": "Point", "coordinates": [lon, lat]},
+                "$maxDistance": radius,
+            }}),
+        };
+        Ok(doc! { ident: condition })
+    }
+
+    fn combine_and(&mut self, parts: Vec<bson::Document>) -> Result<bson::Document, ConvertError> {
+        match parts.is_empty() {
+            true => Ok(bson::Document::new()),
+            false => Ok(bson::doc! {"$and": parts}),
+        }
+    }
+
+    fn combine_or(&mut self, parts: Vec<bson::Document>) -> Result<bson::Document, ConvertError> {
+        match parts.is_empty() {
+            true => Ok(bson::doc! {"$expr": false}),
+            false => Ok(bson::doc! {"$or": parts}),
+        }
+    }
+
+    fn negate(&mut self, part: bson::Document) -> Result<bson::Document, ConvertError> {
+        Ok(bson::doc! {"$nor": [part]})
+    }
+
+    fn finish(&mut self, part: bson::Document) -> Result<bson::Document, ConvertError> {
+        Ok(part)
+    }
+
+    fn visit_missing(&mut self, field: &Path) -> Result<bson::Document, ConvertError> {
+        self.visit_filter(&Query::Missing(field.clone()))
+    }
+}
+
+// 日時は Extended JSON の $date にする
+fn mongo_value(value: &Value) -> serde_json::Value {
+    match value {
+        #[cfg(feature = "chrono")]
+        Value::DateTime(_) | Value::RelativeTime(..) => json!({"$date": json_value(value)}),
+        _ => json_value(value),
+    }
+}
+
+// i64 に収まらない整数は Decimal128 にする。BSON には符号無し整数が無い
+#[cfg(feature = "bson")]
+fn bson_value(value: &Value) -> bson::Bson {
+    use bson::Bson;
+    match value {
+        Value::IntegerLiteral(i) => Bson::Int64(*i),
+        Value::UnsignedLiteral(u) => u
+            .to_string()
+            .parse()
+            .map_or(Bson::Double(*u as f64), Bson::Decimal128),
+        Value::FloatLiteral(f) => Bson::Double(*f),
+        #[cfg(feature = "decimal")]
+        Value::DecimalLiteral(d) => d
+            .to_string()
+            .parse()
+            .map_or(Bson::String(d.to_string()), Bson::Decimal128),
+        Value::Boolean(b) => Bson::Boolean(*b),
+        #[cfg(feature = "chrono")]
+        Value::DateTime(_) | Value::RelativeTime(..) => {
+            match crate::datetime::instant(value, None) {
+                Some(datetime) => {
+                    Bson::DateTime(bson::DateTime::from_millis(datetime.timestamp_millis()))
+                }
+                None => Bson::String(value.to_string()),
+            }
+        }
+        Value::StringLiteral(s) => Bson::String(s.clone()),
+        // 値にならないので condition で弾くか $expr にする
+        Value::Identifier(_) | Value::Call(_) | Value::Variable(_) => {
+            Bson::String(value.to_string())
+        }
+    }
+}
+
+fn escape_regex(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    #[test]
+    fn to_mongo() {
+        let query = Parser::new_from_string(
+            "and(eq(status,\"open\"),or(gt(priority,3),le(due.days,1.5)),ne(active,false))"
+                .to_owned(),
+        )
+        .parse_query()
+        .unwrap();
+        assert_eq!(
+            query.to_mongo().unwrap(),
+            json!({"$and": [
+                {"status": {"$eq": "open"}},
+                {"$or": [{"priority": {"$gt": 3}}, {"due.days": {"$lte": 1.5}}]},
+                {"active": {"$nin": [false, null]}},
+            ]})
+        );
+    }
+
+    #[test]
+    fn ignoring_case() {
+        let query = Parser::new_from_string(
+            "or(ieq(a,\"x.y\"),icontains(b,\"(1)\"),istartswith(c,\"Z\"))".to_owned(),
+        )
+        .parse_query()
+        .unwrap();
+        assert_eq!(
+            query.to_mongo().unwrap(),
+            json!({"$or": [
+                {"a": {"$regex": "^x\\.y$", "$options": "i"}},
+                {"b": {"$regex": "\\(1\\)", "$options": "i"}},
+                {"c": {"$regex": "^Z", "$options": "i"}},
+            ]})
+        );
+    }
+
+    #[test]
+    fn membership() {
+        let query = Parser::from_str(
+            "and(in(status,(\"new\",\"open\")),out(age,(1,2)),contains(name,\"a.b\"),not(contains(scores,3)))",
+        )
+        .parse_query()
+        .unwrap();
+        assert_eq!(
+            query.to_mongo().unwrap(),
+            json!({"$and": [
+                {"status": {"$in": ["new", "open"]}},
+                {"age": {"$nin": [1, 2, null]}},
+                {"name": {"$regex": "a\\.b"}},
+                {"$nor": [{"scores": {"$elemMatch": {"$eq": 3}}}]},
+            ]})
+        );
+    }
+
+    #[test]
+    fn near() {
+        let query = Parser::new_from_string("near(location,35.68,139.76,5000)".to_owned())
+            .parse_query()
+            .unwrap();
+        assert_eq!(
+            query.to_mongo().unwrap(),
+            json!({"location": {"$nearSphere": {
+                "$geometry": {"type": "Point", "coordinates": [139.76, 35.68]},
+                "$maxDistance": 5000.0,
+            }}})
+        );
+    }
+
+    #[test]
+    fn field_to_field() {
+        let to_mongo = |input: &str| Parser::from_str(input).parse_query().unwrap().to_mongo();
+        assert_eq!(
+            to_mongo("and(gt(spent,budget),ne(a,meta.b))").unwrap(),
+            json!({"$and": [
+                {"$expr": {"$gt": ["$spent", "$budget"]}},
+                {"$expr": {"$ne": ["$a", "$meta.b"]}},
+            ]})
+        );
+        assert!(to_mongo("eq(a,items.0)").is_err());
+        assert!(to_mongo("eq(a,`$b`)").is_err());
+    }
+
+    #[test]
+    fn unsigned() {
+        let query = Parser::new_from_string("gt(id,18446744073709551615)".to_owned())
+            .parse_query()
+            .unwrap();
+        assert_eq!(
+            query.to_mongo().unwrap(),
+            json!({"id": {"$gt": 18446744073709551615u64}})
+        );
+    }
+
+    #[cfg(feature = "bson")]
+    mod bson {
+        use crate::convert::ConvertError;
+        use crate::parser::Parser;
+        use bson::{doc, Bson, Decimal128, Document};
+
+        fn to_bson(input: &str) -> Result<Document, ConvertError> {
+            Parser::from_str(input).parse_query().unwrap().to_bson()
+        }
+
+        // decimal フィーチャでは小数は Decimal128 になる
+        fn float(s: &str) -> Bson {
+            #[cfg(feature = "decimal")]
+            return Bson::Decimal128(s.parse().unwrap());
+            #[cfg(not(feature = "decimal"))]
+            return Bson::Double(s.parse().unwrap());
+        }
+
+        #[test]
+        fn operators() {
+            let cases = [
+                ("eq(status,\"open\")", doc! {"status": {"$eq": "open"}}),
+                ("ne(active,false)", doc! {"active": {"$nin": [false, Bson::Null]}}),
+                ("lt(a.b,3)", doc! {"a.b": {"$lt": 3i64}}),
+                ("le(a,1.5)", doc! {"a": {"$lte": float("1.5")}}),
+                ("gt(tags.*,2)", doc! {"tags": {"$gt": 2i64}}),
+                ("ge(a,0)", doc! {"a": {"$gte": 0i64}}),
+                ("eq(a,b)", doc! {"$expr": {"$eq": ["$a", "$b"]}}),
+                ("ne(a,meta.b)", doc! {"$expr": {"$ne": ["$a", "$meta.b"]}}),
+                ("startswith(name,\"Dr.\")", doc! {"name": {"$regex": "^Dr\\."}}),
+                ("endswith(name,\"?\")", doc! {"name": {"$regex": "\\?$"}}),
+                ("ieq(a,\"x\")", doc! {"a": {"$regex": "^x$", "$options": "i"}}),
+                ("icontains(a,\"x\")", doc! {"a": {"$regex": "x", "$options": "i"}}),
+                ("istartswith(a,\"x\")", doc! {"a": {"$regex": "^x", "$options": "i"}}),
+                ("exists(a)", doc! {"a": {"$ne": Bson::Null}}),
+                ("missing(a)", doc! {"a": Bson::Null}),
+                ("in(a,(1,\"x\"))", doc! {"a": {"$in": [1i64, "x"]}}),
+                ("out(a,(1))", doc! {"a": {"$nin": [1i64, Bson::Null]}}),
+                ("contains(tags,true)", doc! {"tags": {"$elemMatch": {"$eq": true}}}),
+                ("not(eq(a,1))", doc! {"$nor": [{"a": {"$eq": 1i64}}]}),
+                (
+                    "near(location,35.68,139.76,5000)",
+                    doc! {"location": {"$nearSphere": {
+                        "$geometry": {"type": "Point", "coordinates": [139.76, 35.68]},
+                        "$maxDistance": 5000.0,
+                    }}},
+                ),
+                (
+                    "gt(id,18446744073709551615)",
+                    doc! {"id": {"$gt": "18446744073709551615".parse::<Decimal128>().unwrap()}},
+                ),
+                (
+                    "and(eq(a,1),or(eq(b,2),missing(c)))",
+                    doc! {"$and": [
+                        {"a": {"$eq": 1i64}},
+                        {"$or": [{"b": {"$eq": 2i64}}, {"c": Bson::Null}]},
+                    ]},
+                ),
+                ("and()", doc! {}),
+                ("or()", doc! {"$expr": false}),
+            ];
+            for (input, expected) in cases {
+                assert_eq!(to_bson(input).unwrap(), expected, "{}", input);
+            }
+        }
+
+        // 数値以外は to_mongo の JSON と同じ形になる
+        #[test]
+        fn same_as_json() {
+            let input = "and(eq(status,\"open\"),or(startswith(name,\"a\"),missing(c)),ne(a,true))";
+            let query = Parser::from_str(input).parse_query().unwrap();
+            assert_eq!(
+                bson::to_document(&query.to_mongo().unwrap()).unwrap(),
+                query.to_bson().unwrap()
+            );
+        }
+
+        #[test]
+        fn unsupported() {
+            assert_eq!(
+                to_bson("and(eq(a,1),sort(+a))").unwrap_err().to_string(),
+                "sort() can't be translated to a MongoDB filter"
+            );
+            assert!(to_bson("eq(a,$x)").is_err());
+            assert!(to_bson("eq(a,items.0)").is_err());
+        }
+
+        #[cfg(feature = "chrono")]
+        #[test]
+        fn dates() {
+            assert_eq!(
+                to_bson("gt(created,date:2024-01-01T09:00:00+09:00)").unwrap(),
+                doc! {"created": {"$gt": bson::DateTime::from_millis(1704067200000)}}
+            );
+        }
+    }
+
+    #[cfg(feature = "mongodb")]
+    mod find {
+        use crate::request::Request;
+        use bson::doc;
+
+        #[test]
+        fn full() {
+            let request = Request::parse(
+                "and(eq(type,\"x\"),sort(-created_at,+user.id),limit(20,40),select(id,user.name))",
+            )
+            .unwrap();
+            let (filter, options) = request.to_mongo_find().unwrap();
+            assert_eq!(filter, doc! {"type": {"$eq": "x"}});
+            assert_eq!(options.sort, Some(doc! {"created_at": -1, "user.id": 1}));
+            assert_eq!(options.limit, Some(20));
+            assert_eq!(options.skip, Some(40));
+            assert_eq!(options.projection, Some(doc! {"id": 1, "user.name": 1}));
+        }
+
+        #[test]
+        fn no_sort() {
+            let request = Request::parse("and(gt(age,18),limit(5))").unwrap();
+            let (filter, options) = request.to_mongo_find().unwrap();
+            assert_eq!(filter, doc! {"age": {"$gt": 18i64}});
+            assert_eq!(options.sort, None);
+            assert_eq!(options.limit, Some(5));
+            assert_eq!(options.skip, None);
+            assert_eq!(options.projection, None);
+        }
+
+        #[test]
+        fn no_limit() {
+            let request = Request::parse("sort(+name)").unwrap();
+            let (filter, options) = request.to_mongo_find().unwrap();
+            assert_eq!(filter, doc! {});
+            assert_eq!(options.sort, Some(doc! {"name": 1}));
+            assert_eq!(options.limit, None);
+            assert_eq!(options.skip, None);
+            assert_eq!(options.projection, None);
+        }
+
+        #[test]
+        fn zero_limit() {
+            let request = Request::parse("and(eq(a,1),limit(0,5))").unwrap();
+            let (filter, options) = request.to_mongo_find().unwrap();
+            assert_eq!(filter, doc! {"$expr": false});
+            assert_eq!(options.limit, None);
+            assert_eq!(options.skip, None);
+        }
+
+        #[test]
+        fn unsupported() {
+            let request = Request::parse("and(search(\"x\"),limit(5))").unwrap();
+            assert_eq!(
+                request.to_mongo_find().unwrap_err().to_string(),
+                "search(\"x\") can't be translated to a MongoDB filter"
+            );
+        }
+    }
+}
@@ -0,0 +1,390 @@
+use crate::ast::{Infix, Path, Query, Value};
+use crate::convert::ConvertError;
+use std::collections::HashMap;
+
+const TARGET: &str = "RediSearch";
+
+/// How a field is indexed in RediSearch; each type has its own query syntax.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldType {
+    /// `@status:{open}`
+    Tag,
+    /// `@title:"hello world"`
+    Text,
+    /// `@age:[30 +inf]`
+    Numeric,
+}
+
+impl Query {
+    /// Renders the query in RediSearch query syntax, such as
+    /// `@status:{open} @age:[30 +inf]`.
+    ///
+    /// `fields` gives the type each field is indexed with, keyed by path.
+    /// Fields not in it are numeric when compared with a number and tags
+    /// otherwise. `and()` is juxtaposition and `or()` is `|`, with nested
+    /// groups in parentheses; `ne()` negates with `-`, leaving out documents
+    /// without the field as evaluation does.
+    ///
+    /// Tags support `eq()`, `ne()` and `startswith()`, with special characters
+    /// in the value escaped. Text fields support the same, `eq()` being a
+    /// phrase search rather than exact equality. Numeric fields support every
+    /// comparison with a number literal. `exists()`, `missing()` and `ne()` use
+    /// `ismissing()`, which needs RediSearch 2.10 with `INDEXMISSING` on the
+    /// field. An empty `or()` has no equivalent and is an error.
+    ///
+    /// `in()` and `out()` are written as the `eq()`s and `ne()`s they stand
+    /// for, and `not()` as `-(...)`. `contains()` looks for a value among the
+    /// tags, or the numbers of a numeric field.
+    ///
+    /// ```
+    /// use rql_parser::convert::redisearch::FieldType;
+    /// use rql_parser::parser::Parser;
+    /// use std::collections::HashMap;
+    ///
+    /// let query = Parser::new_from_string("and(eq(status,\"open\"),ge(age,30))".to_owned())
+    ///     .parse_query()
+    ///     .unwrap();
+    /// let mut fields = HashMap::new();
+    /// fields.insert("status".to_owned(), FieldType::Tag);
+    /// assert_eq!(query.to_redisearch(&fields).unwrap(), "@status:{open} @age:[30 +inf]");
+    /// ```
+    pub fn to_redisearch(
+        &self,
+        fields: &HashMap<String, FieldType>,
+    ) -> Result<String, ConvertError> {
+        match self {
+            Query::And(queries) if queries.is_empty() => Ok("*".to_owned()),
+            Query::Or(queries) if queries.is_empty() => {
+                Err(ConvertError::Unsupported("or()".to_owned(), TARGET))
+            }
+            Query::And(queries) => join(queries, " ", fields),
+            Query::Or(queries) => join(queries, " | ", fields),
+            Query::Not(query) => Ok(format!("-({})", query.to_redisearch(fields)?)),
+            Query::In(..) | Query::Out(..) => self.expand_in().to_redisearch(fields),
+            Query::Contains(path, value) if !path.has_wildcard() => {
+                let unsupported = || ConvertError::Unsupported(self.to_rql(), TARGET);
+                let kind = match (fields.get(&path.to_string()), number(value)) {
+                    (Some(kind), _) => *kind,
+                    (None, Some(_)) => FieldType::Numeric,
+                    (None, None) => FieldType::Tag,
+                };
+                match (kind, value) {
+                    (_, Value::Identifier(_)) => Err(unsupported()),
+                    (FieldType::Tag, _) => {
+                        let s = text(value).ok_or_else(unsupported)?;
+                        Ok(format!("@{}:{{{}}}", field(path), escape_tag(&s)))
+                    }
+                    (FieldType::Numeric, _) => {
+                        let n = number(value).ok_or_else(unsupported)?;
+                        Ok(format!("@{}:[{} {}]", field(path), n, n))
+                    }
+                    (FieldType::Text, _) => Err(unsupported()),
+                }
+            }
+            Query::Contains(..) => Err(ConvertError::Unsupported(self.to_rql(), TARGET)),
+            // フィールド同士は比べられない
+            Query::Filter(_, _, Value::Identifier(_)) => {
+                Err(ConvertError::Unsupported(self.to_rql(), TARGET))
+            }
+            Query::Filter(infix, Value::Identifier(path), value) if !path.has_wildcard() => {
+                let field = field(path);
+                let kind = match (fields.get(&path.to_string()), number(value)) {
+                    (Some(kind), _) => *kind,
+                    (None, Some(_)) => FieldType::Numeric,
+                    (None, None) => FieldType::Tag,
+                };
+                let unsupported = || ConvertError::Unsupported(self.to_rql(), TARGET);
+                let (negated, condition) = match (kind, infix) {
+                    (FieldType::Numeric, _) => {
+                        let n = number(value).ok_or_else(unsupported)?;
+                        let range = match infix {
+                            Infix::Eq | Infix::NotEq => format!("[{} {}]", n, n),
+                            Infix::Lt => format!("[-inf ({}]", n),
+                            Infix::Le => format!("[-inf {}]", n),
+                            Infix::Gt => format!("[({} +inf]", n),
+                            Infix::Ge => format!("[{} +inf]", n),
+                            Infix::StartsWith
+                            | Infix::EndsWith
+                            | Infix::IEq
+                            | Infix::IContains
+                            | Infix::IStartsWith => return Err(unsupported()),
+                        };
+                        (infix == &Infix::NotEq, range)
+                    }
+                    (FieldType::Tag, Infix::Eq | Infix::NotEq | Infix::StartsWith) => {
+                        let s = text(value).ok_or_else(unsupported)?;
+                        let tag = match infix {
+                            Infix::StartsWith => format!("{{{}*}}", escape_tag(&s)),
+                            _ => format!("{{{}}}", escape_tag(&s)),
+                        };
+                        (infix == &Infix::NotEq, tag)
+                    }
+                    // TEXT フィールドの検索はもともと大文字小文字を区別しない
+                    (FieldType::Text, Infix::Eq | Infix::NotEq | Infix::IEq) => {
+                        let s = text(value).ok_or_else(unsupported)?;
+                        let phrase = s.replace('\\', "\\\\").replace('"', "\\\"");
+                        (infix == &Infix::NotEq, format!("\"{}\"", phrase))
+                    }
+                    (FieldType::Text, Infix::StartsWith | Infix::IStartsWith) => {
+                        let s = text(value).ok_or_else(unsupported)?;
+                        (false, format!("{}*", escape_tag(&s)))
+                    }
+                    _ => return Err(unsupported()),
+                };
+                // 否定だけでは値の無いドキュメントにも一致するので、評価時と同じく除く
+                match negated {
+                    true => Ok(format!("(-ismissing(@{}) -@{}:{})", field, field, condition)),
+                    false => Ok(format!("@{}:{}", field, condition)),
+                }
+            }
+            Query::Filter(..) => Err(ConvertError::Unsupported(self.to_rql(), TARGET)),
+            Query::Exists(path) => Ok(format!(
+                "-ismissing(@{})",
+                field(path)
+            )),
+            Query::Missing(path) => {
+                Ok(format!("ismissing(@{})", field(path)))
+            }
+            // GEO フィールドは [経度 緯度 半径 単位]
+            Query::Near(path, lat, lon, radius) => Ok(format!(
+                "@{}:[{} {} {} m]",
+                field(path),
+                lon,
+                lat,
+                radius
+            )),
+            Query::Sort(..) => Err(ConvertError::Unsupported(
+                "sort()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::Limit(..) => Err(ConvertError::Unsupported(
+                "limit()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::Select(..) => Err(ConvertError::Unsupported(
+                "select()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::Distinct(..) => Err(ConvertError::Unsupported(
+                "distinct()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::Aggregate(..) => Err(ConvertError::Unsupported(
+                "aggregate()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::First => Err(ConvertError::Unsupported(
+                "first()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::One => Err(ConvertError::Unsupported(
+                "one()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::Count => Err(ConvertError::Unsupported(
+                "count()".to_owned(),
+                "a RediSearch query",
+            )),
+            Query::Reduce(..) | Query::Search(..) => Err(ConvertError::Unsupported(
+                self.to_rql(),
+                "a RediSearch query",
+            )),
+            Query::None => Ok("*".to_owned()),
+        }
+    }
+}
+
+// 入れ子の and()/or() は括弧で囲む。RediSearch では | のほうが並べるより強く結合する
+fn join(
+    queries: &[Query],
+    separator: &str,
+    fields: &HashMap<String, FieldType>,
+) -> Result<String, ConvertError> {
+    let mut parts = Vec::with_capacity(queries.len());
+    for query in queries {
+        let part = query.to_redisearch(fields)?;
+        match query {
+            Query::And(qs) | Query::Or(qs) if qs.len() > 1 => parts.push(format!("({})", part)),
+            Query::In(_, values) | Query::Out(_, values) if values.len() > 1 => {
+                parts.push(format!("({})", part))
+            }
+            _ => parts.push(part),
+        }
+    }
+    Ok(parts.join(separator))
+}
+
+// フィールド名は英数字と _ 以外をエスケープする
+fn field(path: &Path) -> String {
+    escape_tag(&path.to_string())
+}
+
+// タグの値や接頭辞の中では、英数字と _ 以外の ASCII 文字 (空白を含む) をエスケープする
+fn escape_tag(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii() && !c.is_ascii_alphanumeric() && c != '_' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn number(value: &Value) -> Option<String> {
+    match value {
+        Value::IntegerLiteral(i) => Some(i.to_string()),
+        Value::UnsignedLiteral(u) => Some(u.to_string()),
+        Value::FloatLiteral(f) if f.is_nan() => None,
+        Value::FloatLiteral(f) if f.is_infinite() => {
+            Some(if *f > 0.0 { "+inf" } else { "-inf" }.to_owned())
+        }
+        Value::FloatLiteral(f) => Some(f.to_string()),
+        #[cfg(feature = "decimal")]
+        Value::DecimalLiteral(d) => Some(d.to_string()),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::StringLiteral(s) => Some(s.clone()),
+        Value::Boolean(b) => Some(b.to_string()),
+        value => number(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::redisearch::FieldType;
+    use crate::convert::ConvertError;
+    use crate::parser::Parser;
+    use std::collections::HashMap;
+
+    fn to_redisearch(input: &str) -> Result<String, ConvertError> {
+        let mut fields = HashMap::new();
+        fields.insert("status".to_owned(), FieldType::Tag);
+        fields.insert("email".to_owned(), FieldType::Tag);
+        fields.insert("title".to_owned(), FieldType::Text);
+        fields.insert("code".to_owned(), FieldType::Tag);
+        fields.insert("age".to_owned(), FieldType::Numeric);
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .to_redisearch(&fields)
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            to_redisearch("and(eq(status,\"open\"),ge(age,30))").unwrap(),
+            "@status:{open} @age:[30 +inf]"
+        );
+        assert_eq!(
+            to_redisearch("and(gt(age,18),lt(age,65.5),le(score,3),eq(rank,1))").unwrap(),
+            "@age:[(18 +inf] @age:[-inf (65.5] @score:[-inf 3] @rank:[1 1]"
+        );
+        assert_eq!(
+            to_redisearch("and(ne(status,\"closed\"),ne(age,3))").unwrap(),
+            "(-ismissing(@status) -@status:{closed}) (-ismissing(@age) -@age:[3 3])"
+        );
+        assert_eq!(
+            to_redisearch("and(eq(title,\"hello world\"),startswith(title,\"intro\"))").unwrap(),
+            "@title:\"hello world\" @title:intro*"
+        );
+        // 型の指定が無ければ値から決める
+        assert_eq!(
+            to_redisearch("and(eq(color,\"red\"),eq(active,true),gt(size,2))").unwrap(),
+            "@color:{red} @active:{true} @size:[(2 +inf]"
+        );
+        assert_eq!(
+            to_redisearch("or(exists(phone),missing(user.email))").unwrap(),
+            "-ismissing(@phone) | ismissing(@user\\.email)"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            to_redisearch("eq(email,\"a.b+c@example.com\")").unwrap(),
+            r"@email:{a\.b\+c\@example\.com}"
+        );
+        assert_eq!(
+            to_redisearch("eq(status,\"in progress\")").unwrap(),
+            r"@status:{in\ progress}"
+        );
+        assert_eq!(
+            to_redisearch("eq(code,\"{x|y}-1,2\")").unwrap(),
+            r"@code:{\{x\|y\}\-1\,2}"
+        );
+        assert_eq!(
+            to_redisearch("eq(code,\"back\\\\slash 'q' \\\"dq\\\"\")").unwrap(),
+            r#"@code:{back\\slash\ \'q\'\ \"dq\"}"#
+        );
+        assert_eq!(
+            to_redisearch("eq(status,\"東京\")").unwrap(),
+            "@status:{東京}"
+        );
+        assert_eq!(
+            to_redisearch("startswith(code,\"ab-\")").unwrap(),
+            r"@code:{ab\-*}"
+        );
+        assert_eq!(
+            to_redisearch("eq(title,\"say \\\"hi\\\"\")").unwrap(),
+            r#"@title:"say \"hi\"""#
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            to_redisearch("or(eq(status,\"open\"),and(eq(status,\"closed\"),gt(age,1)))").unwrap(),
+            "@status:{open} | (@status:{closed} @age:[(1 +inf])"
+        );
+        assert_eq!(
+            to_redisearch(
+                "and(eq(code,\"a\"),or(eq(status,\"x\"),eq(status,\"y\")),or(lt(age,3)))"
+            )
+            .unwrap(),
+            "@code:{a} (@status:{x} | @status:{y}) @age:[-inf (3]"
+        );
+        assert_eq!(to_redisearch("and()").unwrap(), "*");
+    }
+
+    #[test]
+    fn membership() {
+        assert_eq!(
+            to_redisearch("and(in(status,(\"new\",\"open\")),out(age,(1)),contains(tags,\"a b\"))")
+                .unwrap(),
+            "(@status:{new} | @status:{open}) (-ismissing(@age) -@age:[1 1]) @tags:{a\\ b}"
+        );
+        assert_eq!(
+            to_redisearch("not(or(eq(code,\"a\"),contains(age,3)))").unwrap(),
+            "-(@code:{a} | @age:[3 3])"
+        );
+        assert!(to_redisearch("contains(title,\"x\")").is_err());
+    }
+
+    #[test]
+    fn unsupported() {
+        let err = |input: &str| to_redisearch(input).unwrap_err().to_string();
+        assert_eq!(err("or()"), "or() can't be translated to RediSearch");
+        assert_eq!(
+            err("gt(status,\"a\")"),
+            "gt(status,\"a\") can't be translated to RediSearch"
+        );
+        assert_eq!(
+            err("eq(age,\"x\")"),
+            "eq(age,\"x\") can't be translated to RediSearch"
+        );
+        assert_eq!(
+            err("endswith(code,\"x\")"),
+            "endswith(code,\"x\") can't be translated to RediSearch"
+        );
+        assert_eq!(
+            err("and(eq(a,1),sort(+a))"),
+            "sort() can't be translated to a RediSearch query"
+        );
+        assert!(to_redisearch("eq(tags.*,\"x\")").is_err());
+    }
+}
@@ -0,0 +1,677 @@
+use crate::ast::{number, Infix, Path, Query, Segment, Value};
+use crate::convert::{json_value, Backend, ConvertError};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialect {
+    Postgres,
+    Mysql,
+    Sqlite,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "postgres" | "postgresql" => Ok(Dialect::Postgres),
+            "mysql" => Ok(Dialect::Mysql),
+            "sqlite" => Ok(Dialect::Sqlite),
+            _ => Err(format!("unknown SQL dialect {}", s)),
+        }
+    }
+}
+
+impl Dialect {
+    // `speed.max` は "speed"."max" (テーブル.カラム) として扱う
+    fn quote_path(&self, path: &Path) -> String {
+        let quote = match self {
+            Dialect::Mysql => '`',
+            Dialect::Postgres | Dialect::Sqlite => '"',
+        };
+        path.segments()
+            .iter()
+            .map(|segment| {
+                let part = match segment {
+                    Segment::Key(key) => key.clone(),
+                    Segment::Index(i) => i.to_string(),
+                    Segment::Wildcard => "*".to_owned(),
+                };
+                let escaped = part.replace(quote, &format!("{}{}", quote, quote));
+                format!("{}{}{}", quote, escaped, quote)
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn quote_string(&self, s: &str) -> String {
+        let s = match self {
+            // MySQL はデフォルトでバックスラッシュもエスケープ文字として解釈する
+            Dialect::Mysql => s.replace('\\', "\\\\"),
+            Dialect::Postgres | Dialect::Sqlite => s.to_owned(),
+        };
+        format!("'{}'", s.replace('\'', "''"))
+    }
+
+    fn placeholder(&self, index: usize) -> String {
+        match self {
+            Dialect::Postgres => format!("${}", index),
+            Dialect::Mysql | Dialect::Sqlite => "?".to_owned(),
+        }
+    }
+}
+
+impl Query {
+    /// Renders the query as a SQL `WHERE` condition with inlined, escaped literals.
+    ///
+    /// Bounds on the same column are merged first with [`Query::merge_ranges`];
+    /// a remaining `ge()`/`le()` pair becomes `BETWEEN`, and bounds that leave
+    /// nothing become `FALSE`. SQL columns have no arrays, so `contains()`
+    /// only takes a string and becomes a `LIKE` for it.
+    pub fn to_sql(&self, dialect: Dialect) -> Result<String, ConvertError> {
+        self.merge_ranges().lower(&mut SqlBackend {
+            dialect,
+            params: None,
+        })
+    }
+
+    /// Renders the query as a SQL `WHERE` condition with placeholders,
+    /// returning the bind values in placeholder order.
+    pub fn to_sql_params(
+        &self,
+        dialect: Dialect,
+    ) -> Result<(String, Vec<serde_json::Value>), ConvertError> {
+        let mut backend = SqlBackend {
+            dialect,
+            params: Some(vec![]),
+        };
+        let sql = self.merge_ranges().lower(&mut backend)?;
+        Ok((sql, backend.params.unwrap_or_default()))
+    }
+}
+
+// LIKE のワイルドカードを ESCAPE '!' でエスケープする。バックスラッシュは方言によって
+// 扱いが異なるので使わない
+fn escape_like(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '!' | '%' | '_') {
+            out.push('!');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// リテラルは finish で書き出す。プレースホルダの番号が SQL に現れる順になるように
+enum Piece {
+    Text(String),
+    Literal(Value),
+}
+
+struct SqlPart {
+    pieces: Vec<Piece>,
+    // 2つ以上をつないだ and() か or()。入れ子にするときは括弧で囲む
+    compound: bool,
+    // 数値の ge() か le() なら (比較, カラム, 値)。and() の中で BETWEEN にまとめる
+    bound: Option<(Infix, Path, Value)>,
+}
+
+impl SqlPart {
+    fn new(pieces: Vec<Piece>) -> SqlPart {
+        SqlPart {
+            pieces,
+            compound: false,
+            bound: None,
+        }
+    }
+
+    fn text(sql: &str) -> SqlPart {
+        SqlPart::new(vec![text(sql)])
+    }
+}
+
+// 比較の演算子。文字列の演算子は LIKE などにするので、ここには来ない
+fn operator(infix: &Infix) -> &'static str {
+    match infix {
+        Infix::Eq => "=",
+        Infix::NotEq => "!=",
+        Infix::Le => "<=",
+        Infix::Ge => ">=",
+        Infix::Lt => "<",
+        Infix::Gt => ">",
+        Infix::StartsWith | Infix::EndsWith | Infix::IEq | Infix::IContains | Infix::IStartsWith => {
+            unreachable!("{} is translated with LIKE", infix)
+        }
+    }
+}
+
+fn text<S: Into<String>>(text: S) -> Piece {
+    Piece::Text(text.into())
+}
+
+fn literal(value: Value) -> Piece {
+    Piece::Literal(value)
+}
+
+// parts[i] が ge() か le() なら、後ろにある同じカラムの逆向きの境界と組にする。
+// (組の位置, カラム, 下限, 上限)
+fn between<'a>(
+    parts: &'a [SqlPart],
+    i: usize,
+    paired: &[bool],
+) -> Option<(usize, &'a Path, &'a Value, &'a Value)> {
+    let (infix, path, value) = parts[i].bound.as_ref()?;
+    parts.iter().enumerate().skip(i + 1).find_map(|(j, part)| match &part.bound {
+        Some((o, p, v)) if o != infix && p == path && !paired[j] => match infix {
+            Infix::Ge => Some((j, path, value, v)),
+            _ => Some((j, path, v, value)),
+        },
+        _ => None,
+    })
+}
+
+struct SqlBackend {
+    dialect: Dialect,
+    params: Option<Vec<serde_json::Value>>,
+}
+
+impl Backend for SqlBackend {
+    type Fragment = SqlPart;
+    type Output = String;
+    type Error = ConvertError;
+
+    fn visit_filter(&mut self, query: &Query) -> Result<SqlPart, ConvertError> {
+        match query {
+            Query::Filter(_, _, value @ (Value::Call(_) | Value::Variable(_))) => {
+                Err(ConvertError::Unsupported(value.to_string(), "SQL"))
+            }
+            Query::Filter(_, Value::Identifier(path), _)
+            | Query::Filter(_, _, Value::Identifier(path))
+                if path.has_wildcard() =>
+            {
+                Err(ConvertError::Unsupported(path.to_string(), "SQL"))
+            }
+            Query::Filter(
+                infix @ (Infix::StartsWith | Infix::EndsWith),
+                Value::Identifier(ident),
+                Value::StringLiteral(s),
+            ) => {
+                let escaped = escape_like(s);
+                let pattern = if infix == &Infix::StartsWith {
+                    format!("{}%", escaped)
+                } else {
+                    format!("%{}", escaped)
+                };
+                Ok(SqlPart::new(vec![
+                    text(format!("{} LIKE ", self.dialect.quote_path(ident))),
+                    literal(Value::StringLiteral(pattern)),
+                    text(" ESCAPE '!'"),
+                ]))
+            }
+            Query::Filter(infix, Value::Identifier(ident), Value::StringLiteral(s))
+                if infix.ignores_case() =>
+            {
+                Ok(self.ignoring_case(infix, ident, s))
+            }
+            Query::Filter(Infix::StartsWith | Infix::EndsWith, ..) => {
+                Err(ConvertError::Unsupported(query.to_rql(), "SQL"))
+            }
+            Query::Filter(infix, ..) if infix.ignores_case() => {
+                Err(ConvertError::Unsupported(query.to_rql(), "SQL"))
+            }
+            // 右辺の識別子はカラムの参照
+            Query::Filter(infix, Value::Identifier(ident), Value::Identifier(other)) => {
+                Ok(SqlPart::new(vec![text(format!(
+                    "{} {} {}",
+                    self.dialect.quote_path(ident),
+                    operator(infix),
+                    self.dialect.quote_path(other)
+                ))]))
+            }
+            Query::Filter(infix, Value::Identifier(ident), value) => Ok(SqlPart {
+                bound: match infix {
+                    Infix::Ge | Infix::Le if number(value).is_some() => {
+                        Some((infix.clone(), ident.clone(), value.clone()))
+                    }
+                    _ => None,
+                },
+                ..SqlPart::new(vec![
+                    text(format!("{} {} ", self.dialect.quote_path(ident), operator(infix))),
+                    literal(value.clone()),
+                ])
+            }),
+            Query::Filter(..) => Err(ConvertError::Unsupported(query.to_rql(), "SQL")),
+            Query::In(ident, values) | Query::Out(ident, values) => {
+                if ident.has_wildcard()
+                    || values
+                        .iter()
+                        .any(|v| matches!(v, Value::Identifier(_) | Value::Call(_) | Value::Variable(_)))
+                {
+                    return Err(ConvertError::Unsupported(query.to_rql(), "SQL"));
+                }
+                // NULL のカラムは IN にも NOT IN にも一致しない
+                let op = if matches!(query, Query::In(..)) { "IN" } else { "NOT IN" };
+                let mut pieces = vec![text(format!("{} {} (", self.dialect.quote_path(ident), op))];
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        pieces.push(text(", "));
+                    }
+                    pieces.push(literal(value.clone()));
+                }
+                pieces.push(text(")"));
+                Ok(SqlPart::new(pieces))
+            }
+            Query::Contains(ident, Value::StringLiteral(s)) if !ident.has_wildcard() => {
+                Ok(SqlPart::new(vec![
+                    text(format!("{} LIKE ", self.dialect.quote_path(ident))),
+                    literal(Value::StringLiteral(format!("%{}%", escape_like(s)))),
+                    text(" ESCAPE '!'"),
+                ]))
+            }
+            Query::Contains(..) => Err(ConvertError::Unsupported(query.to_rql(), "SQL")),
+            Query::Exists(ident) => Ok(SqlPart::new(vec![text(format!(
+                "{} IS NOT NULL",
+                self.dialect.quote_path(ident)
+            ))])),
+            Query::Sort(..) => Err(ConvertError::Unsupported("sort()".to_owned(), "SQL")),
+            Query::Limit(..) => Err(ConvertError::Unsupported("limit()".to_owned(), "SQL")),
+            Query::Select(..) => Err(ConvertError::Unsupported("select()".to_owned(), "SQL")),
+            Query::Distinct(..) => Err(ConvertError::Unsupported("distinct()".to_owned(), "SQL")),
+            Query::Aggregate(..) => Err(ConvertError::Unsupported("aggregate()".to_owned(), "SQL")),
+            Query::First => Err(ConvertError::Unsupported("first()".to_owned(), "SQL")),
+            Query::One => Err(ConvertError::Unsupported("one()".to_owned(), "SQL")),
+            Query::Count => Err(ConvertError::Unsupported("count()".to_owned(), "SQL")),
+            Query::Reduce(..) | Query::Near(..) => {
+                Err(ConvertError::Unsupported(query.to_rql(), "SQL"))
+            }
+            // SQL ではすべてのカラムを探すことはできない
+            Query::Search(fields, _) if fields.is_empty() => {
+                Err(ConvertError::Unsupported(query.to_rql(), "SQL"))
+            }
+            Query::Search(fields, term) => Ok(self.search(fields, term)),
+            Query::And(..) | Query::Or(..) | Query::Not(..) | Query::Missing(..) | Query::None => {
+                unreachable!("lowered by Query::lower")
+            }
+        }
+    }
+
+    // 同じカラムの ge() と le() の組は BETWEEN にする
+    fn combine_and(&mut self, parts: Vec<SqlPart>) -> Result<SqlPart, ConvertError> {
+        if parts.is_empty() {
+            return Ok(SqlPart::text("TRUE"));
+        }
+        let mut paired = vec![false; parts.len()];
+        let mut betweens: Vec<Option<SqlPart>> = (0..parts.len()).map(|_| None).collect();
+        for i in 0..parts.len() {
+            if paired[i] {
+                continue;
+            }
+            if let Some((j, path, low, high)) = between(&parts, i, &paired) {
+                paired[i] = true;
+                paired[j] = true;
+                betweens[i] = Some(SqlPart::new(vec![
+                    text(format!("{} BETWEEN ", self.dialect.quote_path(path))),
+                    literal(low.clone()),
+                    text(" AND "),
+                    literal(high.clone()),
+                ]));
+            }
+        }
+        let compound = parts.len() > 1;
+        let mut joined = Vec::with_capacity(parts.len());
+        for (i, part) in parts.into_iter().enumerate() {
+            match betweens[i].take() {
+                Some(between) => joined.push(between),
+                None if !paired[i] => joined.push(part),
+                None => {}
+            }
+        }
+        Ok(SqlPart {
+            compound,
+            ..join(joined, " AND ")
+        })
+    }
+
+    fn combine_or(&mut self, parts: Vec<SqlPart>) -> Result<SqlPart, ConvertError> {
+        match parts.is_empty() {
+            true => Ok(SqlPart::text("FALSE")),
+            false => Ok(join(parts, " OR ")),
+        }
+    }
+
+    fn negate(&mut self, part: SqlPart) -> Result<SqlPart, ConvertError> {
+        let mut pieces = vec![text("NOT (")];
+        pieces.extend(part.pieces);
+        pieces.push(text(")"));
+        Ok(SqlPart::new(pieces))
+    }
+
+    fn finish(&mut self, part: SqlPart) -> Result<String, ConvertError> {
+        let mut sql = String::new();
+        for piece in &part.pieces {
+            match piece {
+                Piece::Text(text) => sql.push_str(text),
+                Piece::Literal(value) => sql.push_str(&self.literal(value)),
+            }
+        }
+        Ok(sql)
+    }
+
+    fn visit_missing(&mut self, field: &Path) -> Result<SqlPart, ConvertError> {
+        Ok(SqlPart::new(vec![text(format!(
+            "{} IS NULL",
+            self.dialect.quote_path(field)
+        ))]))
+    }
+}
+
+fn join(parts: Vec<SqlPart>, separator: &str) -> SqlPart {
+    let compound = parts.len() > 1;
+    let mut pieces = vec![];
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            pieces.push(text(separator));
+        }
+        if part.compound {
+            pieces.push(text("("));
+            pieces.extend(part.pieces);
+            pieces.push(text(")"));
+        } else {
+            pieces.extend(part.pieces);
+        }
+    }
+    SqlPart {
+        compound,
+        ..SqlPart::new(pieces)
+    }
+}
+
+impl SqlBackend {
+    // ieq は両辺を LOWER() にする。icontains と istartswith は PostgreSQL では ILIKE、
+    // 他では LOWER() にした LIKE にする
+    fn ignoring_case(&self, infix: &Infix, ident: &Path, s: &str) -> SqlPart {
+        let column = self.dialect.quote_path(ident);
+        let pattern = match infix {
+            Infix::IEq => {
+                return SqlPart::new(vec![
+                    text(format!("LOWER({}) = LOWER(", column)),
+                    literal(Value::StringLiteral(s.to_owned())),
+                    text(")"),
+                ]);
+            }
+            Infix::IContains => format!("%{}%", escape_like(s)),
+            _ => format!("{}%", escape_like(s)),
+        };
+        let pattern = literal(Value::StringLiteral(pattern));
+        SqlPart::new(match self.dialect {
+            Dialect::Postgres => vec![
+                text(format!("{} ILIKE ", column)),
+                pattern,
+                text(" ESCAPE '!'"),
+            ],
+            Dialect::Mysql | Dialect::Sqlite => vec![
+                text(format!("LOWER({}) LIKE LOWER(", column)),
+                pattern,
+                text(") ESCAPE '!'"),
+            ],
+        })
+    }
+
+    // 語ごとにどれかのカラムが含んでいればよい。大文字小文字は区別しない。MySQL と SQLite
+    // の LIKE は既定で区別しない
+    fn search(&self, fields: &[Path], term: &str) -> SqlPart {
+        let like = match self.dialect {
+            Dialect::Postgres => "ILIKE",
+            Dialect::Mysql | Dialect::Sqlite => "LIKE",
+        };
+        let mut words = vec![];
+        for word in term.split_whitespace() {
+            let pattern = Value::StringLiteral(format!("%{}%", escape_like(word)));
+            let columns: Vec<SqlPart> = fields
+                .iter()
+                .map(|field| {
+                    SqlPart::new(vec![
+                        text(format!("{} {} ", self.dialect.quote_path(field), like)),
+                        literal(pattern.clone()),
+                        text(" ESCAPE '!'"),
+                    ])
+                })
+                .collect();
+            words.push(join(columns, " OR "));
+        }
+        match words.is_empty() {
+            true => SqlPart::text("TRUE"),
+            // 検索全体は括弧で囲まない
+            false => SqlPart {
+                compound: false,
+                ..join(words, " AND ")
+            },
+        }
+    }
+
+    fn literal(&mut self, value: &Value) -> String {
+        if let Some(params) = &mut self.params {
+            params.push(json_value(value));
+            return self.dialect.placeholder(params.len());
+        }
+        match value {
+            Value::StringLiteral(s) => self.dialect.quote_string(s),
+            Value::Boolean(true) => "TRUE".to_owned(),
+            Value::Boolean(false) => "FALSE".to_owned(),
+            #[cfg(feature = "chrono")]
+            Value::DateTime(_) | Value::RelativeTime(..) => match json_value(value) {
+                serde_json::Value::String(s) => self.dialect.quote_string(&s),
+                _ => value.to_string(),
+            },
+            _ => value.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Prefix, Query, Value};
+    use crate::convert::sql::Dialect;
+    use crate::convert::ConvertError;
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    const QUERY: &str =
+        "and(eq(status,\"open\"),or(gt(priority,3),le(due.days,1.5)),ne(owner,\"o'brien\\\\\"))";
+
+    fn to_sql(input: &str, dialect: Dialect) -> Result<String, ConvertError> {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .to_sql(dialect)
+    }
+
+    #[test]
+    fn dialects() {
+        assert_eq!(
+            to_sql(QUERY, Dialect::Postgres).unwrap(),
+            r#""status" = 'open' AND ("priority" > 3 OR "due"."days" <= 1.5) AND "owner" != 'o''brien\'"#
+        );
+        assert_eq!(
+            to_sql(QUERY, Dialect::Mysql).unwrap(),
+            r#"`status` = 'open' AND (`priority` > 3 OR `due`.`days` <= 1.5) AND `owner` != 'o''brien\\'"#
+        );
+        assert_eq!(
+            to_sql("and(eq(active,true),or())", Dialect::Sqlite).unwrap(),
+            r#""active" = TRUE AND FALSE"#
+        );
+        assert_eq!(
+            to_sql("or(exists(user.phone),missing(email))", Dialect::Postgres).unwrap(),
+            r#""user"."phone" IS NOT NULL OR "email" IS NULL"#
+        );
+        assert_eq!(
+            to_sql("eq(id,18446744073709551615)", Dialect::Mysql).unwrap(),
+            "`id` = 18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn like() {
+        assert_eq!(
+            to_sql("startswith(name,\"Dr. \")", Dialect::Postgres).unwrap(),
+            r#""name" LIKE 'Dr. %' ESCAPE '!'"#
+        );
+        assert_eq!(
+            to_sql("endswith(rate,\"100%_!\")", Dialect::Mysql).unwrap(),
+            r#"`rate` LIKE '%100!%!_!!' ESCAPE '!'"#
+        );
+        let query = Parser::new_from_string("endswith(email,\"@example.com\")".to_owned())
+            .parse_query()
+            .unwrap();
+        let (sql, params) = query.to_sql_params(Dialect::Sqlite).unwrap();
+        assert_eq!(sql, r#""email" LIKE ? ESCAPE '!'"#);
+        assert_eq!(params, vec![json!("%@example.com")]);
+    }
+
+    #[test]
+    fn membership() {
+        assert_eq!(
+            to_sql("and(in(status,(\"new\",\"open\")),out(age,(1,2)))", Dialect::Postgres).unwrap(),
+            r#""status" IN ('new', 'open') AND "age" NOT IN (1, 2)"#
+        );
+        assert_eq!(
+            to_sql("not(or(contains(note,\"50%\"),eq(a,1)))", Dialect::Mysql).unwrap(),
+            "NOT (`note` LIKE '%50!%%' ESCAPE '!' OR `a` = 1)"
+        );
+        let query = Parser::from_str("in(id,(1,2))").parse_query().unwrap();
+        let (sql, params) = query.to_sql_params(Dialect::Postgres).unwrap();
+        assert_eq!(sql, r#""id" IN ($1, $2)"#);
+        assert_eq!(params, vec![json!(1), json!(2)]);
+        assert!(matches!(
+            to_sql("contains(scores,3)", Dialect::Sqlite),
+            Err(ConvertError::Unsupported(_, "SQL"))
+        ));
+    }
+
+    #[test]
+    fn ignoring_case() {
+        let input = "and(ieq(name,\"ß\"),icontains(note,\"50%\"),istartswith(city,\"İ\"))";
+        assert_eq!(
+            to_sql(input, Dialect::Postgres).unwrap(),
+            r#"LOWER("name") = LOWER('ß') AND "note" ILIKE '%50!%%' ESCAPE '!' AND "city" ILIKE 'İ%' ESCAPE '!'"#
+        );
+        assert_eq!(
+            to_sql(input, Dialect::Mysql).unwrap(),
+            "LOWER(`name`) = LOWER('ß') AND LOWER(`note`) LIKE LOWER('%50!%%') ESCAPE '!' \
+             AND LOWER(`city`) LIKE LOWER('İ%') ESCAPE '!'"
+        );
+        assert_eq!(
+            to_sql("ieq(name,\"o'Neil\")", Dialect::Sqlite).unwrap(),
+            r#"LOWER("name") = LOWER('o''Neil')"#
+        );
+        let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+        let (sql, params) = query.to_sql_params(Dialect::Sqlite).unwrap();
+        assert_eq!(
+            sql,
+            r#"LOWER("name") = LOWER(?) AND LOWER("note") LIKE LOWER(?) ESCAPE '!' AND LOWER("city") LIKE LOWER(?) ESCAPE '!'"#
+        );
+        assert_eq!(params, vec![json!("ß"), json!("%50!%%"), json!("İ%")]);
+    }
+
+    #[test]
+    fn search() {
+        assert_eq!(
+            to_sql("search((message),\"100%\")", Dialect::Postgres).unwrap(),
+            r#""message" ILIKE '%100!%%' ESCAPE '!'"#
+        );
+        assert_eq!(
+            to_sql("and(eq(level,3),search((message,details),\"error timeout\"))", Dialect::Mysql)
+                .unwrap(),
+            "`level` = 3 AND (`message` LIKE '%error%' ESCAPE '!' \
+             OR `details` LIKE '%error%' ESCAPE '!') \
+             AND (`message` LIKE '%timeout%' ESCAPE '!' OR `details` LIKE '%timeout%' ESCAPE '!')"
+        );
+        assert_eq!(to_sql("search((a),\" \")", Dialect::Sqlite).unwrap(), "TRUE");
+        assert!(matches!(
+            to_sql("search(\"timeout\")", Dialect::Postgres),
+            Err(ConvertError::Unsupported(..))
+        ));
+        let query = Parser::new_from_string("search((a,b),\"x\")".to_owned())
+            .parse_query()
+            .unwrap();
+        let (sql, params) = query.to_sql_params(Dialect::Postgres).unwrap();
+        assert_eq!(sql, r#"("a" ILIKE $1 ESCAPE '!' OR "b" ILIKE $2 ESCAPE '!')"#);
+        assert_eq!(params, vec![json!("%x%"), json!("%x%")]);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            to_sql("and(ge(price,10),le(price,20),ge(price,12))", Dialect::Postgres).unwrap(),
+            r#""price" BETWEEN 12 AND 20"#
+        );
+        assert_eq!(
+            to_sql("and(eq(kind,\"a\"),gt(price,5),le(price,20),lt(price,30))", Dialect::Mysql)
+                .unwrap(),
+            "`kind` = 'a' AND `price` > 5 AND `price` <= 20"
+        );
+        assert_eq!(
+            to_sql("or(and(gt(x,10),lt(x,5)),eq(y,1))", Dialect::Sqlite).unwrap(),
+            r#"FALSE OR "y" = 1"#
+        );
+        let query = Parser::new_from_string("and(le(a,2.5),eq(b,1),ge(a,1))".to_owned())
+            .parse_query()
+            .unwrap();
+        let (sql, params) = query.to_sql_params(Dialect::Postgres).unwrap();
+        assert_eq!(sql, r#""a" BETWEEN $1 AND $2 AND "b" = $3"#);
+        assert_eq!(params, vec![json!(1), json!(2.5), json!(1)]);
+    }
+
+    #[test]
+    fn params() {
+        let query = Parser::new_from_string(QUERY.to_owned())
+            .parse_query()
+            .unwrap();
+        let (sql, params) = query.to_sql_params(Dialect::Postgres).unwrap();
+        assert_eq!(
+            sql,
+            r#""status" = $1 AND ("priority" > $2 OR "due"."days" <= $3) AND "owner" != $4"#
+        );
+        assert_eq!(params, vec![json!("open"), json!(3), json!(1.5), json!("o'brien\\")]);
+        let (sql, _) = query.to_sql_params(Dialect::Sqlite).unwrap();
+        assert_eq!(
+            sql,
+            r#""status" = ? AND ("priority" > ? OR "due"."days" <= ?) AND "owner" != ?"#
+        );
+    }
+
+    // 右辺の識別子は文字列ではなくカラム
+    #[test]
+    fn field_to_field() {
+        assert_eq!(
+            to_sql("and(gt(spent,budget),eq(a,meta.b))", Dialect::Postgres).unwrap(),
+            r#""spent" > "budget" AND "a" = "meta"."b""#
+        );
+        let query = Parser::new_from_string("and(eq(a,b),eq(c,1))".to_owned())
+            .parse_query()
+            .unwrap();
+        let (sql, params) = query.to_sql_params(Dialect::Mysql).unwrap();
+        assert_eq!(sql, "`a` = `b` AND `c` = ?");
+        assert_eq!(params, vec![json!(1)]);
+        assert!(to_sql("eq(a,items.*.b)", Dialect::Postgres).is_err());
+    }
+
+    #[test]
+    fn unsupported() {
+        let sort = Query::And(vec![Query::Sort(vec![(
+            Prefix::Minus,
+            Value::Identifier("created_at".into()),
+        )])]);
+        assert_eq!(
+            sort.to_sql(Dialect::Postgres).unwrap_err().to_string(),
+            "sort() can't be translated to SQL"
+        );
+        assert_eq!(
+            to_sql("near(location,35.68,139.76,5000)", Dialect::Postgres)
+                .unwrap_err()
+                .to_string(),
+            "near(location,35.68,139.76,5000) can't be translated to SQL"
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Lossless concrete syntax tree, for formatters and linters.
+//!
+//! Unlike [`Query`], the tree keeps every byte of the input: each token with its
+//! text and span, and the whitespace between tokens as trivia. Printing a
+//! [`Node`] gives back its input exactly. The tree only records the call
+//! structure (`name(arg,arg)`), so it is built even for invalid queries;
+//! [`Node::to_query`] reports their errors.
+
+use crate::ast::Query;
+use crate::lexer::tokenize;
+use crate::parser::{Parser, ParserError};
+use crate::token::Token;
+use std::fmt;
+use std::ops::Range;
+
+mod format;
+pub use format::{format_preserving, FormatRules};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    /// The whole input.
+    Root,
+    /// `name(...)`: the name, `(`, the arguments separated by `,` and `)`.
+    /// The `)` is missing if the input ends first.
+    Call,
+    /// One argument of a call, such as `+name` or `eq(a,1)`, with the
+    /// whitespace around it.
+    Argument,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub children: Vec<Element>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Node(Node),
+    Token(SyntaxToken),
+}
+
+/// A token of the input, or a run of whitespace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    /// The lexer's token; `None` for whitespace.
+    pub token: Option<Token>,
+    pub text: String,
+    /// Byte range in the input.
+    pub span: Range<usize>,
+}
+
+impl SyntaxToken {
+    pub fn is_trivia(&self) -> bool {
+        self.token.is_none()
+    }
+}
+
+/// Builds the concrete syntax tree of `input`. It never fails: tokens that
+/// don't fit the call structure, like an extra `)`, are kept where they are.
+pub fn parse_cst(input: &str) -> Node {
+    let mut leaves = vec![];
+    let mut end = 0;
+    for (token, span) in tokenize(input) {
+        if span.start > end {
+            leaves.push(SyntaxToken {
+                token: None,
+                text: input[end..span.start].to_owned(),
+                span: end..span.start,
+            });
+        }
+        if token == Token::Eof {
+            break;
+        }
+        end = span.end;
+        leaves.push(SyntaxToken {
+            token: Some(token),
+            text: input[span.clone()].to_owned(),
+            span,
+        });
+    }
+    let mut builder = Builder { leaves, pos: 0 };
+    let mut children = vec![];
+    while let Some(element) = builder.element() {
+        children.push(element);
+    }
+    Node {
+        kind: NodeKind::Root,
+        children,
+    }
+}
+
+impl Node {
+    /// Byte range of the node in the input; empty for an empty input.
+    pub fn span(&self) -> Range<usize> {
+        let mut tokens = self.tokens();
+        match tokens.next() {
+            Some(first) => first.span.start..tokens.last().unwrap_or(first).span.end,
+            None => 0..0,
+        }
+    }
+
+    /// Every token under the node in input order, trivia included.
+    pub fn tokens(&self) -> impl Iterator<Item = &SyntaxToken> {
+        let mut stack: Vec<&Element> = self.children.iter().rev().collect();
+        std::iter::from_fn(move || loop {
+            match stack.pop()? {
+                Element::Token(token) => return Some(token),
+                Element::Node(node) => stack.extend(node.children.iter().rev()),
+            }
+        })
+    }
+
+    /// The query the node stands for, parsed from its text, so it's the same
+    /// as parsing the input directly. Error positions are relative to the node.
+    pub fn to_query(&self) -> Result<Query, ParserError> {
+        Parser::new_from_string(self.to_string()).parse_query()
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.tokens().try_for_each(|t| f.write_str(&t.text))
+    }
+}
+
+struct Builder {
+    leaves: Vec<SyntaxToken>,
+    pos: usize,
+}
+
+impl Builder {
+    fn peek(&self) -> Option<&Token> {
+        self.leaves.get(self.pos)?.token.as_ref()
+    }
+
+    fn bump(&mut self) -> Element {
+        self.pos += 1;
+        Element::Token(self.leaves[self.pos - 1].clone())
+    }
+
+    fn element(&mut self) -> Option<Element> {
+        let leaf = self.leaves.get(self.pos)?;
+        let name = match &leaf.token {
+            Some(Token::Ident(_)) => true,
+            Some(token) => token.keyword_str().is_some(),
+            None => false,
+        };
+        // 名前の後ろに空白を挟んで ( が来れば呼び出し
+        let lparen = self.leaves[self.pos + 1..]
+            .iter()
+            .find(|l| !l.is_trivia())
+            .is_some_and(|l| l.token == Some(Token::Lparen));
+        if name && lparen {
+            Some(Element::Node(self.call()))
+        } else {
+            Some(self.bump())
+        }
+    }
+
+    fn call(&mut self) -> Node {
+        let mut children = vec![];
+        while self.peek() != Some(&Token::Lparen) {
+            children.push(self.bump());
+        }
+        children.push(self.bump());
+        loop {
+            let mut argument = vec![];
+            while self.pos < self.leaves.len()
+                && !matches!(self.peek(), Some(Token::Comma) | Some(Token::Rparen))
+            {
+                argument.extend(self.element());
+            }
+            if argument
+                .iter()
+                .all(|e| matches!(e, Element::Token(t) if t.is_trivia()))
+            {
+                children.extend(argument);
+            } else {
+                children.push(Element::Node(Node {
+                    kind: NodeKind::Argument,
+                    children: argument,
+                }));
+            }
+            match self.peek() {
+                Some(Token::Comma) => children.push(self.bump()),
+                Some(Token::Rparen) => {
+                    children.push(self.bump());
+                    break;
+                }
+                _ => break,
+            }
+        }
+        Node {
+            kind: NodeKind::Call,
+            children,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cst::{parse_cst, Element, NodeKind};
+    use crate::parser::Parser;
+
+    #[test]
+    fn lossless() {
+        let inputs = [
+            "",
+            "   ",
+            "eq(a,1)",
+            "  and( eq( a ,1 ) ,\n\tsort( +a , -b ) )  ",
+            "or (\r\n  eq(name, \"say \\\"hi\\\"\"),  limit(10, 20)\n)\n",
+            "and(eq(a,1)))",
+            "and(eq(a,1),",
+            "eq(a #, 1)",
+            "aggregate( city , sum( price ) )",
+        ];
+        for input in &inputs {
+            let cst = parse_cst(input);
+            assert_eq!(cst.to_string(), *input);
+            assert_eq!(cst.span(), 0..input.len(), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn to_query() {
+        let inputs = [
+            "eq(a,1)",
+            "  and( eq( a ,1 ) ,\n\tsort( +a , -b ) )  ",
+            "or(eq(name,\"x\"),ge(speed.max , 100.5),exists(tags))",
+            "and(eq(a,1),gt(b 2))",
+        ];
+        for input in &inputs {
+            let direct = Parser::new_from_string(input.to_string()).parse_query();
+            let via_cst = parse_cst(input).to_query();
+            assert_eq!(
+                format!("{:?}", via_cst),
+                format!("{:?}", direct),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn structure() {
+        let cst = parse_cst(" and( eq(a,1) ,sort(+a))");
+        assert_eq!(cst.kind, NodeKind::Root);
+        let call = match &cst.children[..] {
+            [Element::Token(space), Element::Node(call)] => {
+                assert!(space.is_trivia());
+                call
+            }
+            children => panic!("unexpected children {:?}", children),
+        };
+        assert_eq!(call.kind, NodeKind::Call);
+        let arguments: Vec<String> = call
+            .children
+            .iter()
+            .filter_map(|e| match e {
+                Element::Node(n) if n.kind == NodeKind::Argument => Some(n.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(arguments, vec![" eq(a,1) ", "sort(+a)"]);
+        assert_eq!(call.span(), 1..24);
+    }
+}
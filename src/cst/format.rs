@@ -0,0 +1,204 @@
+//! [`format_preserving`], a formatter that leaves the user's layout alone.
+
+use super::parse_cst;
+use crate::ast::Path;
+use crate::lexer::tokenize;
+use crate::parser::{Parser, ParserError};
+use crate::token::Token;
+
+/// What [`format_preserving`] rewrites. Everything is off by default, so the
+/// default rules give back the input unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatRules {
+    /// Rewrites literals spelled differently from how the parser reads them
+    /// into that reading: `007` becomes `7` and `00.50` becomes `0.50`.
+    /// Keywords have a single spelling, so they're never touched.
+    pub normalize_aliases: bool,
+    /// Writes strings with the fewest escapes (`"caf\u{e9}"` becomes
+    /// `"café"`) and drops the backticks around path keys that don't need
+    /// them (`` `user`.name `` becomes `user.name`).
+    pub normalize_quotes: bool,
+    /// Collapses each run of spaces and tabs between tokens to one space and
+    /// removes it at the end of a line or of the input. Line breaks and the
+    /// indentation at the start of a line are kept.
+    pub collapse_spaces: bool,
+}
+
+/// Formats `input` token by token, applying only `rules`: whitespace and
+/// spellings no rule touches are copied as written.
+/// Fails if `input` isn't a valid query, so it can run as a pre-commit check
+/// on saved queries.
+///
+/// ```
+/// use rql_parser::cst::{format_preserving, FormatRules};
+///
+/// let rules = FormatRules {
+///     collapse_spaces: true,
+///     ..FormatRules::default()
+/// };
+/// let formatted = format_preserving("and(eq(a,  1),\n    gt(b,2)  )", rules).unwrap();
+/// assert_eq!(formatted, "and(eq(a, 1),\n    gt(b,2) )");
+/// ```
+pub fn format_preserving(input: &str, rules: FormatRules) -> Result<String, ParserError> {
+    let query = Parser::from_str(input).parse_query()?;
+    let cst = parse_cst(input);
+    let mut tokens = cst.tokens().peekable();
+    let mut out = String::with_capacity(input.len());
+    while let Some(syntax) = tokens.next() {
+        match &syntax.token {
+            None if rules.collapse_spaces => {
+                let start = syntax.span.start == 0;
+                let end = tokens.peek().is_none();
+                out.push_str(&collapse(&syntax.text, start, end));
+            }
+            Some(Token::Int(_)) | Some(Token::Float(_)) if rules.normalize_aliases => {
+                out.push_str(&strip_zeros(&syntax.text));
+            }
+            Some(token @ Token::Str(_)) if rules.normalize_quotes => {
+                out.push_str(&token.to_string());
+            }
+            Some(Token::Ident(raw)) if rules.normalize_quotes => {
+                out.push_str(&unquoted(raw).unwrap_or_else(|| syntax.text.clone()));
+            }
+            _ => out.push_str(&syntax.text),
+        }
+    }
+    debug_assert_eq!(Parser::from_str(&out).parse_query().ok(), Some(query));
+    Ok(out)
+}
+
+// 行の途中の空白は 1 つに、行末と入力末尾の空白は消す。改行と行頭の字下げはそのまま
+fn collapse(space: &str, start: bool, end: bool) -> String {
+    let mut lines = space.split('\n');
+    let mut out = String::new();
+    let first = lines.next().unwrap_or_default();
+    let rest: Vec<&str> = lines.collect();
+    match rest.last() {
+        None if start => out.push_str(first),
+        None if !end => out.push(' '),
+        None => {}
+        Some(indent) => {
+            // 行末の空白は消しても \r\n の \r は残す
+            for line in std::iter::once(&first).chain(&rest[..rest.len() - 1]) {
+                if line.ends_with('\r') {
+                    out.push('\r');
+                }
+                out.push('\n');
+            }
+            if !end {
+                out.push_str(indent);
+            }
+        }
+    }
+    out
+}
+
+fn strip_zeros(number: &str) -> String {
+    let (sign, digits) = match number.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", number),
+    };
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() || digits.starts_with('.') {
+        format!("{}0{}", sign, digits)
+    } else {
+        format!("{}{}", sign, digits)
+    }
+}
+
+// 引用符を外しても同じパスの識別子として読まれるときだけ外す。
+// `true` や `eq` はキーワードに、chrono 有効時の `now` は日時になってしまう
+fn unquoted(raw: &str) -> Option<String> {
+    let path = Path::from(raw);
+    let text = path.to_string();
+    if text == raw || matches!(text.as_str(), "now" | "date") {
+        return None;
+    }
+    match &tokenize(&text)[..] {
+        [(Token::Ident(ident), _), (Token::Eof, _)]
+            if Path::from(ident.as_str()).segments() == path.segments() =>
+        {
+            Some(text)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cst::{format_preserving, FormatRules};
+
+    fn format(input: &str, rules: FormatRules) -> String {
+        format_preserving(input, rules).unwrap()
+    }
+
+    #[test]
+    fn untouched() {
+        let inputs = [
+            "eq(a,1)",
+            "  and( eq( a ,1 ) ,\n\tsort( +a , -b ) )  ",
+            "and (\r\n  eq(name, \"say \\\"hi\\\"\"),  limit(010, 20)\n)\n",
+            "eq(`user`.name,\"caf\\u{e9}\")",
+            "ge(n,  007.50)",
+        ];
+        for input in &inputs {
+            assert_eq!(format(input, FormatRules::default()), *input);
+        }
+    }
+
+    #[test]
+    fn aliases() {
+        let rules = FormatRules {
+            normalize_aliases: true,
+            ..FormatRules::default()
+        };
+        assert_eq!(
+            format("and( eq(n,007) ,\n  gt(x, 00.50),lt(y,0))", rules),
+            "and( eq(n,7) ,\n  gt(x, 0.50),lt(y,0))"
+        );
+        assert_eq!(format("limit(000, 010)", rules), "limit(0, 10)");
+        assert_eq!(format("and(gt(a,-007),lt(b,-00.5))", rules), "and(gt(a,-7),lt(b,-0.5))");
+        // 文字列やパスの中の 0 はそのまま
+        assert_eq!(format("eq(items.007,\"007\")", rules), "eq(items.007,\"007\")");
+    }
+
+    #[test]
+    fn quotes() {
+        let rules = FormatRules {
+            normalize_quotes: true,
+            ..FormatRules::default()
+        };
+        assert_eq!(
+            format("eq( `user`.`name` , \"caf\\u{e9}\\/x\" )", rules),
+            "eq( user.name , \"café\\\\/x\" )"
+        );
+        // 必要な引用符は残す
+        assert_eq!(
+            format("and(eq(`a b`,1),eq(`true`,1),eq(`0`.x,1),exists(`now`))", rules),
+            "and(eq(`a b`,1),eq(`true`,1),eq(`0`.x,1),exists(`now`))"
+        );
+        assert_eq!(format("eq(a,007)", rules), "eq(a,007)");
+    }
+
+    #[test]
+    fn spaces() {
+        let rules = FormatRules {
+            collapse_spaces: true,
+            ..FormatRules::default()
+        };
+        assert_eq!(
+            format("  and( eq(a,\t 1)  ,sort(+a)) \t", rules),
+            "  and( eq(a, 1) ,sort(+a))"
+        );
+        assert_eq!(
+            format("and(  \r\n    eq(a,1),   \n\n    eq(b,  \"x  y\")\n)\n", rules),
+            "and(\r\n    eq(a,1),\n\n    eq(b, \"x  y\")\n)\n"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(format_preserving("and(eq(a,1)", FormatRules::default()).is_err());
+        assert!(format_preserving("eq(a 1)", FormatRules::default()).is_err());
+    }
+}
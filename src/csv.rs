@@ -0,0 +1,193 @@
+//! Evaluation of queries against CSV records.
+//!
+//! Cells are plain strings, so each cell is coerced according to the literal it's
+//! compared with: `gt(score,80)` parses the `score` cell as a number and
+//! `eq(active,true)` as a boolean. A cell that can't be coerced doesn't match,
+//! and a column missing from the header (or a short record) is a missing field.
+
+use crate::ast::{Path, Query, Segment, Value};
+use crate::eval::{compare, search_texts};
+use ::csv::StringRecord;
+use std::borrow::Cow;
+
+impl Query {
+    /// Returns true when the record satisfies the query.
+    /// `headers` names the columns of `record`.
+    pub fn matches_csv(&self, headers: &StringRecord, record: &StringRecord) -> bool {
+        match self {
+            Query::And(queries) => queries.iter().all(|q| q.matches_csv(headers, record)),
+            Query::Or(queries) => queries.iter().any(|q| q.matches_csv(headers, record)),
+            Query::Filter(infix, Value::Identifier(column), literal) => {
+                let column = column_name(column);
+                match headers.iter().position(|h| h == column) {
+                    Some(i) => match record.get(i).and_then(|cell| coerce(cell, literal)) {
+                        Some(v) => compare(infix, literal, &v),
+                        None => false,
+                    },
+                    None => false,
+                }
+            }
+            Query::Filter(..) => false,
+            Query::Not(query) => !query.matches_csv(headers, record),
+            Query::In(..) | Query::Out(..) => self.expand_in().matches_csv(headers, record),
+            // セルは配列にならないので、文字列の部分一致だけ
+            Query::Contains(column, Value::StringLiteral(word)) => {
+                let column = column_name(column);
+                headers
+                    .iter()
+                    .position(|h| h == column)
+                    .and_then(|i| record.get(i))
+                    .is_some_and(|cell| cell.contains(word.as_str()))
+            }
+            Query::Contains(..) => false,
+            // 空のセルは無いものとして扱う
+            Query::Exists(column) => present(headers, record, column),
+            Query::Missing(column) => !present(headers, record, column),
+            Query::Search(columns, term) if columns.is_empty() => search_texts(record.iter(), term),
+            // セルは文字列なので座標にならない
+            Query::Near(..) => false,
+            Query::Search(columns, term) => {
+                let cells = columns.iter().filter_map(|column| {
+                    let column = column_name(column);
+                    let i = headers.iter().position(|h| h == column)?;
+                    record.get(i)
+                });
+                search_texts(cells, term)
+            }
+            Query::Sort(..)
+            | Query::Limit(..)
+            | Query::Select(..)
+            | Query::Distinct(..)
+            | Query::Aggregate(..)
+            | Query::First
+            | Query::One
+            | Query::Count
+            | Query::Reduce(..)
+            | Query::None => true,
+        }
+    }
+}
+
+// 列名そのものを引用したパス (`first name`) は列名として扱う
+fn column_name(path: &Path) -> Cow<'_, str> {
+    match path.segments() {
+        [Segment::Key(key)] => Cow::Borrowed(key),
+        _ => Cow::Owned(path.to_string()),
+    }
+}
+
+fn present(headers: &StringRecord, record: &StringRecord, column: &Path) -> bool {
+    let column = column_name(column);
+    headers
+        .iter()
+        .position(|h| h == column)
+        .and_then(|i| record.get(i))
+        .is_some_and(|cell| !cell.trim().is_empty())
+}
+
+/// Converts a cell into the JSON type the literal is compared against.
+pub fn coerce(cell: &str, literal: &Value) -> Option<serde_json::Value> {
+    let cell = cell.trim();
+    match literal {
+        Value::IntegerLiteral(_) | Value::UnsignedLiteral(_) => match cell.parse::<i64>() {
+            Ok(i) => Some(i.into()),
+            Err(_) => cell.parse::<u64>().ok().map(|u| u.into()),
+        },
+        // NaN や inf のセルは JSON にできないので一致させない
+        Value::FloatLiteral(_) => cell
+            .parse::<f64>()
+            .ok()
+            .filter(|f| f.is_finite())
+            .map(|f| f.into()),
+        #[cfg(feature = "decimal")]
+        // float と同じく読み、比較の際に Decimal にする
+        Value::DecimalLiteral(_) => coerce(cell, &Value::FloatLiteral(0.0)),
+        Value::Boolean(_) => match cell {
+            "true" | "TRUE" | "True" => Some(true.into()),
+            "false" | "FALSE" | "False" => Some(false.into()),
+            _ => None,
+        },
+        Value::StringLiteral(_) | Value::Identifier(_) => Some(cell.into()),
+        #[cfg(feature = "chrono")]
+        Value::DateTime(_) | Value::RelativeTime(..) => Some(cell.into()),
+        Value::Call(_) | Value::Variable(_) => None,
+    }
+}
+
+/// Converts a cell into the most specific JSON scalar: integer, float, boolean or string.
+pub fn infer(cell: &str) -> serde_json::Value {
+    if let Ok(i) = cell.parse::<i64>() {
+        i.into()
+    } else if let Some(f) = cell.parse::<f64>().ok().filter(|f| f.is_finite()) {
+        f.into()
+    } else {
+        match cell {
+            "true" => true.into(),
+            "false" => false.into(),
+            _ => cell.into(),
+        }
+    }
+}
+
+/// Header used for CSV files without a header row: `$1`, `$2`, ...
+pub fn positional_headers(len: usize) -> StringRecord {
+    (1..=len).map(|i| format!("${}", i)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csv::positional_headers;
+    use crate::parser::Parser;
+    use csv::StringRecord;
+
+    fn matches(input: &str, headers: &[&str], record: &[&str]) -> bool {
+        let query = Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap();
+        query.matches_csv(&StringRecord::from(headers), &StringRecord::from(record))
+    }
+
+    #[test]
+    fn coercion() {
+        let headers = ["name", "score", "ratio", "active", "country"];
+        let row = ["alice", "85", "0.5", "true", "JP"];
+        assert!(matches("and(gt(score,80),eq(country,\"JP\"))", &headers, &row));
+        assert!(matches("gt(score,80.5)", &headers, &row));
+        assert!(matches("lt(ratio,1.0)", &headers, &row));
+        assert!(matches("eq(active,true)", &headers, &row));
+        assert!(!matches("eq(score,\"86\")", &headers, &row));
+        assert!(!matches("gt(name,1)", &headers, &row));
+        assert!(!matches("ne(ratio,1.0)", &headers, &["alice", "85", "NaN"]));
+        assert!(!matches("gt(ratio,1.0)", &headers, &["alice", "85", "inf"]));
+    }
+
+    #[test]
+    fn missing_columns() {
+        let headers = ["name", "score"];
+        assert!(!matches("eq(age,1)", &headers, &["alice", "1"]));
+        assert!(!matches("eq(score,1)", &headers, &["alice"]));
+        assert!(matches("ne(score,1)", &headers, &["alice", "2"]));
+        assert!(matches("and(exists(score),missing(age))", &headers, &["alice", "2"]));
+        assert!(matches("missing(score)", &headers, &["alice", " "]));
+    }
+
+    #[test]
+    fn membership() {
+        let headers = ["name", "score"];
+        let row = ["alice", "85"];
+        assert!(matches("and(in(score,(80,85)),out(name,(\"bob\")))", &headers, &row));
+        assert!(matches("and(contains(name,\"lic\"),not(contains(score,8)))", &headers, &row));
+        assert!(!matches("out(score,(85))", &headers, &row));
+        assert!(!matches("out(age,(1))", &headers, &row));
+    }
+
+    #[test]
+    fn positional() {
+        let headers = positional_headers(2);
+        assert_eq!(headers, StringRecord::from(vec!["$1", "$2"]));
+        let query = Parser::new_from_string("eq($2,10)".to_owned())
+            .parse_query()
+            .unwrap();
+        assert!(query.matches_csv(&headers, &StringRecord::from(vec!["a", "10"])));
+    }
+}
@@ -0,0 +1,254 @@
+//! Date/time literals, with the `chrono` feature: `date:2024-01-01T00:00:00Z`
+//! or `date:2024-01-01` in value position, and times relative to the moment
+//! of evaluation such as `now-7d`.
+//!
+//! A date literal compares chronologically with document values that are
+//! RFC 3339 strings, date-only strings (`2024-01-01`, midnight UTC) or numbers
+//! of seconds since the Unix epoch. Timestamps with different offsets compare
+//! as the instants they denote, so `2024-01-01T09:00:00+09:00` equals
+//! `date:2024-01-01`. Any other value doesn't match.
+
+use crate::ast::Value;
+use crate::eval::DocValue;
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Unit of a relative time: `now-30s`, `now-5m`, `now+1h`, `now-7d`, `now-2w`.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum TimeUnit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+}
+
+impl TimeUnit {
+    pub fn suffix(self) -> char {
+        match self {
+            TimeUnit::Second => 's',
+            TimeUnit::Minute => 'm',
+            TimeUnit::Hour => 'h',
+            TimeUnit::Day => 'd',
+            TimeUnit::Week => 'w',
+        }
+    }
+
+    fn seconds(self) -> i64 {
+        match self {
+            TimeUnit::Second => 1,
+            TimeUnit::Minute => 60,
+            TimeUnit::Hour => 60 * 60,
+            TimeUnit::Day => 24 * 60 * 60,
+            TimeUnit::Week => 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Parses an RFC 3339 timestamp, or a `YYYY-MM-DD` date as midnight UTC.
+pub fn parse(s: &str) -> Option<DateTime<FixedOffset>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+        return Some(datetime);
+    }
+    let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().fixed_offset())
+}
+
+/// RFC 3339 with `Z` for UTC and only as many fractional digits as needed.
+pub fn format(datetime: &DateTime<FixedOffset>) -> String {
+    datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Parses the offset after `now`: a sign, a number and a unit (`-7d`).
+pub fn parse_offset(s: &str) -> Option<(i64, TimeUnit)> {
+    let sign = match s.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let unit = match s.chars().last()? {
+        's' => TimeUnit::Second,
+        'm' => TimeUnit::Minute,
+        'h' => TimeUnit::Hour,
+        'd' => TimeUnit::Day,
+        'w' => TimeUnit::Week,
+        _ => return None,
+    };
+    let digits = &s[1..s.len() - 1];
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: i64 = digits.parse().ok()?;
+    // 評価時に溢れないよう、TimeDelta に収まるものだけ受け付ける
+    TimeDelta::try_seconds(amount.checked_mul(unit.seconds())?)?;
+    Some((sign * amount, unit))
+}
+
+/// The current time from the system clock, in UTC.
+pub fn now() -> DateTime<FixedOffset> {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let now = DateTime::from_timestamp(elapsed.as_secs() as i64, elapsed.subsec_nanos());
+    now.unwrap_or_default().fixed_offset()
+}
+
+/// The instant a date literal or relative time stands for, with `now` taken
+/// from the system clock when not given. `None` for other values and for
+/// relative times out of chrono's range.
+pub fn instant(value: &Value, now: Option<DateTime<FixedOffset>>) -> Option<DateTime<FixedOffset>> {
+    match value {
+        Value::DateTime(datetime) => Some(*datetime),
+        Value::RelativeTime(amount, unit) => {
+            let delta = TimeDelta::try_seconds(amount.checked_mul(unit.seconds())?)?;
+            now.unwrap_or_else(self::now).checked_add_signed(delta)
+        }
+        _ => None,
+    }
+}
+
+// 評価の間は同じ時刻を使うよう、相対時刻を固定した日時に置き換える
+pub(crate) fn pin(value: &Value, now: Option<DateTime<FixedOffset>>) -> Cow<'_, Value> {
+    match (value, now) {
+        (Value::RelativeTime(..), Some(_)) => match instant(value, now) {
+            Some(datetime) => Cow::Owned(Value::DateTime(datetime)),
+            None => Cow::Borrowed(value),
+        },
+        _ => Cow::Borrowed(value),
+    }
+}
+
+pub(crate) fn from_doc(value: &DocValue) -> Option<DateTime<FixedOffset>> {
+    let datetime = match value {
+        DocValue::String(s) => return parse(s),
+        DocValue::Integer(secs) => DateTime::from_timestamp(*secs, 0),
+        DocValue::Unsigned(_) | DocValue::Float(_) => {
+            let secs = value.as_f64()?;
+            let nanos = ((secs - secs.floor()) * 1e9) as u32;
+            DateTime::from_timestamp(secs.floor() as i64, nanos)
+        }
+        _ => None,
+    };
+    datetime.map(|d| d.fixed_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Query, Value};
+    use crate::convert::sql::Dialect;
+    use crate::datetime::{parse_offset, TimeUnit};
+    use crate::eval::{apply_with, EvalOptions};
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn offsets() {
+        let query = parse("ge(created_at,date:2024-01-01T00:00:00Z)");
+        assert!(query.matches(&json!({"created_at": "2024-01-01T00:00:00Z"})));
+        assert!(query.matches(&json!({"created_at": "2024-01-01T08:59:59-00:01"})));
+        // 日本時間の 8:59 は UTC では前日
+        assert!(!query.matches(&json!({"created_at": "2024-01-01T08:59:59+09:00"})));
+        assert!(query.matches(&json!({"created_at": 1704067200})));
+        assert!(!query.matches(&json!({"created_at": 1704067199.5})));
+        assert!(!query.matches(&json!({"created_at": "yesterday"})));
+        assert!(parse("eq(at,date:2024-01-01T09:00:00+09:00)")
+            .matches(&json!({"at": "2024-01-01T00:00:00Z"})));
+    }
+
+    #[test]
+    fn date_only() {
+        let query = parse("and(ge(at,date:2024-01-01),lt(at,date:2024-01-02))");
+        assert!(query.matches(&json!({"at": "2024-01-01T23:59:59.999Z"})));
+        assert!(query.matches(&json!({"at": "2024-01-01"})));
+        assert!(!query.matches(&json!({"at": "2024-01-02T00:00:00Z"})));
+        assert!(parse("eq(at,date:2024-01-01)").matches(&json!({"at": "2024-01-01T00:00:00Z"})));
+    }
+
+    #[test]
+    fn render() {
+        let query = parse("and(ge(at,date:2024-01-01),lt(at,date:2024-06-01T12:30:00.5+09:00))");
+        assert_eq!(
+            query.to_rql(),
+            "and(ge(at,date:2024-01-01T00:00:00Z),lt(at,date:2024-06-01T12:30:00.500+09:00))"
+        );
+        assert_eq!(parse(&query.to_rql()), query);
+        assert_eq!(
+            query.to_sql(Dialect::Postgres).unwrap(),
+            r#""at" >= '2024-01-01T00:00:00Z' AND "at" < '2024-06-01T12:30:00.500+09:00'"#
+        );
+        assert_eq!(
+            parse("ge(at,date:2024-01-01)").to_mongo().unwrap(),
+            json!({"at": {"$gte": {"$date": "2024-01-01T00:00:00Z"}}})
+        );
+        assert_eq!(
+            Parser::new_from_string("eq(at,date:2024-13-01)".to_owned())
+                .parse_query()
+                .unwrap_err()
+                .to_string(),
+            "invalid date 2024-13-01 at position 6"
+        );
+    }
+
+    #[test]
+    fn relative() {
+        let options = EvalOptions {
+            now: super::parse("2024-03-10T12:00:00Z"),
+            ..EvalOptions::default()
+        };
+        let query = parse("ge(at,now-7d)");
+        assert_eq!(query.to_rql(), "ge(at,now-7d)");
+        assert!(query.matches_with(&json!({"at": "2024-03-03T12:00:00Z"}), &options));
+        assert!(!query.matches_with(&json!({"at": "2024-03-03T11:59:59Z"}), &options));
+        let docs = vec![
+            json!({"id": 1, "at": "2024-03-10T11:00:00Z"}),
+            json!({"id": 2, "at": "2024-03-10T12:30:00Z"}),
+        ];
+        let page = apply_with(&parse("lt(at,now)"), docs.clone(), &options).unwrap();
+        assert_eq!(page.documents, vec![docs[0].clone()]);
+        let page = apply_with(&parse("gt(at,now+15m)"), docs.clone(), &options).unwrap();
+        assert_eq!(page.documents, vec![docs[1].clone()]);
+
+        assert_eq!(parse_offset("+2w"), Some((2, TimeUnit::Week)));
+        assert_eq!(parse_offset("-30s"), Some((-30, TimeUnit::Second)));
+        assert_eq!(parse_offset("-7"), None);
+        assert_eq!(parse_offset("-d"), None);
+        assert_eq!(parse_offset("-99999999999999999w"), None);
+        assert_eq!(
+            Parser::new_from_string("ge(at,now-7y)".to_owned())
+                .parse_query()
+                .unwrap_err()
+                .to_string(),
+            "invalid date now-7y at position 6"
+        );
+        // now() は従来どおり EvalContext の関数
+        assert_eq!(
+            parse("ge(at,now())"),
+            Query::Filter(
+                crate::ast::Infix::Ge,
+                Value::Identifier("at".into()),
+                Value::Call("now".to_owned())
+            )
+        );
+    }
+
+    #[test]
+    fn relative_sql() {
+        let before = super::now();
+        let sql = parse("ge(at,now-1h)").to_sql(Dialect::Postgres).unwrap();
+        let after = super::now();
+        let rendered = sql
+            .trim_start_matches(r#""at" >= '"#)
+            .trim_end_matches('\'');
+        let at = super::parse(rendered).unwrap();
+        let hour = chrono::TimeDelta::try_hours(1).unwrap();
+        assert!(before - hour <= at && at <= after - hour, "{}", sql);
+    }
+}
@@ -0,0 +1,97 @@
+//! Exact decimal literals, with the `decimal` feature: `eq(price,19.99)` is
+//! compared in decimal arithmetic instead of `f64`.
+//!
+//! Literals with a decimal point parse into [`Value::DecimalLiteral`], keeping
+//! their digits as written (`19.990` stays `19.990`). Literals with more than
+//! the 28 fractional digits a `Decimal` holds fall back to a float.
+//!
+//! JSON numbers are read as decimals from their shortest representation, so a
+//! document's `0.3` equals the literal `0.3` while `0.1 + 0.2` computed
+//! elsewhere (`0.30000000000000004`) doesn't. Numbers a `Decimal` can't hold,
+//! such as `1e300`, are compared as floats.
+//!
+//! [`Value::DecimalLiteral`]: crate::ast::Value::DecimalLiteral
+
+use crate::eval::DocValue;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Parses a literal without rounding away any of its digits.
+pub fn parse(s: &str) -> Option<Decimal> {
+    Decimal::from_str_exact(s).ok()
+}
+
+pub(crate) fn from_doc(value: &DocValue) -> Option<Decimal> {
+    let f = match value {
+        DocValue::Integer(i) => return Some((*i).into()),
+        DocValue::Unsigned(u) => return Some((*u).into()),
+        DocValue::Float(f) => *f,
+        _ => return None,
+    };
+    // serde_json は f64 を往復できる最短の桁で出力する
+    let s = serde_json::Number::from_f64(f)?.to_string();
+    if s.contains(['e', 'E']) {
+        Decimal::from_scientific(&s).ok()
+    } else {
+        Decimal::from_str(&s).ok()
+    }
+}
+
+/// How the literal orders against a document's number. `None` when `value`
+/// isn't one.
+pub(crate) fn compare(literal: &Decimal, value: &DocValue) -> Option<Ordering> {
+    match from_doc(value) {
+        Some(v) => Some(literal.cmp(&v)),
+        None => literal.to_f64()?.partial_cmp(&value.as_f64()?),
+    }
+}
+
+pub(crate) fn to_json(literal: &Decimal) -> serde_json::Value {
+    literal
+        .to_f64()
+        .and_then(serde_json::Number::from_f64)
+        .map_or(serde_json::Value::Null, serde_json::Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Query, Value};
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn tenths() {
+        let query = parse("eq(x,0.3)");
+        assert!(query.matches(&json!({"x": 0.3})));
+        assert!(!query.matches(&json!({"x": 0.1 + 0.2})));
+        assert!(parse("gt(x,0.3)").matches(&json!({"x": 0.1 + 0.2})));
+        assert!(parse("eq(price,19.99)").matches(&json!({"price": 19.99})));
+        assert!(parse("eq(price,20.00)").matches(&json!({"price": 20})));
+        assert!(parse("gt(x,0.5)").matches(&json!({"x": 1e300})));
+        assert!(!parse("lt(x,0.5)").matches(&json!({"x": "1"})));
+    }
+
+    #[test]
+    fn precision() {
+        let query = parse("eq(x,0.1234567890123456789012345678)");
+        assert_eq!(query.to_rql(), "eq(x,0.1234567890123456789012345678)");
+        assert_eq!(parse("ge(price,19.990)").to_rql(), "ge(price,19.990)");
+        // f64 では区別できない値も区別する
+        let query = parse("gt(id,9007199254740992.0)");
+        assert!(query.matches(&json!({"id": 9007199254740993u64})));
+        assert!(!parse("eq(x,0.10000000000000001)").matches(&json!({"x": 0.1})));
+        // Decimal に収まらない桁数は float のまま
+        match parse("eq(x,0.12345678901234567890123456789)") {
+            Query::Filter(_, _, Value::FloatLiteral(_)) => {}
+            query => panic!("{:?}", query),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Compiler-style rendering of parse errors: the message, the offending line of
+//! the query and a caret underline below the token the error points at.
+//!
+//! ```text
+//! error: expected ',', found '2' at position 17
+//!   |
+//! 1 | and(eq(a,1),gt(b 2))
+//!   |                  ^
+//! ```
+
+#[cfg(feature = "miette")]
+mod report;
+
+use crate::lexer::tokenize;
+use crate::parser::ParserError;
+use crate::token::Token;
+use serde::Serialize;
+use std::ops::Range;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A parse error as data, e.g. for the body of a 400 response:
+///
+/// ```json
+/// {"code": "expected_rparen", "message": "expected ')', found '3' at position 7", "position": 7, "token": "3"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorReport {
+    /// [`ParserError::code`]
+    pub code: &'static str,
+    pub message: String,
+    pub position: Option<usize>,
+    /// Text of the offending token or literal, if any.
+    pub token: Option<String>,
+}
+
+impl From<&ParserError> for ErrorReport {
+    fn from(error: &ParserError) -> Self {
+        let token = match error {
+            ParserError::ParseInt(s, _)
+            | ParserError::ParseFloat(s, _)
+            | ParserError::ParseDate(s, _)
+            | ParserError::UnknownFunction(s, _)
+            | ParserError::UnknownOperator(s, _, _)
+            | ParserError::UnquotedIdentifier(s, _)
+            | ParserError::OutOfRange(_, s, _) => Some(s.clone()),
+            ParserError::OperatorNotAllowed(token, _) | ParserError::ShapingInOr(token, _) => {
+                Some(token.to_string())
+            }
+            _ => match error.expected() {
+                Some((_, Token::Eof)) | None => None,
+                Some((_, token)) => Some(token.to_string()),
+            },
+        };
+        ErrorReport {
+            code: error.code(),
+            message: error.to_string(),
+            position: error.position(),
+            token,
+        }
+    }
+}
+
+/// Renders `error` against the `input` it was produced from. With `color`, the
+/// output contains ANSI escape sequences.
+pub fn render(input: &str, error: &ParserError, color: bool) -> String {
+    render_message(input, &error.to_string(), span(input, error), color)
+}
+
+/// Byte range of the token the error points at. `None` if the error has no position.
+pub fn span(input: &str, error: &ParserError) -> Option<Range<usize>> {
+    let pos = error.position()?.min(input.len());
+    let token = tokenize(input)
+        .into_iter()
+        .map(|(_, span)| span)
+        .find(|span| span.start == pos && span.end > span.start);
+    Some(token.unwrap_or(pos..pos))
+}
+
+/// Renders an arbitrary message with the snippet for `span`; this is what
+/// [`render`] uses, exposed for callers that add context to the message.
+pub fn render_message(
+    input: &str,
+    message: &str,
+    span: Option<Range<usize>>,
+    color: bool,
+) -> String {
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_owned()
+        }
+    };
+    let header = format!(
+        "{}{}",
+        paint(RED, "error"),
+        paint(BOLD, &format!(": {}", message))
+    );
+    let span = match span {
+        Some(span) => span,
+        None => return header,
+    };
+    let start = span.start.min(input.len());
+    let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = input[start..].find('\n').map_or(input.len(), |i| start + i);
+    let line = input[line_start..line_end].trim_end_matches('\r');
+    let line_number = input[..line_start].matches('\n').count() + 1;
+    let column = input[line_start..start].chars().count();
+    // 複数行にまたがるトークンは行末までに切り詰める
+    let end = span.end.clamp(start, line_start + line.len());
+    let width = input[start..end].chars().count().max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    let bar = paint(BLUE, &format!("{} |", gutter));
+    format!(
+        "{}\n{}\n{} {}\n{} {}{}",
+        header,
+        bar,
+        paint(BLUE, &format!("{} |", line_number)),
+        line,
+        bar,
+        " ".repeat(column),
+        paint(RED, &"^".repeat(width)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{render, ErrorReport};
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    fn error(input: &str) -> String {
+        let e = Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap_err();
+        render(input, &e, false)
+    }
+
+    #[test]
+    fn snippet() {
+        assert_eq!(
+            error("and(eq(a,1),gt(b 2))"),
+            "error: expected ',', found '2' at position 17\n  |\n1 | and(eq(a,1),gt(b 2))\n  |                  ^"
+        );
+        assert_eq!(
+            error("and(\n  eq(a,1),\n  gt(b \"two\")\n)"),
+            "error: expected ',', found '\"two\"' at position 23\n  |\n3 |   gt(b \"two\")\n  |        ^^^^^"
+        );
+        assert_eq!(
+            error("eq(a,"),
+            "error: expected a value, found end of input at position 5\n  |\n1 | eq(a,\n  |      ^"
+        );
+    }
+
+    #[test]
+    fn color() {
+        let e = Parser::new_from_string("eq(a 1)".to_owned())
+            .parse_query()
+            .unwrap_err();
+        let colored = render("eq(a 1)", &e, true);
+        assert!(colored.starts_with("\x1b[1;31merror\x1b[0m"));
+        assert!(colored.ends_with("\x1b[1;31m^\x1b[0m"));
+    }
+
+    #[test]
+    fn report() {
+        let report = |input: &str| {
+            let e = Parser::new_from_string(input.to_owned())
+                .parse_query()
+                .unwrap_err();
+            serde_json::to_value(ErrorReport::from(&e)).unwrap()
+        };
+        assert_eq!(
+            report("eq(a,1 3)"),
+            json!({
+                "code": "expected_rparen",
+                "message": "expected ')', found '3' at position 7",
+                "position": 7,
+                "token": "3",
+            })
+        );
+        assert_eq!(
+            report("eq(a,"),
+            json!({
+                "code": "expected_value",
+                "message": "expected a value, found end of input at position 5",
+                "position": 5,
+                "token": null,
+            })
+        );
+        assert_eq!(report("frobnicate(a,1)")["token"], "frobnicate");
+    }
+}
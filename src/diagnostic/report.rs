@@ -0,0 +1,144 @@
+//! [`miette`] integration, with the `miette` feature: [`ParserError`] is a
+//! [`miette::Diagnostic`] with an error code, a label under the offending
+//! token and, where there is something to suggest, help text.
+
+use crate::parser::ParserError;
+use crate::parser::ParserError::*;
+use crate::token::Token;
+use miette::{Diagnostic, LabeledSpan, Report};
+use std::fmt::Display;
+
+impl ParserError {
+    /// Wraps the error in a [`miette::Report`] holding `input`, the query it
+    /// was produced from, so that handlers can show the labeled snippet.
+    pub fn into_report(self, input: impl Into<String>) -> Report {
+        Report::new(self).with_source_code(input.into())
+    }
+}
+
+impl Diagnostic for ParserError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(format!("rql::{}", self.code())))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        let help = match self {
+            ExpectedQueryToken(..) | ExpectedFilterToken(..) => {
+                "a query is a call such as eq(name,\"a\"), and(...), or(...) or sort(+name)"
+                    .to_owned()
+            }
+            ExpectedLparen(..) => "arguments go in parentheses, e.g. eq(name,\"a\")".to_owned(),
+            ExpectedRparen(..) => "close the call with ')'".to_owned(),
+            ExpectedComma(..) => "separate arguments with ','".to_owned(),
+            ExpectedStringToken(..) => {
+                "startswith() and endswith() take a quoted string".to_owned()
+            }
+            MaxDepthExceeded(..) => "flatten nested and()/or() groups".to_owned(),
+            ShapingInOr(..) => {
+                "it applies to the whole result; move it out of or()/not() into the top-level and()"
+                    .to_owned()
+            }
+            OutOfRange(..) => {
+                "near() takes a latitude within ±90, a longitude within ±180 and a radius of 0 or more"
+                    .to_owned()
+            }
+            UnknownOperator(_, Some(suggestion), _) => format!("did you mean '{}'?", suggestion),
+            UnquotedIdentifier(s, _) => {
+                format!("quote the value to compare with a string: \"{}\"", s)
+            }
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let position = self.position()?;
+        let (label, len) = match self {
+            ParseInt(s, _) | ParseFloat(s, _) | ParseDate(s, _) => {
+                ("invalid literal".to_owned(), s.len())
+            }
+            OutOfRange(_, s, _) => ("out of range".to_owned(), s.len()),
+            MaxDepthExceeded(..) => ("too deep".to_owned(), 0),
+            UnknownFunction(s, _) => ("unknown function".to_owned(), s.len()),
+            UnknownOperator(s, _, _) => ("unknown operator".to_owned(), s.len()),
+            UnquotedIdentifier(s, _) => ("unquoted".to_owned(), s.len()),
+            OperatorNotAllowed(token, _) => ("not allowed".to_owned(), token_len(token)),
+            ShapingInOr(token, _) => ("inside or()/not()".to_owned(), token_len(token)),
+            _ => match self.expected() {
+                Some((expected, token)) => (format!("expected {}", expected), token_len(token)),
+                None => return None,
+            },
+        };
+        Some(Box::new(std::iter::once(
+            LabeledSpan::new_primary_with_span(Some(label), (position, len)),
+        )))
+    }
+}
+
+// 入力中のトークンの長さ。終端と不正な文字は幅 0 の位置だけを指す
+fn token_len(token: &Token) -> usize {
+    match token {
+        Token::Eof | Token::Illegal => 0,
+        _ => token.to_string().len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use miette::{Diagnostic, NarratableReportHandler};
+
+    fn render(input: &str) -> String {
+        let report = Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap_err()
+            .into_report(input);
+        let mut out = String::new();
+        NarratableReportHandler::new()
+            .render_report(&mut out, report.as_ref())
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn snapshots() {
+        assert_eq!(
+            render("and(eq(a,1),gt(b,2 3))"),
+            "expected ')', found '3' at position 19
+    Diagnostic severity: error
+Begin snippet starting at line 1, column 1
+
+snippet line 1: and(eq(a,1),gt(b,2 3))
+    label at line 1, column 20: expected ')'
+diagnostic help: close the call with ')'
+diagnostic code: rql::expected_rparen
+"
+        );
+        assert_eq!(
+            render("or(eq(a,1),eqq(name,\"x\"))"),
+            "unknown operator 'eqq', did you mean 'eq'?
+    Diagnostic severity: error
+Begin snippet starting at line 1, column 1
+
+snippet line 1: or(eq(a,1),eqq(name,\"x\"))
+    label at line 1, columns 12 to 14: unknown operator
+diagnostic help: did you mean 'eq'?
+diagnostic code: rql::unknown_operator
+"
+        );
+    }
+
+    #[test]
+    fn without_source() {
+        let err = Parser::new_from_string("eq(a 1)".to_owned())
+            .parse_query()
+            .unwrap_err();
+        assert_eq!(
+            Diagnostic::code(&err).unwrap().to_string(),
+            "rql::expected_comma"
+        );
+        let label = err.labels().unwrap().next().unwrap();
+        assert_eq!((label.offset(), label.len()), (5, 1));
+        assert_eq!(label.label(), Some("expected ','"));
+    }
+}
@@ -0,0 +1,1366 @@
+use crate::ast::{Aggregation, Infix, Path, Prefix, Query, Segment, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+
+mod aggregate;
+mod comparator;
+mod compile;
+mod context;
+mod document;
+mod explain;
+mod filterable;
+mod schema;
+
+pub use comparator::{Comparator, CustomComparator};
+pub use compile::CompiledQuery;
+pub use context::EvalContext;
+pub use document::{DocValue, Document};
+pub use explain::{Explain, FailedFilter};
+pub use filterable::Filterable;
+pub use schema::{FieldType, Schema};
+
+impl Query {
+    /// Returns true when the JSON document satisfies the query.
+    /// `Sort`, `Limit`, `Select`, `Distinct`, `Aggregate`, `First`, `One`,
+    /// `Count`, `Reduce` and `None` only shape the result set (see [`apply`] and
+    /// [`evaluate`]), so they always match. The shaping ones take effect at the
+    /// top level or in `and()`; in an `or()` built by hand (the parser rejects
+    /// them there) they're ignored, neither matching nor shaping anything.
+    ///
+    /// How a filter compares the field's value with its literal:
+    ///
+    /// | field value                  | `eq()`   | `ne()`       | `lt()`, `ge()`... |
+    /// |------------------------------|----------|--------------|-------------------|
+    /// | same type as the literal     | equal    | not equal    | ordered           |
+    /// | other scalar type, or `null` | no match | no match     | no match          |
+    /// | array                        | no match | always match | no match          |
+    /// | object                       | no match | always match | no match          |
+    /// | missing                      | no match | no match     | no match          |
+    ///
+    /// So `ne(tags,"x")` matches `{"tags": ["x","y"]}`: an array isn't a
+    /// string. To look inside it, use a wildcard (`eq(tags.*,"x")`), which
+    /// compares each element, or `contains(tags,"x")`. There are no array or
+    /// object literals to compare arrays and objects with as a whole.
+    ///
+    /// `in(a,(1,2))` is `or(eq(a,1),eq(a,2))` and `out(a,(1,2))` is
+    /// `and(ne(a,1),ne(a,2))`, so neither matches a missing field.
+    /// `contains()` matches an array with an element equal to the value, or a
+    /// string with the value as a substring.
+    pub fn matches(&self, doc: &serde_json::Value) -> bool {
+        self.matches_with(doc, &EvalOptions::default())
+    }
+
+    /// Same as [`Query::matches`] with explicit options. A value the
+    /// [`Schema`] can't coerce doesn't match, even with `strict_types`; use
+    /// [`Query::try_matches_with`] to get the error.
+    pub fn matches_with(&self, doc: &serde_json::Value, options: &EvalOptions) -> bool {
+        self.try_matches_with(doc, options).unwrap_or(false)
+    }
+
+    /// Same as [`Query::matches_with`], failing with [`EvalError::Uncoercible`]
+    /// when `strict_types` is set and a value doesn't fit its schema type.
+    pub fn try_matches_with(
+        &self,
+        doc: &serde_json::Value,
+        options: &EvalOptions,
+    ) -> Result<bool, EvalError> {
+        self.try_matches_document_with(doc, options)
+    }
+
+    /// Same as [`Query::matches`] for any [`Document`], such as a
+    /// `toml::Value` with the `toml` feature or a record type of your own.
+    pub fn matches_document(&self, doc: &dyn Document) -> bool {
+        self.matches_document_with(doc, &EvalOptions::default())
+    }
+
+    /// Same as [`Query::matches_with`] for any [`Document`].
+    pub fn matches_document_with(&self, doc: &dyn Document, options: &EvalOptions) -> bool {
+        self.try_matches_document_with(doc, options)
+            .unwrap_or(false)
+    }
+
+    /// Same as [`Query::matches`] for a `bson::Document`, such as one
+    /// fetched with the mongodb driver, read without converting it to JSON.
+    #[cfg(feature = "bson")]
+    pub fn matches_bson(&self, doc: &bson::Document) -> bool {
+        self.matches_document(doc)
+    }
+
+    /// Same as [`Query::matches_with`] for a `bson::Document`.
+    #[cfg(feature = "bson")]
+    pub fn matches_bson_with(&self, doc: &bson::Document, options: &EvalOptions) -> bool {
+        self.matches_document_with(doc, options)
+    }
+
+    /// Same as [`Query::try_matches_with`] for any [`Document`].
+    pub fn try_matches_document_with(
+        &self,
+        doc: &dyn Document,
+        options: &EvalOptions,
+    ) -> Result<bool, EvalError> {
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::matches(self);
+        let matched = self.eval_document(doc, options);
+        #[cfg(feature = "tracing")]
+        crate::trace::matched(&span, &matched);
+        matched
+    }
+
+    fn eval_document(&self, doc: &dyn Document, options: &EvalOptions) -> Result<bool, EvalError> {
+        Ok(match self {
+            Query::And(queries) => {
+                for q in queries {
+                    if !q.eval_document(doc, options)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            Query::Or(queries) => {
+                for q in or_operands(queries) {
+                    if q.eval_document(doc, options)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            Query::Not(query) => !query.eval_document(doc, options)?,
+            Query::Filter(infix, Value::Identifier(path), literal) => {
+                filter(doc, options, infix, path, literal)?
+            }
+            Query::Filter(..) => false,
+            Query::In(path, values) => {
+                for value in values {
+                    if filter(doc, options, &Infix::Eq, path, value)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            Query::Out(path, values) => {
+                for value in values {
+                    if !filter(doc, options, &Infix::NotEq, path, value)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            Query::Contains(path, value) => contains(doc, path, value),
+            Query::Exists(path) => present(doc, path, options),
+            Query::Missing(path) => !present(doc, path, options),
+            Query::Search(fields, term) => search(doc, fields, term),
+            Query::Near(path, lat, lon, radius) => near(doc, path, (*lat, *lon), *radius),
+            Query::Sort(..)
+            | Query::Limit(..)
+            | Query::Select(..)
+            | Query::Distinct(..)
+            | Query::Aggregate(..)
+            | Query::First
+            | Query::One
+            | Query::Count
+            | Query::Reduce(..)
+            | Query::None => true,
+        })
+    }
+}
+
+// フィルタの判定。フィールドの値をスキーマで変換し、比較器があればそれで比べる
+fn filter(
+    doc: &dyn Document,
+    options: &EvalOptions,
+    infix: &Infix,
+    path: &Path,
+    literal: &Value,
+) -> Result<bool, EvalError> {
+    #[cfg(feature = "chrono")]
+    let pinned = crate::datetime::pin(literal, options.now);
+    #[cfg(feature = "chrono")]
+    let literal = pinned.as_ref();
+    let literal = operand(doc, literal);
+    #[cfg(feature = "unicode-normalization")]
+    let literal = match literal {
+        Some(l) if options.normalize_strings => Some(crate::normalize::value(l)),
+        l => l,
+    };
+    // ワイルドカードで複数の値があれば、どれかが一致すればよい
+    let (reached, value) = match (path.has_wildcard(), doc.get_path(path)) {
+        (true, Some(DocValue::Array(values))) => (values, None),
+        (_, value) => (vec![], value),
+    };
+    for v in reached.into_iter().chain(value) {
+        let coerced;
+        let v = match &options.schema {
+            Some(schema) => match schema.coerce(path, &v.to_json()).and_then(|c| match &literal {
+                Some(l) => schema.check(path, l, &c).map(|_| c),
+                None => Ok(c),
+            }) {
+                Ok(c) => {
+                    coerced = c.into_owned();
+                    DocValue::from(&coerced)
+                }
+                Err(err) if options.strict_types => return Err(err),
+                Err(_) => continue,
+            },
+            _ => v,
+        };
+        #[cfg(feature = "unicode-normalization")]
+        let v = match options.normalize_strings {
+            true => crate::normalize::doc(v),
+            false => v,
+        };
+        let custom = match (&options.comparator, &literal) {
+            (Some(c), Some(l)) => c.holds(infix, path, &v.to_json(), l),
+            _ => None,
+        };
+        if let Some(holds) = custom {
+            if holds {
+                return Ok(true);
+            }
+        } else if literal.as_ref().is_some_and(|l| compare(infix, l, v)) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Result of [`apply`]: the requested page of documents and how many documents
+/// matched before `limit()` was applied, for pagination metadata.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Page {
+    pub documents: Vec<serde_json::Value>,
+    pub total: usize,
+}
+
+/// Result of [`apply_ref`]: like [`Page`], borrowing the documents that come
+/// out of the pipeline unchanged.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PageRef<'a> {
+    /// Input documents are borrowed; only the objects built by `aggregate()`
+    /// and `select()` are owned.
+    pub documents: Vec<Cow<'a, serde_json::Value>>,
+    pub total: usize,
+}
+
+/// Result of [`evaluate`]: the documents, or a single value for queries such
+/// as `count()` or `sum(price)` that reduce them.
+#[derive(Debug, PartialEq, Clone)]
+pub enum EvalResult {
+    Records(Vec<serde_json::Value>),
+    Scalar(serde_json::Value),
+}
+
+/// How evaluation treats data that doesn't fit the query.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EvalOptions {
+    /// With `true`, a non-numeric value under `sum()`, `mean()`, `min()` or
+    /// `max()` in `aggregate()` is an [`EvalError::NonNumeric`]. By default such
+    /// values are skipped like missing ones.
+    pub strict_numbers: bool,
+    /// With `true`, `exists()` accepts a field holding `null` and `missing()`
+    /// rejects it. By default `null` counts as missing.
+    pub null_is_present: bool,
+    /// Types to coerce field values to before filters compare them, for
+    /// documents that store numbers as strings and the like.
+    pub schema: Option<Schema>,
+    /// With `true`, a value the schema can't coerce is an
+    /// [`EvalError::Uncoercible`], and a literal of another type than the
+    /// schema's an [`EvalError::Mismatched`]. By default the filter just
+    /// doesn't match.
+    pub strict_types: bool,
+    /// Orders field values against literals before the built-in comparison,
+    /// for values such as version numbers. Sorting and [`CompiledQuery`]
+    /// don't use it.
+    pub comparator: Option<Comparator>,
+    /// The time `now` and `now-7d` are relative to. By default each call to
+    /// [`apply_with`] reads the system clock once, and [`Query::matches_with`]
+    /// reads it for every filter.
+    #[cfg(feature = "chrono")]
+    pub now: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// With `true`, string literals and document strings are compared in
+    /// Unicode NFC, so `"café"` matches whether either side uses a combining
+    /// accent. By default strings are compared byte for byte.
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize_strings: bool,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EvalError {
+    // (集計関数, path, 値)
+    NonNumeric(Aggregation, String, serde_json::Value),
+    // EvalContext に無い $name または name()
+    Unresolved(String),
+    // one() で一致したドキュメントが1件ではなかった
+    NotExactlyOne { found: usize },
+    // (path, スキーマの型, 値)
+    Uncoercible(String, FieldType, serde_json::Value),
+    // (path, スキーマの型, 比べられないリテラル)
+    Mismatched(String, FieldType, Value),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::NonNumeric(function, path, value) => write!(
+                f,
+                "{}({}) can't use the non-numeric value {}",
+                function.name(),
+                path,
+                value
+            ),
+            EvalError::Unresolved(name) => write!(f, "{} is not defined", name),
+            EvalError::NotExactlyOne { found } => {
+                write!(f, "one() expected exactly one document, found {}", found)
+            }
+            EvalError::Uncoercible(path, field_type, value) => {
+                write!(f, "{} can't be read as {}: {}", path, field_type, value)
+            }
+            EvalError::Mismatched(path, field_type, literal) => write!(
+                f,
+                "{} is {}, so it can't be compared with {}",
+                path, field_type, literal
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Runs the query over a set of documents with the default [`EvalOptions`].
+///
+/// The steps are: keep the documents that match, drop duplicates for
+/// `distinct()`, replace them by one object per group for `aggregate()`, order
+/// them if the query contains `sort()`, skip and take according to
+/// `limit(count,offset)`, keep only the first document for `first()` or check
+/// that exactly one is left for `one()`, and finally trim them to the
+/// `select()`ed paths with [`project`]. Without `limit()` every match is
+/// returned.
+///
+/// Sorting compares numbers numerically, strings lexicographically and booleans
+/// with `false < true`. Values of different types are ordered booleans, numbers,
+/// strings, then arrays and objects. Missing and `null` values always come last,
+/// whatever the direction. The sort is stable, so documents that tie on every
+/// key keep their input order.
+///
+/// With the default options, the only error is [`EvalError::NotExactlyOne`].
+/// A [`Schema`] in the options only affects filters, not sorting or
+/// aggregation.
+pub fn apply(query: &Query, docs: Vec<serde_json::Value>) -> Result<Page, EvalError> {
+    apply_with(query, docs, &EvalOptions::default())
+}
+
+/// Same as [`apply`] with explicit options.
+pub fn apply_with(
+    query: &Query,
+    mut docs: Vec<serde_json::Value>,
+    options: &EvalOptions,
+) -> Result<Page, EvalError> {
+    let (rows, total) = pipeline(query, &docs, options)?;
+    // 入力の文書は高々1回しか出てこないので、コピーせずに取り出せる
+    let documents = rows
+        .into_iter()
+        .map(|row| match row {
+            Row::Input(i) => std::mem::take(&mut docs[i]),
+            Row::Built(doc) => doc,
+        })
+        .collect();
+    Ok(Page { documents, total })
+}
+
+/// Same as [`apply_with`] over documents the caller keeps. Nothing is cloned
+/// for filtering, `distinct()`, `sort()`, `limit()`, `first()` or `one()`:
+/// the page borrows the matching documents, and sorting reorders the
+/// references. Only `aggregate()` and `select()` build new objects.
+///
+/// ```
+/// use rql_parser::eval::{apply_ref, EvalOptions};
+/// use rql_parser::parser::Parser;
+/// use serde_json::json;
+/// use std::borrow::Cow;
+///
+/// let docs = vec![json!({"n": 1}), json!({"n": 3}), json!({"n": 2})];
+/// let query = Parser::from_str("and(ge(n,2),sort(-n))").parse_query().unwrap();
+/// let page = apply_ref(&query, &docs, &EvalOptions::default()).unwrap();
+/// assert_eq!(page.documents, vec![Cow::Borrowed(&docs[1]), Cow::Borrowed(&docs[2])]);
+/// ```
+pub fn apply_ref<'a>(
+    query: &Query,
+    docs: &'a [serde_json::Value],
+    options: &EvalOptions,
+) -> Result<PageRef<'a>, EvalError> {
+    let (rows, total) = pipeline(query, docs, options)?;
+    let documents = rows
+        .into_iter()
+        .map(|row| match row {
+            Row::Input(i) => Cow::Borrowed(&docs[i]),
+            Row::Built(doc) => Cow::Owned(doc),
+        })
+        .collect();
+    Ok(PageRef { documents, total })
+}
+
+// パイプラインの途中の文書。入力の何番目か、aggregate() や select() で作ったもの
+enum Row {
+    Input(usize),
+    Built(serde_json::Value),
+}
+
+impl Row {
+    fn get<'a>(&'a self, docs: &'a [serde_json::Value]) -> &'a serde_json::Value {
+        match self {
+            Row::Input(i) => &docs[*i],
+            Row::Built(doc) => doc,
+        }
+    }
+}
+
+// apply の各段階。入力の文書は番号で持ち回り、ソートも番号を並べ替える
+fn pipeline(
+    query: &Query,
+    docs: &[serde_json::Value],
+    options: &EvalOptions,
+) -> Result<(Vec<Row>, usize), EvalError> {
+    #[cfg(feature = "chrono")]
+    let pinned;
+    #[cfg(feature = "chrono")]
+    let options = match options.now {
+        Some(_) => options,
+        None => {
+            pinned = EvalOptions {
+                now: Some(crate::datetime::now()),
+                ..options.clone()
+            };
+            &pinned
+        }
+    };
+    let mut rows = vec![];
+    for (i, doc) in docs.iter().enumerate() {
+        if query.try_matches_with(doc, options)? {
+            rows.push(Row::Input(i));
+        }
+    }
+    if let Some(path) = distinct(query) {
+        rows = dedup(rows, docs, path);
+    }
+    if let Some((keys, functions)) = aggregation(query) {
+        let matched: Vec<&serde_json::Value> = rows.iter().map(|row| row.get(docs)).collect();
+        rows = aggregate::aggregate(keys, functions, &matched, options)?
+            .into_iter()
+            .map(Row::Built)
+            .collect();
+    }
+    let keys = sort_keys(query);
+    if !keys.is_empty() {
+        rows.sort_by(|a, b| {
+            let (a, b) = (a.get(docs), b.get(docs));
+            keys.iter()
+                .map(|(prefix, path)| compare_key(path.resolve(a), path.resolve(b), prefix))
+                .find(|ordering| ordering != &Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+    let total = rows.len();
+    if let Some((count, offset)) = limit(query) {
+        rows = rows.into_iter().skip(offset).take(count).collect();
+    }
+    match pick(query) {
+        Some(Query::First) => rows.truncate(1),
+        Some(_) if rows.len() != 1 => {
+            return Err(EvalError::NotExactlyOne { found: rows.len() });
+        }
+        _ => {}
+    }
+    if let Some(paths) = select(query) {
+        rows = rows
+            .iter()
+            .map(|row| Row::Built(project(paths, row.get(docs))))
+            .collect();
+    }
+    Ok((rows, total))
+}
+
+/// Runs the query like [`apply_with`], then reduces the documents with
+/// [`reduce`]. `aggregate()` gives one record per group.
+pub fn evaluate(
+    query: &Query,
+    docs: Vec<serde_json::Value>,
+    options: &EvalOptions,
+) -> Result<EvalResult, EvalError> {
+    let page = apply_with(query, docs, options)?;
+    Ok(match reduce(query, &page.documents, options)? {
+        Some(value) => EvalResult::Scalar(value),
+        None => EvalResult::Records(page.documents),
+    })
+}
+
+/// The value `count()` or `sum()`, `min()`, `max()` and `mean()` reduce the
+/// documents returned by [`apply`] to, or `None` when the query has no such
+/// operator. They see the documents left at the end of the pipeline, so after
+/// `limit()`.
+///
+/// The functions compute like in `aggregate()`: missing and `null` values are
+/// skipped, the sum of nothing is `0` and the `min()`, `max()` or `mean()` of
+/// nothing is `null`. Sums stay integers unless a float is involved.
+pub fn reduce(
+    query: &Query,
+    docs: &[serde_json::Value],
+    options: &EvalOptions,
+) -> Result<Option<serde_json::Value>, EvalError> {
+    match reducer(query) {
+        Some(Query::Count) => Ok(Some(docs.len().into())),
+        Some(Query::Reduce(function, path)) => {
+            aggregate::reduce(*function, path, docs, options).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// `sort()`, `limit()`, `select()`, `distinct()`, `aggregate()`, `first()`,
+/// `one()`, `count()` and `sum()`-like nodes, which shape the result set
+/// rather than select documents. They take effect at the top level or in
+/// `and()`; in `or()` they're ignored, as the parser rejects them there.
+pub(crate) fn is_shaping(query: &Query) -> bool {
+    matches!(
+        query,
+        Query::Sort(..)
+            | Query::Limit(..)
+            | Query::Select(..)
+            | Query::Distinct(..)
+            | Query::Aggregate(..)
+            | Query::First
+            | Query::One
+            | Query::Count
+            | Query::Reduce(..)
+    )
+}
+
+// or() の中で評価する子。整形するノードは true にも false にもしない
+pub(crate) fn or_operands(queries: &[Query]) -> impl Iterator<Item = &Query> {
+    queries.iter().filter(|q| !is_shaping(q))
+}
+
+// 最初に現れた count() または sum() など
+fn reducer(query: &Query) -> Option<&Query> {
+    match query {
+        Query::Count | Query::Reduce(..) => Some(query),
+        Query::And(queries) => queries.iter().find_map(reducer),
+        _ => None,
+    }
+}
+
+type Aggregations<'a> = (&'a [Path], &'a [(Aggregation, Path)]);
+
+fn aggregation(query: &Query) -> Option<Aggregations<'_>> {
+    match query {
+        Query::Aggregate(keys, functions) => Some((keys, functions)),
+        Query::And(queries) => queries.iter().find_map(aggregation),
+        _ => None,
+    }
+}
+
+// 最初に現れた limit() を使う
+fn limit(query: &Query) -> Option<(usize, usize)> {
+    match query {
+        Query::Limit(count, offset) => Some((*count, *offset)),
+        Query::And(queries) => queries.iter().find_map(limit),
+        _ => None,
+    }
+}
+
+// 最初に現れた first() または one()
+fn pick(query: &Query) -> Option<&Query> {
+    match query {
+        Query::First | Query::One => Some(query),
+        Query::And(queries) => queries.iter().find_map(pick),
+        _ => None,
+    }
+}
+
+fn distinct(query: &Query) -> Option<Option<&Path>> {
+    match query {
+        Query::Distinct(path) => Some(path.as_ref()),
+        Query::And(queries) => queries.iter().find_map(distinct),
+        _ => None,
+    }
+}
+
+/// Keeps the first document for each distinct value at `path`, or for each
+/// distinct document without a path. Values are compared structurally: object
+/// key order doesn't matter and `1` equals `1.0` (serde_json numbers are always
+/// finite, so there is no NaN to worry about). Documents missing the path
+/// all share one key, so only the first of them is kept; `null` is a value of
+/// its own.
+fn dedup(rows: Vec<Row>, docs: &[serde_json::Value], path: Option<&Path>) -> Vec<Row> {
+    let mut seen = HashSet::new();
+    rows.into_iter()
+        .filter(|row| {
+            let doc = row.get(docs);
+            let key = match path {
+                Some(path) => path.resolve(doc).map(canonical),
+                None => Some(canonical(doc)),
+            };
+            seen.insert(key)
+        })
+        .collect()
+}
+
+// 比較用の正規化した文字列。数値を揃え、キーを並べ替える。serde_json の Map は既定では
+// キー順だが、preserve_order (bson などが有効にする) では挿入順になる
+pub(crate) fn canonical(v: &serde_json::Value) -> String {
+    match v {
+        serde_json::Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        serde_json::Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical).collect();
+            format!("[{}]", items.join(","))
+        }
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{}:{}", serde_json::Value::from(k.as_str()), canonical(v)))
+                .collect();
+            entries.sort();
+            format!("{{{}}}", entries.join(","))
+        }
+        _ => v.to_string(),
+    }
+}
+
+fn select(query: &Query) -> Option<&[Path]> {
+    match query {
+        Query::Select(paths) => Some(paths),
+        Query::And(queries) => queries.iter().find_map(select),
+        _ => None,
+    }
+}
+
+/// Builds a new object holding only the given dotted paths of `doc`, nested the
+/// same way (`user.name` gives `{"user":{"name":...}}`). Paths missing from the
+/// document are left out; when one path contains another (`user` and
+/// `user.name`), the whole outer value is kept.
+pub fn project(paths: &[Path], doc: &serde_json::Value) -> serde_json::Value {
+    let mut out = serde_json::Map::new();
+    for path in paths {
+        if let Some(value) = path.resolve(doc) {
+            insert(&mut out, path, value);
+        }
+    }
+    serde_json::Value::Object(out)
+}
+
+pub(crate) fn insert(
+    out: &mut serde_json::Map<String, serde_json::Value>,
+    path: &Path,
+    value: &serde_json::Value,
+) {
+    insert_segments(out, path.segments(), value)
+}
+
+fn insert_segments(
+    out: &mut serde_json::Map<String, serde_json::Value>,
+    segments: &[Segment],
+    value: &serde_json::Value,
+) {
+    let (key, rest) = match segments.split_first() {
+        Some((Segment::Key(key), rest)) => (key.clone(), rest),
+        Some((Segment::Index(i), rest)) => (i.to_string(), rest),
+        Some((Segment::Wildcard, rest)) => ("*".to_owned(), rest),
+        None => return,
+    };
+    if rest.is_empty() {
+        out.insert(key, value.clone());
+        return;
+    }
+    let child = out
+        .entry(key)
+        .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+    // 既に外側のパスで値全体が入っている場合はそのまま
+    if let serde_json::Value::Object(child) = child {
+        insert_segments(child, rest, value);
+    }
+}
+
+// クエリ中の sort() のキーを出現順に集める
+fn sort_keys(query: &Query) -> Vec<(&Prefix, &Path)> {
+    match query {
+        Query::Sort(keys) => keys
+            .iter()
+            .filter_map(|(prefix, value)| match value {
+                Value::Identifier(path) => Some((prefix, path)),
+                _ => None,
+            })
+            .collect(),
+        Query::And(queries) => queries.iter().flat_map(sort_keys).collect(),
+        _ => vec![],
+    }
+}
+
+fn compare_key(
+    a: Option<&serde_json::Value>,
+    b: Option<&serde_json::Value>,
+    prefix: &Prefix,
+) -> Ordering {
+    let a = a.filter(|v| !v.is_null());
+    let b = b.filter(|v| !v.is_null());
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => match prefix {
+            Prefix::Plus => compare_json(a, b),
+            Prefix::Minus => compare_json(b, a),
+        },
+    }
+}
+
+fn compare_json(a: &serde_json::Value, b: &serde_json::Value) -> Ordering {
+    use serde_json::Value::*;
+    fn rank(v: &serde_json::Value) -> u8 {
+        match v {
+            Null => 0,
+            Bool(_) => 1,
+            Number(_) => 2,
+            String(_) => 3,
+            Array(_) | Object(_) => 4,
+        }
+    }
+    match (a, b) {
+        (Bool(a), Bool(b)) => a.cmp(b),
+        (Number(a), Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => a.as_f64().partial_cmp(&b.as_f64()).unwrap_or(Ordering::Equal),
+        },
+        (String(a), String(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+// literal側のメソッドで比較する (lt(a,10) は 10.lt(a) になる)
+pub(crate) fn compare<'a>(infix: &Infix, literal: &Value, v: impl Into<DocValue<'a>>) -> bool {
+    let v = v.into();
+    match infix {
+        Infix::Eq => literal.eq(v),
+        Infix::NotEq => literal.ne(v),
+        Infix::Lt => literal.lt(v),
+        Infix::Le => literal.le(v),
+        Infix::Gt => literal.gt(v),
+        Infix::Ge => literal.ge(v),
+        Infix::StartsWith | Infix::EndsWith => match (literal.as_str(), v.as_str()) {
+            (Some(s), Some(v)) if infix == &Infix::StartsWith => v.starts_with(s),
+            (Some(s), Some(v)) => v.ends_with(s),
+            _ => false,
+        },
+        Infix::IEq | Infix::IContains | Infix::IStartsWith => match (literal.as_str(), v.as_str()) {
+            (Some(s), Some(v)) => match infix {
+                Infix::IEq => folded(v).eq(folded(s)),
+                Infix::IContains => contains_folded(v, s),
+                _ => starts_with_folded(folded(v), s),
+            },
+            _ => false,
+        },
+    }
+}
+
+// 大文字にしてから小文字にして、Unicode の (トルコ語向けでない) full case folding に
+// 近づける。ß と SS は ss に、ς と Σ は σ に揃う。İ は i と U+0307 になり、i とは一致しない
+pub(crate) fn fold_case(s: &str) -> String {
+    folded(s).collect()
+}
+
+// fold_case と同じ変換を、文字列を作らずに1文字ずつ行う。
+// str::to_lowercase は語末の Σ を ς にしてしまうので、文字ごとに変換する
+fn folded(s: &str) -> impl Iterator<Item = char> + Clone + '_ {
+    s.chars()
+        .flat_map(char::to_uppercase)
+        .flat_map(char::to_lowercase)
+}
+
+fn starts_with_folded(mut text: impl Iterator<Item = char>, word: &str) -> bool {
+    folded(word).all(|c| text.next() == Some(c))
+}
+
+// 畳んだ text の中に、畳んだ word が現れるか
+pub(crate) fn contains_folded(text: &str, word: &str) -> bool {
+    let mut start = folded(text);
+    loop {
+        if starts_with_folded(start.clone(), word) {
+            return true;
+        }
+        if start.next().is_none() {
+            return false;
+        }
+    }
+}
+
+// 右辺の識別子は、ドキュメントのそのフィールドの値と比較する (eq(a,b) は a == b)。
+// フィールドが無いときや、null や配列・オブジェクトとは比較しない
+pub(crate) fn operand<'a>(doc: &dyn Document, literal: &'a Value) -> Option<Cow<'a, Value>> {
+    let path = match literal {
+        Value::Identifier(path) => path,
+        _ => return Some(Cow::Borrowed(literal)),
+    };
+    let value = match doc.get_path(path) {
+        // ワイルドカードなら最初に届いた値
+        Some(DocValue::Array(values)) if path.has_wildcard() => values.into_iter().next(),
+        value => value,
+    };
+    let value = value?;
+    let value = match value {
+        DocValue::String(s) => Value::StringLiteral(s.into_owned()),
+        DocValue::Bool(b) => Value::Boolean(b),
+        DocValue::Integer(i) => Value::IntegerLiteral(i),
+        DocValue::Unsigned(u) => Value::UnsignedLiteral(u),
+        #[cfg(feature = "decimal")]
+        DocValue::Float(f) => match crate::decimal::from_doc(&value) {
+            Some(d) => Value::DecimalLiteral(d),
+            None => Value::FloatLiteral(f),
+        },
+        #[cfg(not(feature = "decimal"))]
+        DocValue::Float(f) => Value::FloatLiteral(f),
+        _ => return None,
+    };
+    Some(Cow::Owned(value))
+}
+
+// exists() と missing() の判定
+pub(crate) fn present(doc: &dyn Document, path: &Path, options: &EvalOptions) -> bool {
+    let found = |v: &DocValue| options.null_is_present || !v.is_null();
+    match doc.get_path(path) {
+        Some(DocValue::Array(values)) if path.has_wildcard() => values.iter().any(found),
+        Some(value) => found(&value),
+        None => false,
+    }
+}
+
+// contains() の判定。配列ならどれかの要素が値と等しく、文字列なら値を部分文字列として含む
+pub(crate) fn contains(doc: &dyn Document, path: &Path, value: &Value) -> bool {
+    let value = match operand(doc, value) {
+        Some(value) => value,
+        None => return false,
+    };
+    let holds = |v: DocValue| match v {
+        DocValue::Array(items) => items.into_iter().any(|item| Value::eq(&value, item)),
+        DocValue::String(s) => value.as_str().is_some_and(|word| s.contains(word)),
+        _ => false,
+    };
+    match doc.get_path(path) {
+        Some(DocValue::Array(values)) if path.has_wildcard() => values.into_iter().any(holds),
+        Some(value) => holds(value),
+        None => false,
+    }
+}
+
+// search() の対象。フィールドが無ければドキュメント中のすべての文字列
+pub(crate) fn search(doc: &dyn Document, fields: &[Path], term: &str) -> bool {
+    fn strings<'a>(value: DocValue<'a>, found: &mut Vec<Cow<'a, str>>) {
+        match value {
+            DocValue::String(s) => found.push(s),
+            DocValue::Array(values) => values.into_iter().for_each(|v| strings(v, found)),
+            _ => {}
+        }
+    }
+    let texts = match fields.is_empty() {
+        true => doc.strings(),
+        false => {
+            let mut found = vec![];
+            for value in fields.iter().filter_map(|path| doc.get_path(path)) {
+                strings(value, &mut found);
+            }
+            found
+        }
+    };
+    search_texts(texts.iter().map(|t| t.as_ref()), term)
+}
+
+// 地球の平均半径 (メートル)
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+// path の点が (緯度, 経度) から radius メートル以内か。点は {"lat": .., "lon": ..} か
+// GeoJSON と同じ順の [経度, 緯度]。ワイルドカードのパスには一致しない
+pub(crate) fn near(doc: &dyn Document, path: &Path, center: (f64, f64), radius: f64) -> bool {
+    if path.has_wildcard() {
+        return false;
+    }
+    let child = |key: &str| {
+        let mut segments = path.segments().to_vec();
+        segments.push(Segment::Key(key.to_owned()));
+        doc.get_path(&Path::new(segments))?.as_f64()
+    };
+    let point = match doc.get_path(path) {
+        Some(DocValue::Array(values)) if values.len() == 2 => {
+            values[1].as_f64().zip(values[0].as_f64())
+        }
+        Some(DocValue::Object) => child("lat").zip(child("lon")),
+        _ => None,
+    };
+    point.is_some_and(|point| haversine(point, center) <= radius)
+}
+
+// 2点 (緯度, 経度) 間の大円距離 (メートル)
+pub(crate) fn haversine((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+}
+
+// 空白で区切った語がすべて、大文字小文字を問わずどれかの文字列に含まれるか
+pub(crate) fn search_texts<'a>(texts: impl Iterator<Item = &'a str>, term: &str) -> bool {
+    let texts: Vec<String> = texts.map(fold_case).collect();
+    term.split_whitespace()
+        .map(fold_case)
+        .all(|word| texts.iter().any(|text| text.contains(&word)))
+}
+
+/// Looks up a dotted property path (`speed.max`) in a JSON document. See
+/// [`Path`] for indexes, wildcards and quoted keys.
+pub fn resolve<'a>(doc: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    Path::from(path).resolve(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Path, Query};
+    use crate::eval::{
+        apply, apply_ref, apply_with, evaluate, project, EvalError, EvalOptions, EvalResult,
+    };
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    fn matches(input: &str, doc: serde_json::Value) -> bool {
+        let mut parser = Parser::new_from_string(input.to_owned());
+        parser.parse_query().unwrap().matches(&doc)
+    }
+
+    #[test]
+    fn filter() {
+        let doc = json!({"name": "test", "speed": {"max": 120, "min": 40.5}, "active": true});
+        assert!(matches("eq(name,\"test\")", doc.clone()));
+        assert!(matches("ne(name,\"foo\")", doc.clone()));
+        assert!(matches("gt(speed.max,100)", doc.clone()));
+        assert!(matches("lt(speed.min,60.0)", doc.clone()));
+        assert!(matches("eq(active,true)", doc.clone()));
+        assert!(!matches("le(speed.max,100)", doc.clone()));
+        assert!(!matches("eq(missing,1)", doc));
+    }
+
+    // Query::matches の表の各マス
+    #[test]
+    fn comparison_matrix() {
+        let rows = [
+            (json!(2), [false, true, false, true]),
+            (json!(1), [true, false, false, true]),
+            (json!("1"), [false; 4]),
+            (json!(null), [false; 4]),
+            (json!([1, 2]), [false, true, false, false]),
+            (json!([]), [false, true, false, false]),
+            (json!({"a": 1}), [false, true, false, false]),
+            (json!({}), [false, true, false, false]),
+        ];
+        for (value, expected) in rows {
+            let doc = json!({ "f": value });
+            let got = ["eq(f,1)", "ne(f,1)", "lt(f,1)", "ge(f,1)"].map(|q| matches(q, doc.clone()));
+            assert_eq!(got, expected, "{}", doc);
+        }
+        for q in ["eq(f,1)", "ne(f,1)", "lt(f,1)", "ge(f,1)"] {
+            assert!(!matches(q, json!({})), "{}", q);
+        }
+        // どの型のリテラルでも配列やオブジェクトとは等しくない
+        let doc = json!({"tags": ["x", "y"], "meta": {"x": true}});
+        for literal in ["\"x\"", "true", "1.5"] {
+            for field in ["tags", "meta"] {
+                assert!(!matches(&format!("eq({},{})", field, literal), doc.clone()));
+                assert!(matches(&format!("ne({},{})", field, literal), doc.clone()));
+            }
+        }
+        // 要素と比べるならワイルドカード
+        assert!(matches("eq(tags.*,\"x\")", doc.clone()));
+        assert!(matches("ne(tags.*,\"x\")", doc.clone()));
+        assert!(!matches("ne(tags.*,\"z\")", json!({"tags": ["z"]})));
+    }
+
+    // パーサは受け付けないが、組み立てた or() の中の整形ノードは無視する
+    #[test]
+    fn shaping_in_or() {
+        let parse = |input: &str| Parser::from_str(input).parse_query().unwrap();
+        let limited = Query::Or(vec![parse("eq(a,1)"), parse("limit(10)")]);
+        assert!(!limited.matches(&json!({"a": 2})));
+        assert!(limited.matches(&json!({"a": 1})));
+        assert!(!limited.compile().matches(&json!({"a": 2})));
+        assert!(!limited.explain(&json!({"a": 2})).matched());
+        assert!(!Query::Or(vec![parse("limit(10)")]).matches(&json!({"a": 1})));
+
+        let sorted = Query::Or(vec![parse("eq(a,1)"), parse("sort(-a)")]);
+        let docs = vec![json!({"a": 1, "b": 1}), json!({"a": 3}), json!({"a": 1, "b": 2})];
+        let page = apply(&sorted, docs.clone()).unwrap();
+        assert_eq!(page.documents, vec![docs[0].clone(), docs[2].clone()]);
+        // and() の中ではこれまでどおり効く
+        let query = parse("and(or(eq(a,1),eq(a,3)),sort(-a))");
+        assert_eq!(apply(&query, docs.clone()).unwrap().documents[0], docs[1]);
+    }
+
+    #[test]
+    fn nested() {
+        let doc = json!({"foo": 10, "bar": 200});
+        assert!(matches("and(eq(foo,10),or(gt(bar,100),ne(bar,10)))", doc.clone()));
+        assert!(!matches("and(eq(foo,10),or(lt(bar,100),eq(bar,10)))", doc));
+    }
+
+    #[test]
+    fn startswith_and_endswith() {
+        let doc = json!({"name": "Dr. Who", "email": "who@example.com", "age": 900});
+        assert!(matches("startswith(name,\"Dr. \")", doc.clone()));
+        assert!(!matches("startswith(name,\"Who\")", doc.clone()));
+        assert!(matches("endswith(email,\"@example.com\")", doc.clone()));
+        assert!(matches("endswith(email,\"\")", doc.clone()));
+        assert!(!matches("endswith(name,\"%\")", doc.clone()));
+        // 文字列以外には一致しない
+        assert!(!matches("startswith(age,\"9\")", doc.clone()));
+        assert!(!matches("endswith(missing,\"\")", doc));
+    }
+
+    #[test]
+    fn near() {
+        let docs = [
+            // 北に約 4993m と 5015m
+            json!({"location": {"lat": 35.7249, "lon": 139.76}}),
+            json!({"location": {"lat": 35.7251, "lon": 139.76}}),
+            // [経度, 緯度] で東に約 4986m と 5013m
+            json!({"location": [139.8152, 35.68]}),
+            json!({"location": [139.8155, 35.68]}),
+            json!({"location": {"lat": "35.68", "lon": 139.76}}),
+            json!({"location": [139.76]}),
+            json!({"location": null}),
+        ];
+        let run = |input: &str| -> Vec<bool> {
+            let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+            let compiled = query.compile();
+            docs.iter()
+                .map(|doc| {
+                    let matched = query.matches(doc);
+                    assert_eq!(compiled.matches(doc), matched, "{}", input);
+                    matched
+                })
+                .collect()
+        };
+        let expected = [true, false, true, false, false, false, false];
+        assert_eq!(run("near(location,35.68,139.76,5000)"), expected);
+        assert_eq!(
+            run("and(near(location,35.68,139.76,5000),exists(location.lat))"),
+            [true, false, false, false, false, false, false]
+        );
+        assert_eq!(run("near(location,35.68,139.76,0)"), [false; 7]);
+
+        let sydney = json!({"office": {"lat": -33.87, "lon": 151.2}});
+        assert!(matches("near(office,-33.87,151.21,1000)", sydney.clone()));
+        assert!(!matches("near(office,-33.87,151.21,900)", sydney));
+    }
+
+    #[test]
+    fn ignoring_case() {
+        let doc = json!({
+            "street": "Hauptstraße 5",
+            "city": "İSTANBUL",
+            "word": "ΟΔΟΣ",
+            "code": "abc",
+            "n": 1,
+        });
+        assert!(matches("ieq(code,\"ABC\")", doc.clone()));
+        assert!(!matches("ieq(code,\"AB\")", doc.clone()));
+        assert!(matches("icontains(street,\"STRASSE\")", doc.clone()));
+        assert!(matches("istartswith(street,\"HAUPTSTRASSE 5\")", doc.clone()));
+        assert!(matches("ieq(street,\"hauptstrasse 5\")", doc.clone()));
+        // 語末のシグマも σ として比べる
+        assert!(matches("ieq(word,\"οδοσ\")", doc.clone()));
+        assert!(matches("ieq(word,\"οδος\")", doc.clone()));
+        // İ は i と結合用の点になる。トルコ語のように i とはみなさない
+        assert!(matches("ieq(city,\"i\u{307}stanbul\")", doc.clone()));
+        assert!(!matches("ieq(city,\"istanbul\")", doc.clone()));
+        assert!(matches("icontains(city,\"stanbul\")", doc.clone()));
+        assert!(matches("istartswith(city,\"i\")", doc.clone()));
+        // 文字列以外には一致しない
+        assert!(!matches("ieq(n,\"1\")", doc.clone()));
+        assert!(!matches("icontains(missing,\"\")", doc));
+    }
+
+    #[test]
+    fn exists_and_missing() {
+        let docs = [
+            json!({"user": {"phone": "090"}}),
+            json!({"user": {"phone": null}}),
+            json!({"user": {}}),
+        ];
+        let run = |input: &str, options: &EvalOptions| -> Vec<bool> {
+            let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+            docs.iter().map(|doc| query.matches_with(doc, options)).collect()
+        };
+        let default = EvalOptions::default();
+        assert_eq!(run("exists(user.phone)", &default), [true, false, false]);
+        assert_eq!(run("missing(user.phone)", &default), [false, true, true]);
+        let nested = "or(missing(user),and(exists(user),missing(user.phone)))";
+        assert_eq!(run(nested, &default), [false, true, true]);
+        let null_is_present = EvalOptions {
+            null_is_present: true,
+            ..EvalOptions::default()
+        };
+        assert_eq!(run("exists(user.phone)", &null_is_present), [true, true, false]);
+        assert_eq!(run("missing(user.phone)", &null_is_present), [false, false, true]);
+    }
+
+    #[test]
+    fn search() {
+        let docs = [
+            json!({"message": "Connection TIMEOUT", "details": {"code": "E42"}}),
+            json!({"message": "disk full", "tags": ["error", "Timeout"]}),
+            json!({"message": "ok", "count": 3}),
+        ];
+        let run = |input: &str| -> Vec<bool> {
+            let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+            let compiled = query.compile();
+            docs.iter()
+                .map(|doc| {
+                    let matched = query.matches(doc);
+                    assert_eq!(compiled.matches(doc), matched, "{}", input);
+                    matched
+                })
+                .collect()
+        };
+        // ドキュメント全体。配列やネストした文字列も探す
+        assert_eq!(run("search(\"timeout\")"), [true, true, false]);
+        assert_eq!(run("search(\"  error   timeout \")"), [false, true, false]);
+        assert_eq!(run("search(\"e42 conn\")"), [true, false, false]);
+        assert_eq!(run("search(\"3\")"), [false, false, false]);
+        assert_eq!(run("search(\"\")"), [true, true, true]);
+        // フィールドを限る
+        assert_eq!(run("search((message),\"timeout\")"), [true, false, false]);
+        assert_eq!(run("search((message,tags),\"timeout\")"), [true, true, false]);
+        assert_eq!(run("search((details),\"e42\")"), [false, false, false]);
+        assert_eq!(run("search((details.code,nothing),\"e42\")"), [true, false, false]);
+        // and() や or() の中でも他のフィルタと同じ
+        assert_eq!(
+            run("and(search(\"timeout\"),startswith(message,\"disk\"))"),
+            [false, true, false]
+        );
+        assert_eq!(
+            run("and(search((message),\"full\"),sort(+message),limit(1))"),
+            [false, true, false]
+        );
+        assert_eq!(run("or(search(\"e42\"),eq(count,3))"), [true, false, true]);
+    }
+
+    #[test]
+    fn field_to_field() {
+        let doc = json!({
+            "spent": 120, "budget": 100.5, "owner": "alice", "editor": "alice",
+            "tags": [], "note": null,
+        });
+        assert!(matches("gt(spent,budget)", doc.clone()));
+        assert!(!matches("le(spent,budget)", doc.clone()));
+        assert!(matches("eq(owner,editor)", doc.clone()));
+        assert!(!matches("ne(owner,editor)", doc.clone()));
+        // 右辺のフィールドが無ければ一致しない
+        assert!(!matches("ne(owner,bob)", doc.clone()));
+        assert!(!matches("eq(editor,alice)", doc.clone()));
+        // null や配列のフィールドとは比較しない
+        assert!(!matches("ne(owner,note)", doc.clone()));
+        assert!(!matches("ne(owner,tags)", doc));
+    }
+
+    fn names(input: &str, docs: &serde_json::Value) -> Vec<String> {
+        let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+        let docs = docs.as_array().unwrap().clone();
+        apply(&query, docs)
+            .unwrap()
+            .documents
+            .iter()
+            .map(|doc| doc["name"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn sort() {
+        let docs = json!([
+            {"name": "a", "team": "red", "score": 10},
+            {"name": "b", "team": "blue", "score": 30},
+            {"name": "c", "team": "red", "score": 20.5},
+            {"name": "d", "team": "blue"},
+            {"name": "e", "team": "green", "score": 30},
+            {"name": "f", "team": null, "score": 20.5},
+        ]);
+        assert_eq!(names("sort(+score)", &docs), ["a", "c", "f", "b", "e", "d"]);
+        assert_eq!(names("sort(-score)", &docs), ["b", "e", "c", "f", "a", "d"]);
+        // 同点は2番目のキーで並べ、それでも同じなら入力順
+        assert_eq!(names("sort(-score,+team)", &docs), ["b", "e", "c", "f", "a", "d"]);
+        assert_eq!(names("sort(-score,-team)", &docs), ["e", "b", "c", "f", "a", "d"]);
+        assert_eq!(names("sort(+team)", &docs), ["b", "d", "e", "a", "c", "f"]);
+        assert_eq!(names("and(ne(team,\"blue\"),sort(-team,+score))", &docs), ["a", "c", "e"]);
+        assert_eq!(names("eq(team,\"red\")", &docs), ["a", "c"]);
+    }
+
+    #[test]
+    fn limit() {
+        let docs: Vec<_> = (1..=40).map(|i| json!({"score": i})).collect();
+        let page = |input: &str| {
+            let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+            let page = apply(&query, docs.clone()).unwrap();
+            let scores: Vec<i64> = page
+                .documents
+                .iter()
+                .map(|doc| doc["score"].as_i64().unwrap())
+                .collect();
+            (scores, page.total)
+        };
+        // 6..=40 の35件を降順に並べ、21件目から10件
+        let (scores, total) = page("and(gt(score,5),sort(-score),limit(10,20))");
+        assert_eq!(total, 35);
+        assert_eq!(scores, (11..=20).rev().collect::<Vec<_>>());
+        assert_eq!(page("limit(5)").0, vec![1, 2, 3, 4, 5]);
+        assert_eq!(page("and(gt(score,5),limit(10,100))"), (vec![], 35));
+        assert_eq!(page("and(gt(score,5),limit(0))"), (vec![], 35));
+        assert_eq!(page("gt(score,38)"), (vec![39, 40], 2));
+    }
+
+    #[test]
+    fn project_paths() {
+        let doc = json!({"id": 1, "user": {"name": "alice", "age": 42}, "tags": ["a"]});
+        let paths = |paths: &[&str]| paths.iter().map(|p| Path::from(*p)).collect::<Vec<_>>();
+        assert_eq!(
+            project(&paths(&["id", "user.name", "missing", "user.missing.deep"]), &doc),
+            json!({"id": 1, "user": {"name": "alice"}})
+        );
+        let whole = json!({"user": {"name": "alice", "age": 42}});
+        assert_eq!(project(&paths(&["user", "user.name"]), &doc), whole);
+        assert_eq!(project(&paths(&["user.name", "user"]), &doc), whole);
+        assert_eq!(project(&paths(&["id"]), &json!(5)), json!({}));
+
+        let query = Parser::new_from_string("and(gt(id,0),select(tags,user.age))".to_owned())
+            .parse_query()
+            .unwrap();
+        let page = apply(&query, vec![doc, json!({"id": 0})]).unwrap();
+        assert_eq!(page.documents, vec![json!({"tags": ["a"], "user": {"age": 42}})]);
+    }
+
+    #[test]
+    fn distinct() {
+        let docs = json!([
+            {"name": "a", "team": "red", "meta": {"x": 1, "y": [1, 2]}},
+            {"name": "b", "team": "blue", "meta": {"y": [1, 2], "x": 1.0}},
+            {"name": "c", "team": "red"},
+            {"name": "d", "team": null},
+            {"name": "e"},
+            {"name": "f", "team": null},
+            {"name": "g"},
+            {"name": "a", "team": "red", "meta": {"x": 1, "y": [1, 2]}},
+        ]);
+        assert_eq!(names("distinct(team)", &docs), ["a", "b", "d", "e"]);
+        assert_eq!(names("distinct(meta)", &docs), ["a", "c"]);
+        assert_eq!(names("distinct()", &docs), ["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(names("and(distinct(team),sort(+name),limit(2,1))", &docs), ["b", "d"]);
+    }
+
+    #[test]
+    fn first_and_one() {
+        let docs = json!([
+            {"name": "a", "team": "red", "score": 10},
+            {"name": "b", "team": "blue", "score": 30},
+            {"name": "c", "team": "red", "score": 20},
+        ]);
+        let run = |input: &str| {
+            let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+            apply(&query, docs.as_array().unwrap().clone())
+        };
+        assert_eq!(names("first()", &docs), ["a"]);
+        assert_eq!(names("and(eq(team,\"red\"),sort(-score),first())", &docs), ["c"]);
+        assert_eq!(names("and(eq(team,\"green\"),first())", &docs), Vec::<String>::new());
+        let page = run("and(eq(team,\"red\"),first())").unwrap();
+        assert_eq!(page.total, 2);
+
+        assert_eq!(names("and(eq(team,\"blue\"),one())", &docs), ["b"]);
+        assert_eq!(names("and(sort(+score),limit(1,2),one())", &docs), ["b"]);
+        assert_eq!(
+            run("and(eq(team,\"red\"),one())").unwrap_err(),
+            EvalError::NotExactlyOne { found: 2 }
+        );
+        let err = run("and(eq(team,\"green\"),one())").unwrap_err();
+        assert_eq!(err.to_string(), "one() expected exactly one document, found 0");
+    }
+
+    #[test]
+    fn count() {
+        let docs = json!([
+            {"status": "open", "count": 3},
+            {"status": "closed", "count": 1},
+            {"status": "open"},
+        ]);
+        let run = |input: &str| {
+            let query = Parser::new_from_string(input.to_owned()).parse_query().unwrap();
+            evaluate(&query, docs.as_array().unwrap().clone(), &EvalOptions::default()).unwrap()
+        };
+        assert_eq!(run("count()"), EvalResult::Scalar(json!(3)));
+        assert_eq!(run("and(eq(status,\"open\"),count())"), EvalResult::Scalar(json!(2)));
+        assert_eq!(run("and(eq(status,\"none\"),count())"), EvalResult::Scalar(json!(0)));
+        assert_eq!(run("and(limit(1),count())"), EvalResult::Scalar(json!(1)));
+        // count() の無いクエリはドキュメントを返す。count はフィールド名としても使える
+        assert_eq!(
+            run("gt(count,2)"),
+            EvalResult::Records(vec![json!({"status": "open", "count": 3})])
+        );
+    }
+
+    #[test]
+    fn borrowed() {
+        let docs: Vec<serde_json::Value> = (0..10_000)
+            .map(|i| json!({"id": i, "group": i % 7, "user": {"name": format!("u{}", i % 100)}}))
+            .collect();
+        let run = |input: &str| {
+            let query = Parser::from_str(input).parse_query().unwrap();
+            let page = apply_ref(&query, &docs, &EvalOptions::default()).unwrap();
+            let owned = apply_with(&query, docs.clone(), &EvalOptions::default()).unwrap();
+            assert_eq!(page.total, owned.total, "{}", input);
+            assert!(
+                page.documents.iter().map(|doc| doc.as_ref()).eq(&owned.documents),
+                "{}",
+                input
+            );
+            page
+        };
+
+        // 入力の文書そのものを指していれば、コピーしていない
+        let range = docs.as_ptr_range();
+        for input in [
+            "and(eq(group,3),ge(id,5000))",
+            "and(eq(group,3),sort(-user.name,+id),limit(20,10))",
+            "and(lt(id,100),distinct(user.name),sort(-id))",
+            "and(eq(id,42),one())",
+        ] {
+            let page = run(input);
+            assert!(!page.documents.is_empty(), "{}", input);
+            assert!(
+                page.documents.iter().all(|doc| match doc {
+                    std::borrow::Cow::Borrowed(doc) => range.contains(&(*doc as *const _)),
+                    std::borrow::Cow::Owned(_) => false,
+                }),
+                "{}",
+                input
+            );
+        }
+        let page = run("and(eq(group,3),sort(-id),limit(3),select(user.name))");
+        assert_eq!(page.total, 1429);
+        assert_eq!(
+            page.documents.into_iter().map(|doc| doc.into_owned()).collect::<Vec<_>>(),
+            vec![
+                json!({"user": {"name": "u99"}}),
+                json!({"user": {"name": "u92"}}),
+                json!({"user": {"name": "u85"}})
+            ]
+        );
+        assert!(run("aggregate(group,count(id))")
+            .documents
+            .iter()
+            .all(|doc| matches!(doc, std::borrow::Cow::Owned(_))));
+    }
+}
@@ -0,0 +1,213 @@
+//! Execution of `aggregate()`: one output object per group of documents.
+
+use crate::ast::{Aggregation, Path};
+use crate::eval::{canonical, EvalError, EvalOptions};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+
+/// Groups `docs` by the values at `keys` (in order of first occurrence) and
+/// computes each function per group. A group object holds its key values
+/// (`null` where the document lacks the key) and one entry per function named
+/// like `sum(price)`.
+///
+/// Missing and `null` values are ignored by every function. `count` counts the
+/// remaining values, `sum` gives `0` and `min`, `max` and `mean` give `null`
+/// when nothing is left. Sums of integers stay integers; a float anywhere
+/// makes the result a float. Without keys, the whole input is one group, even
+/// when it is empty.
+pub(crate) fn aggregate(
+    keys: &[Path],
+    functions: &[(Aggregation, Path)],
+    docs: &[&Value],
+    options: &EvalOptions,
+) -> Result<Vec<Value>, EvalError> {
+    let mut groups: Vec<(Vec<Value>, Vec<&Value>)> = vec![];
+    let mut index: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+    for &doc in docs {
+        let values: Vec<Option<&Value>> = keys.iter().map(|key| key.resolve(doc)).collect();
+        let id = values.iter().map(|v| v.map(canonical)).collect();
+        let i = *index.entry(id).or_insert_with(|| {
+            let values = values.iter().map(|v| v.cloned().unwrap_or(Value::Null));
+            groups.push((values.collect(), vec![]));
+            groups.len() - 1
+        });
+        groups[i].1.push(doc);
+    }
+    if keys.is_empty() && groups.is_empty() {
+        groups.push((vec![], vec![]));
+    }
+
+    let mut out = Vec::with_capacity(groups.len());
+    for (values, docs) in groups {
+        let mut object: Map<String, Value> = keys.iter().map(Path::to_string).zip(values).collect();
+        for (function, path) in functions {
+            let result = compute(*function, path, &docs, options)?;
+            object.insert(format!("{}({})", function.name(), path), result);
+        }
+        out.push(Value::Object(object));
+    }
+    Ok(out)
+}
+
+/// One function over all of `docs`, for `sum(price)` and the like used alone.
+pub(crate) fn reduce(
+    function: Aggregation,
+    path: &Path,
+    docs: &[Value],
+    options: &EvalOptions,
+) -> Result<Value, EvalError> {
+    let docs: Vec<&Value> = docs.iter().collect();
+    compute(function, path, &docs, options)
+}
+
+fn compute(
+    function: Aggregation,
+    path: &Path,
+    docs: &[&Value],
+    options: &EvalOptions,
+) -> Result<Value, EvalError> {
+    let values = docs
+        .iter()
+        .filter_map(|doc| path.resolve(doc))
+        .filter(|v| !v.is_null());
+    if function == Aggregation::Count {
+        return Ok(values.count().into());
+    }
+    let mut numbers: Vec<&Number> = vec![];
+    for value in values {
+        match value {
+            Value::Number(n) => numbers.push(n),
+            _ if options.strict_numbers => {
+                return Err(EvalError::NonNumeric(function, path.to_string(), value.clone()))
+            }
+            _ => {}
+        }
+    }
+    let float = |n: &Number| n.as_f64().unwrap_or_default();
+    let result = match function {
+        Aggregation::Sum => sum(&numbers),
+        Aggregation::Mean if numbers.is_empty() => Value::Null,
+        Aggregation::Mean => {
+            let total: f64 = numbers.iter().map(|n| float(n)).sum();
+            (total / numbers.len() as f64).into()
+        }
+        Aggregation::Min => extreme(&numbers, |a, b| float(a) < float(b)),
+        Aggregation::Max => extreme(&numbers, |a, b| float(a) > float(b)),
+        Aggregation::Count => unreachable!(),
+    };
+    Ok(result)
+}
+
+// 全て整数ならi64で足し、溢れるか小数が混ざればf64にする
+fn sum(numbers: &[&Number]) -> Value {
+    let ints: Option<i64> = numbers
+        .iter()
+        .try_fold(0i64, |acc, n| n.as_i64().and_then(|i| acc.checked_add(i)));
+    match ints {
+        Some(total) => total.into(),
+        None => numbers
+            .iter()
+            .map(|n| n.as_f64().unwrap_or_default())
+            .sum::<f64>()
+            .into(),
+    }
+}
+
+fn extreme(numbers: &[&Number], better: impl Fn(&Number, &Number) -> bool) -> Value {
+    let mut best: Option<&Number> = None;
+    for &n in numbers {
+        if best.is_none_or(|b| better(n, b)) {
+            best = Some(n);
+        }
+    }
+    best.map_or(Value::Null, |n| Value::Number(n.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{apply, apply_with, evaluate, EvalError, EvalOptions, EvalResult};
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    fn parse(input: &str) -> crate::ast::Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn group_by_two_keys() {
+        let docs = json!([
+            {"id": 1, "category": "a", "region": "jp", "price": 10},
+            {"id": 2, "category": "a", "region": "jp", "price": 12.5},
+            {"id": 3, "category": "b", "region": "jp", "price": 7},
+            {"id": 4, "category": "a", "region": "us", "price": "n/a"},
+            {"id": 5, "category": "a", "region": "jp", "price": 20},
+            {"id": 6, "category": "a", "region": "us"},
+        ]);
+        let query = parse(
+            "aggregate(category,region,count(id),sum(price),min(price),max(price),mean(price))",
+        );
+        let page = apply(&query, docs.as_array().unwrap().clone()).unwrap();
+        assert_eq!(
+            page.documents,
+            vec![
+                json!({"category": "a", "region": "jp", "count(id)": 3, "sum(price)": 42.5,
+                       "min(price)": 10, "max(price)": 20, "mean(price)": 42.5 / 3.0}),
+                json!({"category": "b", "region": "jp", "count(id)": 1, "sum(price)": 7,
+                       "min(price)": 7, "max(price)": 7, "mean(price)": 7.0}),
+                // price が全て欠けているか数値でないグループ
+                json!({"category": "a", "region": "us", "count(id)": 2, "sum(price)": 0,
+                       "min(price)": null, "max(price)": null, "mean(price)": null}),
+            ]
+        );
+
+        let strict = EvalOptions {
+            strict_numbers: true,
+            ..EvalOptions::default()
+        };
+        let err = apply_with(&query, docs.as_array().unwrap().clone(), &strict).unwrap_err();
+        assert!(matches!(err, EvalError::NonNumeric(_, ref path, _) if path == "price"));
+        assert_eq!(
+            err.to_string(),
+            "sum(price) can't use the non-numeric value \"n/a\""
+        );
+    }
+
+    #[test]
+    fn empty_input() {
+        let page = apply(&parse("aggregate(category,count(id))"), vec![]).unwrap();
+        assert_eq!(page.documents, Vec::<serde_json::Value>::new());
+        let page = apply(&parse("aggregate(count(id),sum(price))"), vec![]).unwrap();
+        assert_eq!(page.documents, vec![json!({"count(id)": 0, "sum(price)": 0})]);
+    }
+
+    #[test]
+    fn standalone() {
+        let docs = json!([
+            {"id": 1, "price": 10, "weight": 1.5, "note": null},
+            {"id": 2, "price": 2.5, "weight": 2},
+            {"id": 3, "price": null},
+            {"id": 4, "weight": 3},
+        ]);
+        let run = |input: &str| {
+            let docs = docs.as_array().unwrap().clone();
+            match evaluate(&parse(input), docs, &EvalOptions::default()).unwrap() {
+                EvalResult::Scalar(value) => value,
+                records => panic!("{:?}", records),
+            }
+        };
+        assert_eq!(run("sum(price)"), json!(12.5));
+        assert_eq!(run("sum(id)"), json!(10));
+        assert_eq!(run("mean(weight)"), json!(6.5 / 3.0));
+        assert_eq!(run("min(weight)"), json!(1.5));
+        assert_eq!(run("max(price)"), json!(10));
+        assert_eq!(run("and(gt(id,1),sum(price))"), json!(2.5));
+        // 値が1つも無い列
+        assert_eq!(run("sum(note)"), json!(0));
+        assert_eq!(run("mean(note)"), json!(null));
+        assert_eq!(run("min(note)"), json!(null));
+        assert_eq!(run("and(eq(id,99),sum(price))"), json!(0));
+        assert_eq!(parse("and(sort(-price),max(price))").to_rql(), "and(sort(-price),max(price))");
+    }
+}
@@ -0,0 +1,171 @@
+//! Comparisons with domain-specific ordering, such as version numbers.
+
+use crate::ast::{Infix, Path, Value};
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+/// Orders a document value against a filter's literal in place of the
+/// built-in comparison, for `eq()`, `ne()`, `lt()`, `le()`, `gt()` and
+/// `ge()`. Set it in [`EvalOptions::comparator`](crate::eval::EvalOptions).
+///
+/// `field` is the filter's path as written in RQL, in its canonical form
+/// ([`Path`]'s `Display`). Return how `json` compares with `literal`, or
+/// `None` to fall back to the built-in comparison.
+///
+/// ```
+/// use rql_parser::ast::Value;
+/// use rql_parser::eval::{Comparator, CustomComparator, EvalOptions};
+/// use rql_parser::parser::Parser;
+/// use std::cmp::Ordering;
+///
+/// // "Tokyo" と "tokyo" を同じにする
+/// struct Cities;
+///
+/// impl CustomComparator for Cities {
+///     fn compare(
+///         &self,
+///         field: &str,
+///         json: &serde_json::Value,
+///         literal: &Value,
+///     ) -> Option<Ordering> {
+///         match (field, json.as_str(), literal.as_str()) {
+///             ("city", Some(a), Some(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let options = EvalOptions {
+///     comparator: Some(Comparator::new(Cities)),
+///     ..EvalOptions::default()
+/// };
+/// let query = Parser::from_str("eq(city,\"tokyo\")").parse_query().unwrap();
+/// assert!(query.matches_with(&serde_json::json!({"city": "Tokyo"}), &options));
+/// ```
+pub trait CustomComparator {
+    fn compare(&self, field: &str, json: &serde_json::Value, literal: &Value) -> Option<Ordering>;
+}
+
+/// A shared [`CustomComparator`], as [`EvalOptions`](crate::eval::EvalOptions)
+/// holds it. Two are equal when they're the same instance.
+#[derive(Clone)]
+pub struct Comparator(Arc<dyn CustomComparator + Send + Sync>);
+
+impl Comparator {
+    pub fn new<C: CustomComparator + Send + Sync + 'static>(comparator: C) -> Self {
+        Comparator(Arc::new(comparator))
+    }
+
+    // 比較器が決めた順序でフィルタが成り立つか。順序で決まらない演算子や、
+    // 比較器が None を返したときは None。パスは比較器に渡すときだけ文字列にする
+    pub(crate) fn holds(
+        &self,
+        infix: &Infix,
+        field: &Path,
+        json: &serde_json::Value,
+        literal: &Value,
+    ) -> Option<bool> {
+        let accepts: fn(Ordering) -> bool = match infix {
+            Infix::Eq => Ordering::is_eq,
+            Infix::NotEq => Ordering::is_ne,
+            Infix::Lt => Ordering::is_lt,
+            Infix::Le => Ordering::is_le,
+            Infix::Gt => Ordering::is_gt,
+            Infix::Ge => Ordering::is_ge,
+            _ => return None,
+        };
+        self.0.compare(&field.to_string(), json, literal).map(accepts)
+    }
+}
+
+impl fmt::Debug for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Comparator(..)")
+    }
+}
+
+impl PartialEq for Comparator {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Value;
+    use crate::eval::{Comparator, CustomComparator, EvalOptions};
+    use crate::parser::Parser;
+    use serde_json::json;
+    use std::cmp::Ordering;
+
+    // "1.10.0" > "1.9.2" になるよう、. で区切った数の列として比べる
+    struct Semver;
+
+    fn parts(s: &str) -> Option<Vec<u64>> {
+        s.split('.').map(|part| part.parse().ok()).collect()
+    }
+
+    impl CustomComparator for Semver {
+        fn compare(
+            &self,
+            field: &str,
+            json: &serde_json::Value,
+            literal: &Value,
+        ) -> Option<Ordering> {
+            if !field.ends_with("version") {
+                return None;
+            }
+            Some(parts(json.as_str()?)?.cmp(&parts(literal.as_str()?)?))
+        }
+    }
+
+    #[test]
+    fn semver() {
+        let options = EvalOptions {
+            comparator: Some(Comparator::new(Semver)),
+            ..EvalOptions::default()
+        };
+        let doc = json!({
+            "app_version": "1.10.0",
+            "name": "1.10.0",
+            "build": "beta",
+            "app": {"version": "1.10.0"}
+        });
+        let matches = |input: &str, options: &EvalOptions| {
+            Parser::from_str(input)
+                .parse_query()
+                .unwrap()
+                .matches_with(&doc, options)
+        };
+        for (input, semver) in [
+            ("ge(app_version,\"1.9.2\")", true),
+            ("gt(app_version,\"1.9.2\")", true),
+            ("lt(app_version,\"1.9.2\")", false),
+            ("le(app_version,\"1.10\")", false),
+            ("eq(app_version,\"1.10.0\")", true),
+            ("ne(app_version,\"1.10.0\")", false),
+            // 入れ子のパスは "app.version" として渡る
+            ("gt(app.version,\"1.9.2\")", true),
+            // 比較器が None を返せば組み込みの比較になり、文字列には順序が無い
+            ("lt(name,\"1.9.2\")", false),
+            ("lt(app_version,\"beta\")", false),
+            ("eq(name,\"1.10.0\")", true),
+            ("startswith(app_version,\"1.1\")", true),
+        ] {
+            assert_eq!(matches(input, &options), semver, "{}", input);
+        }
+        assert!(!matches(
+            "gt(app_version,\"1.9.2\")",
+            &EvalOptions::default()
+        ));
+        assert_eq!(options.clone(), options);
+        assert_ne!(
+            EvalOptions {
+                comparator: Some(Comparator::new(Semver)),
+                ..EvalOptions::default()
+            },
+            options
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Queries prepared for evaluating many documents.
+
+use crate::ast::{Infix, Path, Query, Segment, Value};
+use crate::eval::{compare, contains_folded, haversine, operand, or_operands, DocValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A query with its property paths turned into lookups up front, so that
+/// [`CompiledQuery::matches`] walks documents without allocating. Filters on
+/// the same path share one lookup. Built with [`Query::compile`]; matches
+/// exactly like [`Query::matches`].
+///
+/// Two comparisons still allocate: one between two fields copies the
+/// right-hand value, and, with the `decimal` feature, one between a decimal
+/// literal and a float reads the float through its shortest decimal form.
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    node: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    And(Vec<Node>),
+    Or(Vec<Node>),
+    Not(Box<Node>),
+    Filter(Infix, Arc<Resolver>, Value),
+    // contains() のパスと値
+    Contains(Arc<Resolver>, Value),
+    // exists() なら true、missing() なら false
+    Exists(Arc<Resolver>, bool),
+    // search() のフィールドと、空白で区切った語
+    Search(Vec<Arc<Resolver>>, Vec<String>),
+    // near() のパス、中心 (緯度, 経度) と半径
+    Near(Arc<Resolver>, (f64, f64), f64),
+    // sort() など絞り込まないノード
+    Always,
+    // 左辺が識別子でないフィルター
+    Never,
+}
+
+// パスをたどる手順。キーはクエリ全体で共有する
+#[derive(Debug)]
+struct Resolver {
+    steps: Vec<Step>,
+    wildcard: bool,
+}
+
+#[derive(Debug)]
+enum Step {
+    Key(Arc<str>),
+    // 配列の位置と、数字のキーとして引くときの文字列
+    Index(usize, Arc<str>),
+    Wildcard,
+}
+
+impl Query {
+    pub fn compile(&self) -> CompiledQuery {
+        CompiledQuery {
+            node: Compiler::default().compile(self),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    resolvers: HashMap<Path, Arc<Resolver>>,
+    keys: HashMap<String, Arc<str>>,
+}
+
+impl Compiler {
+    fn compile(&mut self, query: &Query) -> Node {
+        match query {
+            Query::And(queries) => Node::And(queries.iter().map(|q| self.compile(q)).collect()),
+            Query::Or(queries) => Node::Or(or_operands(queries).map(|q| self.compile(q)).collect()),
+            Query::Filter(infix, Value::Identifier(path), literal) => {
+                Node::Filter(infix.clone(), self.resolver(path), literal.clone())
+            }
+            Query::Filter(..) => Node::Never,
+            Query::Not(query) => Node::Not(Box::new(self.compile(query))),
+            Query::In(..) | Query::Out(..) => self.compile(&query.expand_in()),
+            Query::Contains(path, literal) => Node::Contains(self.resolver(path), literal.clone()),
+            Query::Exists(path) => Node::Exists(self.resolver(path), true),
+            Query::Missing(path) => Node::Exists(self.resolver(path), false),
+            Query::Search(fields, term) => Node::Search(
+                fields.iter().map(|f| self.resolver(f)).collect(),
+                term.split_whitespace().map(str::to_owned).collect(),
+            ),
+            Query::Near(path, lat, lon, radius) => {
+                Node::Near(self.resolver(path), (*lat, *lon), *radius)
+            }
+            _ => Node::Always,
+        }
+    }
+
+    fn resolver(&mut self, path: &Path) -> Arc<Resolver> {
+        if let Some(resolver) = self.resolvers.get(path) {
+            return Arc::clone(resolver);
+        }
+        let steps = path
+            .segments()
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => Step::Key(self.key(key)),
+                Segment::Index(i) => Step::Index(*i, self.key(&i.to_string())),
+                Segment::Wildcard => Step::Wildcard,
+            })
+            .collect();
+        let resolver = Arc::new(Resolver {
+            steps,
+            wildcard: path.has_wildcard(),
+        });
+        self.resolvers.insert(path.clone(), Arc::clone(&resolver));
+        resolver
+    }
+
+    fn key(&mut self, key: &str) -> Arc<str> {
+        Arc::clone(
+            self.keys
+                .entry(key.to_owned())
+                .or_insert_with(|| Arc::from(key)),
+        )
+    }
+}
+
+impl Resolver {
+    // Path::visit と同じく、見つかった値ごとに f を呼び、f が true を返したらそこでやめる
+    fn visit<'a>(
+        &self,
+        doc: &'a serde_json::Value,
+        f: &mut dyn FnMut(&'a serde_json::Value) -> bool,
+    ) -> bool {
+        match self.wildcard {
+            true => visit(doc, &self.steps, f),
+            false => self.get(doc).is_some_and(f),
+        }
+    }
+
+    // ワイルドカードの無いパスは再帰せずにたどる
+    fn get<'a>(&self, doc: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        use serde_json::Value::{Array, Object};
+        let mut v = doc;
+        for step in &self.steps {
+            v = match (step, v) {
+                (Step::Key(key), Object(map)) => map.get(&**key)?,
+                (Step::Index(i, _), Array(items)) => items.get(*i)?,
+                (Step::Index(_, key), Object(map)) => map.get(&**key)?,
+                _ => return None,
+            };
+        }
+        Some(v)
+    }
+}
+
+fn visit<'a>(
+    v: &'a serde_json::Value,
+    steps: &[Step],
+    f: &mut dyn FnMut(&'a serde_json::Value) -> bool,
+) -> bool {
+    use serde_json::Value::{Array, Object};
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return f(v),
+    };
+    let child = match (step, v) {
+        (Step::Key(key), Object(map)) => map.get(&**key),
+        (Step::Index(i, _), Array(items)) => items.get(*i),
+        (Step::Index(_, key), Object(map)) => map.get(&**key),
+        (Step::Wildcard, Array(items)) => return items.iter().any(|v| visit(v, rest, f)),
+        (Step::Wildcard, Object(map)) => return map.values().any(|v| visit(v, rest, f)),
+        _ => None,
+    };
+    child.is_some_and(|child| visit(child, rest, f))
+}
+
+// 値の中の文字列に f を呼び、true が返ればそこでやめる。配列の中は常に、
+// オブジェクトの中は objects のときだけ見る
+fn strings(v: &serde_json::Value, objects: bool, f: &mut dyn FnMut(&str) -> bool) -> bool {
+    match v {
+        serde_json::Value::String(s) => f(s),
+        serde_json::Value::Array(items) => items.iter().any(|v| strings(v, objects, f)),
+        serde_json::Value::Object(map) if objects => map.values().any(|v| strings(v, objects, f)),
+        _ => false,
+    }
+}
+
+fn point(v: &serde_json::Value) -> Option<(f64, f64)> {
+    match v {
+        serde_json::Value::Array(items) if items.len() == 2 => {
+            items[1].as_f64().zip(items[0].as_f64())
+        }
+        serde_json::Value::Object(map) => map.get("lat")?.as_f64().zip(map.get("lon")?.as_f64()),
+        _ => None,
+    }
+}
+
+impl CompiledQuery {
+    pub fn matches(&self, doc: &serde_json::Value) -> bool {
+        self.node.matches(doc)
+    }
+}
+
+impl Node {
+    fn matches(&self, doc: &serde_json::Value) -> bool {
+        match self {
+            Node::And(nodes) => nodes.iter().all(|n| n.matches(doc)),
+            Node::Or(nodes) => nodes.iter().any(|n| n.matches(doc)),
+            Node::Filter(infix, resolver, literal) => {
+                let literal = operand(doc, literal);
+                resolver.visit(doc, &mut |v| {
+                    literal
+                        .as_ref()
+                        .is_some_and(|l| compare(infix, l, DocValue::shallow(v)))
+                })
+            }
+            Node::Not(node) => !node.matches(doc),
+            // eval::contains と同じく、配列の要素か文字列の一部
+            Node::Contains(resolver, literal) => {
+                let literal = match operand(doc, literal) {
+                    Some(literal) => literal,
+                    None => return false,
+                };
+                resolver.visit(doc, &mut |v| match v {
+                    serde_json::Value::Array(items) => items
+                        .iter()
+                        .any(|item| Value::eq(&literal, DocValue::shallow(item))),
+                    serde_json::Value::String(s) => literal.as_str().is_some_and(|word| s.contains(word)),
+                    _ => false,
+                })
+            }
+            Node::Exists(resolver, exists) => {
+                resolver.visit(doc, &mut |v| !v.is_null()) == *exists
+            }
+            // eval::search と同じく、語がすべてどれかの文字列に含まれるか
+            Node::Search(fields, words) => words.iter().all(|word| {
+                let found = &mut |s: &str| contains_folded(s, word);
+                match fields.is_empty() {
+                    true => strings(doc, true, found),
+                    false => fields
+                        .iter()
+                        .any(|field| field.visit(doc, &mut |v| strings(v, false, found))),
+                }
+            }),
+            // eval::near と同じく、点は {"lat": .., "lon": ..} か [経度, 緯度]
+            Node::Near(resolver, center, radius) => {
+                !resolver.wildcard
+                    && resolver
+                        .get(doc)
+                        .and_then(point)
+                        .is_some_and(|point| haversine(point, *center) <= *radius)
+            }
+            Node::Always => true,
+            Node::Never => false,
+        }
+    }
+}
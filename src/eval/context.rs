@@ -0,0 +1,197 @@
+//! Values supplied by the host application at evaluation time: `$name`
+//! variables and `name()` functions in value position. Variables are only
+//! parsed with [`ParserOptions::variables`](crate::parser::ParserOptions).
+
+use crate::ast::{Query, Value};
+use crate::eval::{compare, contains, operand, or_operands, EvalError};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+type Function = Box<dyn Fn() -> Value + Send + Sync>;
+
+/// Maps names to the values `$name` and `name()` stand for.
+///
+/// ```
+/// use rql_parser::ast::Value;
+/// use rql_parser::eval::EvalContext;
+/// use rql_parser::parser::{Parser, ParserOptions};
+///
+/// let mut ctx = EvalContext::new();
+/// ctx.variable("current_user", Value::StringLiteral("alice".to_owned()))
+///     .function("now", || Value::IntegerLiteral(1_700_000_000));
+/// let options = ParserOptions {
+///     variables: true,
+///     ..ParserOptions::default()
+/// };
+/// let query = Parser::new_from_string("and(eq(owner,$current_user),gt(expires_at,now()))".to_owned())
+///     .with_options(options)
+///     .parse_query()
+///     .unwrap();
+/// let doc = serde_json::json!({"owner": "alice", "expires_at": 1_800_000_000});
+/// assert_eq!(query.try_matches(&doc, &ctx), Ok(true));
+/// ```
+#[derive(Default)]
+pub struct EvalContext {
+    variables: HashMap<String, Value>,
+    functions: HashMap<String, Function>,
+}
+
+impl EvalContext {
+    pub fn new() -> Self {
+        EvalContext::default()
+    }
+
+    /// Binds `$name` to a constant.
+    pub fn variable(&mut self, name: &str, value: Value) -> &mut Self {
+        self.variables.insert(name.to_owned(), value);
+        self
+    }
+
+    /// Binds `name()` to a callback, called every time a filter uses it.
+    pub fn function<F>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        F: Fn() -> Value + Send + Sync + 'static,
+    {
+        self.functions.insert(name.to_owned(), Box::new(function));
+        self
+    }
+
+    /// Replaces a variable or call by its value; other values are returned as is.
+    pub fn resolve<'a>(&self, value: &'a Value) -> Result<Cow<'a, Value>, EvalError> {
+        let resolved = match value {
+            Value::Variable(name) => self.variables.get(name).cloned(),
+            Value::Call(name) => self.functions.get(name).map(|f| f()),
+            _ => return Ok(Cow::Borrowed(value)),
+        };
+        match resolved {
+            Some(v) => Ok(Cow::Owned(v)),
+            None => Err(EvalError::Unresolved(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Debug for EvalContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EvalContext")
+            .field("variables", &self.variables)
+            .field("functions", &self.functions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Query {
+    /// Like [`Query::matches`], resolving `$name` and `name()` through `ctx`.
+    /// A name `ctx` doesn't know is an [`EvalError::Unresolved`], reported
+    /// only when evaluation reaches it.
+    pub fn try_matches(
+        &self,
+        doc: &serde_json::Value,
+        ctx: &EvalContext,
+    ) -> Result<bool, EvalError> {
+        match self {
+            Query::And(queries) => {
+                for q in queries {
+                    if !q.try_matches(doc, ctx)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Query::Or(queries) => {
+                for q in or_operands(queries) {
+                    if q.try_matches(doc, ctx)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Query::Filter(infix, Value::Identifier(path), literal) => {
+                let literal = ctx.resolve(literal)?;
+                let literal = operand(doc, &literal);
+                Ok(path.visit(doc, &mut |v| {
+                    literal.as_ref().is_some_and(|l| compare(infix, l, v))
+                }))
+            }
+            Query::Not(query) => Ok(!query.try_matches(doc, ctx)?),
+            Query::In(..) | Query::Out(..) => self.expand_in().try_matches(doc, ctx),
+            Query::Contains(path, literal) => Ok(contains(doc, path, &*ctx.resolve(literal)?)),
+            _ => Ok(self.matches(doc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Value;
+    use crate::eval::{EvalContext, EvalError};
+    use crate::parser::{Parser, ParserOptions};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::Arc;
+
+    fn parse(input: &str) -> crate::ast::Query {
+        Parser::new_from_string(input.to_owned())
+            .with_options(ParserOptions {
+                variables: true,
+                ..ParserOptions::default()
+            })
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn variables_and_functions() {
+        let clock = Arc::new(AtomicI64::new(100));
+        let now = clock.clone();
+        let mut ctx = EvalContext::new();
+        ctx.variable("current_user", Value::StringLiteral("alice".to_owned()))
+            .function("now", move || {
+                Value::IntegerLiteral(now.load(Ordering::SeqCst))
+            });
+
+        let query = parse("and(eq(owner,$current_user),gt(expires_at,now()))");
+        let doc = json!({"owner": "alice", "expires_at": 150});
+        assert_eq!(query.try_matches(&doc, &ctx), Ok(true));
+        clock.store(200, Ordering::SeqCst);
+        assert_eq!(query.try_matches(&doc, &ctx), Ok(false));
+        assert_eq!(
+            query.try_matches(&json!({"owner": "bob", "expires_at": 300}), &ctx),
+            Ok(false)
+        );
+        // コンテキスト無しでは解決できないので一致しない
+        assert!(!query.matches(&doc));
+        assert_eq!(
+            query.to_rql(),
+            "and(eq(owner,$current_user),gt(expires_at,now()))"
+        );
+    }
+
+    #[test]
+    fn unresolved() {
+        let ctx = EvalContext::new();
+        let doc = json!({"owner": "alice"});
+        assert_eq!(
+            parse("eq(owner,$me)").try_matches(&doc, &ctx),
+            Err(EvalError::Unresolved("$me".to_owned()))
+        );
+        let err = parse("or(eq(owner,\"alice\"),lt(at,today()))").try_matches(&doc, &ctx);
+        assert_eq!(err, Ok(true));
+        let err = parse("or(eq(owner,\"bob\"),lt(at,today()))").try_matches(&doc, &ctx);
+        assert_eq!(err.unwrap_err().to_string(), "today() is not defined");
+    }
+
+    #[test]
+    fn membership() {
+        let mut ctx = EvalContext::new();
+        ctx.variable("me", Value::StringLiteral("alice".to_owned()));
+        let doc = json!({"owner": "alice", "tags": ["alice", "x"]});
+        assert_eq!(parse("in(owner,(\"bob\",$me))").try_matches(&doc, &ctx), Ok(true));
+        assert_eq!(parse("not(out(owner,($me)))").try_matches(&doc, &ctx), Ok(true));
+        assert_eq!(parse("contains(tags,$me)").try_matches(&doc, &ctx), Ok(true));
+        assert_eq!(
+            parse("not(contains(tags,$you))").try_matches(&doc, &ctx),
+            Err(EvalError::Unresolved("$you".to_owned()))
+        );
+    }
+}
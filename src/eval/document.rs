@@ -0,0 +1,632 @@
+//! What queries are evaluated against: [`Document`] and the values it yields.
+
+use crate::ast::Path;
+#[cfg(any(feature = "toml", feature = "bson"))]
+use crate::ast::Segment;
+use std::borrow::Cow;
+
+/// A document queries can be evaluated against, such as a
+/// `serde_json::Value`, a `toml::Value` with the `toml` feature, a
+/// `bson::Document` with the `bson` feature, or your own record type.
+///
+/// ```
+/// use rql_parser::ast::Path;
+/// use rql_parser::eval::{DocValue, Document};
+/// use rql_parser::parser::Parser;
+/// use std::collections::HashMap;
+///
+/// struct Record(HashMap<String, i64>);
+///
+/// impl Document for Record {
+///     fn get_path(&self, path: &Path) -> Option<DocValue<'_>> {
+///         self.0.get(&path.to_string()).map(|v| DocValue::Integer(*v))
+///     }
+/// }
+///
+/// let mut record = Record(HashMap::new());
+/// record.0.insert("age".to_owned(), 42);
+/// let query = Parser::new_from_string("gt(age,30)".to_owned())
+///     .parse_query()
+///     .unwrap();
+/// assert!(query.matches_document(&record));
+/// ```
+pub trait Document {
+    /// The value at `path`, or `None` if there is none. A path with a
+    /// wildcard yields a [`DocValue::Array`] of every value it reaches.
+    fn get_path(&self, path: &Path) -> Option<DocValue<'_>>;
+
+    /// Every string in the document at any depth, keys aside, which
+    /// `search()` without a field list looks through. There are none by
+    /// default, so such a search doesn't match your documents unless you
+    /// implement this.
+    fn strings(&self) -> Vec<Cow<'_, str>> {
+        vec![]
+    }
+}
+
+/// A value of a [`Document`], borrowing from it where it can.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocValue<'a> {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    // i64 に収まらない正の整数
+    Unsigned(u64),
+    Float(f64),
+    String(Cow<'a, str>),
+    Array(Vec<DocValue<'a>>),
+    /// An object or table. Filters don't compare with it, but `exists()`
+    /// sees it.
+    Object,
+}
+
+impl DocValue<'_> {
+    pub fn is_null(&self) -> bool {
+        matches!(self, DocValue::Null)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DocValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            DocValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Any number as a float, like `serde_json::Value::as_f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            DocValue::Integer(i) => Some(*i as f64),
+            DocValue::Unsigned(u) => Some(*u as f64),
+            DocValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_i128(&self) -> Option<i128> {
+        match self {
+            DocValue::Integer(i) => Some(i128::from(*i)),
+            DocValue::Unsigned(u) => Some(i128::from(*u)),
+            _ => None,
+        }
+    }
+
+    /// The value as JSON; an object becomes `{}` and a non-finite float
+    /// `null`.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            DocValue::Null => serde_json::Value::Null,
+            DocValue::Bool(b) => serde_json::Value::Bool(*b),
+            DocValue::Integer(i) => serde_json::Value::from(*i),
+            DocValue::Unsigned(u) => serde_json::Value::from(*u),
+            DocValue::Float(f) => serde_json::Number::from_f64(*f)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            DocValue::String(s) => serde_json::Value::String(s.to_string()),
+            DocValue::Array(values) => values.iter().map(DocValue::to_json).collect(),
+            DocValue::Object => serde_json::Value::Object(serde_json::Map::new()),
+        }
+    }
+}
+
+impl<'a> From<&'a serde_json::Value> for DocValue<'a> {
+    fn from(value: &'a serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => DocValue::Null,
+            serde_json::Value::Bool(b) => DocValue::Bool(*b),
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => DocValue::Integer(i),
+                (None, Some(u)) => DocValue::Unsigned(u),
+                (None, None) => DocValue::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => DocValue::String(Cow::Borrowed(s)),
+            serde_json::Value::Array(values) => {
+                DocValue::Array(values.iter().map(DocValue::from).collect())
+            }
+            serde_json::Value::Object(_) => DocValue::Object,
+        }
+    }
+}
+
+impl<'a> DocValue<'a> {
+    // 比較だけに使う変換。比較は配列の中を見ないので、要素を写さず空の配列にする
+    pub(crate) fn shallow(value: &'a serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Array(_) => DocValue::Array(Vec::new()),
+            value => DocValue::from(value),
+        }
+    }
+}
+
+impl Document for serde_json::Value {
+    fn get_path(&self, path: &Path) -> Option<DocValue<'_>> {
+        if path.has_wildcard() {
+            let values = path.resolve_all(self).into_iter().map(DocValue::from);
+            return Some(DocValue::Array(values.collect()));
+        }
+        path.resolve(self).map(DocValue::from)
+    }
+
+    fn strings(&self) -> Vec<Cow<'_, str>> {
+        fn visit<'a>(v: &'a serde_json::Value, found: &mut Vec<Cow<'a, str>>) {
+            match v {
+                serde_json::Value::String(s) => found.push(Cow::Borrowed(s)),
+                serde_json::Value::Array(values) => values.iter().for_each(|v| visit(v, found)),
+                serde_json::Value::Object(map) => map.values().for_each(|v| visit(v, found)),
+                _ => {}
+            }
+        }
+        let mut found = vec![];
+        visit(self, &mut found);
+        found
+    }
+}
+
+/// Tables are objects, resolved like JSON objects. Datetimes are strings of
+/// their TOML text, which is RFC 3339 for offset datetimes, so they compare
+/// with `date:` literals like JSON strings do.
+#[cfg(feature = "toml")]
+impl Document for toml::Value {
+    fn get_path(&self, path: &Path) -> Option<DocValue<'_>> {
+        let mut found = vec![];
+        toml_visit(self, path.segments(), &mut found);
+        if path.has_wildcard() {
+            return Some(DocValue::Array(found));
+        }
+        found.pop()
+    }
+
+    fn strings(&self) -> Vec<Cow<'_, str>> {
+        fn visit<'a>(v: &'a toml::Value, found: &mut Vec<Cow<'a, str>>) {
+            match v {
+                toml::Value::String(s) => found.push(Cow::Borrowed(s)),
+                toml::Value::Array(values) => values.iter().for_each(|v| visit(v, found)),
+                toml::Value::Table(table) => table.values().for_each(|v| visit(v, found)),
+                _ => {}
+            }
+        }
+        let mut found = vec![];
+        visit(self, &mut found);
+        found
+    }
+}
+
+#[cfg(feature = "toml")]
+fn toml_visit<'a>(v: &'a toml::Value, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+    use toml::Value::{Array, Table};
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return found.push(DocValue::from(v)),
+    };
+    let child = match (segment, v) {
+        (Segment::Key(key), Table(table)) => table.get(key),
+        (Segment::Index(i), Array(items)) => items.get(*i),
+        (Segment::Index(i), Table(table)) => table.get(&i.to_string()),
+        (Segment::Wildcard, Array(items)) => {
+            return items.iter().for_each(|v| toml_visit(v, rest, found))
+        }
+        (Segment::Wildcard, Table(table)) => {
+            return table.values().for_each(|v| toml_visit(v, rest, found))
+        }
+        _ => None,
+    };
+    if let Some(child) = child {
+        toml_visit(child, rest, found);
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<'a> From<&'a toml::Value> for DocValue<'a> {
+    fn from(value: &'a toml::Value) -> Self {
+        match value {
+            toml::Value::String(s) => DocValue::String(Cow::Borrowed(s)),
+            toml::Value::Integer(i) => DocValue::Integer(*i),
+            toml::Value::Float(f) => DocValue::Float(*f),
+            toml::Value::Boolean(b) => DocValue::Bool(*b),
+            toml::Value::Datetime(d) => DocValue::String(Cow::Owned(d.to_string())),
+            toml::Value::Array(values) => {
+                DocValue::Array(values.iter().map(DocValue::from).collect())
+            }
+            toml::Value::Table(_) => DocValue::Object,
+        }
+    }
+}
+
+/// Embedded documents are objects, resolved like JSON objects. Int32, Int64
+/// and Double are numbers and compare with each other; Decimal128 is read as
+/// a float. Datetimes are RFC 3339 strings, so they compare with `date:`
+/// literals, and object IDs are hex strings. Binary data, regular
+/// expressions, timestamps and the other BSON types only count for
+/// `exists()`.
+#[cfg(feature = "bson")]
+impl Document for bson::Document {
+    fn get_path(&self, path: &Path) -> Option<DocValue<'_>> {
+        let mut found = vec![];
+        match path.segments().split_first() {
+            Some((segment, rest)) => bson_visit_fields(self, segment, rest, &mut found),
+            None => found.push(DocValue::Object),
+        }
+        if path.has_wildcard() {
+            return Some(DocValue::Array(found));
+        }
+        found.pop()
+    }
+
+    fn strings(&self) -> Vec<Cow<'_, str>> {
+        fn visit<'a>(v: &'a bson::Bson, found: &mut Vec<Cow<'a, str>>) {
+            match v {
+                bson::Bson::String(s) | bson::Bson::Symbol(s) => found.push(Cow::Borrowed(s)),
+                bson::Bson::Array(values) => values.iter().for_each(|v| visit(v, found)),
+                bson::Bson::Document(doc) => doc.values().for_each(|v| visit(v, found)),
+                _ => {}
+            }
+        }
+        let mut found = vec![];
+        self.values().for_each(|v| visit(v, &mut found));
+        found
+    }
+}
+
+#[cfg(feature = "bson")]
+fn bson_visit<'a>(v: &'a bson::Bson, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+    let (segment, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return found.push(DocValue::from(v)),
+    };
+    match (segment, v) {
+        (_, bson::Bson::Document(doc)) => bson_visit_fields(doc, segment, rest, found),
+        (Segment::Index(i), bson::Bson::Array(items)) => {
+            if let Some(item) = items.get(*i) {
+                bson_visit(item, rest, found);
+            }
+        }
+        (Segment::Wildcard, bson::Bson::Array(items)) => {
+            items.iter().for_each(|v| bson_visit(v, rest, found))
+        }
+        _ => {}
+    }
+}
+
+#[cfg(feature = "bson")]
+fn bson_visit_fields<'a>(
+    doc: &'a bson::Document,
+    segment: &Segment,
+    rest: &[Segment],
+    found: &mut Vec<DocValue<'a>>,
+) {
+    let child = match segment {
+        Segment::Key(key) => doc.get(key),
+        Segment::Index(i) => doc.get(i.to_string()),
+        Segment::Wildcard => return doc.values().for_each(|v| bson_visit(v, rest, found)),
+    };
+    if let Some(child) = child {
+        bson_visit(child, rest, found);
+    }
+}
+
+#[cfg(feature = "bson")]
+impl<'a> From<&'a bson::Bson> for DocValue<'a> {
+    fn from(value: &'a bson::Bson) -> Self {
+        use bson::Bson;
+        match value {
+            Bson::Null | Bson::Undefined => DocValue::Null,
+            Bson::Boolean(b) => DocValue::Bool(*b),
+            Bson::Int32(i) => DocValue::Integer(i64::from(*i)),
+            Bson::Int64(i) => DocValue::Integer(*i),
+            Bson::Double(f) => DocValue::Float(*f),
+            Bson::Decimal128(d) => DocValue::Float(d.to_string().parse().unwrap_or(f64::NAN)),
+            Bson::String(s) | Bson::Symbol(s) => DocValue::String(Cow::Borrowed(s)),
+            Bson::DateTime(datetime) => DocValue::String(Cow::Owned(
+                datetime
+                    .try_to_rfc3339_string()
+                    .unwrap_or_else(|_| datetime.to_string()),
+            )),
+            Bson::ObjectId(id) => DocValue::String(Cow::Owned(id.to_hex())),
+            Bson::Array(values) => DocValue::Array(values.iter().map(DocValue::from).collect()),
+            _ => DocValue::Object,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Path;
+    use crate::eval::{DocValue, Document, EvalOptions};
+    use crate::parser::Parser;
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    enum Scalar {
+        Text(String),
+        Number(f64),
+        Flag(bool),
+    }
+
+    // JSON にせずに評価できる独自のレコード
+    struct Record {
+        fields: HashMap<String, Scalar>,
+        tags: Vec<String>,
+    }
+
+    impl Document for Record {
+        fn get_path(&self, path: &Path) -> Option<DocValue<'_>> {
+            let path = path.to_string();
+            if path == "tags" || path == "tags.*" {
+                let tags = self.tags.iter().map(|t| DocValue::String(Cow::Borrowed(t)));
+                return Some(DocValue::Array(tags.collect()));
+            }
+            Some(match self.fields.get(&path)? {
+                Scalar::Text(s) => DocValue::String(Cow::Borrowed(s)),
+                Scalar::Number(n) => DocValue::Float(*n),
+                Scalar::Flag(b) => DocValue::Bool(*b),
+            })
+        }
+    }
+
+    fn record() -> Record {
+        let mut fields = HashMap::new();
+        fields.insert("name".to_owned(), Scalar::Text("alice".to_owned()));
+        fields.insert("score".to_owned(), Scalar::Number(87.5));
+        fields.insert("admin".to_owned(), Scalar::Flag(false));
+        fields.insert("nick".to_owned(), Scalar::Text("alice".to_owned()));
+        Record {
+            fields,
+            tags: vec!["rust".to_owned(), "toml".to_owned()],
+        }
+    }
+
+    fn matches(input: &str, doc: &dyn Document) -> bool {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .matches_document(doc)
+    }
+
+    #[test]
+    fn custom_document() {
+        let record = record();
+        assert!(matches("eq(name,\"alice\")", &record));
+        assert!(matches("and(gt(score,80.0),eq(admin,false))", &record));
+        assert!(matches("startswith(name,\"al\")", &record));
+        assert!(matches("eq(tags.*,\"toml\")", &record));
+        assert!(!matches("eq(tags,\"toml\")", &record));
+        assert!(matches("eq(nick,name)", &record));
+        assert!(matches("and(exists(score),missing(email))", &record));
+        assert!(!matches("or(lt(score,50.0),eq(admin,true))", &record));
+        assert!(Parser::new_from_string("eq(name,\"alice\")".to_owned())
+            .parse_query()
+            .unwrap()
+            .matches_document_with(&record, &EvalOptions::default()));
+    }
+
+    #[test]
+    fn json_document() {
+        let doc = serde_json::json!({"a": [1, {"b": "x"}], "n": null, "big": u64::MAX});
+        let get = |path: &str| doc.get_path(&Path::from(path));
+        assert_eq!(get("a.0"), Some(DocValue::Integer(1)));
+        assert_eq!(get("a.1"), Some(DocValue::Object));
+        assert_eq!(get("a.1.b"), Some(DocValue::String(Cow::Borrowed("x"))));
+        assert_eq!(get("n"), Some(DocValue::Null));
+        assert_eq!(get("big"), Some(DocValue::Unsigned(u64::MAX)));
+        assert_eq!(get("missing"), None);
+        assert_eq!(
+            get("a.*"),
+            Some(DocValue::Array(vec![DocValue::Integer(1), DocValue::Object]))
+        );
+        assert_eq!(get("a.1.b").unwrap().to_json(), "x");
+    }
+
+    #[cfg(feature = "toml")]
+    mod toml {
+        use crate::eval::{EvalOptions, FieldType, Schema};
+        use crate::parser::Parser;
+
+        const CONFIG: &str = r#"
+title = "production"
+owner = { name = "ops", since = 1979-05-27T07:32:00-08:00 }
+
+[server]
+host = "10.0.0.1"
+port = 8080
+timeout = 2.5
+tls = true
+
+[database]
+ports = [8000, 8001, 8002]
+max_connections = "500"
+
+[[replicas]]
+name = "alpha"
+weight = 0.7
+
+[[replicas]]
+name = "beta"
+weight = 0.3
+"#;
+
+        fn matches(input: &str) -> bool {
+            matches_with(input, &EvalOptions::default())
+        }
+
+        fn matches_with(input: &str, options: &EvalOptions) -> bool {
+            let config: toml::Value = toml::from_str(CONFIG).unwrap();
+            Parser::new_from_string(input.to_owned())
+                .parse_query()
+                .unwrap()
+                .matches_document_with(&config, options)
+        }
+
+        #[test]
+        fn toml() {
+            assert!(matches("eq(server.port,8080)"));
+            assert!(matches("and(eq(title,\"production\"),eq(server.tls,true))"));
+            assert!(matches("lt(server.timeout,3.0)"));
+            assert!(matches("gt(server.port,8079.5)"));
+            assert!(matches("eq(database.ports.1,8001)"));
+            assert!(matches("eq(database.ports.*,8002)"));
+            assert!(matches("eq(replicas.*.name,\"beta\")"));
+            assert!(matches("ge(replicas.0.weight,0.7)"));
+            assert!(matches("startswith(owner.since,\"1979-05-27\")"));
+            assert!(matches("exists(owner.name)"));
+            assert!(matches("exists(server)"));
+            assert!(matches("missing(server.user)"));
+            assert!(!matches("eq(server.port,\"8080\")"));
+            assert!(!matches("gt(database.ports.*,9000)"));
+
+            // 文字列で書かれた数値も JSON と同じく Schema で読める
+            assert!(!matches("gt(database.max_connections,100)"));
+            let mut schema = Schema::new();
+            schema.field("database.max_connections", FieldType::Integer);
+            let options = EvalOptions {
+                schema: Some(schema),
+                ..EvalOptions::default()
+            };
+            assert!(matches_with("gt(database.max_connections,100)", &options));
+        }
+
+        #[cfg(feature = "chrono")]
+        #[test]
+        fn toml_datetime() {
+            assert!(matches("lt(owner.since,date:1979-05-28)"));
+            assert!(matches("gt(owner.since,date:1979-05-27T15:00:00Z)"));
+        }
+    }
+
+    #[cfg(feature = "bson")]
+    mod bson {
+        use crate::ast::Path;
+        use crate::eval::{DocValue, Document};
+        use crate::parser::Parser;
+        use bson::{doc, oid::ObjectId, Bson, Decimal128};
+        use serde_json::json;
+        use std::borrow::Cow;
+
+        // 同じ内容の BSON と JSON
+        fn docs() -> (bson::Document, serde_json::Value) {
+            let id = ObjectId::parse_str("65a1b2c3d4e5f60718293a4b").unwrap();
+            let bson = doc! {
+                "_id": id,
+                "small": 5i32,
+                "large": 9_000_000_000i64,
+                "ratio": 0.25,
+                "price": "19.90".parse::<Decimal128>().unwrap(),
+                "name": "alice",
+                "active": true,
+                "note": Bson::Null,
+                "address": {"city": "Tokyo", "zip": "100"},
+                "scores": [1i32, 2i64, 3.5],
+                "items": [{"sku": "A", "qty": 2i32}, {"sku": "B", "qty": 10i64}],
+                "created": bson::DateTime::from_millis(1704067200000),
+            };
+            let json = json!({
+                "_id": "65a1b2c3d4e5f60718293a4b",
+                "small": 5,
+                "large": 9_000_000_000i64,
+                "ratio": 0.25,
+                "price": 19.9,
+                "name": "alice",
+                "active": true,
+                "note": null,
+                "address": {"city": "Tokyo", "zip": "100"},
+                "scores": [1, 2, 3.5],
+                "items": [{"sku": "A", "qty": 2}, {"sku": "B", "qty": 10}],
+                "created": "2024-01-01T00:00:00Z",
+            });
+            (bson, json)
+        }
+
+        #[test]
+        fn same_as_json() {
+            let (bson, json) = docs();
+            for input in [
+                "eq(small,5)",
+                "eq(small,5.0)",
+                "gt(large,8999999999)",
+                "and(gt(large,5),lt(ratio,0.3))",
+                "lt(price,20)",
+                "gt(price,19.89)",
+                "eq(price,19.9)",
+                "eq(_id,\"65a1b2c3d4e5f60718293a4b\")",
+                "and(eq(name,\"alice\"),eq(active,true))",
+                "ne(name,\"bob\")",
+                "startswith(address.zip,\"10\")",
+                "eq(address.*,\"Tokyo\")",
+                "eq(scores.*,2)",
+                "eq(scores.2,3.5)",
+                "gt(scores.*,3)",
+                "and(eq(items.*.sku,\"B\"),gt(items.*.qty,5))",
+                "eq(items.0.qty,2)",
+                "startswith(created,\"2024-01-01\")",
+                "exists(note)",
+                "missing(note)",
+                "missing(address.country)",
+                "exists(address)",
+                "ieq(name,\"ALICE\")",
+                "search(\"tokyo\")",
+                "search((name),\"ali\")",
+            ] {
+                let query = Parser::from_str(input).parse_query().unwrap();
+                assert_eq!(query.matches_bson(&bson), query.matches(&json), "{}", input);
+            }
+        }
+
+        #[test]
+        fn values() {
+            let (bson, _) = docs();
+            let get = |path: &str| bson.get_path(&Path::from(path));
+            assert_eq!(get("small"), Some(DocValue::Integer(5)));
+            assert_eq!(get("large"), Some(DocValue::Integer(9_000_000_000)));
+            assert_eq!(get("price"), Some(DocValue::Float(19.9)));
+            assert_eq!(get("note"), Some(DocValue::Null));
+            assert_eq!(get("address"), Some(DocValue::Object));
+            assert_eq!(get("address.city"), Some(DocValue::String(Cow::Borrowed("Tokyo"))));
+            assert_eq!(get("address.country"), None);
+            assert_eq!(
+                get("created"),
+                Some(DocValue::String(Cow::Borrowed("2024-01-01T00:00:00Z")))
+            );
+            assert_eq!(
+                get("items.*.qty"),
+                Some(DocValue::Array(vec![DocValue::Integer(2), DocValue::Integer(10)]))
+            );
+            assert_eq!(get("scores.name"), None);
+            let binary = doc! {"data": Bson::Binary(bson::Binary {
+                subtype: bson::spec::BinarySubtype::Generic,
+                bytes: vec![1, 2],
+            })};
+            assert_eq!(binary.get_path(&Path::from("data")), Some(DocValue::Object));
+        }
+
+        // Int32、Int64、Double、Decimal128 は互いに数値として比べる
+        #[test]
+        fn numbers() {
+            let (bson, _) = docs();
+            let matches = |input: &str| {
+                let query = Parser::from_str(input).parse_query().unwrap();
+                query.matches_bson(&bson)
+            };
+            assert!(matches("and(eq(small,5),eq(small,5.0),eq(large,9000000000.0))"));
+            assert!(matches("and(gt(price,19.89),lt(price,19.91))"));
+            assert!(matches("and(eq(scores.0,1),eq(scores.1,2.0),gt(scores.2,3.0))"));
+            assert!(!matches("eq(small,\"5\")"));
+        }
+
+        #[cfg(feature = "chrono")]
+        #[test]
+        fn dates() {
+            let (bson, _) = docs();
+            let matches = |input: &str| {
+                let query = Parser::from_str(input).parse_query().unwrap();
+                query.matches_bson(&bson)
+            };
+            assert!(matches("eq(created,date:2024-01-01T09:00:00+09:00)"));
+            assert!(matches("lt(created,date:2024-01-02)"));
+            assert!(!matches("gt(created,date:2024-01-01T00:00:01Z)"));
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! Annotated evaluation of a query against one document, for answering "why
+//! did (or didn't) this record match?".
+
+use crate::ast::{Query, Value};
+use crate::convert::json_value;
+use crate::eval::{compare, is_shaping, operand, or_operands};
+use serde::Serialize;
+use std::fmt;
+
+/// Tree mirroring the query, with the outcome of every node.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "node", rename_all = "snake_case")]
+pub enum Explain {
+    /// `decided_by` is the index of the first child that failed, or `None`
+    /// when every child matched.
+    And {
+        matched: bool,
+        decided_by: Option<usize>,
+        children: Vec<Explain>,
+    },
+    /// `decided_by` is the index of the first child that matched, or `None`
+    /// when none did.
+    Or {
+        matched: bool,
+        decided_by: Option<usize>,
+        children: Vec<Explain>,
+    },
+    /// `value` is what `path` resolved to in the document, `None` if missing.
+    Filter {
+        matched: bool,
+        path: String,
+        operator: &'static str,
+        literal: serde_json::Value,
+        value: Option<serde_json::Value>,
+    },
+    /// Nodes that don't filter (`sort()`, `limit()`, ...) always match.
+    Other { matched: bool, query: String },
+}
+
+impl Explain {
+    pub fn matched(&self) -> bool {
+        match self {
+            Explain::And { matched, .. }
+            | Explain::Or { matched, .. }
+            | Explain::Filter { matched, .. }
+            | Explain::Other { matched, .. } => *matched,
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let indent = "  ".repeat(depth);
+        let mark = if self.matched() { "match" } else { "no match" };
+        match self {
+            Explain::And {
+                decided_by,
+                children,
+                ..
+            }
+            | Explain::Or {
+                decided_by,
+                children,
+                ..
+            } => {
+                let name = if let Explain::And { .. } = self { "and" } else { "or" };
+                write!(f, "{}{}: {}", indent, name, mark)?;
+                if let Some(i) = decided_by {
+                    write!(f, " (decided by #{})", i + 1)?;
+                }
+                for child in children {
+                    writeln!(f)?;
+                    child.write(f, depth + 1)?;
+                }
+                Ok(())
+            }
+            Explain::Filter {
+                path,
+                operator,
+                literal,
+                value,
+                ..
+            } => {
+                write!(f, "{}{}({},{}): {} ", indent, operator, path, literal, mark)?;
+                match value {
+                    Some(value) => write!(f, "({} = {})", path, value),
+                    None => write!(f, "({} is missing)", path),
+                }
+            }
+            Explain::Other { query, .. } => write!(f, "{}{}: {}", indent, query, mark),
+        }
+    }
+}
+
+impl fmt::Display for Explain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// A leaf of the query a document failed, from [`Query::failing_conditions`].
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct FailedFilter {
+    pub path: String,
+    /// The RQL keyword of the comparison, or `in`, `out`, `contains`,
+    /// `exists` or `missing`.
+    pub operator: &'static str,
+    /// What the document was compared with, an array for `in()` and `out()`;
+    /// `None` for `exists()` and `missing()`.
+    pub literal: Option<serde_json::Value>,
+    /// What `path` resolved to in the document, `None` if missing.
+    pub value: Option<serde_json::Value>,
+    /// Set when the filter is a branch of an `or()` none of whose branches
+    /// matched: filters with the same number are alternatives, any of which
+    /// would have satisfied that `or()`. Groups are numbered from 0.
+    pub or_group: Option<usize>,
+}
+
+impl fmt::Display for FailedFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.literal {
+            Some(literal) => write!(f, "{}({},{}): ", self.operator, self.path, literal)?,
+            None => write!(f, "{}({}): ", self.operator, self.path)?,
+        }
+        match &self.value {
+            Some(value) => write!(f, "{} = {}", self.path, value)?,
+            None => write!(f, "{} is missing", self.path)?,
+        }
+        if let Some(group) = self.or_group {
+            write!(f, " (no branch of or #{} matched)", group + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Query {
+    /// The filters that made `doc` not match, empty if it matches. Every
+    /// failing child of an `and()` is reported, and every branch of an
+    /// `or()` that failed as a whole, marked with [`FailedFilter::or_group`];
+    /// filters that passed, or sit in an `or()` another branch satisfied,
+    /// are left out. So is a failing `not()`, which failed on conditions
+    /// that matched.
+    ///
+    /// ```
+    /// use rql_parser::parser::Parser;
+    /// use serde_json::json;
+    ///
+    /// let query = Parser::from_str("and(eq(status,\"open\"),gt(age,30))").parse_query().unwrap();
+    /// let failed = query.failing_conditions(&json!({"status": "open", "age": 25}));
+    /// assert_eq!(failed.len(), 1);
+    /// assert_eq!(failed[0].to_string(), "gt(age,30): age = 25");
+    /// ```
+    pub fn failing_conditions(&self, doc: &serde_json::Value) -> Vec<FailedFilter> {
+        let mut failed = vec![];
+        self.collect_failed(doc, None, &mut 0, &mut failed);
+        failed
+    }
+
+    fn collect_failed(
+        &self,
+        doc: &serde_json::Value,
+        group: Option<usize>,
+        groups: &mut usize,
+        failed: &mut Vec<FailedFilter>,
+    ) {
+        match self {
+            Query::And(queries) => {
+                for query in queries.iter().filter(|q| !q.matches(doc)) {
+                    query.collect_failed(doc, group, groups, failed);
+                }
+            }
+            Query::Or(queries) => {
+                if or_operands(queries).any(|q| q.matches(doc)) {
+                    return;
+                }
+                let group = *groups;
+                *groups += 1;
+                for query in queries {
+                    query.collect_failed(doc, Some(group), groups, failed);
+                }
+            }
+            Query::Filter(..) => {
+                if let Explain::Filter {
+                    matched: false,
+                    path,
+                    operator,
+                    literal,
+                    value,
+                } = self.explain(doc)
+                {
+                    failed.push(FailedFilter {
+                        path,
+                        operator,
+                        literal: Some(literal),
+                        value,
+                        or_group: group,
+                    });
+                }
+            }
+            Query::In(path, values) | Query::Out(path, values) if !self.matches(doc) => {
+                failed.push(FailedFilter {
+                    path: path.to_string(),
+                    operator: if let Query::In(..) = self { "in" } else { "out" },
+                    literal: Some(values.iter().map(json_value).collect()),
+                    value: path.resolve(doc).cloned(),
+                    or_group: group,
+                });
+            }
+            Query::Contains(path, literal) if !self.matches(doc) => {
+                failed.push(FailedFilter {
+                    path: path.to_string(),
+                    operator: "contains",
+                    literal: Some(json_value(literal)),
+                    value: path.resolve(doc).cloned(),
+                    or_group: group,
+                });
+            }
+            Query::Exists(path) | Query::Missing(path) if !self.matches(doc) => {
+                failed.push(FailedFilter {
+                    path: path.to_string(),
+                    operator: if let Query::Exists(_) = self { "exists" } else { "missing" },
+                    literal: None,
+                    value: path.resolve(doc).cloned(),
+                    or_group: group,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Evaluates the query like [`Query::matches`], keeping the outcome of every
+    /// node. Unlike `matches`, every child of and/or is evaluated.
+    pub fn explain(&self, doc: &serde_json::Value) -> Explain {
+        match self {
+            Query::And(queries) => {
+                let children: Vec<Explain> = queries.iter().map(|q| q.explain(doc)).collect();
+                let decided_by = children.iter().position(|c| !c.matched());
+                Explain::And {
+                    matched: decided_by.is_none(),
+                    decided_by,
+                    children,
+                }
+            }
+            Query::Or(queries) => {
+                let children: Vec<Explain> = queries.iter().map(|q| q.explain(doc)).collect();
+                // 整形するノードは or() の結果を決めない
+                let decided_by = queries
+                    .iter()
+                    .zip(&children)
+                    .position(|(q, c)| !is_shaping(q) && c.matched());
+                Explain::Or {
+                    matched: decided_by.is_some(),
+                    decided_by,
+                    children,
+                }
+            }
+            Query::Filter(infix, ident, literal) => {
+                let values = match ident {
+                    Value::Identifier(path) => path.resolve_all(doc),
+                    _ => vec![],
+                };
+                // ワイルドカードでは一致した値、無ければ最初の値を示す
+                let l = operand(doc, literal);
+                let hit = values
+                    .iter()
+                    .position(|v| l.as_ref().is_some_and(|l| compare(infix, l, *v)));
+                Explain::Filter {
+                    matched: hit.is_some(),
+                    path: ident.to_string(),
+                    operator: infix.keyword(),
+                    literal: json_value(literal),
+                    value: values.get(hit.unwrap_or(0)).map(|v| (*v).clone()),
+                }
+            }
+            _ => Explain::Other {
+                matched: self.matches(doc),
+                query: self.to_rql(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{Explain, FailedFilter};
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    #[test]
+    fn nested() {
+        let query = Parser::new_from_string(
+            "and(eq(name,\"alice\"),or(gt(age,50),eq(address.country,\"JP\")),lt(score,10))"
+                .to_owned(),
+        )
+        .parse_query()
+        .unwrap();
+        let doc = json!({"name": "alice", "age": 42, "address": {"country": "JP"}});
+        let explain = query.explain(&doc);
+        assert!(!explain.matched());
+        assert_eq!(explain.matched(), query.matches(&doc));
+        match &explain {
+            Explain::And {
+                decided_by,
+                children,
+                ..
+            } => {
+                assert_eq!(*decided_by, Some(2));
+                assert!(matches!(children[1], Explain::Or { decided_by: Some(1), .. }));
+            }
+            e => panic!("unexpected {:?}", e),
+        }
+        assert_eq!(
+            explain.to_string(),
+            "and: no match (decided by #3)\n\
+             \x20 eq(name,\"alice\"): match (name = \"alice\")\n\
+             \x20 or: match (decided by #2)\n\
+             \x20   gt(age,50): no match (age = 42)\n\
+             \x20   eq(address.country,\"JP\"): match (address.country = \"JP\")\n\
+             \x20 lt(score,10): no match (score is missing)"
+        );
+        let json = serde_json::to_value(&explain).unwrap();
+        assert_eq!(json["node"], "and");
+        assert_eq!(
+            json["children"][2],
+            json!({"node": "filter", "matched": false, "path": "score", "operator": "lt",
+                   "literal": 10, "value": null})
+        );
+    }
+
+    #[test]
+    fn failing_conjunct() {
+        let input = "and(eq(name,\"alice\"),ge(age,18),eq(address.country,\"JP\"))";
+        let query = Parser::from_str(input).parse_query().unwrap();
+        let doc = json!({"name": "alice", "age": 42, "address": {"country": "US"}});
+        let failed = query.failing_conditions(&doc);
+        assert_eq!(
+            failed,
+            vec![FailedFilter {
+                path: "address.country".to_owned(),
+                operator: "eq",
+                literal: Some(json!("JP")),
+                value: Some(json!("US")),
+                or_group: None,
+            }]
+        );
+        assert_eq!(
+            failed[0].to_string(),
+            "eq(address.country,\"JP\"): address.country = \"US\""
+        );
+        assert_eq!(
+            serde_json::to_value(&failed[0]).unwrap(),
+            json!({"path": "address.country", "operator": "eq", "literal": "JP",
+                   "value": "US", "or_group": null})
+        );
+        assert!(query
+            .failing_conditions(&json!({"name": "alice", "age": 42, "address": {"country": "JP"}}))
+            .is_empty());
+    }
+
+    #[test]
+    fn failing_or() {
+        let input = "and(or(eq(role,\"admin\"),and(eq(role,\"editor\"),exists(team))),\
+                     or(gt(age,50),lt(age,20)),sort(+age))";
+        let query = Parser::from_str(input).parse_query().unwrap();
+        let doc = json!({"role": "viewer", "age": 30});
+        let failed: Vec<String> = query
+            .failing_conditions(&doc)
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                "eq(role,\"admin\"): role = \"viewer\" (no branch of or #1 matched)",
+                "eq(role,\"editor\"): role = \"viewer\" (no branch of or #1 matched)",
+                "exists(team): team is missing (no branch of or #1 matched)",
+                "gt(age,50): age = 30 (no branch of or #2 matched)",
+                "lt(age,20): age = 30 (no branch of or #2 matched)",
+            ]
+        );
+        // 満たされた or() の中の失敗は原因ではない
+        let doc = json!({"role": "admin", "age": 30});
+        assert_eq!(query.failing_conditions(&doc).len(), 2);
+    }
+
+    #[test]
+    fn failing_membership() {
+        let input = "and(in(role,(\"admin\",\"editor\")),contains(tags,\"x\"),not(eq(age,30)))";
+        let query = Parser::from_str(input).parse_query().unwrap();
+        let failed: Vec<String> = query
+            .failing_conditions(&json!({"role": "viewer", "tags": ["y"], "age": 30}))
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        assert_eq!(
+            failed,
+            vec![
+                "in(role,[\"admin\",\"editor\"]): role = \"viewer\"",
+                "contains(tags,\"x\"): tags = [\"y\"]",
+            ]
+        );
+    }
+}
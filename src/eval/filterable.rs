@@ -0,0 +1,193 @@
+//! Filtering Rust structs directly: [`Filterable`], usually derived with
+//! `rql_parser_macros::Filterable`.
+
+use crate::ast::{Path, Query, Segment};
+use crate::eval::{DocValue, Document, EvalOptions};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+/// A struct or field value queries can read without serializing it.
+///
+/// `#[derive(Filterable)]` from `rql-parser-macros` implements it for structs
+/// with named fields, reading each field under its name or under
+/// `#[filterable(rename = "...")]`. Fields may be numbers, `bool`, `char`,
+/// strings, other `Filterable` structs, and `Option`, `Vec` and `Box` of
+/// those. `None` is a missing field, so `missing(nickname)` matches it. A
+/// `Vec` is an array: `eq(tags.*,"rust")` matches when any element is
+/// `"rust"`, and `tags.0` is the first element.
+///
+/// ```
+/// use rql_parser::ast::{Path, Segment};
+/// use rql_parser::eval::{DocValue, Filterable};
+/// use rql_parser::parser::Parser;
+///
+/// // derive が生成するものと同じ
+/// struct User {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// impl Filterable for User {
+///     fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+///         match segments.split_first() {
+///             None => found.push(DocValue::Object),
+///             Some((Segment::Key(key), rest)) => match key.as_str() {
+///                 "name" => self.name.visit_field(rest, found),
+///                 "age" => self.age.visit_field(rest, found),
+///                 _ => {}
+///             },
+///             Some((Segment::Wildcard, rest)) => {
+///                 self.name.visit_field(rest, found);
+///                 self.age.visit_field(rest, found);
+///             }
+///             Some(_) => {}
+///         }
+///     }
+/// }
+///
+/// let user = User { name: "alice".to_owned(), age: 42 };
+/// assert_eq!(user.get_field(&Path::from("age")), Some(DocValue::Integer(42)));
+/// let query = Parser::from_str("and(eq(name,\"alice\"),gt(age,30))").parse_query().unwrap();
+/// assert!(query.matches_struct(&user));
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` isn't Filterable",
+    label = "queries can't read this type",
+    note = "fields can be numbers, bool, char, strings, Filterable structs, \
+            and Option, Vec and Box of those"
+)]
+pub trait Filterable {
+    /// Pushes every value `segments` reaches from `self` onto `found`: `self`
+    /// itself when there are no segments left, nothing when the path doesn't
+    /// exist.
+    fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>);
+
+    /// The value at `path`, like [`Document::get_path`]: a path with a
+    /// wildcard yields a [`DocValue::Array`] of every value it reaches.
+    fn get_field(&self, path: &Path) -> Option<DocValue<'_>> {
+        let mut found = vec![];
+        self.visit_field(path.segments(), &mut found);
+        if path.has_wildcard() {
+            return Some(DocValue::Array(found));
+        }
+        found.pop()
+    }
+}
+
+impl Query {
+    /// Same as [`Query::matches`] for a [`Filterable`] struct, read field by
+    /// field without going through JSON.
+    pub fn matches_struct<T: Filterable + ?Sized>(&self, value: &T) -> bool {
+        self.matches_struct_with(value, &EvalOptions::default())
+    }
+
+    /// Same as [`Query::matches_with`] for a [`Filterable`] struct.
+    pub fn matches_struct_with<T: Filterable + ?Sized>(
+        &self,
+        value: &T,
+        options: &EvalOptions,
+    ) -> bool {
+        self.matches_document_with(&Fields(value), options)
+    }
+}
+
+struct Fields<'a, T: ?Sized>(&'a T);
+
+impl<T: Filterable + ?Sized> Document for Fields<'_, T> {
+    fn get_path(&self, path: &Path) -> Option<DocValue<'_>> {
+        self.0.get_field(path)
+    }
+}
+
+macro_rules! scalar {
+    ($($ty:ty => |$v:ident| $value:expr),* $(,)?) => {
+        $(impl Filterable for $ty {
+            fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+                let $v = self;
+                if segments.is_empty() {
+                    found.push($value);
+                }
+            }
+        })*
+    };
+}
+
+scalar!(
+    bool => |v| DocValue::Bool(*v),
+    i8 => |v| DocValue::Integer(i64::from(*v)),
+    i16 => |v| DocValue::Integer(i64::from(*v)),
+    i32 => |v| DocValue::Integer(i64::from(*v)),
+    i64 => |v| DocValue::Integer(*v),
+    isize => |v| DocValue::Integer(*v as i64),
+    u8 => |v| DocValue::Integer(i64::from(*v)),
+    u16 => |v| DocValue::Integer(i64::from(*v)),
+    u32 => |v| DocValue::Integer(i64::from(*v)),
+    u64 => |v| unsigned(*v),
+    usize => |v| unsigned(*v as u64),
+    f32 => |v| DocValue::Float(f64::from(*v)),
+    f64 => |v| DocValue::Float(*v),
+    char => |v| DocValue::String(Cow::Owned(v.to_string())),
+    str => |v| DocValue::String(Cow::Borrowed(v)),
+    String => |v| DocValue::String(Cow::Borrowed(v)),
+    Cow<'_, str> => |v| DocValue::String(Cow::Borrowed(v)),
+);
+
+// i64 に収まるものは JSON と同じく Integer
+fn unsigned(u: u64) -> DocValue<'static> {
+    match i64::try_from(u) {
+        Ok(i) => DocValue::Integer(i),
+        Err(_) => DocValue::Unsigned(u),
+    }
+}
+
+impl<T: Filterable + ?Sized> Filterable for &T {
+    fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+        (**self).visit_field(segments, found)
+    }
+}
+
+impl<T: Filterable + ?Sized> Filterable for Box<T> {
+    fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+        (**self).visit_field(segments, found)
+    }
+}
+
+// None は値が無い (missing) ことにする
+impl<T: Filterable> Filterable for Option<T> {
+    fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+        if let Some(value) = self {
+            value.visit_field(segments, found)
+        }
+    }
+}
+
+impl<T: Filterable> Filterable for [T] {
+    fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+        match segments.split_first() {
+            None => {
+                let mut items = vec![];
+                for item in self {
+                    item.visit_field(&[], &mut items);
+                }
+                found.push(DocValue::Array(items));
+            }
+            Some((Segment::Index(i), rest)) => {
+                if let Some(item) = self.get(*i) {
+                    item.visit_field(rest, found);
+                }
+            }
+            Some((Segment::Wildcard, rest)) => {
+                for item in self {
+                    item.visit_field(rest, found);
+                }
+            }
+            Some((Segment::Key(_), _)) => {}
+        }
+    }
+}
+
+impl<T: Filterable> Filterable for Vec<T> {
+    fn visit_field<'a>(&'a self, segments: &[Segment], found: &mut Vec<DocValue<'a>>) {
+        self.as_slice().visit_field(segments, found)
+    }
+}
@@ -0,0 +1,257 @@
+//! Declared field types, used to coerce document values before comparison.
+
+use crate::ast::{self, Path};
+use crate::eval::EvalError;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FieldType {
+    Integer,
+    Float,
+    Boolean,
+    String,
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FieldType::Integer => "an integer",
+            FieldType::Float => "a float",
+            FieldType::Boolean => "a boolean",
+            FieldType::String => "a string",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Maps dotted paths to the type their values are read as. Paths not in the
+/// schema are compared as they are.
+///
+/// ```
+/// use rql_parser::eval::{EvalOptions, FieldType, Schema};
+/// use rql_parser::parser::Parser;
+///
+/// let mut schema = Schema::new();
+/// schema.field("age", FieldType::Integer);
+/// let options = EvalOptions {
+///     schema: Some(schema),
+///     ..EvalOptions::default()
+/// };
+/// let query = Parser::new_from_string("gt(age,30)".to_owned())
+///     .parse_query()
+///     .unwrap();
+/// assert!(query.matches_with(&serde_json::json!({"age": "42"}), &options));
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Schema {
+    fields: HashMap<Path, FieldType>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    pub fn field(&mut self, path: &str, field_type: FieldType) -> &mut Self {
+        self.fields.insert(Path::from(path), field_type);
+        self
+    }
+
+    pub fn get(&self, path: &Path) -> Option<FieldType> {
+        self.fields.get(path).copied()
+    }
+
+    /// Converts `value` to the type declared for `path`:
+    ///
+    /// - strings are parsed as integers, floats or booleans (`true`/`false`,
+    ///   `1`/`0`, ignoring case and surrounding spaces),
+    /// - numbers and booleans are formatted as strings,
+    /// - integers widen to floats, and floats without a fractional part narrow
+    ///   to integers; `1` and `0` are booleans.
+    ///
+    /// `null` is left alone. Anything else, arrays and objects included, is
+    /// an [`EvalError::Uncoercible`].
+    pub fn coerce<'a>(&self, path: &Path, value: &'a Value) -> Result<Cow<'a, Value>, EvalError> {
+        let field_type = match self.get(path) {
+            Some(t) => t,
+            None => return Ok(Cow::Borrowed(value)),
+        };
+        let coerced = match (field_type, value) {
+            (_, Value::Null) => return Ok(Cow::Borrowed(value)),
+            (FieldType::Integer, Value::Number(n)) if n.is_i64() || n.is_u64() => {
+                return Ok(Cow::Borrowed(value))
+            }
+            (FieldType::Integer, Value::Number(n)) => n
+                .as_f64()
+                .filter(|f| f.fract() == 0.0 && f.abs() < 9e15)
+                .map(|f| (f as i64).into()),
+            (FieldType::Integer, Value::String(s)) => s.trim().parse::<i64>().ok().map(Value::from),
+            (FieldType::Float, Value::Number(n)) => n.as_f64().map(Value::from),
+            (FieldType::Float, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|f| f.is_finite())
+                .map(Value::from),
+            (FieldType::Boolean, Value::Bool(_)) => return Ok(Cow::Borrowed(value)),
+            (FieldType::Boolean, Value::Number(n)) => n
+                .as_f64()
+                .filter(|f| *f == 1.0 || *f == 0.0)
+                .map(|f| (f == 1.0).into()),
+            (FieldType::Boolean, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+                "true" | "1" => Some(true.into()),
+                "false" | "0" => Some(false.into()),
+                _ => None,
+            },
+            (FieldType::String, Value::String(_)) => return Ok(Cow::Borrowed(value)),
+            (FieldType::String, Value::Number(n)) => Some(n.to_string().into()),
+            (FieldType::String, Value::Bool(b)) => Some(b.to_string().into()),
+            _ => None,
+        };
+        coerced
+            .map(Cow::Owned)
+            .ok_or_else(|| EvalError::Uncoercible(path.to_string(), field_type, value.clone()))
+    }
+
+    /// Checks with [`ast::Value::json_type_matches`] that `literal` can be
+    /// compared with `value`, a value of `path` already coerced: a string
+    /// literal against a field declared as an integer is an
+    /// [`EvalError::Mismatched`]. `null`, paths not in the schema and
+    /// unresolved calls and variables always pass.
+    pub fn check(&self, path: &Path, literal: &ast::Value, value: &Value) -> Result<(), EvalError> {
+        match self.get(path) {
+            Some(field_type)
+                if !value.is_null()
+                    && !matches!(literal, ast::Value::Call(_) | ast::Value::Variable(_))
+                    && !literal.json_type_matches(value) =>
+            {
+                Err(EvalError::Mismatched(path.to_string(), field_type, literal.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Value;
+    use crate::eval::{apply_with, EvalError, EvalOptions, FieldType, Schema};
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    fn options(strict_types: bool) -> EvalOptions {
+        let mut schema = Schema::new();
+        schema
+            .field("age", FieldType::Integer)
+            .field("score", FieldType::Float)
+            .field("active", FieldType::Boolean)
+            .field("zip", FieldType::String);
+        EvalOptions {
+            schema: Some(schema),
+            strict_types,
+            ..EvalOptions::default()
+        }
+    }
+
+    fn matches(input: &str, doc: serde_json::Value) -> Result<bool, EvalError> {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .try_matches_with(&doc, &options(true))
+    }
+
+    #[test]
+    fn numeric_strings() {
+        assert_eq!(matches("gt(age,30)", json!({"age": "42"})), Ok(true));
+        assert_eq!(matches("eq(age,42)", json!({"age": " 42 "})), Ok(true));
+        assert_eq!(matches("eq(age,42)", json!({"age": 42.0})), Ok(true));
+        assert_eq!(matches("lt(score,1.0)", json!({"score": "0.5"})), Ok(true));
+        assert_eq!(
+            matches("eq(zip,\"01234\")", json!({"zip": "01234"})),
+            Ok(true)
+        );
+        assert_eq!(
+            matches("eq(zip,\"94103\")", json!({"zip": 94103})),
+            Ok(true)
+        );
+        // スキーマが無ければ文字列と数値は一致しない
+        let query = Parser::new_from_string("gt(age,30)".to_owned())
+            .parse_query()
+            .unwrap();
+        assert!(!query.matches(&json!({"age": "42"})));
+    }
+
+    #[test]
+    fn boolean_strings() {
+        assert_eq!(
+            matches("eq(active,true)", json!({"active": "true"})),
+            Ok(true)
+        );
+        assert_eq!(matches("eq(active,true)", json!({"active": "1"})), Ok(true));
+        assert_eq!(
+            matches("eq(active,true)", json!({"active": "FALSE"})),
+            Ok(false)
+        );
+        assert_eq!(matches("eq(active,false)", json!({"active": 0})), Ok(true));
+    }
+
+    #[test]
+    fn uncoercible() {
+        let err = matches("gt(age,30)", json!({"age": "forty"})).unwrap_err();
+        assert_eq!(
+            err,
+            EvalError::Uncoercible("age".to_owned(), FieldType::Integer, json!("forty"))
+        );
+        assert_eq!(
+            err.to_string(),
+            "age can't be read as an integer: \"forty\""
+        );
+        assert!(matches("eq(active,true)", json!({"active": "yes"})).is_err());
+
+        let query = Parser::new_from_string("gt(age,30)".to_owned())
+            .parse_query()
+            .unwrap();
+        let docs = vec![
+            json!({"age": "42"}),
+            json!({"age": "forty"}),
+            json!({"age": 50}),
+        ];
+        let page = apply_with(&query, docs.clone(), &options(false)).unwrap();
+        assert_eq!(
+            page.documents,
+            vec![json!({"age": "42"}), json!({"age": 50})]
+        );
+        assert!(apply_with(&query, docs, &options(true)).is_err());
+    }
+
+    #[test]
+    fn mismatched_literals() {
+        let err = matches("eq(age,\"42\")", json!({"age": "42"})).unwrap_err();
+        assert_eq!(
+            err,
+            EvalError::Mismatched(
+                "age".to_owned(),
+                FieldType::Integer,
+                Value::StringLiteral("42".to_owned())
+            )
+        );
+        assert_eq!(
+            err.to_string(),
+            "age is an integer, so it can't be compared with \"42\""
+        );
+        assert!(matches("eq(zip,94103)", json!({"zip": "94103"})).is_err());
+        assert!(matches("eq(active,1)", json!({"active": true})).is_err());
+        // 整数のフィールドと浮動小数点数のリテラルは比べられる
+        assert_eq!(matches("lt(age,42.5)", json!({"age": "42"})), Ok(true));
+        assert_eq!(matches("eq(age,\"42\")", json!({"age": null})), Ok(false));
+
+        // strict_types でなければ一致しないだけ
+        let query = Parser::new_from_string("eq(age,\"42\")".to_owned())
+            .parse_query()
+            .unwrap();
+        assert!(!query.matches_with(&json!({"age": "42"}), &options(false)));
+    }
+}
@@ -0,0 +1,222 @@
+//! C ABI for embedding the parser in non-Rust services.
+//!
+//! Every function catches panics and reports failures through
+//! `rql_last_error_message` instead of unwinding across the FFI boundary.
+//! The matching header lives in `include/rql.h`.
+
+use crate::ast::Query;
+use crate::parser::Parser;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Opaque handle to a parsed query.
+pub struct RqlQuery {
+    query: Query,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // NULを含むメッセージはCStringにできないので置き換える
+    let message = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+fn guard<T>(fallback: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    clear_last_error();
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(v)) => v,
+        Ok(Err(message)) => {
+            set_last_error(message);
+            fallback
+        }
+        Err(_) => {
+            set_last_error("internal error: panic in rql".to_string());
+            fallback
+        }
+    }
+}
+
+unsafe fn read_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is null", name));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|e| format!("{} is not valid UTF-8: {}", name, e))
+}
+
+/// Parses a NUL-terminated RQL string.
+/// Returns NULL on failure; the reason is available from `rql_last_error_message`.
+///
+/// # Safety
+/// `input` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rql_parse(input: *const c_char) -> *mut RqlQuery {
+    guard(ptr::null_mut(), || {
+        let input = read_str(input, "input")?;
+        let mut parser = Parser::from_str(input);
+        let query = parser
+            .parse_query()
+            .map_err(|e| format!("parse error: {}", e))?;
+        Ok(Box::into_raw(Box::new(RqlQuery { query })))
+    })
+}
+
+/// Renders the query in canonical RQL form.
+/// The returned string must be released with `rql_string_free`.
+///
+/// # Safety
+/// `query` must be NULL or a pointer returned by `rql_parse`.
+#[no_mangle]
+pub unsafe extern "C" fn rql_to_string(query: *const RqlQuery) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let query = query.as_ref().ok_or("query is null")?;
+        let s = CString::new(query.query.to_rql()).map_err(|e| e.to_string())?;
+        Ok(s.into_raw())
+    })
+}
+
+/// Evaluates the query against a JSON document.
+/// Returns 1 when it matches, 0 when it doesn't, and -1 on error.
+///
+/// # Safety
+/// `query` must be NULL or a pointer returned by `rql_parse`,
+/// and `json` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rql_matches_json(query: *const RqlQuery, json: *const c_char) -> c_int {
+    guard(-1, || {
+        let query = query.as_ref().ok_or("query is null")?;
+        let json = read_str(json, "json")?;
+        let doc: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| format!("invalid JSON: {}", e))?;
+        Ok(query.query.matches(&doc) as c_int)
+    })
+}
+
+/// Returns the error message of the last failed call on this thread, or NULL.
+/// The pointer stays valid until the next `rql_*` call on the same thread.
+#[no_mangle]
+pub extern "C" fn rql_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Releases a query returned by `rql_parse`. NULL is ignored.
+///
+/// # Safety
+/// `query` must be NULL or a pointer returned by `rql_parse` that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rql_free(query: *mut RqlQuery) {
+    if !query.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(query))));
+    }
+}
+
+/// Releases a string returned by `rql_to_string`. NULL is ignored.
+///
+/// # Safety
+/// `s` must be NULL or a pointer returned by `rql_to_string` that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rql_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(CString::from_raw(s))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ffi::*;
+
+    // C側と同じく extern "C" の関数ポインタ経由で呼び出す
+    type ParseFn = unsafe extern "C" fn(*const c_char) -> *mut RqlQuery;
+    type ToStringFn = unsafe extern "C" fn(*const RqlQuery) -> *mut c_char;
+    type MatchesFn = unsafe extern "C" fn(*const RqlQuery, *const c_char) -> c_int;
+    type ErrorFn = extern "C" fn() -> *const c_char;
+
+    const PARSE: ParseFn = rql_parse;
+    const TO_STRING: ToStringFn = rql_to_string;
+    const MATCHES: MatchesFn = rql_matches_json;
+    const LAST_ERROR: ErrorFn = rql_last_error_message;
+
+    fn last_error() -> Option<String> {
+        let p = LAST_ERROR();
+        if p.is_null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned())
+        }
+    }
+
+    #[test]
+    fn parse_to_string_and_match() {
+        let input = CString::new("and( eq(name,\"test\"), gt(speed.max,100) )").unwrap();
+        unsafe {
+            let query = PARSE(input.as_ptr());
+            assert!(!query.is_null());
+            assert_eq!(last_error(), None);
+
+            let s = TO_STRING(query);
+            assert_eq!(
+                CStr::from_ptr(s).to_str().unwrap(),
+                "and(eq(name,\"test\"),gt(speed.max,100))"
+            );
+            rql_string_free(s);
+
+            let hit = CString::new(r#"{"name":"test","speed":{"max":120}}"#).unwrap();
+            let miss = CString::new(r#"{"name":"test","speed":{"max":80}}"#).unwrap();
+            assert_eq!(MATCHES(query, hit.as_ptr()), 1);
+            assert_eq!(MATCHES(query, miss.as_ptr()), 0);
+
+            rql_free(query);
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        let input = CString::new("eq(name").unwrap();
+        let query = unsafe { PARSE(input.as_ptr()) };
+        assert!(query.is_null());
+        assert!(last_error().unwrap().starts_with("parse error"));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let input = [0x65u8, 0x71, 0xff, 0xfe, 0x00];
+        let query = unsafe { PARSE(input.as_ptr() as *const c_char) };
+        assert!(query.is_null());
+        assert!(last_error().unwrap().contains("UTF-8"));
+    }
+
+    #[test]
+    fn null_arguments() {
+        unsafe {
+            assert!(PARSE(ptr::null()).is_null());
+            assert!(last_error().unwrap().contains("null"));
+            assert!(TO_STRING(ptr::null()).is_null());
+            assert_eq!(MATCHES(ptr::null(), ptr::null()), -1);
+            rql_free(ptr::null_mut());
+            rql_string_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn invalid_json() {
+        let input = CString::new("eq(a,1)").unwrap();
+        let json = CString::new("{not json").unwrap();
+        unsafe {
+            let query = PARSE(input.as_ptr());
+            assert_eq!(MATCHES(query, json.as_ptr()), -1);
+            assert!(last_error().unwrap().starts_with("invalid JSON"));
+            rql_free(query);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Reading a query out of the query string of a URL, such as
+//! `filter=and(eq(status,%22open%22),gt(age,30))`, before any
+//! framework-specific extractor.
+
+use crate::ast::Query;
+use crate::parser::{Parser, ParserError};
+use std::fmt;
+
+/// The parameter [`ExtractOptions::default`] reads the query from.
+pub const DEFAULT_PARAM: &str = "filter";
+
+/// What to do when the parameter is given more than once, as in
+/// `filter=eq(a,1)&filter=eq(b,2)`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Repeated {
+    /// Combine the queries with `and()`.
+    And,
+    /// Fail with [`ExtractError::Repeated`].
+    Error,
+}
+
+/// Where to find the query in the query string.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExtractOptions {
+    /// The parameter holding the query, `filter` by default. With `None`, the
+    /// whole query string is RQL: each `&`-separated part is a query, and
+    /// several are combined with `and()`.
+    pub param: Option<String>,
+    /// What to do with a repeated parameter, an error by default.
+    pub repeated: Repeated,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            param: Some(DEFAULT_PARAM.to_owned()),
+            repeated: Repeated::Error,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExtractError {
+    // 指定したパラメータが無い
+    Missing(String),
+    // パラメータが2回以上ある
+    Repeated(String),
+    // %zz のような壊れたエスケープや、UTF-8 にならないバイト列
+    InvalidEncoding(String),
+    Parse(ParserError),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExtractError::Missing(param) => {
+                write!(f, "no {} parameter in the query string", param)
+            }
+            ExtractError::Repeated(param) => {
+                write!(f, "the {} parameter is given more than once", param)
+            }
+            ExtractError::InvalidEncoding(s) => write!(f, "invalid percent-encoding in {}", s),
+            ExtractError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl From<ParserError> for ExtractError {
+    fn from(e: ParserError) -> Self {
+        ExtractError::Parse(e)
+    }
+}
+
+/// Parses the query in parameter `param` of `raw`, the part of a URL after
+/// `?`. The value is decoded as `application/x-www-form-urlencoded`: `%XX`
+/// escapes are decoded and `+` is a space, so a sort order is written
+/// `sort(%2Bname)`. A repeated parameter is an error; see
+/// [`from_query_string_with`] for combining them.
+///
+/// ```
+/// use rql_parser::http::from_query_string;
+///
+/// let query = from_query_string(
+///     "page=2&filter=and(eq(status,%22open%22),gt(age,30))",
+///     "filter",
+/// )
+/// .unwrap();
+/// assert_eq!(query.to_rql(), "and(eq(status,\"open\"),gt(age,30))");
+/// ```
+pub fn from_query_string(raw: &str, param: &str) -> Result<Query, ExtractError> {
+    from_query_string_with(
+        raw,
+        &ExtractOptions {
+            param: Some(param.to_owned()),
+            ..ExtractOptions::default()
+        },
+    )
+}
+
+/// Same as [`from_query_string`], with [`ExtractOptions`].
+///
+/// With no parameter name, each `&`-separated part of `raw` is a query:
+/// `eq(a,1)&sort(+b)` is `and(eq(a,1),sort(+b))`. `+` stays a `+` there, as
+/// it's the ascending `sort()` prefix.
+pub fn from_query_string_with(
+    raw: &str,
+    options: &ExtractOptions,
+) -> Result<Query, ExtractError> {
+    let raw = raw.strip_prefix('?').unwrap_or(raw);
+    let pairs = raw.split('&').filter(|pair| !pair.is_empty());
+    let param = match &options.param {
+        Some(param) => param,
+        None => {
+            let mut queries = vec![];
+            for part in pairs {
+                queries.push(parse(&decode(part, false)?)?);
+            }
+            return Ok(Query::and_all(queries));
+        }
+    };
+    let mut queries = vec![];
+    for pair in pairs {
+        let (key, value) = match pair.find('=') {
+            Some(i) => (&pair[..i], &pair[i + 1..]),
+            None => (pair, ""),
+        };
+        if decode(key, true)? != *param {
+            continue;
+        }
+        if !queries.is_empty() && options.repeated == Repeated::Error {
+            return Err(ExtractError::Repeated(param.clone()));
+        }
+        queries.push(parse(&decode(value, true)?)?);
+    }
+    match queries.len() {
+        0 => Err(ExtractError::Missing(param.clone())),
+        1 => Ok(queries.remove(0)),
+        _ => Ok(Query::And(queries)),
+    }
+}
+
+fn parse(input: &str) -> Result<Query, ParserError> {
+    Parser::from_str(input).parse_query()
+}
+
+// %XX を戻す。plus が true なら + は空白
+fn decode(s: &str, plus: bool) -> Result<String, ExtractError> {
+    let invalid = || ExtractError::InvalidEncoding(s.to_owned());
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3).ok_or_else(invalid)?;
+                let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                i += 3;
+                continue;
+            }
+            b'+' if plus => decoded.push(b' '),
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{from_query_string, from_query_string_with, ExtractOptions, Repeated};
+
+    fn rql(raw: &str, param: &str) -> String {
+        from_query_string(raw, param).unwrap().to_rql()
+    }
+
+    #[test]
+    fn param() {
+        assert_eq!(
+            rql(
+                "?page=2&filter=and(eq(status%2C%22open%22)%2Cgt(age%2C30))&per_page=20",
+                "filter"
+            ),
+            "and(eq(status,\"open\"),gt(age,30))"
+        );
+        // フォームから送られたもの: 空白は +、+ は %2B
+        assert_eq!(
+            rql(
+                "q=and(eq(name%2C%22Mary+Ann%22)%2Csort(%2Bage%2C-name))&submit=Search",
+                "q"
+            ),
+            "and(eq(name,\"Mary Ann\"),sort(+age,-name))"
+        );
+        assert_eq!(
+            rql("filter=eq(city,%22%E4%BA%AC%E9%83%BD%22)", "filter"),
+            "eq(city,\"京都\")"
+        );
+        assert_eq!(rql("my%20filter=eq(a,1)", "my filter"), "eq(a,1)");
+        assert_eq!(
+            from_query_string("page=2", "filter")
+                .unwrap_err()
+                .to_string(),
+            "no filter parameter in the query string"
+        );
+        assert_eq!(
+            from_query_string("filter=eq(a,%zz)", "filter")
+                .unwrap_err()
+                .to_string(),
+            "invalid percent-encoding in eq(a,%zz)"
+        );
+        assert!(from_query_string("filter=eq(a,%FF)", "filter").is_err());
+        assert_eq!(
+            from_query_string("filter=eq(a,1", "filter")
+                .unwrap_err()
+                .to_string(),
+            "expected ')', found end of input at position 6"
+        );
+    }
+
+    #[test]
+    fn repeated() {
+        let raw = "filter=eq(a,1)&sort=name&filter=gt(b,2)";
+        assert_eq!(
+            from_query_string(raw, "filter").unwrap_err().to_string(),
+            "the filter parameter is given more than once"
+        );
+        let options = ExtractOptions {
+            repeated: Repeated::And,
+            ..ExtractOptions::default()
+        };
+        assert_eq!(
+            from_query_string_with(raw, &options).unwrap().to_rql(),
+            "and(eq(a,1),gt(b,2))"
+        );
+    }
+
+    #[test]
+    fn whole() {
+        let options = ExtractOptions {
+            param: None,
+            ..ExtractOptions::default()
+        };
+        let rql = |raw: &str| from_query_string_with(raw, &options).unwrap().to_rql();
+        assert_eq!(
+            rql("and(eq(status,%22open%22),ge(age,18))"),
+            "and(eq(status,\"open\"),ge(age,18))"
+        );
+        assert_eq!(
+            rql("?eq(name,%22Mary%20Ann%22)&sort(+age)&limit(10)"),
+            "and(eq(name,\"Mary Ann\"),sort(+age),limit(10))"
+        );
+    }
+}
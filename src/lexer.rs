@@ -1,41 +1,162 @@
+use crate::parser::ParserError;
 use crate::token;
 use crate::token::Token;
-use std::iter::Peekable;
-use std::mem;
-use std::str::Chars;
+use std::io::BufRead;
+use std::ops::Range;
 
-pub struct Lexer {
-    input: String,
+mod source;
+
+pub use source::{ReadSource, Source};
+
+/// The longest string literal a lexer accepts by default, in bytes.
+pub const DEFAULT_MAX_STRING_LEN: usize = 64 * 1024;
+/// The longest identifier a lexer accepts by default, in bytes.
+pub const DEFAULT_MAX_IDENTIFIER_LEN: usize = 64 * 1024;
+
+/// Splits a query into tokens. By default it lexes a whole `String`;
+/// [`Lexer::from_reader`] lexes a reader instead, a bit at a time.
+pub struct Lexer<S = String> {
+    input: S,
     // Current position in input (points to current char)
     position: usize,
+    // Current reading position in input (after current char)
+    read_position: usize,
     // current char under examination
     ch: char,
-    // Use `Chars` to support UTF-8.
-    chars: Peekable<Chars<'static>>,
+    // 入力を読み終えた
+    eof: bool,
+    // $.a[0] のような JSONPath を1つのトークンとして読む
+    jsonpath: bool,
+    // $name を変数として読む。読まなければ $ で始まる識別子
+    variables: bool,
+    max_string_len: usize,
+    max_identifier_len: usize,
+    // 長すぎるトークンで読むのをやめた
+    error: Option<ParserError>,
 }
 
 impl Lexer {
     pub fn new(input: String) -> Self {
-        let chars = unsafe { mem::transmute(input.chars().peekable()) };
+        Lexer::with_source(input)
+    }
+
+    /// Lexes the UTF-8 text `reader` yields, reading only as far as the
+    /// token being lexed: input already lexed isn't kept, so a query of any
+    /// size takes about as much memory as its longest token. Spans are byte
+    /// offsets from the start of the reader.
+    ///
+    /// Invalid UTF-8 and read errors end the input; the parser then fails
+    /// with [`ParserError::InvalidUtf8`] or [`ParserError::ReadError`] at the
+    /// offset where reading stopped.
+    ///
+    /// ```
+    /// use rql_parser::lexer::Lexer;
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let input: &[u8] = b"and(eq(a,1),gt(b,2))";
+    /// let query = Parser::new(Lexer::from_reader(input)).parse_query().unwrap();
+    /// assert_eq!(query.to_rql(), "and(eq(a,1),gt(b,2))");
+    /// ```
+    pub fn from_reader<R: BufRead>(reader: R) -> Lexer<ReadSource<R>> {
+        Lexer::with_source(ReadSource::new(reader))
+    }
+
+    /// Lexes `input` without copying it. Identifiers and literals are still
+    /// copied into their tokens.
+    // FromStr では借用を返せない
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &str) -> Lexer<&str> {
+        Lexer::with_source(input)
+    }
+}
+
+impl<S: AsRef<str>> Lexer<S> {
+    pub fn input(&self) -> &str {
+        self.input.as_ref()
+    }
+}
+
+impl<S: Source> Lexer<S> {
+    /// A lexer over any [`Source`].
+    pub fn with_source(input: S) -> Self {
         let mut lexer = Lexer {
             input,
             position: 0,
+            read_position: 0,
             ch: '\u{0}',
-            chars,
+            eof: false,
+            jsonpath: false,
+            variables: false,
+            max_string_len: DEFAULT_MAX_STRING_LEN,
+            max_identifier_len: DEFAULT_MAX_IDENTIFIER_LEN,
+            error: None,
         };
         lexer.read_char();
         lexer
     }
 
-    pub fn input(&self) -> &str {
-        &self.input
+    /// With `true`, `$` followed by `.` or `[` starts a JSONPath such as
+    /// `$.items[0]['unit price']`, lexed as a single [`Token::Var`] whose
+    /// name starts with `.` or `[`. Brackets, and quotes inside them, may
+    /// hold commas and spaces.
+    pub fn with_jsonpath(mut self, jsonpath: bool) -> Self {
+        self.jsonpath = jsonpath;
+        self
+    }
+
+    /// With `true`, `$name` is lexed as a [`Token::Var`]. By default it's an
+    /// identifier like any other, so fields such as `$id` keep working.
+    pub fn with_variables(mut self, variables: bool) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    /// Caps the length in bytes of a string literal between its quotes,
+    /// [`DEFAULT_MAX_STRING_LEN`] by default. The lexer stops reading as soon
+    /// as a literal goes over, ending the input with a
+    /// [`ParserError::StringTooLong`] at the position of its opening quote.
+    pub fn with_max_string_len(mut self, max: usize) -> Self {
+        self.max_string_len = max;
+        self
+    }
+
+    /// Caps the length in bytes of an identifier, including a JSONPath and
+    /// the name of a variable, [`DEFAULT_MAX_IDENTIFIER_LEN`] by default.
+    /// Going over is a [`ParserError::IdentifierTooLong`], as for
+    /// [`Lexer::with_max_string_len`].
+    pub fn with_max_identifier_len(mut self, max: usize) -> Self {
+        self.max_identifier_len = max;
+        self
+    }
+
+    /// The error that ended the input early, if a token was too long or
+    /// reading failed. It's returned once.
+    pub fn take_error(&mut self) -> Option<ParserError> {
+        self.error.take().or_else(|| self.input.take_error())
+    }
+
+    /// How many bytes of input have been read, all of it for a string.
+    pub fn bytes_read(&self) -> usize {
+        self.input.bytes_read()
+    }
+
+    /// Same as `next_token`, also returning the byte range of the token in the input.
+    pub fn next_token_with_span(&mut self) -> (Token, Range<usize>) {
+        self.skip_whitespace();
+        let start = self.position;
+        let tok = self.next_token();
+        (tok, start..self.position)
     }
 
     pub fn next_token(&mut self) -> Token {
         self.skip_whitespace();
+        // ここより前はもう読まない
+        self.input.discard(self.position);
 
         let tok: Token;
-        match self.ch {
+        // ガードで先を読むので、今の文字はコピーしておく
+        let ch = self.ch;
+        match ch {
             '(' => {
                 tok = Token::Lparen;
             }
@@ -48,28 +169,47 @@ impl Lexer {
             '+' => {
                 tok = Token::Plus;
             }
+            // 数字の前の - は負の数。sort(-a) の - は識別子の前なので記号のまま
+            '-' if is_digit(self.peek_char()) => {
+                let position = self.position;
+                self.read_char();
+                return self.read_number_token(position);
+            }
             '-' => {
                 tok = Token::Minus;
             }
             '"' => {
-                tok = Token::Str(self.read_string().to_string());
+                tok = Token::Str(self.read_string());
+            }
+            '$' if self.jsonpath && matches!(self.peek_char(), '.' | '[') => {
+                self.read_char();
+                return Token::Var(self.read_jsonpath().to_owned());
+            }
+            '$' if self.variables && is_letter(self.peek_char()) => {
+                self.read_char();
+                return Token::Var(self.read_identifier().to_owned());
             }
-            '\u{0}' => {
+            '\u{0}' if self.is_eof() => {
                 tok = Token::Eof;
             }
             _ => {
-                if is_letter(self.ch) {
-                    let ident = self.read_identifier();
+                if is_letter(self.ch) || self.ch == '`' {
+                    let position = self.position;
+                    self.read_identifier();
+                    let ident = self.input.slice(position..self.position);
+                    #[cfg(feature = "chrono")]
+                    {
+                        if ident == "date" && self.ch == ':' {
+                            self.read_char();
+                            return Token::Date(self.read_literal().to_owned());
+                        }
+                        if ident == "now" && (self.ch == '+' || self.ch == '-') {
+                            return Token::Now(self.read_literal().to_owned());
+                        }
+                    }
                     return token::lookup_ident(ident);
                 } else if is_digit(self.ch) {
-                    let integer_part = self.read_number().to_string();
-                    if self.ch == '.' && is_digit(self.peek_char()) {
-                        self.read_char();
-                        let fractional_part = self.read_number();
-                        return Token::Float(format!("{}.{}", integer_part, fractional_part));
-                    } else {
-                        return Token::Int(integer_part);
-                    }
+                    return self.read_number_token(self.position);
                 } else {
                     tok = Token::Illegal
                 }
@@ -87,29 +227,157 @@ impl Lexer {
             self.read_char();
         }
         // The second character and after can be a letter or a digit.
-        while is_letter(self.ch) || is_digit(self.ch) {
+        // Keys quoted with backticks can hold anything else.
+        while is_letter(self.ch) || is_digit(self.ch) || is_mark(self.ch) || self.ch == '`' {
+            if self.identifier_too_long(position) {
+                break;
+            }
+            if self.ch == '`' {
+                self.read_quoted_key(position);
+            } else {
+                self.read_char();
+            }
+        }
+        self.input.slice(position..self.position)
+    }
+
+    // 括弧の外の区切り文字まで読む。正しい JSONPath かはパーサが調べる
+    fn read_jsonpath(&mut self) -> &str {
+        let start = self.position;
+        let mut depth = 0;
+        let mut quote = None;
+        while !self.is_eof() && !self.identifier_too_long(start) {
+            match (quote, self.ch) {
+                (Some(_), '\\') => self.read_char(),
+                (Some(q), ch) if ch == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') if depth > 0 => quote = Some(self.ch),
+                (None, '[') => depth += 1,
+                (None, ']') if depth > 0 => depth -= 1,
+                (None, ',' | '(' | ')') if depth == 0 => break,
+                (None, ch) if depth == 0 && is_whitespace(ch) => break,
+                _ => {}
+            }
             self.read_char();
         }
-        &self.input[position..self.position]
+        self.input.slice(start..self.position)
     }
 
-    fn read_number(&mut self) -> &str {
-        let position = self.position;
+    // `a.b` のようなキー。`` は ` そのもの。start は識別子の先頭
+    fn read_quoted_key(&mut self, start: usize) {
+        loop {
+            self.read_char();
+            if self.is_eof() || self.identifier_too_long(start) {
+                return;
+            }
+            if self.ch == '`' {
+                if self.peek_char() != '`' {
+                    self.read_char();
+                    return;
+                }
+                self.read_char();
+            }
+        }
+    }
+
+    // date: や now の後ろを区切り文字まで読む。正しい形かはパーサが調べる
+    #[cfg(feature = "chrono")]
+    fn read_literal(&mut self) -> &str {
+        let start = self.position;
+        while !matches!(self.ch, ',' | '(' | ')') && !is_whitespace(self.ch) && !self.is_eof() {
+            self.read_char();
+        }
+        self.input.slice(start..self.position)
+    }
+
+    fn read_number(&mut self) {
         while is_digit(self.ch) {
             self.read_char();
         }
-        &self.input[position..self.position]
     }
 
-    fn read_string(&mut self) -> &str {
-        let position = self.position + 1;
+    // position から始まる整数か小数。符号があれば読み終えている
+    fn read_number_token(&mut self, position: usize) -> Token {
+        self.read_number();
+        if self.ch == '.' && is_digit(self.peek_char()) {
+            self.read_char();
+            self.read_number();
+            Token::Float(self.input.slice(position..self.position).to_owned())
+        } else {
+            Token::Int(self.input.slice(position..self.position).to_owned())
+        }
+    }
+
+    // \" や \\、\n などのエスケープを戻す。知らないエスケープはバックスラッシュごと残す
+    fn read_string(&mut self) -> String {
+        let start = self.position;
+        let mut s = String::new();
         loop {
             self.read_char();
-            if self.ch == '"' || self.ch == '\u{0}' {
-                break;
+            if self.ch == '"' || self.is_eof() {
+                return s;
+            }
+            if self.ch == '\\' {
+                // エスケープされた文字と、バックスラッシュの後ろで読み進める文字数
+                let (ch, len) = match self.peek_char() {
+                    '"' => ('"', 1),
+                    '\\' => ('\\', 1),
+                    'n' => ('\n', 1),
+                    'r' => ('\r', 1),
+                    't' => ('\t', 1),
+                    'u' => self.unicode_escape().unwrap_or(('\\', 0)),
+                    _ => ('\\', 0),
+                };
+                for _ in 0..len {
+                    self.read_char();
+                }
+                s.push(ch);
+            } else {
+                s.push(self.ch);
+            }
+            // 引用符の間の、今の文字までの長さ
+            if self.read_position - start - 1 > self.max_string_len {
+                self.stop(ParserError::StringTooLong(start, self.max_string_len));
+                return s;
+            }
+        }
+    }
+
+    // \u{1f} の形なら、その文字と u から } までの文字数
+    fn unicode_escape(&mut self) -> Option<(char, usize)> {
+        let start = self.read_position;
+        if self.input.char_at(start)? != 'u' || self.input.char_at(start + 1)? != '{' {
+            return None;
+        }
+        // 16 進数になりえない文字が来たら } を探すのをやめる
+        let mut hex = String::new();
+        while hex.len() <= self.max_string_len {
+            match self.input.char_at(start + 2 + hex.len())? {
+                '}' => break,
+                ch if ch.is_ascii_hexdigit() || ch == '+' => hex.push(ch),
+                _ => return None,
             }
         }
-        &self.input[position..self.position]
+        let ch = u32::from_str_radix(&hex, 16)
+            .ok()
+            .and_then(char::from_u32)?;
+        Some((ch, "u{}".len() + hex.len()))
+    }
+
+    // 今の文字まで入れると長すぎるなら、そこで入力を終わりにする
+    fn identifier_too_long(&mut self, start: usize) -> bool {
+        if self.read_position - start <= self.max_identifier_len {
+            return false;
+        }
+        self.stop(ParserError::IdentifierTooLong(start, self.max_identifier_len));
+        true
+    }
+
+    // これ以上は読まない
+    fn stop(&mut self, error: ParserError) {
+        self.error = Some(error);
+        self.ch = '\u{0}';
+        self.eof = true;
     }
 
     fn skip_whitespace(&mut self) {
@@ -118,19 +386,50 @@ impl Lexer {
         }
     }
 
-    // -- Low-level methods that touches the `Chars`.
+    // -- Low-level methods that touches the input.
+    // `read_position` always points at a char boundary, so slicing never panics.
+    // A NUL in the input is a regular (illegal) char; only `is_eof` ends the input.
 
     fn read_char(&mut self) {
-        self.position += if self.ch == '\u{0}' {
-            0
-        } else {
-            self.ch.len_utf8()
-        };
-        self.ch = self.chars.next().unwrap_or('\u{0}');
+        // 終わった後は読まない。長すぎるトークンで止めたときは続きがまだある
+        if self.eof {
+            return;
+        }
+        self.position = self.read_position;
+        match self.input.char_at(self.read_position) {
+            Some(ch) => {
+                self.ch = ch;
+                self.read_position += ch.len_utf8();
+            }
+            None => {
+                self.ch = '\u{0}';
+                self.eof = true;
+            }
+        }
     }
 
     fn peek_char(&mut self) -> char {
-        self.chars.peek().cloned().unwrap_or('\u{0}')
+        self.input
+            .char_at(self.read_position)
+            .unwrap_or('\u{0}')
+    }
+
+    fn is_eof(&self) -> bool {
+        self.eof
+    }
+}
+
+/// Lexes the whole input into tokens with their byte ranges, ending with `Eof`.
+pub fn tokenize(input: &str) -> Vec<(Token, Range<usize>)> {
+    let mut lexer = Lexer::from_str(input);
+    let mut tokens = vec![];
+    loop {
+        let (token, span) = lexer.next_token_with_span();
+        let eof = token == Token::Eof;
+        tokens.push((token, span));
+        if eof {
+            return tokens;
+        }
     }
 }
 
@@ -139,12 +438,25 @@ fn is_letter(ch: char) -> bool {
         // propertyにperiodも入るため、文字列判別
         || ch == '.'
         || ch == '$'
+        // tags.* のワイルドカード
+        || ch == '*'
         // 漢字も含まれる
         || ch.is_alphabetic()
 }
 
+// 分解された (NFD の) アクセント記号など
+#[cfg(feature = "unicode-normalization")]
+fn is_mark(ch: char) -> bool {
+    unicode_normalization::char::is_combining_mark(ch)
+}
+
+#[cfg(not(feature = "unicode-normalization"))]
+fn is_mark(_: char) -> bool {
+    false
+}
+
 fn is_digit(ch: char) -> bool {
-    '0' <= ch && ch <= '9'
+    ch.is_ascii_digit()
 }
 
 fn is_whitespace(ch: char) -> bool {
@@ -153,7 +465,8 @@ fn is_whitespace(ch: char) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::Lexer;
+    use crate::lexer::{tokenize, Lexer};
+    use crate::parser::ParserError;
     use crate::token::Token;
 
     #[test]
@@ -194,4 +507,124 @@ mod tests {
             assert_eq!(&token, expected_token, "tests[{}]", i);
         }
     }
+
+    #[test]
+    fn negative_numbers() {
+        let tokens: Vec<Token> = tokenize("eq(a,-1),gt(b,-0.5),sort(-c),- 2")
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Eq,
+                Token::Lparen,
+                Token::Ident("a".to_owned()),
+                Token::Comma,
+                Token::Int("-1".to_owned()),
+                Token::Rparen,
+                Token::Comma,
+                Token::Gt,
+                Token::Lparen,
+                Token::Ident("b".to_owned()),
+                Token::Comma,
+                Token::Float("-0.5".to_owned()),
+                Token::Rparen,
+                Token::Comma,
+                Token::Sort,
+                Token::Lparen,
+                Token::Minus,
+                Token::Ident("c".to_owned()),
+                Token::Rparen,
+                Token::Comma,
+                Token::Minus,
+                Token::Int("2".to_owned()),
+                Token::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn nul_and_multibyte() {
+        // NUL は入力の終端ではなく不正な文字として扱う
+        let mut lexer = Lexer::new("\"a\u{0}b\"\u{0}é".to_owned());
+        assert_eq!(lexer.next_token(), Token::Str("a\u{0}b".to_string()));
+        assert_eq!(lexer.next_token(), Token::Illegal);
+        assert_eq!(lexer.next_token_with_span(), (Token::Ident("é".to_string()), 6..8));
+        assert_eq!(lexer.next_token(), Token::Eof);
+        assert_eq!(lexer.next_token(), Token::Eof);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let mut lexer = Lexer::new(r#""a\"b" "C:\dir\\" "\u{e9}\u{zz}""#.to_owned());
+        assert_eq!(lexer.next_token(), Token::Str("a\"b".to_owned()));
+        // 知らないエスケープはそのまま
+        assert_eq!(lexer.next_token(), Token::Str("C:\\dir\\".to_owned()));
+        assert_eq!(lexer.next_token(), Token::Str("\u{e9}\\u{zz}".to_owned()));
+        assert_eq!(lexer.next_token(), Token::Eof);
+    }
+
+    #[test]
+    fn tokenize_spans() {
+        assert_eq!(
+            tokenize("lt(a, 1.5)#"),
+            vec![
+                (Token::Lt, 0..2),
+                (Token::Lparen, 2..3),
+                (Token::Ident("a".to_string()), 3..4),
+                (Token::Comma, 4..5),
+                (Token::Float("1.5".to_string()), 6..9),
+                (Token::Rparen, 9..10),
+                (Token::Illegal, 10..11),
+                (Token::Eof, 11..11),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_lengths() {
+        let lex = |input: &str| {
+            let mut lexer = Lexer::from_str(input)
+                .with_variables(true)
+                .with_max_string_len(5)
+                .with_max_identifier_len(3);
+            let tokens: Vec<Token> = std::iter::from_fn(|| match lexer.next_token() {
+                Token::Eof => None,
+                token => Some(token),
+            })
+            .collect();
+            (tokens, lexer.take_error())
+        };
+        // エスケープは戻す前の長さで数える
+        let (tokens, error) = lex(r#"abc "abcde" "\"\"" `a` $ab"#);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("abc".to_owned()),
+                Token::Str("abcde".to_owned()),
+                Token::Str("\"\"".to_owned()),
+                Token::Ident("`a`".to_owned()),
+                Token::Var("ab".to_owned()),
+            ]
+        );
+        assert!(error.is_none());
+        for (input, expected) in [
+            (r#"eq(a,"abcdef")"#, ParserError::StringTooLong(5, 5)),
+            (r#"eq(a,"\u{e9}")"#, ParserError::StringTooLong(5, 5)),
+            ("eq(abcd,1)", ParserError::IdentifierTooLong(3, 3)),
+            ("eq(`ab`,1)", ParserError::IdentifierTooLong(3, 3)),
+            ("eq(a,$abcd)", ParserError::IdentifierTooLong(6, 3)),
+        ] {
+            let (tokens, error) = lex(input);
+            assert_eq!(
+                error.map(|e| (e.code(), e.position())),
+                Some((expected.code(), expected.position())),
+                "{}",
+                input
+            );
+            // 止めたところで入力が終わる
+            assert!(!tokens.contains(&Token::Rparen), "{}", input);
+        }
+    }
 }
@@ -0,0 +1,327 @@
+//! Where the [`Lexer`](crate::lexer::Lexer) reads characters from: a whole
+//! string, or a reader decoded as the lexer goes.
+
+use crate::parser::ParserError;
+use std::io::{BufRead, ErrorKind};
+use std::ops::Range;
+
+/// The input of a [`Lexer`](crate::lexer::Lexer), addressed by byte offset
+/// from its start. The lexer asks for offsets at char boundaries, never
+/// before the last offset it passed to [`Source::discard`].
+pub trait Source {
+    /// The char starting at byte offset `at`, or `None` at the end of the
+    /// input or where it couldn't be read.
+    fn char_at(&mut self, at: usize) -> Option<char>;
+
+    /// The text between two offsets already returned by [`Source::char_at`].
+    fn slice(&self, range: Range<usize>) -> &str;
+
+    /// Tells the source the lexer won't look before `before` again.
+    fn discard(&mut self, _before: usize) {}
+
+    /// How many bytes have been read.
+    fn bytes_read(&self) -> usize;
+
+    /// Why the input ended before the end, if it did.
+    fn take_error(&mut self) -> Option<ParserError> {
+        None
+    }
+}
+
+impl Source for String {
+    fn char_at(&mut self, at: usize) -> Option<char> {
+        self[at..].chars().next()
+    }
+
+    fn slice(&self, range: Range<usize>) -> &str {
+        &self[range]
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Source for &str {
+    fn char_at(&mut self, at: usize) -> Option<char> {
+        self[at..].chars().next()
+    }
+
+    fn slice(&self, range: Range<usize>) -> &str {
+        &self[range]
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A [`Source`] reading a [`BufRead`] as needed, made by
+/// [`Lexer::from_reader`](crate::lexer::Lexer::from_reader).
+pub struct ReadSource<R> {
+    reader: R,
+    // 読んだ文字列のうち、まだ要るもの。先頭は入力の offset バイト目
+    buf: String,
+    offset: usize,
+    // 読んだバイト列の末尾の、途中で切れた文字
+    partial: Vec<u8>,
+    done: bool,
+    error: Option<ParserError>,
+}
+
+impl<R: BufRead> ReadSource<R> {
+    pub fn new(reader: R) -> Self {
+        ReadSource {
+            reader,
+            buf: String::new(),
+            offset: 0,
+            partial: vec![],
+            done: false,
+            error: None,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // 読めるだけ読んで、UTF-8 として正しいところまでを buf に足す
+    fn fill(&mut self) {
+        let chunk = loop {
+            match self.reader.fill_buf() {
+                Ok(chunk) => break chunk,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let at = self.bytes_read();
+                    return self.fail(ParserError::ReadError(e, at));
+                }
+            }
+        };
+        if chunk.is_empty() {
+            self.done = true;
+            if !self.partial.is_empty() {
+                let at = self.bytes_read();
+                self.fail(ParserError::InvalidUtf8(at));
+            }
+            return;
+        }
+        let len = chunk.len();
+        self.partial.extend_from_slice(chunk);
+        self.reader.consume(len);
+        let (valid, invalid) = match std::str::from_utf8(&self.partial) {
+            Ok(s) => (s, false),
+            Err(e) => (
+                std::str::from_utf8(&self.partial[..e.valid_up_to()]).unwrap_or_default(),
+                e.error_len().is_some(),
+            ),
+        };
+        self.buf.push_str(valid);
+        let valid = valid.len();
+        self.partial.drain(..valid);
+        if invalid {
+            let at = self.bytes_read();
+            self.fail(ParserError::InvalidUtf8(at));
+        }
+    }
+
+    fn fail(&mut self, error: ParserError) {
+        self.done = true;
+        self.error = Some(error);
+    }
+}
+
+impl<R: BufRead> Source for ReadSource<R> {
+    fn char_at(&mut self, at: usize) -> Option<char> {
+        while at - self.offset >= self.buf.len() && !self.done {
+            self.fill();
+        }
+        self.buf.get(at - self.offset..)?.chars().next()
+    }
+
+    fn slice(&self, range: Range<usize>) -> &str {
+        &self.buf[range.start - self.offset..range.end - self.offset]
+    }
+
+    // 読み終えた部分が半分を超えたら詰める。毎回詰めると長い入力で遅くなる
+    fn discard(&mut self, before: usize) {
+        let done = before - self.offset;
+        if done > self.buf.len() / 2 {
+            self.buf.drain(..done);
+            self.offset = before;
+        }
+    }
+
+    fn bytes_read(&self) -> usize {
+        self.offset + self.buf.len()
+    }
+
+    fn take_error(&mut self) -> Option<ParserError> {
+        self.error.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{tokenize, Lexer};
+    use crate::parser::{Parser, ParserError, ParserOptions};
+    use crate::token::Token;
+    use std::io::{self, BufRead, Read};
+
+    // size バイトずつしか返さない reader
+    struct Chunks<'a> {
+        data: &'a [u8],
+        size: usize,
+    }
+
+    impl Read for Chunks<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.fill_buf()?.len().min(buf.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl BufRead for Chunks<'_> {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            Ok(&self.data[..self.size.min(self.data.len())])
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.data = &self.data[amt..];
+        }
+    }
+
+    fn lex(input: &[u8], size: usize) -> Vec<(Token, std::ops::Range<usize>)> {
+        let mut lexer = Lexer::from_reader(Chunks { data: input, size });
+        let mut tokens = vec![];
+        loop {
+            let (token, span) = lexer.next_token_with_span();
+            let eof = token == Token::Eof;
+            tokens.push((token, span));
+            if eof {
+                return tokens;
+            }
+        }
+    }
+
+    #[test]
+    fn same_tokens() {
+        let inputs = [
+            r#"and(eq(foo,"test"),or(gt(bar.baz,100),ge(test,60.0)))"#,
+            "eq(名前,\"京都\") \t\n sort(+`a.b`,-`x``y`)",
+            r#"eq(a,"\"\\\n\u{e9}\u{1f600}\u{zz}\u{12") eq(a,"unterminated"#,
+            "eq(a,$name) ne(é,\u{0}) 12.5 7.x `open",
+        ];
+        for input in &inputs {
+            for size in 1..=5 {
+                assert_eq!(
+                    lex(input.as_bytes(), size),
+                    tokenize(input),
+                    "{} / {}",
+                    input,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_from_reader() {
+        let input = r#"and(eq(名前,"京都"),or(ge(a,1),lt(b,2)),sort(-b))"#;
+        for size in 1..=4 {
+            let lexer = Lexer::from_reader(Chunks {
+                data: input.as_bytes(),
+                size,
+            });
+            let query = Parser::new(lexer).parse_query().unwrap();
+            assert_eq!(query.to_rql(), input);
+        }
+        let options = ParserOptions {
+            jsonpath: true,
+            ..ParserOptions::default()
+        };
+        let lexer = Lexer::from_reader(&b"eq($.items[0]['unit price'],3)"[..]);
+        assert_eq!(
+            Parser::new(lexer)
+                .with_options(options)
+                .parse_query()
+                .unwrap()
+                .to_rql(),
+            "eq(items.0.`unit price`,3)"
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        // 3 バイトの 京 の途中で壊れている
+        let mut input = b"and(eq(a,\"".to_vec();
+        input.extend_from_slice(&"京".as_bytes()[..2]);
+        input.extend_from_slice(b"\"),eq(b,1))");
+        for size in 1..=3 {
+            let lexer = Lexer::from_reader(Chunks { data: &input, size });
+            match Parser::new(lexer).parse_query() {
+                Err(ParserError::InvalidUtf8(10)) => {}
+                other => panic!("{:?}", other),
+            }
+        }
+        // 入力の末尾で切れている
+        let lexer = Lexer::from_reader(&"eq(a,1) 京".as_bytes()[..9]);
+        let err = Parser::new(lexer).parse_query().unwrap_err();
+        assert_eq!(err.to_string(), "invalid UTF-8 at position 8");
+    }
+
+    #[test]
+    fn read_error() {
+        struct Failing<'a>(&'a [u8]);
+        impl Read for Failing<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0.is_empty() {
+                    return Err(io::Error::other("disk on fire"));
+                }
+                let n = self.0.read(buf)?;
+                Ok(n)
+            }
+        }
+        let lexer = Lexer::from_reader(io::BufReader::with_capacity(4, Failing(b"and(eq(a,1),")));
+        let err = Parser::new(lexer).parse_query().unwrap_err();
+        assert_eq!(err.code(), "read_error");
+        assert_eq!(
+            err.to_string(),
+            "failed to read the input at position 12: disk on fire"
+        );
+    }
+
+    #[test]
+    fn stops_early() {
+        // 読まれたバイト数を数える reader
+        struct Counting<'a> {
+            data: &'a [u8],
+            read: usize,
+        }
+        impl Read for Counting<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = self.data.read(buf)?;
+                self.read += n;
+                Ok(n)
+            }
+        }
+        for (open, close, code) in [
+            ("eq(a,\"", "\")", "string_too_long"),
+            ("eq(`", "`,1)", "identifier_too_long"),
+            ("eq(a", ",1)", "identifier_too_long"),
+        ] {
+            let input = format!("{}{}{}", open, "x".repeat(10 << 20), close);
+            let mut reader = Counting {
+                data: input.as_bytes(),
+                read: 0,
+            };
+            let lexer = Lexer::from_reader(io::BufReader::with_capacity(4096, &mut reader));
+            let err = Parser::new(lexer).parse_query().unwrap_err();
+            assert_eq!(err.code(), code);
+            assert_eq!(err.position(), Some(open.len() - 1));
+            // 上限の 64 KiB を少し超えたところでやめている
+            assert!(reader.read < 70 * 1024, "{} bytes read", reader.read);
+        }
+    }
+}
@@ -1,4 +1,35 @@
-mod token;
+pub mod token;
 pub mod lexer;
-mod ast;
-pub mod parser;
\ No newline at end of file
+pub mod ast;
+pub mod parser;
+pub mod eval;
+pub mod convert;
+pub mod diagnostic;
+pub mod cst;
+pub mod policy;
+pub mod request;
+pub mod http;
+pub mod cache;
+pub mod transform;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "chrono")]
+pub mod datetime;
+#[cfg(feature = "decimal")]
+pub mod decimal;
+#[cfg(feature = "unicode-normalization")]
+pub mod normalize;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+
+/// `rql!` and `#[derive(Filterable)]` from `rql-parser-macros`.
+#[cfg(feature = "macros")]
+pub use rql_parser_macros::{rql, Filterable};
@@ -0,0 +1,87 @@
+//! Unicode normalization, with the `unicode-normalization` feature: `café`
+//! typed as one code point (NFC) or as `e` followed by a combining accent (NFD)
+//! are different strings and never match unless both are normalized.
+//!
+//! [`ParserOptions::normalize`](crate::parser::ParserOptions::normalize)
+//! normalizes identifiers when parsing, and
+//! [`EvalOptions::normalize_strings`](crate::eval::EvalOptions::normalize_strings)
+//! string literals and document strings when comparing them. Both use NFC.
+
+use crate::ast::Value;
+use crate::eval::DocValue;
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// `s` in NFC, without copying when it already is.
+pub fn nfc(s: &str) -> Cow<'_, str> {
+    match is_nfc_quick(s.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(s),
+        _ => Cow::Owned(s.nfc().collect()),
+    }
+}
+
+pub(crate) fn value<'a>(value: Cow<'a, Value>) -> Cow<'a, Value> {
+    match value.as_ref() {
+        Value::StringLiteral(s) => match nfc(s) {
+            Cow::Owned(s) => Cow::Owned(Value::StringLiteral(s)),
+            Cow::Borrowed(_) => value,
+        },
+        _ => value,
+    }
+}
+
+pub(crate) fn doc(value: DocValue) -> DocValue {
+    match value {
+        DocValue::String(s) => {
+            let normalized = match nfc(&s) {
+                Cow::Owned(n) => Some(n),
+                Cow::Borrowed(_) => None,
+            };
+            DocValue::String(normalized.map_or(s, Cow::Owned))
+        }
+        v => v,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::EvalOptions;
+    use crate::parser::{Parser, ParserOptions};
+    use serde_json::json;
+
+    const NFC: &str = "caf\u{e9}";
+    const NFD: &str = "cafe\u{301}";
+
+    fn parse(input: &str, normalize: bool) -> crate::ast::Query {
+        Parser::new_from_string(input.to_owned())
+            .with_options(ParserOptions {
+                normalize,
+                ..ParserOptions::default()
+            })
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn strings() {
+        let query = parse(&format!("eq(name,\"{}\")", NFD), false);
+        let doc = json!({ "name": NFC });
+        let options = EvalOptions {
+            normalize_strings: true,
+            ..EvalOptions::default()
+        };
+        assert!(!query.matches(&doc));
+        assert!(query.matches_with(&doc, &options));
+        let query = parse(&format!("startswith(name,\"{}\")", NFC), false);
+        assert!(!query.matches(&json!({ "name": format!("{} au lait", NFD) })));
+        assert!(query.matches_with(&json!({ "name": format!("{} au lait", NFD) }), &options));
+    }
+
+    #[test]
+    fn identifiers() {
+        let doc = json!({ NFC: 1 });
+        assert!(!parse(&format!("eq({},1)", NFD), false).matches(&doc));
+        assert!(parse(&format!("eq({},1)", NFD), true).matches(&doc));
+        assert!(parse(&format!("exists(menu.{})", NFD), true).matches(&json!({"menu": { NFC: 1 }})));
+    }
+}
@@ -0,0 +1,48 @@
+//! Parallel evaluation over in-memory documents with rayon.
+
+use crate::ast::Query;
+use rayon::prelude::*;
+
+impl Query {
+    /// Returns the documents matching the query, in input order, evaluating
+    /// them on the rayon thread pool. Same result as filtering with
+    /// [`Query::matches`].
+    pub fn par_filter<'a>(&self, docs: &'a [serde_json::Value]) -> Vec<&'a serde_json::Value> {
+        docs.par_iter().filter(|doc| self.matches(doc)).collect()
+    }
+
+    /// Counts the documents matching the query in parallel.
+    pub fn par_matches(&self, docs: &[serde_json::Value]) -> usize {
+        docs.par_iter().filter(|doc| self.matches(doc)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Query;
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    // 評価をスレッド間で共有できることをコンパイル時に確認する
+    #[allow(dead_code)]
+    fn assert_send_sync<T: Send + Sync>() {}
+    const _: fn() = assert_send_sync::<Query>;
+
+    #[test]
+    fn same_as_sequential() {
+        let query = Parser::new_from_string(
+            "and(ne(active,false),or(gt(score,90),eq(team,\"red\")))".to_owned(),
+        )
+        .parse_query()
+        .unwrap();
+        let docs: Vec<serde_json::Value> = (0..10_000)
+            .map(|i| {
+                let team = if i % 2 == 0 { "red" } else { "blue" };
+                json!({"id": i, "score": i % 100, "team": team, "active": i % 3 != 0})
+            })
+            .collect();
+        let sequential: Vec<_> = docs.iter().filter(|d| query.matches(d)).collect();
+        assert_eq!(query.par_filter(&docs), sequential);
+        assert_eq!(query.par_matches(&docs), sequential.len());
+    }
+}
@@ -1,76 +1,489 @@
-use crate::ast::{Infix, Query, Value};
-use crate::lexer::Lexer;
+use crate::ast::{Aggregation, Infix, Path, Prefix, Query, Value};
+use crate::lexer::{Lexer, Source, DEFAULT_MAX_IDENTIFIER_LEN, DEFAULT_MAX_STRING_LEN};
 use crate::parser::ParserError::*;
-use crate::token::Token;
+use crate::token::{Token, TokenKind};
+#[cfg(not(feature = "tracing"))]
 use log::debug;
+#[cfg(feature = "tracing")]
+use tracing::debug;
+use std::collections::HashSet;
+use std::fmt;
 
 type Result<T> = std::result::Result<T, ParserError>;
 
+// and/or のネストの上限。これを超える入力はスタックオーバーフローを避けるためにエラーにする
+pub const MAX_DEPTH: usize = 128;
+
+// Token を持つエラーは、そのトークンの入力中の位置 (バイトオフセット) も持つ
 #[derive(Debug)]
 pub enum ParserError {
-    ExpectedQueryToken(Token),
-    ExpectedFilterToken(Token),
-    ExpectedValueToken(Token),
-    ExpectedSomethingToken(Token),
-    ExpectedIdentifierToken(Token),
-    ExpectedBooleanToken(Token),
-    ExpectedIntegerToken(Token),
-    ExpectedFloatToken(Token),
-    ExpectedStringToken(Token),
-    ExpectedLparen(Token),
-    ExpectedRparen(Token),
-    ExpectedComma(Token),
-    ParseInt(String),
-    ParseFloat(String),
+    ExpectedQueryToken(Token, usize),
+    ExpectedFilterToken(Token, usize),
+    ExpectedValueToken(Token, usize),
+    ExpectedSomethingToken(Token, usize),
+    ExpectedIdentifierToken(Token, usize),
+    ExpectedBooleanToken(Token, usize),
+    ExpectedIntegerToken(Token, usize),
+    ExpectedFloatToken(Token, usize),
+    ExpectedStringToken(Token, usize),
+    ExpectedLparen(Token, usize),
+    ExpectedRparen(Token, usize),
+    ExpectedComma(Token, usize),
+    ParseInt(String, usize),
+    ParseFloat(String, usize),
+    ParseDate(String, usize),
+    // (上限, 位置)
+    MaxDepthExceeded(usize, usize),
+    UnknownFunction(String, usize),
+    // eqq(name,"x") のような知らない演算子。近いキーワードがあれば候補として持つ
+    UnknownOperator(String, Option<&'static str>, usize),
+    // eq(name,test) の test のような引用符の無い値
+    UnquotedIdentifier(String, usize),
+    // (パス, 対応していない機能, 位置)
+    UnsupportedJsonPath(String, &'static str, usize),
+    // ParserOptions::allowed_operators に無い演算子
+    OperatorNotAllowed(Token, usize),
+    // or() の中の sort() や limit() など、結果を整形する演算子
+    ShapingInOr(Token, usize),
+    // near() の範囲外の引数。(引数の名前, 値, 位置)
+    OutOfRange(&'static str, String, usize),
+    // (トークンの位置, 上限)
+    StringTooLong(usize, usize),
+    IdentifierTooLong(usize, usize),
+    // 入力を読む途中のエラー。位置は読めたバイト数
+    InvalidUtf8(usize),
+    ReadError(std::io::Error, usize),
     NotImplemented(String),
 }
 
-type ValueParseFn = fn(&mut Parser) -> Result<Value>;
+/// How forgiving the parser is.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParserOptions {
+    /// An unquoted identifier in value position (`eq(name,test)`) compares
+    /// with the field `test`. With `true`, it's a
+    /// [`ParserError::UnquotedIdentifier`], as it's usually a forgotten pair
+    /// of quotes. By default it's accepted and reported in
+    /// [`Parser::warnings`].
+    pub strict: bool,
+    /// With `true`, identifiers are normalized to Unicode NFC, so that a path
+    /// typed with combining accents finds keys stored precomposed.
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize: bool,
+    /// With `true`, identifiers starting with `$.` or `$[` are JSONPaths:
+    /// `eq($.items[0]['unit price'],3)` is `` eq(items.0.`unit price`,3) ``.
+    /// Only child, index and wildcard selectors are supported; filters,
+    /// slices, unions and recursive descent are a
+    /// [`ParserError::UnsupportedJsonPath`].
+    pub jsonpath: bool,
+    /// With `true`, `$name` in value position is a [`Value::Variable`] for
+    /// an [`EvalContext`](crate::eval::EvalContext) to bind:
+    /// `eq(owner,$current_user)`. By default, and always in property
+    /// position, `$name` is a field name such as `$id`.
+    pub variables: bool,
+    /// With `Some`, only these operators may be used, named by their RQL
+    /// keyword (`eq`, `and`, `sort`, `count`...); any other operator is a
+    /// [`ParserError::OperatorNotAllowed`] as soon as it's read. `None`, the
+    /// default, allows them all.
+    pub allowed_operators: Option<HashSet<String>>,
+    /// The longest string literal accepted, in bytes between the quotes,
+    /// [`DEFAULT_MAX_STRING_LEN`] by default. See
+    /// [`Lexer::with_max_string_len`].
+    pub max_string_len: usize,
+    /// The longest identifier accepted, in bytes,
+    /// [`DEFAULT_MAX_IDENTIFIER_LEN`] by default.
+    pub max_identifier_len: usize,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            strict: false,
+            #[cfg(feature = "unicode-normalization")]
+            normalize: false,
+            jsonpath: false,
+            variables: false,
+            allowed_operators: None,
+            max_string_len: DEFAULT_MAX_STRING_LEN,
+            max_identifier_len: DEFAULT_MAX_IDENTIFIER_LEN,
+        }
+    }
+}
+
+impl ParserError {
+    /// Stable identifier of the kind of error, e.g. `expected_rparen` or
+    /// `max_depth_exceeded`, for programs that branch on it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ExpectedQueryToken(..) => "expected_query",
+            ExpectedFilterToken(..) => "expected_filter",
+            ExpectedValueToken(..) => "expected_value",
+            ExpectedSomethingToken(..) => "expected_token",
+            ExpectedIdentifierToken(..) => "expected_identifier",
+            ExpectedBooleanToken(..) => "expected_boolean",
+            ExpectedIntegerToken(..) => "expected_integer",
+            ExpectedFloatToken(..) => "expected_float",
+            ExpectedStringToken(..) => "expected_string",
+            ExpectedLparen(..) => "expected_lparen",
+            ExpectedRparen(..) => "expected_rparen",
+            ExpectedComma(..) => "expected_comma",
+            ParseInt(..) => "invalid_integer",
+            ParseFloat(..) => "invalid_float",
+            ParseDate(..) => "invalid_date",
+            MaxDepthExceeded(..) => "max_depth_exceeded",
+            UnknownFunction(..) => "unknown_function",
+            UnknownOperator(..) => "unknown_operator",
+            UnquotedIdentifier(..) => "unquoted_identifier",
+            UnsupportedJsonPath(..) => "unsupported_jsonpath",
+            OperatorNotAllowed(..) => "operator_not_allowed",
+            ShapingInOr(..) => "shaping_in_or",
+            OutOfRange(..) => "out_of_range",
+            StringTooLong(..) => "string_too_long",
+            IdentifierTooLong(..) => "identifier_too_long",
+            InvalidUtf8(..) => "invalid_encoding",
+            ReadError(..) => "read_error",
+            NotImplemented(..) => "not_implemented",
+        }
+    }
+
+    /// Byte offset in the input where the error was detected.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            ExpectedQueryToken(_, pos)
+            | ExpectedFilterToken(_, pos)
+            | ExpectedValueToken(_, pos)
+            | ExpectedSomethingToken(_, pos)
+            | ExpectedIdentifierToken(_, pos)
+            | ExpectedBooleanToken(_, pos)
+            | ExpectedIntegerToken(_, pos)
+            | ExpectedFloatToken(_, pos)
+            | ExpectedStringToken(_, pos)
+            | ExpectedLparen(_, pos)
+            | ExpectedRparen(_, pos)
+            | ExpectedComma(_, pos)
+            | ParseInt(_, pos)
+            | ParseFloat(_, pos)
+            | ParseDate(_, pos)
+            | MaxDepthExceeded(_, pos)
+            | UnknownFunction(_, pos)
+            | UnknownOperator(_, _, pos)
+            | UnquotedIdentifier(_, pos)
+            | UnsupportedJsonPath(_, _, pos)
+            | OperatorNotAllowed(_, pos)
+            | ShapingInOr(_, pos)
+            | OutOfRange(_, _, pos)
+            | StringTooLong(pos, _)
+            | IdentifierTooLong(pos, _)
+            | InvalidUtf8(pos)
+            | ReadError(_, pos) => Some(*pos),
+            NotImplemented(_) => None,
+        }
+    }
+
+    // Expected* の「何が来るべきだったか」と、実際に見つかったトークン
+    pub(crate) fn expected(&self) -> Option<(&'static str, &Token)> {
+        let expected = match self {
+            ExpectedQueryToken(t, _) => ("a query", t),
+            ExpectedFilterToken(t, _) => ("a comparison operator", t),
+            ExpectedValueToken(t, _) => ("a value", t),
+            ExpectedSomethingToken(t, _) => ("a token", t),
+            ExpectedIdentifierToken(t, _) => ("an identifier", t),
+            ExpectedBooleanToken(t, _) => ("a boolean", t),
+            ExpectedIntegerToken(t, _) => ("an integer", t),
+            ExpectedFloatToken(t, _) => ("a float", t),
+            ExpectedStringToken(t, _) => ("a string", t),
+            ExpectedLparen(t, _) => ("'('", t),
+            ExpectedRparen(t, _) => ("')'", t),
+            ExpectedComma(t, _) => ("','", t),
+            _ => return None,
+        };
+        Some(expected)
+    }
+
+    /// Renders the error with the offending line of `input` and a caret under the
+    /// bad token, without colors. See [`crate::diagnostic`].
+    pub fn render(&self, input: &str) -> String {
+        crate::diagnostic::render(input, self, false)
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((expected, token)) = self.expected() {
+            return write!(
+                f,
+                "expected {}, found {} at position {}",
+                expected,
+                token.describe(),
+                self.position().unwrap_or_default()
+            );
+        }
+        match self {
+            ParseInt(s, pos) => write!(f, "invalid integer {} at position {}", s, pos),
+            ParseFloat(s, pos) => write!(f, "invalid float {} at position {}", s, pos),
+            ParseDate(s, pos) => write!(f, "invalid date {} at position {}", s, pos),
+            MaxDepthExceeded(max, pos) => {
+                write!(f, "nesting deeper than {} at position {}", max, pos)
+            }
+            UnknownFunction(s, pos) => write!(f, "unknown function {}() at position {}", s, pos),
+            UnknownOperator(s, Some(suggestion), _) => {
+                write!(f, "unknown operator '{}', did you mean '{}'?", s, suggestion)
+            }
+            UnknownOperator(s, None, _) => write!(f, "unknown operator '{}'", s),
+            UnquotedIdentifier(s, pos) => {
+                write!(f, "unquoted identifier {} in value position at position {}", s, pos)
+            }
+            UnsupportedJsonPath(path, feature, pos) => write!(
+                f,
+                "unsupported JSONPath {} in {} at position {}; only {} are supported",
+                feature,
+                path,
+                pos,
+                crate::ast::JSONPATH_SUPPORTED
+            ),
+            OperatorNotAllowed(token, pos) => {
+                write!(f, "operator {} is not allowed at position {}", token.describe(), pos)
+            }
+            ShapingInOr(token, pos) => {
+                write!(f, "{} can't be used inside or() or not() at position {}", token.describe(), pos)
+            }
+            OutOfRange(name, s, pos) => {
+                write!(f, "{} {} is out of range at position {}", name, s, pos)
+            }
+            StringTooLong(pos, max) => write!(
+                f,
+                "string literal at position {} is longer than {} bytes",
+                pos, max
+            ),
+            IdentifierTooLong(pos, max) => write!(
+                f,
+                "identifier at position {} is longer than {} bytes",
+                pos, max
+            ),
+            InvalidUtf8(pos) => write!(f, "invalid UTF-8 at position {}", pos),
+            ReadError(e, pos) => {
+                write!(f, "failed to read the input at position {}: {}", pos, e)
+            }
+            NotImplemented(s) => write!(f, "{} is not implemented", s),
+            // Expected* は expected() で書いた
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl std::error::Error for ParserError {}
+
+type ValueParseFn<S> = fn(&mut Parser<S>) -> Result<Value>;
 
-pub struct Parser {
-    lexer: Lexer,
+/// Parses a query from a [`Lexer`], by default one over a `String`.
+pub struct Parser<S = String> {
+    lexer: Lexer<S>,
     errors: Vec<ParserError>,
+    warnings: Vec<ParserError>,
+    options: ParserOptions,
 
     cur_token: Token,
     peek_token: Token,
+    cur_pos: usize,
+    peek_pos: usize,
+    depth: usize,
+    // or() か not() の中を読んでいるか
+    in_or: bool,
+    // 最初の2つのトークンを読んだか
+    primed: bool,
 }
 
 impl Parser {
     pub fn new_from_string(s: String) -> Self {
-        let lexer = Lexer::new(s);
-        Self::new(lexer)
+        Parser::with_source(s)
     }
 
-    pub fn new(lexer: Lexer) -> Self {
-        let mut p = Parser {
-            lexer,
-            errors: vec![],
-            cur_token: Token::Illegal,
-            peek_token: Token::Illegal,
-        };
-        p.next_token();
-        p.next_token();
-        p
+    /// Parses `input` where it is, without taking a copy of it as
+    /// [`Parser::new_from_string`] needs.
+    ///
+    /// ```
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let input = "and(eq(a,1),gt(b,2))";
+    /// let query = Parser::from_str(input).parse_query().unwrap();
+    /// assert_eq!(query.to_rql(), input);
+    /// ```
+    // FromStr では借用を返せない
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &str) -> Parser<&str> {
+        Parser::with_source(input)
     }
+}
 
+impl<S: AsRef<str>> Parser<S> {
     pub fn input(&self) -> &str {
         self.lexer.input()
     }
+}
+
+impl<S: Source> Parser<S> {
+    // new_from_string と from_str で共有する。String も &str もそのまま字句解析器に渡す
+    fn with_source(input: S) -> Self {
+        Parser::new(Lexer::with_source(input))
+    }
+
+    pub fn new(lexer: Lexer<S>) -> Self {
+        Parser {
+            lexer,
+            errors: vec![],
+            warnings: vec![],
+            options: ParserOptions::default(),
+            cur_token: Token::Illegal,
+            peek_token: Token::Illegal,
+            cur_pos: 0,
+            peek_pos: 0,
+            depth: 0,
+            in_or: false,
+            primed: false,
+        }
+    }
 
     pub fn errors(&self) -> &[ParserError] {
         &self.errors
     }
 
+    pub fn with_options(mut self, options: ParserOptions) -> Self {
+        // トークンは parse_query で初めて読むので、字句解析器の設定を変えるだけでよい
+        self.lexer = Self::configure(self.lexer, &options);
+        self.options = options;
+        self
+    }
+
+    /// Problems the lenient parser let through, such as
+    /// [`ParserError::UnquotedIdentifier`].
+    pub fn warnings(&self) -> &[ParserError] {
+        &self.warnings
+    }
+
+    /// Starts over on `input`, keeping the options and the memory already
+    /// allocated, to parse many queries with one parser.
+    ///
+    /// ```
+    /// use rql_parser::parser::Parser;
+    ///
+    /// let mut parser = Parser::from_str("eq(a,1)");
+    /// for input in ["eq(a,1)", "and(gt(b,2),lt(b,5))", "sort(+c)"] {
+    ///     parser.reset(input);
+    ///     assert_eq!(parser.parse_query().unwrap().to_rql(), input);
+    /// }
+    /// ```
+    pub fn reset(&mut self, input: S) {
+        self.lexer = Self::configure(Lexer::with_source(input), &self.options);
+        self.errors.clear();
+        self.warnings.clear();
+        self.cur_token = Token::Illegal;
+        self.peek_token = Token::Illegal;
+        self.cur_pos = 0;
+        self.peek_pos = 0;
+        self.depth = 0;
+        self.in_or = false;
+        self.primed = false;
+    }
+
+    fn configure(lexer: Lexer<S>, options: &ParserOptions) -> Lexer<S> {
+        lexer
+            .with_jsonpath(options.jsonpath)
+            .with_variables(options.variables)
+            .with_max_string_len(options.max_string_len)
+            .with_max_identifier_len(options.max_identifier_len)
+    }
+
     fn next_token(&mut self) {
-        self.cur_token = std::mem::replace(&mut self.peek_token, self.lexer.next_token());
+        let (token, span) = self.lexer.next_token_with_span();
+        self.cur_token = std::mem::replace(&mut self.peek_token, token);
+        self.cur_pos = std::mem::replace(&mut self.peek_pos, span.start);
     }
 
     pub fn parse_query(&mut self) -> Result<Query> {
+        if !self.primed {
+            self.primed = true;
+            self.next_token();
+            self.next_token();
+        }
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::parse_query(self.lexer.bytes_read(), self.depth, self.cur_pos);
+        let mut query = self.parse_query_node();
+        // 入力が途中で読めなくなっていれば、そちらが本当の原因
+        if self.depth == 0 {
+            if let Some(e) = self.lexer.take_error() {
+                query = Err(e);
+            }
+        }
+        #[cfg(feature = "tracing")]
+        crate::trace::parsed(&span, self.depth == 0, &query);
+        query
+    }
+
+    fn parse_query_node(&mut self) -> Result<Query> {
+        // キーワードの表にある演算子はすべてここで調べる
+        if let Some(allowed) = &self.options.allowed_operators {
+            let operator = match self.cur_token.kind() {
+                TokenKind::Logical | TokenKind::Comparison | TokenKind::Keyword => {
+                    self.cur_token.keyword_str()
+                }
+                _ => None,
+            };
+            if operator.is_some_and(|op| !allowed.contains(op)) {
+                return Err(OperatorNotAllowed(self.cur_token.clone(), self.cur_pos));
+            }
+        }
         match &self.cur_token {
-            Token::And => return self.parse_and(),
-            Token::Or => return self.parse_or(),
-            _ => return self.parse_filter(),
-        };
+            Token::And | Token::Or | Token::Not => {
+                if self.depth >= MAX_DEPTH {
+                    return Err(MaxDepthExceeded(MAX_DEPTH, self.cur_pos));
+                }
+                self.depth += 1;
+                let query = if self.cur_token == Token::And {
+                    self.parse_and()
+                } else {
+                    let outer = std::mem::replace(&mut self.in_or, true);
+                    let query = match self.cur_token {
+                        Token::Or => self.parse_or(),
+                        _ => self.parse_not(),
+                    };
+                    self.in_or = outer;
+                    query
+                };
+                self.depth -= 1;
+                query
+            }
+            // 結果の整形は文書ごとの条件ではないので、or() の選択肢にはならない
+            Token::Sort
+            | Token::Limit
+            | Token::Select
+            | Token::Distinct
+            | Token::Aggregate
+            | Token::First
+            | Token::One
+            | Token::Count
+            | Token::Sum
+            | Token::Min
+            | Token::Max
+            | Token::Mean
+                if self.in_or =>
+            {
+                Err(ShapingInOr(self.cur_token.clone(), self.cur_pos))
+            }
+            Token::Sort => self.parse_sort(),
+            Token::Limit => self.parse_limit(),
+            Token::Select => self.parse_select(),
+            Token::Distinct => self.parse_distinct(),
+            Token::Aggregate => self.parse_aggregate(),
+            Token::First => self.parse_no_args(Query::First),
+            Token::One => self.parse_no_args(Query::One),
+            Token::Count => self.parse_no_args(Query::Count),
+            Token::Sum | Token::Min | Token::Max | Token::Mean => self.parse_reduce(),
+            Token::Exists | Token::Missing => self.parse_exists(),
+            Token::Search => self.parse_search(),
+            Token::Near => self.parse_near(),
+            Token::In | Token::Out => self.parse_in(),
+            Token::Contains => self.parse_contains(),
+            _ => self.parse_filter(),
+        }
     }
 
     fn parse_and(&mut self) -> Result<Query> {
@@ -88,7 +501,7 @@ impl Parser {
             debug!("cur {}, {}", self.cur_token, self.peek_token);
         }
         self.next_token();
-        return Ok(Query::And(queries));
+        Ok(Query::And(queries))
     }
 
     fn parse_or(&mut self) -> Result<Query> {
@@ -106,10 +519,31 @@ impl Parser {
             debug!("cur {}, {}", self.cur_token, self.peek_token);
         }
         self.next_token();
-        return Ok(Query::Or(queries));
+        Ok(Query::Or(queries))
+    }
+
+    // not(条件)。条件は1つだけ
+    fn parse_not(&mut self) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let query = self.parse_query()?;
+        if self.cur_token != Token::Rparen {
+            return Err(ExpectedRparen(self.cur_token.clone(), self.cur_pos));
+        }
+        self.next_token();
+        Ok(Query::Not(Box::new(query)))
     }
 
     fn parse_filter(&mut self) -> Result<Query> {
+        #[cfg(feature = "tracing")]
+        let span = crate::trace::parse_filter(self.lexer.bytes_read(), self.depth, self.cur_pos);
+        let query = self.parse_filter_node();
+        #[cfg(feature = "tracing")]
+        crate::trace::parsed(&span, false, &query);
+        query
+    }
+
+    fn parse_filter_node(&mut self) -> Result<Query> {
         // cur_token: eq, ne, ge, le, gt, lt
         let filter = match &self.cur_token {
             Token::Eq => Infix::Eq,
@@ -118,7 +552,15 @@ impl Parser {
             Token::Ge => Infix::Ge,
             Token::Lt => Infix::Lt,
             Token::Gt => Infix::Gt,
-            _ => return Err(ExpectedFilterToken(self.cur_token.clone())),
+            Token::StartsWith => Infix::StartsWith,
+            Token::EndsWith => Infix::EndsWith,
+            Token::IEq => Infix::IEq,
+            Token::IContains => Infix::IContains,
+            Token::IStartsWith => Infix::IStartsWith,
+            Token::Ident(name) if self.peek_token == Token::Lparen => {
+                return Err(UnknownOperator(name.clone(), suggest_operator(name), self.cur_pos))
+            }
+            _ => return Err(ExpectedFilterToken(self.cur_token.clone(), self.cur_pos)),
         };
         self.expect_peek(Token::Lparen, ExpectedLparen)?;
 
@@ -127,96 +569,484 @@ impl Parser {
         self.expect_peek(Token::Comma, ExpectedComma)?;
 
         self.next_token();
-        let value = self
-            .parse_value()
-            .ok_or_else(|| ExpectedValueToken(self.cur_token.clone()))?;
-        let val = value(self)?;
+        // startswith/endswith と大文字小文字を区別しないものは文字列しか取らない
+        let string_only =
+            filter == Infix::StartsWith || filter == Infix::EndsWith || filter.ignores_case();
+        if string_only && !matches!(self.cur_token, Token::Str(_)) {
+            return Err(ExpectedStringToken(self.cur_token.clone(), self.cur_pos));
+        }
+        let val = self.parse_operand()?;
         self.expect_peek(Token::Rparen, ExpectedRparen)?;
         self.next_token();
         Ok(Query::Filter(filter, idnet, val))
     }
 
-    fn parse_value(&self) -> Option<ValueParseFn> {
+    // in(path,(値,...)) または out(path,(値,...))
+    fn parse_in(&mut self) -> Result<Query> {
+        let out = self.cur_token == Token::Out;
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let path = self.parse_path()?;
+        self.expect_peek(Token::Comma, ExpectedComma)?;
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        let mut values = vec![];
+        loop {
+            self.next_token();
+            values.push(self.parse_operand()?);
+            if self.peek_token != Token::Comma {
+                break;
+            }
+            self.next_token();
+        }
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(if out {
+            Query::Out(path, values)
+        } else {
+            Query::In(path, values)
+        })
+    }
+
+    // contains(path,値)
+    fn parse_contains(&mut self) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let path = self.parse_path()?;
+        self.expect_peek(Token::Comma, ExpectedComma)?;
+        self.next_token();
+        let value = self.parse_operand()?;
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Contains(path, value))
+    }
+
+    // 比較する値。引用符の無い識別子は他のフィールドとの比較になるので、警告する
+    fn parse_operand(&mut self) -> Result<Value> {
+        let value = self
+            .parse_value()
+            .ok_or_else(|| ExpectedValueToken(self.cur_token.clone(), self.cur_pos))?;
+        let pos = self.cur_pos;
+        let val = value(self)?;
+        if let Value::Identifier(s) = &val {
+            let err = UnquotedIdentifier(s.to_string(), pos);
+            if self.options.strict {
+                return Err(err);
+            }
+            self.warnings.push(err);
+        }
+        Ok(val)
+    }
+
+    fn parse_value(&self) -> Option<ValueParseFn<S>> {
         match &self.cur_token {
-            Token::Ident(_) => Some(Parser::parse_identifier),
-            Token::Int(_) => Some(Parser::parse_integer_literal),
-            Token::Float(_) => Some(Parser::parse_float_literal),
-            Token::Str(_) => Some(Parser::parse_string_literal),
-            Token::True => Some(Parser::parse_boolean),
-            Token::False => Some(Parser::parse_boolean),
+            Token::Ident(_) if self.peek_token == Token::Lparen => Some(Self::parse_call),
+            // chrono があれば、素の now は現在時刻
+            #[cfg(feature = "chrono")]
+            Token::Ident(ident) if ident == "now" => Some(Self::parse_date_literal),
+            Token::Ident(_) => Some(Self::parse_identifier),
+            t if t.soft_keyword().is_some() => Some(Self::parse_identifier),
+            Token::Var(_) => Some(Self::parse_variable),
+            Token::Int(_) => Some(Self::parse_integer_literal),
+            Token::Float(_) => Some(Self::parse_float_literal),
+            #[cfg(feature = "chrono")]
+            Token::Date(_) | Token::Now(_) => Some(Self::parse_date_literal),
+            Token::Str(_) => Some(Self::parse_string_literal),
+            Token::True => Some(Self::parse_boolean),
+            Token::False => Some(Self::parse_boolean),
             _ => None,
         }
     }
 
     fn parse_identifier(&mut self) -> Result<Value> {
-        self.parse_identifier_string().map(Value::Identifier)
+        self.parse_path().map(Value::Identifier)
     }
 
-    fn parse_identifier_string(&self) -> Result<String> {
-        if let Token::Ident(ident) = &self.cur_token {
-            Ok(ident.to_string())
+    fn parse_path(&mut self) -> Result<Path> {
+        self.parse_identifier_string().map(Path::from)
+    }
+
+    // 現在のトークンはこの後読み捨てるので、文字列はコピーせずに取り出す
+    fn parse_identifier_string(&mut self) -> Result<String> {
+        if let Some(keyword) = self.cur_token.soft_keyword() {
+            return Ok(keyword.to_owned());
+        }
+        if let Token::Var(name) = &self.cur_token {
+            if self.options.jsonpath && name.starts_with(['.', '[']) {
+                let jsonpath = format!("${}", name);
+                return match Path::from_jsonpath(&jsonpath) {
+                    Ok(path) => Ok(path.to_string()),
+                    Err(feature) => Err(UnsupportedJsonPath(jsonpath, feature, self.cur_pos)),
+                };
+            }
+            // 変数になるのは値の位置だけで、プロパティの位置の $name はそのままの名前
+            if !name.starts_with(['.', '[']) {
+                return Ok(format!("${}", name));
+            }
+        }
+        if let Token::Ident(ident) = &mut self.cur_token {
+            #[cfg(feature = "unicode-normalization")]
+            if self.options.normalize {
+                return Ok(crate::normalize::nfc(ident).into_owned());
+            }
+            Ok(std::mem::take(ident))
         } else {
-            Err(ExpectedIdentifierToken(self.cur_token.clone()))
+            Err(ExpectedIdentifierToken(self.cur_token.clone(), self.cur_pos))
+        }
+    }
+
+    // 値の位置の name() は引数を取らない
+    fn parse_call(&mut self) -> Result<Value> {
+        let name = self.parse_identifier_string()?;
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        Ok(Value::Call(name))
+    }
+
+    fn parse_variable(&mut self) -> Result<Value> {
+        match &mut self.cur_token {
+            Token::Var(name) => Ok(Value::Variable(std::mem::take(name))),
+            _ => Err(ExpectedValueToken(self.cur_token.clone(), self.cur_pos)),
         }
     }
 
     fn parse_integer_literal(&mut self) -> Result<Value> {
         if let Token::Int(int) = &self.cur_token {
-            match int.parse() {
-                Ok(value) => Ok(Value::IntegerLiteral(value)),
-                Err(_) => Err(ParseInt(int.to_string())),
+            // i64 に収まらなければ u64 として読む
+            match (int.parse(), int.parse()) {
+                (Ok(value), _) => Ok(Value::IntegerLiteral(value)),
+                (Err(_), Ok(value)) => Ok(Value::UnsignedLiteral(value)),
+                (Err(_), Err(_)) => Err(ParseInt(int.to_string(), self.cur_pos)),
             }
         } else {
-            Err(ExpectedIntegerToken(self.cur_token.clone()))
+            Err(ExpectedIntegerToken(self.cur_token.clone(), self.cur_pos))
         }
     }
 
     fn parse_float_literal(&mut self) -> Result<Value> {
         if let Token::Float(float) = &self.cur_token {
-            match float.parse() {
-                Ok(value) => Ok(Value::FloatLiteral(value)),
-                Err(_) => Err(ParseFloat(float.to_string())),
+            #[cfg(feature = "decimal")]
+            if let Some(d) = crate::decimal::parse(float) {
+                return Ok(Value::DecimalLiteral(d));
+            }
+            // 桁が多すぎるリテラルは inf になるが、NaN にはしない
+            match float.parse::<f64>() {
+                Ok(value) if !value.is_nan() => Ok(Value::FloatLiteral(value)),
+                _ => Err(ParseFloat(float.to_string(), self.cur_pos)),
             }
         } else {
-            Err(ExpectedFloatToken(self.cur_token.clone()))
+            Err(ExpectedFloatToken(self.cur_token.clone(), self.cur_pos))
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    fn parse_date_literal(&mut self) -> Result<Value> {
+        match &self.cur_token {
+            Token::Date(s) => match crate::datetime::parse(s) {
+                Some(datetime) => Ok(Value::DateTime(datetime)),
+                None => Err(ParseDate(s.to_string(), self.cur_pos)),
+            },
+            Token::Now(offset) => match crate::datetime::parse_offset(offset) {
+                Some((amount, unit)) => Ok(Value::RelativeTime(amount, unit)),
+                None => Err(ParseDate(self.cur_token.to_string(), self.cur_pos)),
+            },
+            Token::Ident(_) => Ok(Value::RelativeTime(0, crate::datetime::TimeUnit::Second)),
+            _ => Err(ExpectedValueToken(self.cur_token.clone(), self.cur_pos)),
         }
     }
 
     fn parse_string_literal(&mut self) -> Result<Value> {
-        if let Token::Str(s) = &self.cur_token {
-            Ok(Value::StringLiteral(s.to_string()))
+        if let Token::Str(s) = &mut self.cur_token {
+            Ok(Value::StringLiteral(std::mem::take(s)))
         } else {
-            Err(ExpectedStringToken(self.cur_token.clone()))
+            Err(ExpectedStringToken(self.cur_token.clone(), self.cur_pos))
         }
     }
     fn parse_boolean(&mut self) -> Result<Value> {
         match &self.cur_token {
             Token::True => Ok(Value::Boolean(true)),
             Token::False => Ok(Value::Boolean(false)),
-            _ => Err(ExpectedBooleanToken(self.cur_token.clone())),
+            _ => Err(ExpectedBooleanToken(self.cur_token.clone(), self.cur_pos)),
         }
     }
 
-    #[allow(dead_code)]
-    // TODO: sort implementation will start after finished filter
+    // sort(+a,-b)。符号を省略したキーは昇順
     fn parse_sort(&mut self) -> Result<Query> {
-        Err(NotImplemented("sort".to_string()))
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        let mut keys = vec![];
+        loop {
+            self.next_token();
+            let prefix = match self.cur_token {
+                Token::Minus => Prefix::Minus,
+                _ => Prefix::Plus,
+            };
+            if self.cur_token == Token::Plus || self.cur_token == Token::Minus {
+                self.next_token();
+            }
+            keys.push((prefix, self.parse_identifier()?));
+            if self.peek_token != Token::Comma {
+                break;
+            }
+            self.next_token();
+        }
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Sort(keys))
+    }
+
+    fn parse_select(&mut self) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        let mut paths = vec![];
+        loop {
+            self.next_token();
+            paths.push(self.parse_path()?);
+            if self.peek_token != Token::Comma {
+                break;
+            }
+            self.next_token();
+        }
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Select(paths))
+    }
+
+    // distinct() または distinct(path)
+    fn parse_distinct(&mut self) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        let mut path = None;
+        if self.peek_token != Token::Rparen {
+            self.next_token();
+            path = Some(self.parse_path()?);
+        }
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Distinct(path))
+    }
+
+    // aggregate(category,count(id),sum(price))。関数名はキーワードではなく識別子として読む
+    fn parse_aggregate(&mut self) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        let (mut keys, mut functions) = (vec![], vec![]);
+        loop {
+            self.next_token();
+            let pos = self.cur_pos;
+            let name = self.parse_identifier_string()?;
+            if self.peek_token == Token::Lparen {
+                let function = match Aggregation::from_name(&name) {
+                    Some(function) => function,
+                    None => return Err(UnknownFunction(name, pos)),
+                };
+                self.next_token();
+                self.next_token();
+                functions.push((function, self.parse_path()?));
+                self.expect_peek(Token::Rparen, ExpectedRparen)?;
+            } else {
+                keys.push(Path::from(name));
+            }
+            if self.peek_token != Token::Comma {
+                break;
+            }
+            self.next_token();
+        }
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Aggregate(keys, functions))
     }
 
-    fn expect_peek(&mut self, token: Token, expected: fn(Token) -> ParserError) -> Result<()> {
+    // first() や one() のように引数を取らないもの
+    fn parse_no_args(&mut self, query: Query) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(query)
+    }
+
+    // sum(price) のように集計関数を単独で使う
+    fn parse_reduce(&mut self) -> Result<Query> {
+        let function = self
+            .cur_token
+            .soft_keyword()
+            .and_then(Aggregation::from_name)
+            .ok_or_else(|| ExpectedQueryToken(self.cur_token.clone(), self.cur_pos))?;
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let path = self.parse_path()?;
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Reduce(function, path))
+    }
+
+    // exists(path) または missing(path)
+    fn parse_exists(&mut self) -> Result<Query> {
+        let exists = self.cur_token == Token::Exists;
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let path = self.parse_path()?;
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(if exists {
+            Query::Exists(path)
+        } else {
+            Query::Missing(path)
+        })
+    }
+
+    // search("語") または search((path,path),"語")
+    fn parse_search(&mut self) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let mut fields = vec![];
+        if self.cur_token == Token::Lparen {
+            loop {
+                self.next_token();
+                fields.push(self.parse_path()?);
+                if self.peek_token != Token::Comma {
+                    break;
+                }
+                self.next_token();
+            }
+            self.expect_peek(Token::Rparen, ExpectedRparen)?;
+            self.expect_peek(Token::Comma, ExpectedComma)?;
+            self.next_token();
+        }
+        let term = match &mut self.cur_token {
+            Token::Str(s) => std::mem::take(s),
+            _ => return Err(ExpectedStringToken(self.cur_token.clone(), self.cur_pos)),
+        };
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Search(fields, term))
+    }
+
+    // near(path,緯度,経度,半径)。西経や南緯のために数値の前に - を書ける。
+    // 緯度は ±90、経度は ±180 まで、半径は 0 以上
+    fn parse_near(&mut self) -> Result<Query> {
+        const ARGS: [(&str, f64, f64); 3] = [
+            ("latitude", -90.0, 90.0),
+            ("longitude", -180.0, 180.0),
+            ("radius", 0.0, f64::MAX),
+        ];
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let path = self.parse_path()?;
+        let mut args = [0.0; 3];
+        for (arg, (name, min, max)) in args.iter_mut().zip(ARGS) {
+            self.expect_peek(Token::Comma, ExpectedComma)?;
+            self.next_token();
+            let pos = self.cur_pos;
+            *arg = self.parse_signed_number()?;
+            if !(min..=max).contains(arg) {
+                return Err(OutOfRange(name, arg.to_string(), pos));
+            }
+        }
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        let [lat, lon, radius] = args;
+        Ok(Query::Near(path, lat, lon, radius))
+    }
+
+    fn parse_signed_number(&mut self) -> Result<f64> {
+        let negative = self.cur_token == Token::Minus;
+        if negative {
+            self.next_token();
+        }
+        let n = match &self.cur_token {
+            Token::Int(s) | Token::Float(s) => match s.parse::<f64>() {
+                Ok(n) if n.is_finite() => n,
+                _ => return Err(ParseFloat(s.clone(), self.cur_pos)),
+            },
+            _ => return Err(ExpectedFloatToken(self.cur_token.clone(), self.cur_pos)),
+        };
+        Ok(if negative { -n } else { n })
+    }
+
+    // limit(件数) または limit(件数,開始位置)
+    fn parse_limit(&mut self) -> Result<Query> {
+        self.expect_peek(Token::Lparen, ExpectedLparen)?;
+        self.next_token();
+        let count = self.parse_usize()?;
+        let mut offset = 0;
+        if self.peek_token == Token::Comma {
+            self.next_token();
+            self.next_token();
+            offset = self.parse_usize()?;
+        }
+        self.expect_peek(Token::Rparen, ExpectedRparen)?;
+        self.next_token();
+        Ok(Query::Limit(count, offset))
+    }
+
+    fn parse_usize(&self) -> Result<usize> {
+        match &self.cur_token {
+            Token::Int(int) => int
+                .parse()
+                .map_err(|_| ParseInt(int.to_string(), self.cur_pos)),
+            _ => Err(ExpectedIntegerToken(self.cur_token.clone(), self.cur_pos)),
+        }
+    }
+
+    fn expect_peek(&mut self, token: Token, expected: fn(Token, usize) -> ParserError) -> Result<()> {
         if self.peek_token != token {
-            return Err(expected(self.peek_token.clone()));
+            return Err(expected(self.peek_token.clone(), self.peek_pos));
         }
         self.next_token();
         Ok(())
     }
 }
 
+// クエリの位置に書けるキーワード。同じ距離なら先にあるものを候補にする (gte → ge)
+const OPERATORS: &[&str] = &[
+    "eq", "ne", "le", "ge", "lt", "gt", "startswith", "endswith", "and", "or", "exists",
+    "missing", "sort", "limit", "select", "distinct", "aggregate", "first", "one", "count",
+    "sum", "min", "max", "mean", "search", "ieq", "icontains", "istartswith", "near", "in",
+    "out", "contains", "not",
+];
+
+// 長さの 1/3 (最低 1) 文字までの違いなら打ち間違いとみなす
+fn suggest_operator(name: &str) -> Option<&'static str> {
+    let max = (name.chars().count() / 3).max(1);
+    OPERATORS
+        .iter()
+        .map(|op| (edit_distance(name, op), *op))
+        .filter(|(distance, _)| *distance <= max)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, op)| op)
+}
+
+// レーベンシュタイン距離
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::ast::{Infix, Query, Value};
+    use crate::ast::{Aggregation, Infix, Prefix, Query, Value};
     use crate::lexer::Lexer;
-    use crate::parser::Parser;
+    use crate::parser::{Parser, ParserError, ParserOptions, MAX_DEPTH};
+    use crate::token::Token;
+
+    // decimal フィーチャでは小数は Decimal として読まれる
+    fn float(f: f64) -> Value {
+        #[cfg(feature = "decimal")]
+        return Value::DecimalLiteral(rust_decimal::Decimal::from_f64_retain(f).unwrap());
+        #[cfg(not(feature = "decimal"))]
+        Value::FloatLiteral(f)
+    }
 
     #[test]
     fn single_filter() {
@@ -229,7 +1059,7 @@ mod tests {
             query.unwrap(),
             Query::Filter(
                 Infix::Eq,
-                Value::Identifier("foo.bar".to_string()),
+                Value::Identifier("foo.bar".into()),
                 Value::StringLiteral("a".to_string()),
             )
         );
@@ -248,13 +1078,13 @@ mod tests {
             Query::And(
                 vec![Query::Filter(
                     Infix::Eq,
-                    Value::Identifier("speed.max".to_string()),
+                    Value::Identifier("speed.max".into()),
                     Value::IntegerLiteral(100),
                 ),
                      Query::Filter(
                          Infix::Lt,
-                         Value::Identifier("speed.min".to_string()),
-                         Value::FloatLiteral(60.0),
+                         Value::Identifier("speed.min".into()),
+                         float(60.0),
                      )
                 ],
             )
@@ -274,19 +1104,19 @@ mod tests {
                 vec![Query::And(
                     vec![Query::Filter(
                         Infix::Eq,
-                        Value::Identifier("speed.max".to_string()),
+                        Value::Identifier("speed.max".into()),
                         Value::IntegerLiteral(100),
                     ),
                          Query::Filter(
                              Infix::Lt,
-                             Value::Identifier("speed.min".to_string()),
-                             Value::FloatLiteral(60.0),
+                             Value::Identifier("speed.min".into()),
+                             float(60.0),
                          ),
                     ]
                 ),
                      Query::Filter(
                          Infix::Eq,
-                         Value::Identifier("name".to_string()),
+                         Value::Identifier("name".into()),
                          Value::StringLiteral("test".to_string()),
                      )
                 ],
@@ -308,19 +1138,19 @@ mod tests {
                     vec![
                         Query::Filter(
                             Infix::Eq,
-                            Value::Identifier("speed.max".to_string()),
+                            Value::Identifier("speed.max".into()),
                             Value::IntegerLiteral(100),
                         ),
                         Query::Filter(
                             Infix::Lt,
-                            Value::Identifier("speed.min".to_string()),
-                            Value::FloatLiteral(60.0),
+                            Value::Identifier("speed.min".into()),
+                            float(60.0),
                         ),
                     ]
                 ),
                      Query::Filter(
                          Infix::Eq,
-                         Value::Identifier("name".to_string()),
+                         Value::Identifier("name".into()),
                          Value::StringLiteral("test".to_string()),
                      )
                 ]
@@ -341,22 +1171,683 @@ mod tests {
                 vec![Query::And(
                     vec![Query::Filter(
                         Infix::Eq,
-                        Value::Identifier("foo".to_string()),
+                        Value::Identifier("foo".into()),
                         Value::IntegerLiteral(100),
                     ),
                          Query::Filter(
                              Infix::Lt,
-                             Value::Identifier("bar".to_string()),
-                             Value::FloatLiteral(60.0),
+                             Value::Identifier("bar".into()),
+                             float(60.0),
                          )]
                 ),
                      Query::Filter(
                          Infix::Eq,
-                         Value::Identifier("baz".to_string()),
+                         Value::Identifier("baz".into()),
                          Value::StringLiteral("test".to_string()),
                      ),
                 ]
             )
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn error_position() {
+        let input = "and(eq(foo,1), gt(bar 2))";
+        let mut parser = Parser::new_from_string(input.to_owned());
+        let err = parser.parse_query().unwrap_err();
+        match &err {
+            ParserError::ExpectedComma(Token::Int(i), pos) => {
+                assert_eq!(i, "2");
+                assert_eq!(*pos, 22);
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!(err.to_string(), "expected ',', found '2' at position 22");
+
+        let err = Parser::new_from_string("and(values(a))".to_owned()).parse_query().unwrap_err();
+        assert_eq!(err.to_string(), "expected a comparison operator, found 'values' at position 4");
+    }
+
+    #[test]
+    fn codes() {
+        let token = || Token::Eof;
+        let errors = vec![
+            ParserError::ExpectedQueryToken(token(), 0),
+            ParserError::ExpectedFilterToken(token(), 0),
+            ParserError::ExpectedValueToken(token(), 0),
+            ParserError::ExpectedSomethingToken(token(), 0),
+            ParserError::ExpectedIdentifierToken(token(), 0),
+            ParserError::ExpectedBooleanToken(token(), 0),
+            ParserError::ExpectedIntegerToken(token(), 0),
+            ParserError::ExpectedFloatToken(token(), 0),
+            ParserError::ExpectedStringToken(token(), 0),
+            ParserError::ExpectedLparen(token(), 0),
+            ParserError::ExpectedRparen(token(), 0),
+            ParserError::ExpectedComma(token(), 0),
+            ParserError::ParseInt("1".to_owned(), 0),
+            ParserError::ParseFloat("1".to_owned(), 0),
+            ParserError::ParseDate("1".to_owned(), 0),
+            ParserError::MaxDepthExceeded(MAX_DEPTH, 0),
+            ParserError::UnknownFunction("f".to_owned(), 0),
+            ParserError::UnknownOperator("f".to_owned(), None, 0),
+            ParserError::UnquotedIdentifier("a".to_owned(), 0),
+            ParserError::UnsupportedJsonPath("$..a".to_owned(), "recursive descent (..)", 0),
+            ParserError::OperatorNotAllowed(token(), 0),
+            ParserError::ShapingInOr(token(), 0),
+            ParserError::OutOfRange("radius", "-1".to_owned(), 0),
+            ParserError::StringTooLong(0, 0),
+            ParserError::IdentifierTooLong(0, 0),
+            ParserError::InvalidUtf8(0),
+            ParserError::ReadError(std::io::ErrorKind::UnexpectedEof.into(), 0),
+            ParserError::NotImplemented("in".to_owned()),
+        ];
+        let codes: std::collections::HashSet<&str> = errors.iter().map(|e| e.code()).collect();
+        assert_eq!(codes.len(), errors.len());
+        assert!(codes
+            .iter()
+            .all(|c| c.chars().all(|ch| ch.is_ascii_lowercase() || ch == '_')));
+        let err = Parser::new_from_string("eq(a,1 3)".to_owned()).parse_query().unwrap_err();
+        assert_eq!(err.code(), "expected_rparen");
+    }
+
+    #[test]
+    fn unknown_operator() {
+        let cases = [
+            ("eqq(name,\"x\")", Some("eq")),
+            ("gte(age,18)", Some("ge")),
+            ("strtswith(name,\"a\")", Some("startswith")),
+            ("and(eq(a,1),selct(a))", Some("select")),
+            ("frobnicate(a,1)", None),
+            ("foo(a,1)", None),
+        ];
+        for (input, expected) in &cases {
+            match Parser::new_from_string(input.to_string()).parse_query() {
+                Err(ParserError::UnknownOperator(_, suggestion, _)) => {
+                    assert_eq!(suggestion, *expected, "{}", input)
+                }
+                r => panic!("unexpected result {:?} for {}", r, input),
+            }
+        }
+        // 括弧が続かなければ演算子とはみなさない
+        assert!(matches!(
+            Parser::new_from_string("eqq".to_owned()).parse_query(),
+            Err(ParserError::ExpectedFilterToken(..))
+        ));
+
+        let input = "and(eq(a,1),eqq(name,\"x\"))";
+        let err = Parser::new_from_string(input.to_owned()).parse_query().unwrap_err();
+        assert_eq!(err.position(), Some(12));
+        assert_eq!(
+            err.render(input),
+            "error: unknown operator 'eqq', did you mean 'eq'?\n  |\n1 | and(eq(a,1),eqq(name,\"x\"))\n  |             ^^^"
+        );
+        let err = Parser::new_from_string("frobnicate(a,1)".to_owned()).parse_query().unwrap_err();
+        assert_eq!(err.to_string(), "unknown operator 'frobnicate'");
+    }
+
+    #[test]
+    fn max_depth() {
+        let nested = |depth: usize| "and(".repeat(depth) + "eq(a,1)" + &")".repeat(depth);
+        assert!(Parser::new_from_string(nested(MAX_DEPTH)).parse_query().is_ok());
+        match Parser::new_from_string(nested(100_000)).parse_query() {
+            Err(ParserError::MaxDepthExceeded(max, pos)) => {
+                assert_eq!(max, MAX_DEPTH);
+                assert_eq!(pos, MAX_DEPTH * 4);
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn sort() {
+        let mut parser = Parser::new_from_string("and(eq(a,1),sort(-score, +name, age))".to_owned());
+        let query = parser.parse_query().unwrap();
+        let key = |prefix, name: &str| (prefix, Value::Identifier(name.into()));
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Filter(Infix::Eq, Value::Identifier("a".into()), Value::IntegerLiteral(1)),
+                Query::Sort(vec![
+                    key(Prefix::Minus, "score"),
+                    key(Prefix::Plus, "name"),
+                    key(Prefix::Plus, "age"),
+                ]),
+            ])
+        );
+        assert_eq!(query.to_rql(), "and(eq(a,1),sort(-score,+name,+age))");
+        for input in ["sort()", "sort(-)", "sort(+a,)", "sort(a b)"] {
+            assert!(Parser::new_from_string(input.to_owned()).parse_query().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn limit() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        assert_eq!(parse("limit(10,20)").unwrap(), Query::Limit(10, 20));
+        assert_eq!(parse("limit(10)").unwrap(), Query::Limit(10, 0));
+        assert_eq!(parse("limit(10,0)").unwrap().to_rql(), "limit(10)");
+        assert!(parse("limit(a)").is_err());
+        assert!(parse("limit(1.5)").is_err());
+        assert!(parse("limit(1,2,3)").is_err());
+    }
+
+    #[test]
+    fn select() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("select(id, user.name)").unwrap();
+        assert_eq!(query, Query::Select(vec!["id".into(), "user.name".into()]));
+        assert_eq!(query.to_rql(), "select(id,user.name)");
+        assert!(parse("select()").is_err());
+        assert!(parse("select(\"id\")").is_err());
+    }
+
+    #[test]
+    fn distinct() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        assert_eq!(parse("distinct()").unwrap(), Query::Distinct(None));
+        let query = parse("distinct(user.id)").unwrap();
+        assert_eq!(query, Query::Distinct(Some("user.id".into())));
+        assert_eq!(query.to_rql(), "distinct(user.id)");
+        assert!(parse("distinct(a,b)").is_err());
+    }
+
+    #[test]
+    fn first_and_one() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("and(sort(-score),first())").unwrap();
+        assert_eq!(query.to_rql(), "and(sort(-score),first())");
+        assert_eq!(parse("one()").unwrap(), Query::One);
+        assert!(parse("first(a)").is_err());
+        assert!(parse("one").is_err());
+    }
+
+    #[test]
+    fn ignoring_case() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let input = "or(ieq(a,\"X\"),icontains(b,\"y\"),istartswith(c.d,\"z\"))";
+        assert_eq!(parse(input).unwrap().to_rql(), input);
+        assert_eq!(
+            parse("ieq(icontains,\"x\")").unwrap(),
+            Query::Filter(
+                Infix::IEq,
+                Value::Identifier("icontains".into()),
+                Value::StringLiteral("x".to_owned())
+            )
+        );
+        assert_eq!(
+            parse("icontains(a,1)").unwrap_err().code(),
+            "expected_string"
+        );
+        assert!(parse("ieq(a,b)").is_err());
+    }
+
+    #[test]
+    fn near() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        assert_eq!(
+            parse("near(store.location,35.68,139.76,5000)").unwrap(),
+            Query::Near("store.location".into(), 35.68, 139.76, 5000.0)
+        );
+        let query = parse("near(location,-33.87,-70,1.5)").unwrap();
+        assert_eq!(query.to_rql(), "near(location,-33.87,-70,1.5)");
+        assert_eq!(parse(&query.to_rql()).unwrap(), query);
+        assert_eq!(parse("eq(near,1)").unwrap().to_rql(), "eq(near,1)");
+        assert!(parse("near(location,35.68,139.76)").is_err());
+        assert!(parse("near(location,\"35.68\",139.76,10)").is_err());
+        assert!(parse("near(35.68,139.76,10,10)").is_err());
+        assert!(parse("near(location,1,2,3,4)").is_err());
+        assert_eq!(parse("near(loc,-90,180,0)").unwrap().to_rql(), "near(loc,-90,180,0)");
+    }
+
+    #[test]
+    fn near_out_of_range() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let err = parse("near(loc,91,0,5)").unwrap_err();
+        assert_eq!(err.code(), "out_of_range");
+        assert_eq!(err.to_string(), "latitude 91 is out of range at position 9");
+        assert_eq!(
+            parse("near(loc,1,-180.5,5)").unwrap_err().to_string(),
+            "longitude -180.5 is out of range at position 11"
+        );
+        assert_eq!(
+            parse("near(loc,1,2,-5)").unwrap_err().to_string(),
+            "radius -5 is out of range at position 13"
+        );
+        assert_eq!(
+            parse("near(loc,1,2,- 0.5)").unwrap_err().to_string(),
+            "radius -0.5 is out of range at position 13"
+        );
+        let huge = format!("near(loc,1,2,1{}.0)", "0".repeat(400));
+        assert_eq!(parse(&huge).unwrap_err().code(), "invalid_float");
+    }
+
+    #[test]
+    fn search() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("and(search(\"error timeout\"),search((message,log.details),\"x\"))");
+        assert_eq!(
+            query.unwrap().to_rql(),
+            "and(search(\"error timeout\"),search((message,log.details),\"x\"))"
+        );
+        assert_eq!(
+            parse("search((count),\"a\\\"b\")").unwrap(),
+            Query::Search(vec!["count".into()], "a\"b".to_owned())
+        );
+        assert_eq!(parse("eq(search,1)").unwrap().to_rql(), "eq(search,1)");
+        assert!(parse("search()").is_err());
+        assert!(parse("search(timeout)").is_err());
+        assert!(parse("search(message,\"x\")").is_err());
+        assert!(parse("search((),\"x\")").is_err());
+        assert!(parse("search((a)\"x\")").is_err());
+    }
+
+    #[test]
+    fn count() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("and(eq(status,\"open\"),count())").unwrap();
+        assert_eq!(query.to_rql(), "and(eq(status,\"open\"),count())");
+        assert!(parse("count(a)").is_err());
+        assert_eq!(parse("mean(a.b)").unwrap(), Query::Reduce(Aggregation::Mean, "a.b".into()));
+        assert!(parse("sum()").is_err());
+        assert!(parse("max(a,b)").is_err());
+    }
+
+    #[test]
+    fn startswith_and_endswith() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("or(startswith(name,\"Dr. \"),endswith(email,\"@example.com\"))").unwrap();
+        assert_eq!(
+            query.to_rql(),
+            "or(startswith(name,\"Dr. \"),endswith(email,\"@example.com\"))"
+        );
+        let err = parse("startswith(name,1)").unwrap_err();
+        assert_eq!(err.to_string(), "expected a string, found '1' at position 16");
+        assert!(parse("endswith(name,foo)").is_err());
+    }
+
+    #[test]
+    fn exists_and_missing() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("or(exists(user.phone),and(missing(email),eq(exists,1)))").unwrap();
+        assert_eq!(query.to_rql(), "or(exists(user.phone),and(missing(email),eq(exists,1)))");
+        assert_eq!(parse("missing(a)").unwrap(), Query::Missing("a".into()));
+        assert!(parse("exists()").is_err());
+        assert!(parse("exists(\"a\")").is_err());
+        // 引数の位置ではフィールド名として読む
+        let query = parse("and(eq(count,first),sort(-one),aggregate(count,count(count)))").unwrap();
+        assert_eq!(
+            query.to_rql(),
+            "and(eq(count,first),sort(-one),aggregate(count,count(count)))"
+        );
+    }
+
+    #[test]
+    fn aggregate() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("aggregate(category, count(id), sum(order.price))").unwrap();
+        assert_eq!(
+            query,
+            Query::Aggregate(
+                vec!["category".into()],
+                vec![
+                    (Aggregation::Count, "id".into()),
+                    (Aggregation::Sum, "order.price".into()),
+                ]
+            )
+        );
+        assert_eq!(query.to_rql(), "aggregate(category,count(id),sum(order.price))");
+        let err = parse("aggregate(a,median(b))").unwrap_err();
+        assert_eq!(err.to_string(), "unknown function median() at position 12");
+        assert!(parse("aggregate(count(a,b))").is_err());
+    }
+
+    #[test]
+    fn calls_and_variables() {
+        let parse = |input: &str| {
+            Parser::new_from_string(input.to_owned())
+                .with_options(ParserOptions {
+                    variables: true,
+                    ..ParserOptions::default()
+                })
+                .parse_query()
+        };
+        let query = parse("and(gt(expires_at,now()),eq(owner,$current_user))").unwrap();
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Filter(
+                    Infix::Gt,
+                    Value::Identifier("expires_at".into()),
+                    Value::Call("now".to_owned())
+                ),
+                Query::Filter(
+                    Infix::Eq,
+                    Value::Identifier("owner".into()),
+                    Value::Variable("current_user".to_owned())
+                ),
+            ])
+        );
+        assert_eq!(query.to_rql(), "and(gt(expires_at,now()),eq(owner,$current_user))");
+        assert!(parse("eq(a,now(1))").is_err());
+    }
+
+    #[test]
+    fn dollar_properties() {
+        let parse = |input: &str, variables: bool| {
+            Parser::new_from_string(input.to_owned())
+                .with_options(ParserOptions {
+                    variables,
+                    ..ParserOptions::default()
+                })
+                .parse_query()
+        };
+        let query = |right: Value| {
+            Query::And(vec![
+                Query::Filter(
+                    Infix::Eq,
+                    Value::Identifier("$type".into()),
+                    Value::IntegerLiteral(1)
+                ),
+                Query::Filter(Infix::Eq, Value::Identifier("a".into()), right),
+                Query::Sort(vec![(Prefix::Plus, Value::Identifier("$id".into()))]),
+            ])
+        };
+        let input = "and(eq($type,1),eq(a,$type),sort(+$id))";
+        // 既定では値の位置の $type も $type というフィールド
+        let fields = query(Value::Identifier("$type".into()));
+        assert_eq!(parse(input, false).unwrap(), fields);
+        assert_eq!(parse(&fields.to_rql(), false).unwrap(), fields);
+        assert_eq!(parse(&fields.to_rql(), true).unwrap(), fields);
+        // variables があれば値の位置だけ変数になる
+        let variable = query(Value::Variable("type".to_owned()));
+        assert_eq!(parse(input, true).unwrap(), variable);
+        assert_eq!(parse(&variable.to_rql(), true).unwrap(), variable);
+        for variables in [false, true] {
+            assert_eq!(
+                parse("select($meta.id)", variables).unwrap(),
+                Query::Select(vec!["$meta.id".into()])
+            );
+        }
+    }
+
+    #[test]
+    fn unquoted_identifier() {
+        let input = "and(eq(name,test),eq(id,\"1\"))";
+        let mut parser = Parser::new_from_string(input.to_owned());
+        let query = parser.parse_query().unwrap();
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Filter(
+                    Infix::Eq,
+                    Value::Identifier("name".into()),
+                    Value::Identifier("test".into())
+                ),
+                Query::Filter(
+                    Infix::Eq,
+                    Value::Identifier("id".into()),
+                    Value::StringLiteral("1".to_owned())
+                ),
+            ])
+        );
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(
+            parser.warnings()[0].to_string(),
+            "unquoted identifier test in value position at position 12"
+        );
+
+        #[allow(clippy::needless_update)]
+        let strict = ParserOptions {
+            strict: true,
+            ..ParserOptions::default()
+        };
+        let err = Parser::new_from_string(input.to_owned())
+            .with_options(strict.clone())
+            .parse_query()
+            .unwrap_err();
+        assert_eq!(err.position(), Some(12));
+        assert!(Parser::new_from_string("eq(id,\"1\")".to_owned())
+            .with_options(strict)
+            .parse_query()
+            .is_ok());
+    }
+
+    #[test]
+    fn float_limits() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let huge = format!("gt(a,1{}.0)", "0".repeat(400));
+        assert_eq!(
+            parse(&huge).unwrap(),
+            Query::Filter(
+                Infix::Gt,
+                Value::Identifier("a".into()),
+                Value::FloatLiteral(f64::INFINITY)
+            )
+        );
+        // NaN や inf という名前は識別子
+        assert_eq!(
+            parse("eq(a,NaN)").unwrap(),
+            Query::Filter(
+                Infix::Eq,
+                Value::Identifier("a".into()),
+                Value::Identifier("NaN".into())
+            )
+        );
+    }
+
+    #[test]
+    fn integer_limits() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let literal = |input: &str| match parse(input).unwrap() {
+            Query::Filter(_, _, value) => value,
+            query => panic!("{:?}", query),
+        };
+        assert_eq!(
+            literal("eq(id,9223372036854775807)"),
+            Value::IntegerLiteral(i64::MAX)
+        );
+        assert_eq!(
+            literal("eq(id,9223372036854775808)"),
+            Value::UnsignedLiteral(i64::MAX as u64 + 1)
+        );
+        assert_eq!(
+            literal("eq(id,18446744073709551615)"),
+            Value::UnsignedLiteral(u64::MAX)
+        );
+        assert_eq!(
+            parse("eq(id,18446744073709551616)").unwrap_err().to_string(),
+            "invalid integer 18446744073709551616 at position 6"
+        );
+        assert_eq!(
+            parse("eq(id,18446744073709551615)").unwrap().to_rql(),
+            "eq(id,18446744073709551615)"
+        );
+        assert_eq!(literal("eq(id,-1)"), Value::IntegerLiteral(-1));
+        assert_eq!(
+            literal("eq(id,-9223372036854775808)"),
+            Value::IntegerLiteral(i64::MIN)
+        );
+        assert_eq!(
+            parse("eq(id,-9223372036854775809)").unwrap_err().to_string(),
+            "invalid integer -9223372036854775809 at position 6"
+        );
+        assert!(parse("limit(-1)").is_err());
+    }
+
+    #[test]
+    fn negative_floats() {
+        let parse = |input: &str| Parser::new_from_string(input.to_owned()).parse_query();
+        let query = parse("and(gt(a,-0.5),lt(b,-12.25),sort(-a))").unwrap();
+        assert_eq!(
+            query,
+            Query::And(vec![
+                Query::Filter(Infix::Gt, Value::Identifier("a".into()), float(-0.5)),
+                Query::Filter(Infix::Lt, Value::Identifier("b".into()), float(-12.25)),
+                Query::Sort(vec![(Prefix::Minus, Value::Identifier("a".into()))]),
+            ])
+        );
+        assert_eq!(query.to_rql(), "and(gt(a,-0.5),lt(b,-12.25),sort(-a))");
+        assert!(query.matches(&serde_json::json!({"a": -0.25, "b": -13})));
+        // - と数字の間に空白があれば値にならない
+        assert!(parse("eq(a,- 1)").is_err());
+    }
+
+    #[test]
+    fn reset() {
+        let inputs: Vec<String> = (0..100)
+            .map(|i| match i % 5 {
+                0 => format!("eq(f{},{})", i, i),
+                1 => format!("and(gt(a,{}),or(eq(b,\"x{}\"),eq(c,name)))", i, i),
+                2 => format!("and(sort(-f{}),limit({},{}))", i, i, i * 2),
+                // 壊れた入力の後でも次の入力は正しく読める
+                3 => format!("and(eq(a,{}),or(eq(b", i),
+                _ => format!("aggregate(g{},sum(x))", i),
+            })
+            .collect();
+        let mut parser = Parser::from_str("");
+        for input in &inputs {
+            parser.reset(input);
+            let mut fresh = Parser::new_from_string(input.clone());
+            match (parser.parse_query(), fresh.parse_query()) {
+                (Ok(a), Ok(b)) => assert_eq!(a, b, "{}", input),
+                (Err(a), Err(b)) => assert_eq!(a.to_string(), b.to_string(), "{}", input),
+                (a, b) => panic!("{}: {:?} / {:?}", input, a, b),
+            }
+            assert_eq!(parser.warnings().len(), fresh.warnings().len(), "{}", input);
+        }
+
+        // オプションは引き継ぐ
+        #[allow(clippy::needless_update)]
+        let options = ParserOptions {
+            strict: true,
+            jsonpath: true,
+            ..ParserOptions::default()
+        };
+        let mut parser = Parser::new_from_string(String::new()).with_options(options);
+        parser.reset("eq($.a[0],\"x\")".to_owned());
+        assert_eq!(parser.parse_query().unwrap().to_rql(), "eq(a.0,\"x\")");
+        parser.reset("eq(a,b)".to_owned());
+        assert!(parser.parse_query().is_err());
+    }
+
+    #[test]
+    fn shaping_in_or() {
+        let parse = |input: &str| Parser::from_str(input).parse_query();
+        let err = parse("or(eq(a,1),limit(10))").unwrap_err();
+        assert_eq!(err.code(), "shaping_in_or");
+        assert_eq!(
+            err.to_string(),
+            "'limit' can't be used inside or() or not() at position 11"
+        );
+        assert!(matches!(err, ParserError::ShapingInOr(Token::Limit, 11)));
+        let inputs = [
+            "or(eq(a,1),sort(-a))",
+            "or(and(eq(a,1),select(a)),eq(b,2))",
+            "and(eq(a,1),or(eq(b,2),count()))",
+            "or(sum(price))",
+            "or(first())",
+        ];
+        for input in inputs {
+            assert_eq!(parse(input).unwrap_err().code(), "shaping_in_or", "{}", input);
+        }
+        // or() を閉じれば使える。フィールド名としてのキーワードも構わない
+        assert!(parse("and(or(eq(a,1),eq(count,2)),sort(+a),limit(10))").is_ok());
+        assert!(parse("and(or(eq(a,1)),or(eq(b,2)),count())").is_ok());
+    }
+
+    #[test]
+    fn membership_and_negation() {
+        let parse = |input: &str| Parser::from_str(input).parse_query();
+        for input in [
+            "in(status,(\"new\",\"open\"))",
+            "out(age,(1,2.5,true))",
+            "contains(tags,\"rust\")",
+            "not(or(eq(a,1),in(b,(2))))",
+            "and(not(contains(user.roles,\"admin\")),eq(not,1))",
+        ] {
+            assert_eq!(parse(input).unwrap().to_rql(), input);
+        }
+        assert_eq!(
+            parse("in(a,(1,2))").unwrap(),
+            Query::In("a".into(), vec![Value::IntegerLiteral(1), Value::IntegerLiteral(2)])
+        );
+        assert_eq!(
+            parse("not(eq(a,1))").unwrap(),
+            Query::Not(Box::new(Query::Filter(
+                Infix::Eq,
+                Value::Identifier("a".into()),
+                Value::IntegerLiteral(1)
+            )))
+        );
+        assert_eq!(parse("in(a,())").unwrap_err().code(), "expected_value");
+        assert_eq!(parse("in(a,1)").unwrap_err().code(), "expected_lparen");
+        assert_eq!(parse("not(eq(a,1),eq(b,2))").unwrap_err().code(), "expected_rparen");
+        assert_eq!(parse("not(limit(1))").unwrap_err().code(), "shaping_in_or");
+    }
+
+    #[test]
+    fn allowed_operators() {
+        #[allow(clippy::needless_update)]
+        let options = ParserOptions {
+            allowed_operators: Some(["eq", "and"].iter().map(|op| op.to_string()).collect()),
+            ..ParserOptions::default()
+        };
+        let parse = |input: &str| {
+            Parser::from_str(input)
+                .with_options(options.clone())
+                .parse_query()
+        };
+        assert_eq!(
+            parse("and(eq(a,1),eq(b,2))").unwrap().to_rql(),
+            "and(eq(a,1),eq(b,2))"
+        );
+        // フィールド名としてのキーワードは演算子ではない
+        assert!(parse("eq(count,1)").is_ok());
+        let err = parse("and(eq(a,1),gt(b,2))").unwrap_err();
+        assert_eq!(err.code(), "operator_not_allowed");
+        assert_eq!(err.to_string(), "operator 'gt' is not allowed at position 12");
+        assert!(matches!(err, ParserError::OperatorNotAllowed(Token::Gt, 12)));
+        for input in ["or(eq(a,1))", "sort(+a)", "count()", "exists(a)", "startswith(a,\"x\")"] {
+            assert_eq!(parse(input).unwrap_err().code(), "operator_not_allowed", "{}", input);
+        }
+        // 既定ではすべて使える
+        assert!(Parser::from_str("and(gt(b,2),sort(+a))").parse_query().is_ok());
+    }
+
+    #[test]
+    fn max_lengths() {
+        let options = ParserOptions {
+            max_string_len: 4,
+            max_identifier_len: 4,
+            ..ParserOptions::default()
+        };
+        let parse = |input: &str| {
+            Parser::from_str(input)
+                .with_options(options.clone())
+                .parse_query()
+        };
+        assert!(parse("eq(abcd,\"wxyz\")").is_ok());
+        let err = parse("and(eq(abcd,\"vwxyz\"),eq(b,1))").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "string literal at position 12 is longer than 4 bytes"
+        );
+        let err = parse("and(eq(b,1),eq(abcde,\"wxyz\"))").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "identifier at position 15 is longer than 4 bytes"
+        );
+        // reset の後も同じ上限
+        let mut parser = Parser::from_str("").with_options(options.clone());
+        parser.reset("eq(a,\"vwxyz\")");
+        assert_eq!(parser.parse_query().unwrap_err().code(), "string_too_long");
+
+        let long = "x".repeat(crate::lexer::DEFAULT_MAX_STRING_LEN);
+        assert!(Parser::from_str(&format!("eq(a,\"{}\")", long))
+            .parse_query()
+            .is_ok());
+        assert!(Parser::from_str(&format!("eq(a,\"{}x\")", long))
+            .parse_query()
+            .is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! Limits on what a query may ask for, for public APIs that take queries from
+//! untrusted clients.
+
+use crate::ast::{Query, Value};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+mod cost;
+
+pub use cost::CostModel;
+
+/// Which operators a query may use and how often it may filter on a field.
+/// Operators are named by their RQL keyword: `eq`, `startswith`, `or`,
+/// `sort`, `sum`...
+///
+/// ```
+/// use rql_parser::parser::Parser;
+/// use rql_parser::policy::Policy;
+///
+/// let mut policy = Policy::new();
+/// policy.deny("endswith").max_filters_per_field(2);
+/// let query = Parser::new_from_string("or(eq(a,1),eq(a,2),eq(a,3))".to_owned())
+///     .parse_query()
+///     .unwrap();
+/// assert!(query.enforce(&policy).is_err());
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Policy {
+    denied: HashSet<String>,
+    // Some なら、ここにある演算子しか使えない
+    allowed: Option<HashSet<String>>,
+    max_filters: Option<usize>,
+    max_filters_by_field: HashMap<String, usize>,
+}
+
+impl Policy {
+    pub fn new() -> Self {
+        Policy::default()
+    }
+
+    /// Rejects queries using `operator`, even if it's also allowed.
+    pub fn deny(&mut self, operator: &str) -> &mut Self {
+        self.denied.insert(operator.to_owned());
+        self
+    }
+
+    /// Adds `operator` to the whitelist. Once anything is allowed, operators
+    /// not on the whitelist are denied.
+    pub fn allow(&mut self, operator: &str) -> &mut Self {
+        self.allowed
+            .get_or_insert_with(HashSet::new)
+            .insert(operator.to_owned());
+        self
+    }
+
+    /// Caps how many filters (comparisons, `exists()`, `missing()`, `near()`
+    /// and `search()` over the field) may test any one field.
+    pub fn max_filters_per_field(&mut self, max: usize) -> &mut Self {
+        self.max_filters = Some(max);
+        self
+    }
+
+    /// Same as [`Policy::max_filters_per_field`] for the field at `path`
+    /// only, taking precedence over it.
+    pub fn max_filters(&mut self, path: &str, max: usize) -> &mut Self {
+        self.max_filters_by_field.insert(path.to_owned(), max);
+        self
+    }
+
+    pub fn is_allowed(&self, operator: &str) -> bool {
+        !self.denied.contains(operator)
+            && self.allowed.as_ref().is_none_or(|a| a.contains(operator))
+    }
+
+    fn max_filters_for(&self, path: &str) -> Option<usize> {
+        self.max_filters_by_field
+            .get(path)
+            .copied()
+            .or(self.max_filters)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "violation", rename_all = "snake_case")]
+pub enum PolicyViolation {
+    /// The query uses an operator the policy denies.
+    DeniedOperator { operator: String },
+    /// The query filters on `field` more often than `limit`.
+    TooManyFilters {
+        field: String,
+        count: usize,
+        limit: usize,
+    },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyViolation::DeniedOperator { operator } => {
+                write!(f, "operator {} is not allowed", operator)
+            }
+            PolicyViolation::TooManyFilters {
+                field,
+                count,
+                limit,
+            } => write!(
+                f,
+                "{} is filtered {} times, more than the limit of {}",
+                field, count, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PolicyViolation {}
+
+impl Query {
+    /// Checks the query against `policy`, returning every violation: denied
+    /// operators in the order they first appear, then fields over their
+    /// filter limit by name.
+    pub fn enforce(&self, policy: &Policy) -> Result<(), Vec<PolicyViolation>> {
+        let mut operators = vec![];
+        let mut counts = BTreeMap::new();
+        collect(self, &mut operators, &mut counts);
+
+        let mut violations: Vec<PolicyViolation> = operators
+            .into_iter()
+            .filter(|op| !policy.is_allowed(op))
+            .map(|op| PolicyViolation::DeniedOperator {
+                operator: op.to_owned(),
+            })
+            .collect();
+        for (field, count) in counts {
+            match policy.max_filters_for(&field) {
+                Some(limit) if count > limit => violations.push(PolicyViolation::TooManyFilters {
+                    field,
+                    count,
+                    limit,
+                }),
+                _ => {}
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+// 使われている演算子 (重複なし、出現順) とフィールドごとのフィルター数を数える
+fn collect(query: &Query, operators: &mut Vec<&'static str>, counts: &mut BTreeMap<String, usize>) {
+    match query {
+        Query::Filter(_, Value::Identifier(path), _) => {
+            *counts.entry(path.to_string()).or_insert(0) += 1
+        }
+        Query::Exists(path)
+        | Query::Missing(path)
+        | Query::Near(path, ..)
+        | Query::In(path, _)
+        | Query::Out(path, _)
+        | Query::Contains(path, _) => {
+            *counts.entry(path.to_string()).or_insert(0) += 1
+        }
+        Query::Search(fields, _) => {
+            for field in fields {
+                *counts.entry(field.to_string()).or_insert(0) += 1
+            }
+        }
+        _ => {}
+    }
+    let operator = match operator(query) {
+        Some(operator) => operator,
+        None => return,
+    };
+    if !operators.contains(&operator) {
+        operators.push(operator);
+    }
+    match query {
+        Query::And(queries) | Query::Or(queries) => {
+            for q in queries {
+                collect(q, operators, counts);
+            }
+        }
+        Query::Not(q) => collect(q, operators, counts),
+        _ => {}
+    }
+}
+
+// ノードの RQL のキーワード
+fn operator(query: &Query) -> Option<&'static str> {
+    let operator = match query {
+        Query::And(_) => "and",
+        Query::Or(_) => "or",
+        Query::Not(_) => "not",
+        Query::In(..) => "in",
+        Query::Out(..) => "out",
+        Query::Contains(..) => "contains",
+        Query::Sort(_) => "sort",
+        Query::Limit(..) => "limit",
+        Query::Select(_) => "select",
+        Query::Distinct(_) => "distinct",
+        Query::Aggregate(..) => "aggregate",
+        Query::First => "first",
+        Query::One => "one",
+        Query::Count => "count",
+        Query::Reduce(function, _) => function.name(),
+        Query::Filter(infix, ..) => infix.keyword(),
+        Query::Exists(_) => "exists",
+        Query::Missing(_) => "missing",
+        Query::Search(..) => "search",
+        Query::Near(..) => "near",
+        Query::None => return None,
+    };
+    Some(operator)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Query;
+    use crate::parser::Parser;
+    use crate::policy::{Policy, PolicyViolation};
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn field_count() {
+        let arms: Vec<String> = (0..5).map(|i| format!("eq(status,{})", i)).collect();
+        let query = parse(&format!(
+            "and(or({}),exists(status),eq(id,1))",
+            arms.join(",")
+        ));
+        let mut policy = Policy::new();
+        policy.max_filters_per_field(3);
+        assert_eq!(
+            query.enforce(&policy),
+            Err(vec![PolicyViolation::TooManyFilters {
+                field: "status".to_owned(),
+                count: 6,
+                limit: 3,
+            }])
+        );
+        policy.max_filters("status", 10);
+        assert_eq!(query.enforce(&policy), Ok(()));
+        policy.max_filters("id", 0);
+        assert_eq!(
+            query.enforce(&policy).unwrap_err()[0].to_string(),
+            "id is filtered 1 times, more than the limit of 0"
+        );
+    }
+
+    #[test]
+    fn near_and_search_count() {
+        let query = parse(
+            "and(near(location,35.68,139.76,500),or(near(location,0,0,1),exists(location)),\
+             search((title,body),\"x\"),search((title),\"y\"),search(\"z\"))",
+        );
+        let mut policy = Policy::new();
+        policy.max_filters_per_field(2);
+        assert_eq!(
+            query.enforce(&policy),
+            Err(vec![PolicyViolation::TooManyFilters {
+                field: "location".to_owned(),
+                count: 3,
+                limit: 2,
+            }])
+        );
+        policy.max_filters_per_field(1);
+        let fields: Vec<String> = query
+            .enforce(&policy)
+            .unwrap_err()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "location is filtered 3 times, more than the limit of 1",
+                "title is filtered 2 times, more than the limit of 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn membership_count() {
+        let query = parse("and(in(a,(1,2)),not(or(out(a,(3)),contains(a,\"x\"))))");
+        let mut policy = Policy::new();
+        policy.max_filters_per_field(2).deny("not");
+        let violations: Vec<String> =
+            query.enforce(&policy).unwrap_err().iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            violations,
+            vec![
+                "operator not is not allowed",
+                "a is filtered 3 times, more than the limit of 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn denied_operator() {
+        let query = parse("and(endswith(email,\".jp\"),or(endswith(name,\"a\"),eq(a,1)),sort(+a))");
+        let mut policy = Policy::new();
+        policy.deny("endswith");
+        assert_eq!(
+            query.enforce(&policy),
+            Err(vec![PolicyViolation::DeniedOperator {
+                operator: "endswith".to_owned()
+            }])
+        );
+
+        // ホワイトリストに無いものはすべて拒否
+        let mut policy = Policy::new();
+        policy
+            .allow("and")
+            .allow("or")
+            .allow("eq")
+            .allow("endswith");
+        let violations = query.enforce(&policy).unwrap_err();
+        assert_eq!(
+            violations,
+            vec![PolicyViolation::DeniedOperator {
+                operator: "sort".to_owned()
+            }]
+        );
+        assert_eq!(violations[0].to_string(), "operator sort is not allowed");
+        assert_eq!(
+            serde_json::to_value(&violations[0]).unwrap(),
+            serde_json::json!({"violation": "denied_operator", "operator": "sort"})
+        );
+    }
+
+    #[test]
+    fn compliant() {
+        let query = parse("and(eq(a,1),or(eq(b,2),ge(c,3)),sort(+a),limit(10))");
+        let mut policy = Policy::new();
+        policy
+            .deny("startswith")
+            .deny("endswith")
+            .max_filters_per_field(1);
+        assert_eq!(query.enforce(&policy), Ok(()));
+        assert_eq!(Query::None.enforce(&policy), Ok(()));
+        assert_eq!(query.enforce(&Policy::new()), Ok(()));
+    }
+}
@@ -0,0 +1,199 @@
+//! Pricing a query before running it, for gateways that rate-limit by how
+//! much work a filter asks the database for.
+
+use crate::ast::Query;
+use crate::policy::operator;
+use std::collections::BTreeMap;
+
+/// Weights for [`Query::estimate_cost`]. Operators are named by their RQL
+/// keyword, as in [`Policy`](crate::policy::Policy).
+///
+/// The cost of a node is its weight, plus the cost of its children for
+/// `and()` and `not()`. An `or()` is the cost of its branches times their number, capped
+/// by [`CostModel::max_or_factor`], so that wide and nested `or()`s, which
+/// defeat indexes, get expensive quickly. Arithmetic saturates at
+/// `u64::MAX`.
+///
+/// [`CostModel::new`] is the default model: comparisons cost 1 or 2,
+/// `startswith()` 5 and `endswith()`, which no index helps with, 20;
+/// `distinct()`, aggregations and `sort()` cost more than paging and
+/// projection, which are free.
+///
+/// ```
+/// use rql_parser::parser::Parser;
+/// use rql_parser::policy::CostModel;
+///
+/// let query = Parser::new_from_string("or(eq(a,1),eq(b,2),endswith(c,\"x\"))".to_owned())
+///     .parse_query()
+///     .unwrap();
+/// let mut model = CostModel::new();
+/// assert_eq!(query.estimate_cost(&model), (1 + 1 + 20) * 3);
+/// model.weight("endswith", 100).max_or_factor(2);
+/// assert_eq!(query.estimate_cost(&model), (1 + 1 + 100) * 2);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct CostModel {
+    weights: BTreeMap<String, u64>,
+    max_or_factor: u64,
+}
+
+const DEFAULT_WEIGHTS: &[(&str, u64)] = &[
+    ("and", 0),
+    ("or", 0),
+    ("not", 0),
+    ("eq", 1),
+    ("exists", 1),
+    ("missing", 1),
+    ("in", 1),
+    // 等しくないものや範囲はインデックスから多く読む
+    ("ne", 2),
+    ("lt", 2),
+    ("le", 2),
+    ("gt", 2),
+    ("ge", 2),
+    ("out", 2),
+    ("startswith", 5),
+    // 文字列の部分一致もインデックスが効かない
+    ("contains", 20),
+    // 後方一致はインデックスが効かない
+    ("endswith", 20),
+    ("sort", 3),
+    ("limit", 0),
+    ("select", 0),
+    ("first", 0),
+    ("one", 0),
+    ("count", 5),
+    ("sum", 5),
+    ("min", 5),
+    ("max", 5),
+    ("mean", 5),
+    ("distinct", 10),
+    ("aggregate", 20),
+];
+
+impl CostModel {
+    pub fn new() -> Self {
+        CostModel::default()
+    }
+
+    /// Sets the weight of `operator`. Operators without a weight cost 1.
+    pub fn weight(&mut self, operator: &str, weight: u64) -> &mut Self {
+        self.weights.insert(operator.to_owned(), weight);
+        self
+    }
+
+    /// Caps the factor an `or()` multiplies the cost of its branches by,
+    /// 4 by default. With 1, an `or()` costs as much as an `and()`.
+    pub fn max_or_factor(&mut self, max: u64) -> &mut Self {
+        self.max_or_factor = max;
+        self
+    }
+
+    fn weight_of(&self, operator: &str) -> u64 {
+        self.weights.get(operator).copied().unwrap_or(1)
+    }
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            weights: DEFAULT_WEIGHTS
+                .iter()
+                .map(|&(operator, weight)| (operator.to_owned(), weight))
+                .collect(),
+            max_or_factor: 4,
+        }
+    }
+}
+
+impl Query {
+    /// Estimates how expensive the query is to run under `model`. The same
+    /// query and model always give the same cost.
+    pub fn estimate_cost(&self, model: &CostModel) -> u64 {
+        let weight = operator(self).map_or(0, |op| model.weight_of(op));
+        match self {
+            Query::And(queries) => queries.iter().fold(weight, |cost, q| {
+                cost.saturating_add(q.estimate_cost(model))
+            }),
+            Query::Not(query) => weight.saturating_add(query.estimate_cost(model)),
+            Query::Or(queries) => {
+                let branches = queries
+                    .iter()
+                    .fold(0u64, |cost, q| cost.saturating_add(q.estimate_cost(model)));
+                let factor = (queries.len() as u64).min(model.max_or_factor).max(1);
+                weight.saturating_add(branches.saturating_mul(factor))
+            }
+            _ => weight,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::policy::CostModel;
+
+    fn cost(input: &str, model: &CostModel) -> u64 {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .estimate_cost(model)
+    }
+
+    #[test]
+    fn default_model() {
+        let model = CostModel::new();
+        let eqs: Vec<String> = (0..8).map(|i| format!("eq(f{},{})", i, i)).collect();
+        let flat = cost(&format!("and({})", eqs.join(",")), &model);
+        let wide = cost(&format!("or({})", eqs.join(",")), &model);
+        assert_eq!(flat, 8);
+        assert_eq!(wide, 8 * 4);
+
+        let suffixes: Vec<String> = (0..8)
+            .map(|i| format!("endswith(f{},\"{}\")", i, i))
+            .collect();
+        let heavy = cost(&format!("and({})", suffixes.join(",")), &model);
+        assert_eq!(heavy, 8 * 20);
+        assert!(flat < wide && wide < heavy);
+
+        assert_eq!(
+            cost(
+                "and(ge(age,18),startswith(name,\"A\"),sort(-age),limit(10))",
+                &model
+            ),
+            2 + 5 + 3
+        );
+        // 入れ子の or() は掛け合わされる
+        assert_eq!(
+            cost("or(eq(a,1),or(eq(b,1),eq(b,2)))", &model),
+            (1 + (1 + 1) * 2) * 2
+        );
+        assert_eq!(cost("or()", &model), 0);
+        assert_eq!(
+            cost("not(and(in(a,(1,2)),out(b,(3)),contains(c,\"x\")))", &model),
+            1 + 2 + 20
+        );
+    }
+
+    #[test]
+    fn configured() {
+        let mut model = CostModel::new();
+        model.weight("eq", 10).weight("and", 1).max_or_factor(1);
+        assert_eq!(
+            cost("and(eq(a,1),or(eq(b,1),eq(c,1)))", &model),
+            1 + 10 + 20
+        );
+        model.max_or_factor(100);
+        assert_eq!(
+            cost("and(eq(a,1),or(eq(b,1),eq(c,1)))", &model),
+            1 + 10 + 20 * 2
+        );
+
+        let mut model = CostModel::new();
+        model.weight("endswith", u64::MAX);
+        let query = "or(endswith(a,\"x\"),endswith(b,\"x\"))";
+        assert_eq!(cost(query, &model), u64::MAX);
+        // 同じ入力には常に同じ値
+        assert_eq!(cost(query, &model), cost(query, &model.clone()));
+    }
+}
@@ -0,0 +1,160 @@
+//! Python bindings. Build the extension module with `maturin develop --features python`.
+
+use crate::parser::{Parser, ParserError};
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pythonize::{depythonize, pythonize};
+
+create_exception!(rql_parser, ParseError, PyValueError, "Invalid RQL query.");
+create_exception!(
+    rql_parser,
+    UnexpectedTokenError,
+    ParseError,
+    "A different token was expected at `position`."
+);
+create_exception!(
+    rql_parser,
+    InvalidLiteralError,
+    ParseError,
+    "A numeric literal is out of range."
+);
+create_exception!(
+    rql_parser,
+    UnsupportedError,
+    ParseError,
+    "The query uses a feature that isn't implemented."
+);
+
+// 例外には kind (ParserErrorのバリアント名) と position を属性として持たせる
+fn to_py_err(py: Python<'_>, e: &ParserError) -> PyErr {
+    let err = match e {
+        ParserError::ParseInt(..)
+        | ParserError::ParseFloat(..)
+        | ParserError::ParseDate(..)
+        | ParserError::OutOfRange(..) => InvalidLiteralError::new_err(e.to_string()),
+        ParserError::NotImplemented(..)
+        | ParserError::UnknownFunction(..)
+        | ParserError::UnsupportedJsonPath(..)
+        | ParserError::OperatorNotAllowed(..) => {
+            UnsupportedError::new_err(e.to_string())
+        }
+        ParserError::MaxDepthExceeded(..)
+        | ParserError::ShapingInOr(..)
+        | ParserError::StringTooLong(..)
+        | ParserError::IdentifierTooLong(..)
+        | ParserError::InvalidUtf8(..)
+        | ParserError::ReadError(..) => ParseError::new_err(e.to_string()),
+        _ => UnexpectedTokenError::new_err(e.to_string()),
+    };
+    let debug = format!("{:?}", e);
+    let kind = debug.split('(').next().unwrap_or_default();
+    let value = err.value(py);
+    if let Err(e) = value
+        .setattr("kind", kind)
+        .and_then(|_| value.setattr("position", e.position()))
+    {
+        return e;
+    }
+    err
+}
+
+fn parse_str(py: Python<'_>, s: &str) -> PyResult<crate::ast::Query> {
+    Parser::from_str(s)
+        .parse_query()
+        .map_err(|e| to_py_err(py, &e))
+}
+
+/// Parses an RQL string into its AST, represented as nested dicts.
+#[pyfunction]
+fn parse<'py>(py: Python<'py>, s: &str) -> PyResult<Bound<'py, PyAny>> {
+    let query = parse_str(py, s)?;
+    Ok(pythonize(py, &query)?)
+}
+
+/// Returns True when the object (dicts, lists and scalars) satisfies the query.
+#[pyfunction]
+fn matches(py: Python<'_>, s: &str, obj: &Bound<'_, PyAny>) -> PyResult<bool> {
+    let query = parse_str(py, s)?;
+    let doc: serde_json::Value = depythonize(obj)?;
+    Ok(query.matches(&doc))
+}
+
+#[pymodule]
+fn rql_parser(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_function(wrap_pyfunction!(matches, m)?)?;
+    m.add("ParseError", py.get_type::<ParseError>())?;
+    m.add("UnexpectedTokenError", py.get_type::<UnexpectedTokenError>())?;
+    m.add("InvalidLiteralError", py.get_type::<InvalidLiteralError>())?;
+    m.add("UnsupportedError", py.get_type::<UnsupportedError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::python::rql_parser;
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+    use std::ffi::CString;
+
+    // 埋め込みインタプリタにモジュールを登録し、pytest風のassertで検証する
+    fn run(code: &str) {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "rql_parser").unwrap();
+            rql_parser(&module).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("rql", module).unwrap();
+            let code = CString::new(code).unwrap();
+            if let Err(e) = py.run(&code, None, Some(&locals)) {
+                e.print(py);
+                panic!("python assertion failed");
+            }
+        });
+    }
+
+    #[test]
+    fn parse() {
+        run(r#"
+ast = rql.parse('and(eq(name,"test"),gt(speed.max,100))')
+assert ast == {"And": [
+    {"Filter": ("Eq", {"Identifier": "name"}, {"StringLiteral": "test"})},
+    {"Filter": ("Gt", {"Identifier": "speed.max"}, {"IntegerLiteral": 100})},
+]}, ast
+"#);
+    }
+
+    #[test]
+    fn matches() {
+        run(r#"
+doc = {"name": "test", "speed": {"max": 120, "min": 40.5}}
+assert rql.matches('and(eq(name,"test"),gt(speed.max,100))', doc)
+assert not rql.matches('lt(speed.min,10.0)', doc)
+assert not rql.matches('eq(missing,1)', doc)
+"#);
+    }
+
+    #[test]
+    fn errors() {
+        run(r#"
+try:
+    rql.parse("eq(foo 1)")
+    assert False, "should raise"
+except rql.UnexpectedTokenError as e:
+    assert isinstance(e, rql.ParseError)
+    assert isinstance(e, ValueError)
+    assert e.kind == "ExpectedComma", e.kind
+    assert e.position == 7, e.position
+    assert str(e) == "expected ',', found '1' at position 7", str(e)
+
+try:
+    rql.matches("eq(id,99999999999999999999)", {})
+    assert False, "should raise"
+except rql.InvalidLiteralError as e:
+    assert e.kind == "ParseInt"
+    assert e.position == 6
+"#);
+    }
+}
@@ -0,0 +1,238 @@
+//! A query split into the parts of a typical list endpoint: what to filter,
+//! how to order, how many to return and which fields.
+
+use crate::ast::{Path, Prefix, Query, Value};
+use crate::parser::{Parser, ParserError};
+use std::fmt;
+
+/// The parts of a pipeline such as
+/// `and(eq(type,"x"),sort(-created_at),limit(20),select(id,name))`, for
+/// backends that handle filtering, ordering and paging separately.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Request {
+    /// Everything that isn't `sort()`, `limit()` or `select()`, combined with
+    /// `and`; `None` if there is nothing to filter on.
+    pub filter: Option<Query>,
+    /// The `sort()` keys in order of priority.
+    pub sort: Vec<(Prefix, Path)>,
+    /// `limit(count,offset)`.
+    pub limit: Option<(u64, u64)>,
+    /// The `select()`ed paths; empty means every field.
+    pub select: Vec<Path>,
+}
+
+#[derive(Debug)]
+pub enum RequestError {
+    Parse(ParserError),
+    // sort(), limit(), select() が2回以上ある
+    Duplicate(&'static str),
+    // or() や not() の中の sort() など、最上位の and() の外にある
+    Misplaced(&'static str),
+    // distinct() や count() など、Request に入れる場所が無い
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::Parse(e) => write!(f, "{}", e),
+            RequestError::Duplicate(node) => write!(f, "{}() is given more than once", node),
+            RequestError::Misplaced(node) => {
+                write!(f, "{}() must be at the top level, not inside or() or not()", node)
+            }
+            RequestError::Unsupported(node) => write!(f, "{}() isn't supported here", node),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+impl From<ParserError> for RequestError {
+    fn from(e: ParserError) -> Self {
+        RequestError::Parse(e)
+    }
+}
+
+impl Request {
+    /// Parses `input` and splits it with [`Request::from_query`].
+    pub fn parse(input: &str) -> Result<Request, RequestError> {
+        let query = Parser::from_str(input).parse_query()?;
+        Request::from_query(query)
+    }
+
+    /// Splits the query. `sort()`, `limit()` and `select()` must appear at
+    /// most once each, at the top level or in `and()`s nested directly in it;
+    /// the other operators of a pipeline (`distinct()`, `aggregate()`,
+    /// `first()`, `count()`...) are rejected.
+    pub fn from_query(query: Query) -> Result<Request, RequestError> {
+        let mut request = Request::default();
+        let mut filters = vec![];
+        split(query, &mut request, &mut filters)?;
+        request.filter = match Query::and_all(filters) {
+            Query::None => None,
+            filter => Some(filter),
+        };
+        Ok(request)
+    }
+}
+
+fn split(
+    query: Query,
+    request: &mut Request,
+    filters: &mut Vec<Query>,
+) -> Result<(), RequestError> {
+    match query {
+        Query::And(queries) => {
+            for q in queries {
+                split(q, request, filters)?;
+            }
+        }
+        Query::Sort(keys) => {
+            if !request.sort.is_empty() {
+                return Err(RequestError::Duplicate("sort"));
+            }
+            request.sort = keys
+                .into_iter()
+                .map(|(prefix, key)| match key {
+                    Value::Identifier(path) => (prefix, path),
+                    key => (prefix, Path::from(key.to_string())),
+                })
+                .collect();
+        }
+        Query::Limit(count, offset) => {
+            if request.limit.is_some() {
+                return Err(RequestError::Duplicate("limit"));
+            }
+            request.limit = Some((count as u64, offset as u64));
+        }
+        Query::Select(paths) => {
+            if !request.select.is_empty() {
+                return Err(RequestError::Duplicate("select"));
+            }
+            request.select = paths;
+        }
+        Query::None => {}
+        query => {
+            check_filter(&query)?;
+            filters.push(query);
+        }
+    }
+    Ok(())
+}
+
+// フィルターとして残すノードに、パイプラインの演算子が含まれていないか
+fn check_filter(query: &Query) -> Result<(), RequestError> {
+    match query {
+        Query::Or(queries) | Query::And(queries) => queries.iter().try_for_each(check_child),
+        Query::Not(q) => check_child(q),
+        Query::Distinct(_) => Err(RequestError::Unsupported("distinct")),
+        Query::Aggregate(..) => Err(RequestError::Unsupported("aggregate")),
+        Query::First => Err(RequestError::Unsupported("first")),
+        Query::One => Err(RequestError::Unsupported("one")),
+        Query::Count => Err(RequestError::Unsupported("count")),
+        Query::Reduce(function, _) => Err(RequestError::Unsupported(function.name())),
+        _ => Ok(()),
+    }
+}
+
+// or() や not() の中には sort() なども置けない
+fn check_child(query: &Query) -> Result<(), RequestError> {
+    match query {
+        Query::Sort(_) => Err(RequestError::Misplaced("sort")),
+        Query::Limit(..) => Err(RequestError::Misplaced("limit")),
+        Query::Select(_) => Err(RequestError::Misplaced("select")),
+        q => check_filter(q),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Prefix, Query};
+    use crate::parser::Parser;
+    use crate::request::{Request, RequestError};
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn full() {
+        let request = Request::parse(
+            "and(eq(type,\"x\"),sort(-created_at,+id),limit(20,40),select(id,name),gt(age,18))",
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request {
+                filter: Some(parse("and(eq(type,\"x\"),gt(age,18))")),
+                sort: vec![
+                    (Prefix::Minus, "created_at".into()),
+                    (Prefix::Plus, "id".into())
+                ],
+                limit: Some((20, 40)),
+                select: vec!["id".into(), "name".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn partial() {
+        assert_eq!(
+            Request::parse("eq(a,1)").unwrap(),
+            Request {
+                filter: Some(parse("eq(a,1)")),
+                ..Request::default()
+            }
+        );
+        // 入れ子の and() も最上位として扱う
+        assert_eq!(
+            Request::parse("and(and(limit(5),or(eq(a,1),eq(a,2))))").unwrap(),
+            Request {
+                filter: Some(parse("or(eq(a,1),eq(a,2))")),
+                limit: Some((5, 0)),
+                ..Request::default()
+            }
+        );
+        assert_eq!(Request::parse("sort(+a)").unwrap().filter, None);
+        assert_eq!(
+            Request::from_query(Query::None).unwrap(),
+            Request::default()
+        );
+    }
+
+    #[test]
+    fn conflicting() {
+        let err = |input: &str| Request::parse(input).unwrap_err().to_string();
+        assert_eq!(
+            err("and(limit(10),eq(a,1),limit(20))"),
+            "limit() is given more than once"
+        );
+        assert_eq!(
+            err("and(sort(+a),sort(-b))"),
+            "sort() is given more than once"
+        );
+        // パーサは or() の中の select() などを受け付けないので、組み立てて渡す
+        let misplaced = Query::Or(vec![parse("eq(a,1)"), parse("and(eq(b,2),select(a))")]);
+        assert_eq!(
+            Request::from_query(misplaced).unwrap_err().to_string(),
+            "select() must be at the top level, not inside or() or not()"
+        );
+        let negated = Query::Not(Box::new(parse("and(eq(b,2),limit(1))")));
+        assert!(matches!(
+            Request::from_query(negated),
+            Err(RequestError::Misplaced("limit"))
+        ));
+        let reduced = Query::Or(vec![parse("eq(a,1)"), parse("sum(b)")]);
+        assert_eq!(
+            Request::from_query(reduced).unwrap_err().to_string(),
+            "sum() isn't supported here"
+        );
+        assert_eq!(err("and(eq(a,1),count())"), "count() isn't supported here");
+        assert!(matches!(
+            Request::parse("and(eq(a,1)"),
+            Err(RequestError::Parse(_))
+        ));
+    }
+}
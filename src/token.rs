@@ -1,4 +1,4 @@
-use std::fmt;
+use std::fmt::{self, Write};
 use Token::*;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -11,12 +11,16 @@ pub enum Token {
     Int(String),    // 123456
     Float(String),  // 123.456
     Str(String), // "hello"
+    Var(String),    // $name
+    Date(String),   // date:2024-01-01T00:00:00Z
+    Now(String),    // now-7d の -7d
     True,
     False,
 
     // Query
     And,
     Or,
+    Not,
     Plus,
     Minus,
     Sort,
@@ -29,7 +33,22 @@ pub enum Token {
     Contains,
     Excludes,
     Limit,
-
+    First,
+    One,
+    Count,
+    Sum,
+    Min,
+    Max,
+    Mean,
+    Exists,
+    Missing,
+    Search,
+    Near,
+    StartsWith,
+    EndsWith,
+    IEq,
+    IContains,
+    IStartsWith,
 
     // Operators
     Eq,
@@ -45,6 +64,25 @@ pub enum Token {
     Rparen,
 }
 
+/// Writes `s` as a string literal the lexer reads back as `s`: between double
+/// quotes, with `"` and `\` escaped by a backslash and control characters
+/// written as `\n`, `\r`, `\t` or `\u{..}`.
+pub fn write_quoted(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in s.chars() {
+        match ch {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            ch if ch.is_control() => write!(f, "\\u{{{:x}}}", ch as u32)?,
+            ch => f.write_char(ch)?,
+        }
+    }
+    f.write_char('"')
+}
+
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
@@ -54,27 +92,144 @@ impl fmt::Display for Token {
             Ident(ident) => write!(f, "{}", ident),
             Int(int) => write!(f, "{}", int),
             Float(float) => write!(f, "{}", float),
-            // TODO: Escape `"` in a string as `\"`...
-            Str(s) => write!(f, "\"{}\"", s),
-            True => write!(f, "true"),
-            False => write!(f, "false"),
+            Str(s) => write_quoted(f, s),
+            Var(name) => write!(f, "${}", name),
+            Date(s) => write!(f, "date:{}", s),
+            Now(offset) => write!(f, "now{}", offset),
 
             Plus => write!(f, "+"),
             Minus => write!(f, "-"),
-            And => write!(f, "and"),
-            Or => write!(f, "or"),
-
-            Eq => write!(f, "eq"),
-            NotEq => write!(f, "ne"),
-            Le => write!(f, "le"),
-            Ge => write!(f, "ge"),
-            Lt => write!(f, "lt"),
-            Gt => write!(f, "gt"),
-
             Comma => write!(f, ","),
             Lparen => write!(f, "("),
             Rparen => write!(f, ")"),
-            _ => write!(f, "not implemented"),
+
+            True | False | And | Or | Not | Sort | Select | Values | Aggregate | Distinct | In | Out
+            | Contains | Excludes | Limit | First | One | Count | Sum | Min | Max | Mean
+            | Exists | Missing | Search | Near | StartsWith | EndsWith | IEq | IContains
+            | IStartsWith | Eq | NotEq | Le | Ge | Lt | Gt => {
+                write!(f, "{}", self.keyword_str().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Coarse classification of tokens, for highlighting and error messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    // 識別子と $name
+    Identifier,
+    // 数値、文字列、true/false
+    Literal,
+    // and, or, not
+    Logical,
+    // eq, ne, lt, le, gt, ge, startswith, endswith, ieq, icontains, istartswith
+    Comparison,
+    // sort, limit, count など
+    Keyword,
+    // , ( ) + -
+    Punct,
+    Eof,
+    Illegal,
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Ident(_) | Var(_) => TokenKind::Identifier,
+            Int(_) | Float(_) | Str(_) | Date(_) | Now(_) | True | False => TokenKind::Literal,
+            And | Or | Not => TokenKind::Logical,
+            Eq | NotEq | Le | Ge | Lt | Gt | StartsWith | EndsWith | IEq | IContains
+            | IStartsWith => TokenKind::Comparison,
+            Sort | Select | Values | Aggregate | Distinct | In | Out | Contains | Excludes
+            | Limit | First | One | Count | Sum | Min | Max | Mean | Exists | Missing
+            | Search | Near => TokenKind::Keyword,
+            Plus | Minus | Comma | Lparen | Rparen => TokenKind::Punct,
+            Eof => TokenKind::Eof,
+            Illegal => TokenKind::Illegal,
+        }
+    }
+
+    /// `eq`, `ne`, `lt`, `le`, `gt`, `ge`, `startswith`, `endswith` and the
+    /// case-insensitive `ieq`, `icontains` and `istartswith`: the tokens that
+    /// start a filter.
+    pub fn is_comparison_operator(&self) -> bool {
+        self.kind() == TokenKind::Comparison
+    }
+
+    pub fn is_logical(&self) -> bool {
+        self.kind() == TokenKind::Logical
+    }
+
+    pub fn is_literal(&self) -> bool {
+        self.kind() == TokenKind::Literal
+    }
+
+    pub fn is_punct(&self) -> bool {
+        self.kind() == TokenKind::Punct
+    }
+
+    /// The text of a keyword token (`true`, `and`, `eq`, `sort`...), as
+    /// [`lookup_ident`] recognizes it.
+    pub fn keyword_str(&self) -> Option<&'static str> {
+        let keyword = match self {
+            True => "true",
+            False => "false",
+            And => "and",
+            Or => "or",
+            Not => "not",
+            Sort => "sort",
+            Select => "select",
+            Values => "values",
+            Aggregate => "aggregate",
+            Distinct => "distinct",
+            In => "in",
+            Out => "out",
+            Contains => "contains",
+            Excludes => "excludes",
+            Limit => "limit",
+            First => "first",
+            One => "one",
+            Count => "count",
+            Sum => "sum",
+            Min => "min",
+            Max => "max",
+            Mean => "mean",
+            Exists => "exists",
+            Missing => "missing",
+            Search => "search",
+            Near => "near",
+            StartsWith => "startswith",
+            EndsWith => "endswith",
+            IEq => "ieq",
+            IContains => "icontains",
+            IStartsWith => "istartswith",
+            Eq => "eq",
+            NotEq => "ne",
+            Le => "le",
+            Ge => "ge",
+            Lt => "lt",
+            Gt => "gt",
+            Illegal | Eof | Ident(_) | Int(_) | Float(_) | Str(_) | Var(_) | Date(_) | Now(_)
+            | Plus | Minus | Comma | Lparen | Rparen => return None,
+        };
+        Some(keyword)
+    }
+
+    /// Keywords that only act as operators when followed by `(`; elsewhere,
+    /// such as `eq(count,1)`, they are read as field names.
+    pub fn soft_keyword(&self) -> Option<&'static str> {
+        match self {
+            Not | First | One | Count | Sum | Min | Max | Mean | Exists | Missing | Search
+            | Near | StartsWith | EndsWith | IEq | IContains | IStartsWith => self.keyword_str(),
+            _ => None,
+        }
+    }
+
+    // エラーメッセージで「何が見つかったか」を表す
+    pub(crate) fn describe(&self) -> String {
+        match self.kind() {
+            TokenKind::Eof => "end of input".to_owned(),
+            _ => format!("'{}'", self),
         }
     }
 }
@@ -95,7 +250,7 @@ fn keyword_to_token(keyword: &str) -> Option<Token> {
         "gt" => Some(Gt),
         "and" => Some(And),
         "or" => Some(Or),
-        // TODO : not implement
+        "not" => Some(Not),
         "sort" => Some(Sort),
         "select" => Some(Select),
         "values" => Some(Values),
@@ -106,6 +261,109 @@ fn keyword_to_token(keyword: &str) -> Option<Token> {
         "contains" => Some(Contains),
         "excludes" => Some(Excludes),
         "limit" => Some(Limit),
+        "first" => Some(First),
+        "one" => Some(One),
+        "count" => Some(Count),
+        "sum" => Some(Sum),
+        "min" => Some(Min),
+        "max" => Some(Max),
+        "mean" => Some(Mean),
+        "exists" => Some(Exists),
+        "missing" => Some(Missing),
+        "search" => Some(Search),
+        "near" => Some(Near),
+        "startswith" => Some(StartsWith),
+        "endswith" => Some(EndsWith),
+        "ieq" => Some(IEq),
+        "icontains" => Some(IContains),
+        "istartswith" => Some(IStartsWith),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::Lexer;
+    use crate::token::Token::*;
+    use crate::token::{lookup_ident, Token, TokenKind};
+
+    #[test]
+    fn display_lexes_back() {
+        let tokens = [
+            Ident("speed.max".to_owned()),
+            Int("123".to_owned()),
+            Float("1.5".to_owned()),
+            Str("hello".to_owned()),
+            Str("say \"hi\"\n\tC:\\dir\\\r\u{1b}[0m".to_owned()),
+            Var("user".to_owned()),
+            True, False, And, Or, Not, Plus, Minus, Sort, Select, Values, Aggregate, Distinct,
+            In, Out, Contains, Excludes, Limit, First, One, Count, Sum, Min, Max, Mean,
+            Exists, Missing, Search, Near, StartsWith, EndsWith, IEq, IContains, IStartsWith,
+            Eq, NotEq, Le, Ge, Lt, Gt, Comma, Lparen, Rparen,
+        ];
+        for token in tokens {
+            let text = token.to_string();
+            let mut lexer = Lexer::new(text.clone()).with_variables(true);
+            assert_eq!(lexer.next_token(), token, "{}", text);
+            assert_eq!(lexer.next_token(), Eof, "{}", text);
+            if text.chars().all(|c| c.is_ascii_lowercase()) {
+                assert_eq!(lookup_ident(&text), token);
+            }
+        }
+        assert_eq!(
+            Str("a \"b\"\\\n".to_owned()).to_string(),
+            r#""a \"b\"\\\n""#
+        );
+        assert_eq!(Token::Illegal.to_string(), "ILLEGAL");
+        assert_eq!(Token::Eof.to_string(), "EOF");
+    }
+
+    #[test]
+    fn classification() {
+        let cases = vec![
+            (Illegal, TokenKind::Illegal),
+            (Eof, TokenKind::Eof),
+            (Ident("a".to_owned()), TokenKind::Identifier),
+            (Var("a".to_owned()), TokenKind::Identifier),
+            (Int("1".to_owned()), TokenKind::Literal),
+            (Float("1.5".to_owned()), TokenKind::Literal),
+            (Str("a".to_owned()), TokenKind::Literal),
+            (True, TokenKind::Literal),
+            (False, TokenKind::Literal),
+            (And, TokenKind::Logical),
+            (Or, TokenKind::Logical),
+            (Not, TokenKind::Logical),
+            (Plus, TokenKind::Punct),
+            (Minus, TokenKind::Punct),
+            (Comma, TokenKind::Punct),
+            (Lparen, TokenKind::Punct),
+            (Rparen, TokenKind::Punct),
+        ];
+        let comparisons = vec![
+            Eq, NotEq, Le, Ge, Lt, Gt, StartsWith, EndsWith, IEq, IContains, IStartsWith,
+        ];
+        let keywords = vec![
+            Sort, Select, Values, Aggregate, Distinct, In, Out, Contains, Excludes, Limit, First,
+            One, Count, Sum, Min, Max, Mean, Exists, Missing, Search, Near,
+        ];
+        let cases = cases
+            .into_iter()
+            .chain(comparisons.into_iter().map(|t| (t, TokenKind::Comparison)))
+            .chain(keywords.into_iter().map(|t| (t, TokenKind::Keyword)));
+        for (token, kind) in cases {
+            assert_eq!(token.kind(), kind, "{:?}", token);
+            assert_eq!(token.is_comparison_operator(), kind == TokenKind::Comparison);
+            assert_eq!(token.is_logical(), kind == TokenKind::Logical);
+            assert_eq!(token.is_literal(), kind == TokenKind::Literal);
+            assert_eq!(token.is_punct(), kind == TokenKind::Punct);
+            let keyword = matches!(
+                kind,
+                TokenKind::Logical | TokenKind::Comparison | TokenKind::Keyword
+            ) || matches!(token, True | False);
+            assert_eq!(token.keyword_str().is_some(), keyword, "{:?}", token);
+            if let Some(text) = token.keyword_str() {
+                assert_eq!(lookup_ident(text), token);
+            }
+        }
+    }
+}
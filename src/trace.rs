@@ -0,0 +1,220 @@
+//! Spans and events for the `tracing` feature.
+//!
+//! Parsing opens a `parse_query` span for every query node and a
+//! `parse_filter` span inside it for every filter, at debug level, so nested
+//! `and()`/`or()` show up as nested spans. They carry the input length, the
+//! nesting depth and, once parsed, the number of nodes; a failed parse records
+//! the error code on every span it unwinds and emits one event at the top.
+//! Evaluation opens a `matches` span per document, at trace level, recording
+//! the node count and whether the document matched.
+
+use crate::ast::Query;
+use crate::eval::EvalError;
+use crate::parser::ParserError;
+use tracing::field::Empty;
+use tracing::span::EnteredSpan;
+use tracing::{debug, debug_span, trace_span};
+
+pub(crate) fn parse_query(len: usize, depth: usize, position: usize) -> EnteredSpan {
+    debug_span!(
+        "parse_query",
+        len,
+        depth,
+        position,
+        nodes = Empty,
+        error = Empty
+    )
+    .entered()
+}
+
+pub(crate) fn parse_filter(len: usize, depth: usize, position: usize) -> EnteredSpan {
+    debug_span!(
+        "parse_filter",
+        len,
+        depth,
+        position,
+        nodes = Empty,
+        error = Empty
+    )
+    .entered()
+}
+
+pub(crate) fn parsed(span: &EnteredSpan, top: bool, result: &Result<Query, ParserError>) {
+    match result {
+        Ok(query) => {
+            span.record("nodes", node_count(query));
+        }
+        Err(e) => {
+            span.record("error", e.code());
+            if top {
+                debug!(code = e.code(), "{}", e);
+            }
+        }
+    }
+}
+
+pub(crate) fn matches(query: &Query) -> EnteredSpan {
+    trace_span!("matches", nodes = node_count(query), matched = Empty).entered()
+}
+
+pub(crate) fn matched(span: &EnteredSpan, result: &Result<bool, EvalError>) {
+    match result {
+        Ok(matched) => {
+            span.record("matched", matched);
+        }
+        Err(e) => debug!("{}", e),
+    }
+}
+
+fn node_count(query: &Query) -> usize {
+    match query {
+        Query::And(queries) | Query::Or(queries) => {
+            1 + queries.iter().map(node_count).sum::<usize>()
+        }
+        Query::Not(query) => 1 + node_count(query),
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::{EvalOptions, FieldType, Schema};
+    use crate::parser::Parser;
+    use std::fmt::{self, Write};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    // 開いたスパン、記録されたフィールド、イベントを1行ずつ文字列で残す
+    #[derive(Clone, Default)]
+    struct Capture {
+        lines: Arc<Mutex<Vec<String>>>,
+        next_id: Arc<AtomicU64>,
+    }
+
+    struct Fields<'a>(&'a mut String);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            write!(self.0, " {}={:?}", field.name(), value).unwrap();
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut line = format!("span {}", span.metadata().name());
+            span.record(&mut Fields(&mut line));
+            self.lines.lock().unwrap().push(line);
+            Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _: &Id, values: &Record) {
+            let mut line = "record".to_owned();
+            values.record(&mut Fields(&mut line));
+            self.lines.lock().unwrap().push(line);
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event) {
+            let mut line = "event".to_owned();
+            event.record(&mut Fields(&mut line));
+            self.lines.lock().unwrap().push(line);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn capture(f: impl FnOnce()) -> Vec<String> {
+        let subscriber = Capture::default();
+        let lines = subscriber.lines.clone();
+        tracing::subscriber::with_default(subscriber, f);
+        let lines = lines.lock().unwrap().clone();
+        lines
+    }
+
+    #[test]
+    fn parse() {
+        let lines = capture(|| {
+            Parser::new_from_string("and(eq(a,1),sort(+a))".to_owned())
+                .parse_query()
+                .unwrap();
+        });
+        // トークンを出力する debug! のイベントは除く
+        let spans: Vec<&String> = lines.iter().filter(|l| !l.starts_with("event")).collect();
+        assert_eq!(
+            spans,
+            vec![
+                "span parse_query len=21 depth=0 position=0",
+                "span parse_query len=21 depth=1 position=4",
+                "span parse_filter len=21 depth=1 position=4",
+                "record nodes=1",
+                "record nodes=1",
+                "span parse_query len=21 depth=1 position=12",
+                "record nodes=1",
+                "record nodes=3",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_error() {
+        let lines = capture(|| {
+            Parser::new_from_string("or(eq(a 1))".to_owned())
+                .parse_query()
+                .unwrap_err();
+        });
+        assert_eq!(
+            lines[lines.len() - 2..],
+            [
+                "record error=\"expected_comma\"".to_owned(),
+                "event message=expected ',', found '1' at position 8 code=\"expected_comma\""
+                    .to_owned(),
+            ]
+        );
+        assert_eq!(lines.iter().filter(|l| l.starts_with("event")).count(), 1);
+    }
+
+    #[test]
+    fn matches() {
+        let query = Parser::new_from_string("or(eq(a,1),eq(a,2))".to_owned())
+            .parse_query()
+            .unwrap();
+        let lines = capture(|| {
+            assert!(query.matches(&serde_json::json!({"a": 2})));
+            assert!(!query.matches(&serde_json::json!({"a": 3})));
+        });
+        assert_eq!(
+            lines,
+            vec![
+                "span matches nodes=3",
+                "record matched=true",
+                "span matches nodes=3",
+                "record matched=false",
+            ]
+        );
+
+        let mut schema = Schema::new();
+        schema.field("a", FieldType::Integer);
+        let options = EvalOptions {
+            schema: Some(schema),
+            strict_types: true,
+            ..Default::default()
+        };
+        let lines = capture(|| {
+            query
+                .try_matches_with(&serde_json::json!({"a": "x"}), &options)
+                .unwrap_err();
+        });
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("event message=a can't be read as"));
+    }
+}
@@ -0,0 +1,9 @@
+//! Rewrites of a query's AST before it is evaluated or translated.
+
+mod partial;
+mod ranges;
+mod rename;
+mod rewrite;
+
+pub use rename::{Case, MapError, MapOptions};
+pub use rewrite::{ExpandField, RewriteError, Rewriter, Rule, SubstituteOperator};
@@ -0,0 +1,200 @@
+//! Partial evaluation: deciding the filters on fields whose values are
+//! already known, e.g. a tenant id taken from the session.
+
+use crate::ast::{Path, Query, Value};
+use crate::eval::{insert, is_shaping, or_operands};
+use serde_json::Map;
+
+impl Query {
+    /// Decides every filter, `in()`, `out()`, `contains()`, `exists()`,
+    /// `missing()`, `search()` and `near()` whose fields are all bound and
+    /// simplifies around the outcome: a false conjunct makes its `and()`
+    /// false, a true disjunct makes its `or()` true, a decided `not()` is
+    /// flipped, and decided children are dropped otherwise. The residual query
+    /// only refers to unknown fields and matches the same documents as `self`
+    /// among those consistent with `bindings`.
+    ///
+    /// Keys of `bindings` are dotted paths. A bound object also decides the
+    /// paths below it: with `{"user": {"id": 1}}`, `user.name` is known to be
+    /// missing. A filter comparing with another field (`eq(a,b)`) is decided
+    /// only when both are bound; ones using `$variables` or `calls()` are kept.
+    ///
+    /// A query decided to be true is `and()`, one decided to be false is
+    /// `or()`. Shaping nodes such as `sort()` and `limit()` are kept.
+    pub fn partial_eval(&self, bindings: &Map<String, serde_json::Value>) -> Query {
+        // 束縛をネストしたドキュメントにしておき、決まったフィルタはそのまま評価する
+        let mut doc = Map::new();
+        for (path, value) in bindings {
+            insert(&mut doc, &Path::from(path.as_str()), value);
+        }
+        let doc = serde_json::Value::Object(doc);
+        partial(self, bindings, &doc)
+    }
+}
+
+fn partial(
+    query: &Query,
+    bindings: &Map<String, serde_json::Value>,
+    doc: &serde_json::Value,
+) -> Query {
+    match query {
+        Query::And(queries) => {
+            let queries: Vec<Query> = queries.iter().map(|q| partial(q, bindings, doc)).collect();
+            if queries.iter().any(is_false) {
+                let mut shaping: Vec<Query> = queries.into_iter().filter(is_shaping).collect();
+                if shaping.is_empty() {
+                    return Query::Or(vec![]);
+                }
+                shaping.insert(0, Query::Or(vec![]));
+                return Query::And(shaping);
+            }
+            let mut queries: Vec<Query> = queries.into_iter().filter(|q| !is_true(q)).collect();
+            // and(limit(1)) は or() の中では真だが、limit(1) だけなら無視される
+            if queries.len() == 1 && !is_shaping(&queries[0]) {
+                queries.remove(0)
+            } else {
+                Query::And(queries)
+            }
+        }
+        Query::Or(queries) => {
+            let queries: Vec<Query> = or_operands(queries).map(|q| partial(q, bindings, doc)).collect();
+            if queries.iter().any(is_true) {
+                return constant(true);
+            }
+            let mut queries: Vec<Query> = queries.into_iter().filter(|q| !is_false(q)).collect();
+            if queries.len() == 1 {
+                queries.remove(0)
+            } else {
+                Query::Or(queries)
+            }
+        }
+        Query::Not(q) => match partial(q, bindings, doc) {
+            q if is_true(&q) => constant(false),
+            q if is_false(&q) => constant(true),
+            q => Query::Not(Box::new(q)),
+        },
+        Query::Filter(_, Value::Identifier(path), literal) | Query::Contains(path, literal)
+            if bound(bindings, path) =>
+        {
+            match decidable(bindings, literal) {
+                true => constant(query.matches(doc)),
+                false => query.clone(),
+            }
+        }
+        Query::In(path, values) | Query::Out(path, values)
+            if bound(bindings, path) && values.iter().all(|v| decidable(bindings, v)) =>
+        {
+            constant(query.matches(doc))
+        }
+        Query::Exists(path) | Query::Missing(path) | Query::Near(path, ..)
+            if bound(bindings, path) =>
+        {
+            constant(query.matches(doc))
+        }
+        Query::Search(fields, _)
+            if !fields.is_empty()
+                && fields.iter().all(|f| bound(bindings, f)) =>
+        {
+            constant(query.matches(doc))
+        }
+        _ => query.clone(),
+    }
+}
+
+// 値がバインディングだけで決まるか
+fn decidable(bindings: &Map<String, serde_json::Value>, value: &Value) -> bool {
+    match value {
+        Value::Call(_) | Value::Variable(_) => false,
+        Value::Identifier(other) => bound(bindings, other),
+        _ => true,
+    }
+}
+
+// パス自体か、その親のどれかが束縛されていれば値(または欠損)が決まる
+fn bound(bindings: &Map<String, serde_json::Value>, path: &Path) -> bool {
+    let segments = path.segments();
+    (1..=segments.len()).any(|i| {
+        let prefix = Path::new(segments[..i].to_vec());
+        bindings.contains_key(&prefix.to_string())
+    })
+}
+
+fn constant(outcome: bool) -> Query {
+    if outcome {
+        Query::And(vec![])
+    } else {
+        Query::Or(vec![])
+    }
+}
+
+fn is_true(query: &Query) -> bool {
+    matches!(query, Query::And(queries) if queries.is_empty())
+}
+
+fn is_false(query: &Query) -> bool {
+    matches!(query, Query::Or(queries) if queries.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{Parser, ParserOptions};
+    use serde_json::json;
+
+    fn partial(input: &str, bindings: serde_json::Value) -> String {
+        let query = Parser::new_from_string(input.to_owned())
+            .with_options(ParserOptions {
+                variables: true,
+                ..ParserOptions::default()
+            })
+            .parse_query()
+            .unwrap();
+        query.partial_eval(bindings.as_object().unwrap()).to_rql()
+    }
+
+    #[test]
+    fn simplify() {
+        let bindings = json!({"tenant_id": 7, "user": {"role": "admin"}});
+        assert_eq!(
+            partial("and(eq(tenant_id,7),gt(price,10))", bindings.clone()),
+            "gt(price,10)"
+        );
+        assert_eq!(
+            partial("and(eq(tenant_id,8),gt(price,10))", bindings.clone()),
+            "or()"
+        );
+        assert_eq!(
+            partial("or(eq(user.role,\"admin\"),eq(owner,3))", bindings.clone()),
+            "and()"
+        );
+        assert_eq!(
+            partial(
+                "or(eq(user.role,\"guest\"),eq(owner,3),exists(user.name))",
+                bindings.clone()
+            ),
+            "eq(owner,3)"
+        );
+        // 片方だけ束縛されたフィールド同士の比較は残す
+        assert_eq!(
+            partial("eq(tenant_id,owner)", bindings.clone()),
+            "eq(tenant_id,owner)"
+        );
+        assert_eq!(
+            partial("and(eq(tenant_id,8),sort(-price),limit(10))", bindings),
+            "and(or(),sort(-price),limit(10))"
+        );
+    }
+
+    #[test]
+    fn membership() {
+        let bindings = json!({"tenant_id": 7, "roles": ["admin"]});
+        assert_eq!(
+            partial("and(in(tenant_id,(7,8)),not(contains(roles,\"guest\")),gt(price,10))", bindings.clone()),
+            "gt(price,10)"
+        );
+        assert_eq!(partial("not(out(tenant_id,(7)))", bindings.clone()), "and()");
+        assert_eq!(
+            partial("or(in(tenant_id,(1,$mine)),not(contains(tags,\"x\")))", bindings),
+            "or(in(tenant_id,(1,$mine)),not(contains(tags,\"x\")))"
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Merging the bounds an `and()` puts on one numeric field, as UI sliders
+//! tend to produce them, into a single interval.
+
+use crate::ast::{number, Infix, Query, Value};
+use crate::eval::is_shaping;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+impl Query {
+    /// Collapses the `eq()`, `lt()`, `le()`, `gt()` and `ge()` filters an
+    /// `and()` applies to the same field with number literals into the
+    /// tightest lower and upper bound, or the single `eq()` they allow:
+    /// `and(ge(price,10),le(price,20),ge(price,12))` becomes
+    /// `and(ge(price,12),le(price,20))`. Directly nested `and()`s are merged
+    /// with their parent. When the bounds leave no number at all (`gt(x,10)`
+    /// with `lt(x,5)`, or two different `eq()`s), the `and()` becomes the
+    /// constant false `or()`; shaping nodes such as `sort()` and `limit()`
+    /// are kept beside it.
+    ///
+    /// The result matches the same documents. An integer literal only matches
+    /// integers, so when the tightest bound is a float and another one is an
+    /// integer, one integer bound is kept to carry that. Paths with wildcards
+    /// and comparisons with other fields are left alone.
+    ///
+    /// [`Query::to_sql`] and [`Query::to_es`] apply this before translating.
+    pub fn merge_ranges(&self) -> Query {
+        match self {
+            Query::And(queries) => merge(queries.iter().map(Query::merge_ranges).collect()),
+            Query::Or(queries) => Query::Or(queries.iter().map(Query::merge_ranges).collect()),
+            Query::Not(query) => Query::Not(Box::new(query.merge_ranges())),
+            query => query.clone(),
+        }
+    }
+}
+
+struct Bound {
+    index: usize,
+    infix: Infix,
+    value: f64,
+    integer: bool,
+}
+
+fn bound(index: usize, query: &Query) -> Option<(String, Bound)> {
+    match query {
+        Query::Filter(
+            infix @ (Infix::Eq | Infix::Lt | Infix::Le | Infix::Gt | Infix::Ge),
+            Value::Identifier(path),
+            value,
+        ) if !path.has_wildcard() => {
+            let (value, integer) = number(value)?;
+            Some((
+                path.to_string(),
+                Bound {
+                    index,
+                    infix: infix.clone(),
+                    value,
+                    integer,
+                },
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn merge(queries: Vec<Query>) -> Query {
+    let mut conjuncts = vec![];
+    for query in &queries {
+        match query {
+            Query::And(children) => conjuncts.extend(children.iter().cloned()),
+            query => conjuncts.push(query.clone()),
+        }
+    }
+    let mut fields: BTreeMap<String, Vec<Bound>> = BTreeMap::new();
+    for (path, bound) in conjuncts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, q)| bound(i, q))
+    {
+        fields.entry(path).or_default().push(bound);
+    }
+    // 消えるフィルタの位置と、フィールドの最初のフィルタの位置に置くフィルタ
+    let mut dropped = vec![false; conjuncts.len()];
+    let mut replaced: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for bounds in fields.values() {
+        let kept = match keep(bounds) {
+            Some(kept) => kept,
+            None => {
+                let mut shaping: Vec<Query> = conjuncts.into_iter().filter(is_shaping).collect();
+                if shaping.is_empty() {
+                    return Query::Or(vec![]);
+                }
+                shaping.insert(0, Query::Or(vec![]));
+                return Query::And(shaping);
+            }
+        };
+        if kept.len() < bounds.len() {
+            bounds.iter().for_each(|b| dropped[b.index] = true);
+            replaced.insert(bounds[0].index, kept);
+        }
+    }
+    if replaced.is_empty() {
+        return Query::And(queries);
+    }
+    let mut merged = vec![];
+    for (i, query) in conjuncts.iter().enumerate() {
+        match replaced.get(&i) {
+            Some(kept) => merged.extend(kept.iter().map(|&k| conjuncts[k].clone())),
+            None if dropped[i] => {}
+            None => merged.push(query.clone()),
+        }
+    }
+    if merged.len() == 1 {
+        merged.remove(0)
+    } else {
+        Query::And(merged)
+    }
+}
+
+// 残すフィルタの位置。どの数値も満たせなければ None
+fn keep(bounds: &[Bound]) -> Option<Vec<usize>> {
+    let (eqs, ranges): (Vec<&Bound>, Vec<&Bound>) =
+        bounds.iter().partition(|b| b.infix == Infix::Eq);
+    let mut kept: Vec<&Bound> = if let Some(first) = eqs.first() {
+        if eqs.iter().any(|e| e.value != first.value) {
+            return None;
+        }
+        let eq = eqs.iter().find(|e| e.integer).unwrap_or(first);
+        if !ranges.iter().all(|b| holds(&b.infix, eq.value, b.value)) {
+            return None;
+        }
+        vec![eq]
+    } else {
+        let lower = ranges
+            .iter()
+            .copied()
+            .filter(|b| is_lower(b))
+            .max_by(|x, y| tighter(x, y));
+        let upper = ranges
+            .iter()
+            .copied()
+            .filter(|b| !is_lower(b))
+            .max_by(|x, y| tighter(x, y));
+        if let (Some(l), Some(u)) = (lower, upper) {
+            let strict = l.infix == Infix::Gt || u.infix == Infix::Lt;
+            if l.value > u.value || (l.value == u.value && strict) {
+                return None;
+            }
+        }
+        lower.into_iter().chain(upper).collect()
+    };
+    // 整数リテラルは整数にしか一致しないので、その条件を持つフィルタを1つ残す
+    if !kept.iter().any(|b| b.integer) {
+        let integer = ranges.iter().copied().filter(|b| b.integer);
+        let lower = integer
+            .clone()
+            .filter(|b| is_lower(b))
+            .max_by(|x, y| tighter(x, y));
+        let upper = integer
+            .filter(|b| !is_lower(b))
+            .max_by(|x, y| tighter(x, y));
+        kept.extend(lower.or(upper));
+    }
+    Some(kept.iter().map(|b| b.index).collect())
+}
+
+fn is_lower(bound: &Bound) -> bool {
+    matches!(bound.infix, Infix::Gt | Infix::Ge)
+}
+
+// 同じ向きの境界のうち、x のほうが狭ければ Greater。値が同じなら厳密なもの、整数のものを優先する
+fn tighter(x: &Bound, y: &Bound) -> Ordering {
+    let value = x.value.partial_cmp(&y.value).unwrap_or(Ordering::Equal);
+    let value = if is_lower(x) { value } else { value.reverse() };
+    let strict = |b: &Bound| matches!(b.infix, Infix::Gt | Infix::Lt);
+    value
+        .then(strict(x).cmp(&strict(y)))
+        .then(x.integer.cmp(&y.integer))
+        .then(y.index.cmp(&x.index))
+}
+
+fn holds(infix: &Infix, x: f64, n: f64) -> bool {
+    match infix {
+        Infix::Lt => x < n,
+        Infix::Le => x <= n,
+        Infix::Gt => x > n,
+        Infix::Ge => x >= n,
+        _ => x == n,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Query;
+    use crate::parser::Parser;
+
+    fn merge(input: &str) -> String {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+            .merge_ranges()
+            .to_rql()
+    }
+
+    #[test]
+    fn overlapping() {
+        assert_eq!(
+            merge("and(ge(price,10),le(price,20),ge(price,12))"),
+            "and(ge(price,12),le(price,20))"
+        );
+        assert_eq!(
+            merge("and(eq(kind,\"a\"),lt(price,30),le(price,20),gt(price,5))"),
+            "and(eq(kind,\"a\"),gt(price,5),le(price,20))"
+        );
+        assert_eq!(merge("and(ge(x,5),gt(x,5))"), "gt(x,5)");
+        assert_eq!(merge("and(le(x,5),lt(x,5),le(x,7))"), "lt(x,5)");
+        assert_eq!(merge("and(ge(x,5),ge(x,5))"), "ge(x,5)");
+        assert_eq!(merge("and(eq(x,3),ge(x,1),lt(x,10))"), "eq(x,3)");
+        assert_eq!(merge("and(eq(x,3),eq(x,3.0))"), "eq(x,3)");
+        // 一方だけの境界は変えない
+        assert_eq!(merge("and(ge(x,1),le(x,2))"), "and(ge(x,1),le(x,2))");
+        assert_eq!(merge("and(ge(x,1),ge(y,2))"), "and(ge(x,1),ge(y,2))");
+        assert_eq!(merge("and(ge(x,1),ne(x,2))"), "and(ge(x,1),ne(x,2))");
+    }
+
+    #[test]
+    fn integer_and_float() {
+        assert_eq!(merge("and(gt(x,4.5),gt(x,7))"), "gt(x,7)");
+        // gt(x,5.5) だけでは 6.5 にも一致してしまう
+        assert_eq!(
+            merge("and(gt(x,5.5),gt(x,3),gt(x,1))"),
+            "and(gt(x,5.5),gt(x,3))"
+        );
+        assert_eq!(
+            merge("and(gt(x,5.5),gt(x,3),lt(x,9))"),
+            "and(gt(x,5.5),lt(x,9))"
+        );
+        assert_eq!(
+            merge("and(eq(x,4.0),ge(x,1),ge(x,2))"),
+            "and(eq(x,4.0),ge(x,2))"
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            merge("and(ge(price,10),and(le(price,20),ge(price,12)))"),
+            "and(ge(price,12),le(price,20))"
+        );
+        assert_eq!(
+            merge("or(and(gt(a,1),gt(a,2)),and(lt(b,1),lt(b,0)))"),
+            "or(gt(a,2),lt(b,0))"
+        );
+        assert_eq!(
+            merge("and(gt(a,1),or(gt(a,2),eq(b,1)))"),
+            "and(gt(a,1),or(gt(a,2),eq(b,1)))"
+        );
+        assert_eq!(
+            merge("and(gt(tags.*,1),gt(tags.*,2))"),
+            "and(gt(tags.*,1),gt(tags.*,2))"
+        );
+        assert_eq!(merge("and(gt(a,b),gt(a,2))"), "and(gt(a,b),gt(a,2))");
+    }
+
+    #[test]
+    fn contradictory() {
+        assert_eq!(merge("and(gt(x,10),lt(x,5))"), "or()");
+        assert_eq!(merge("and(ge(x,5),lt(x,5))"), "or()");
+        assert_eq!(merge("and(eq(x,1),eq(x,2))"), "or()");
+        assert_eq!(merge("and(eq(x,1),gt(x,1))"), "or()");
+        assert_eq!(
+            merge("or(and(gt(x,10),lt(x,5)),eq(y,1))"),
+            "or(or(),eq(y,1))"
+        );
+        assert_eq!(
+            merge("and(sort(+x),gt(x,10),lt(x,5),limit(10))"),
+            "and(or(),sort(+x),limit(10))"
+        );
+        // 1 と 2 の間の整数は無いが、実数としては空でない
+        assert_eq!(merge("and(gt(x,1),lt(x,2))"), "and(gt(x,1),lt(x,2))");
+        assert_eq!(merge("and(ge(x,5),le(x,5))"), "and(ge(x,5),le(x,5))");
+        assert!(matches!(
+            Parser::new_from_string("and(gt(x,1),lt(x,1))".to_owned())
+                .parse_query()
+                .unwrap()
+                .merge_ranges(),
+            Query::Or(queries) if queries.is_empty()
+        ));
+    }
+}
@@ -0,0 +1,297 @@
+//! Renaming of the fields a query refers to, e.g. from public API names to
+//! storage column names.
+
+use crate::ast::{Path, Query, Segment, Value};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+
+/// A field the mapping function refused.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MapError {
+    pub field: String,
+    pub reason: String,
+}
+
+impl MapError {
+    pub fn new(field: &str, reason: &str) -> Self {
+        MapError {
+            field: field.to_owned(),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.reason)
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// Which identifiers [`Query::map_identifiers_with`] rewrites.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MapOptions {
+    /// With `true`, identifiers in value position (`eq(a,b)`, compared as the
+    /// field `b` when the document has it) are mapped too. By default only
+    /// property positions are.
+    pub values: bool,
+}
+
+/// Naming convention for [`Query::convert_case`], applied to each segment of
+/// a dotted path.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Case {
+    /// `userName` → `user_name`
+    Snake,
+    /// `user_name` → `userName`
+    Camel,
+}
+
+impl Query {
+    /// Rewrites every field path in property position: filter fields and the
+    /// paths of `sort()`, `select()`, `distinct()`, `aggregate()`, `sum()` and
+    /// the like, `exists()` and `missing()`. String literals are left alone.
+    /// Stops at the first path `f` rejects.
+    pub fn map_identifiers<F>(&self, f: F) -> Result<Query, MapError>
+    where
+        F: FnMut(&str) -> Result<String, MapError>,
+    {
+        self.map_identifiers_with(&MapOptions::default(), f)
+    }
+
+    /// Same as [`Query::map_identifiers`] with explicit options.
+    pub fn map_identifiers_with<F>(&self, options: &MapOptions, mut f: F) -> Result<Query, MapError>
+    where
+        F: FnMut(&str) -> Result<String, MapError>,
+    {
+        map(self, options, &mut f)
+    }
+
+    /// Renames fields through `fields`, keyed by the names used in the query.
+    /// A field missing from the map is an error, so the map doubles as the
+    /// list of fields callers may filter on.
+    pub fn rename_fields(&self, fields: &HashMap<String, String>) -> Result<Query, MapError> {
+        self.map_identifiers(|path| {
+            fields
+                .get(path)
+                .cloned()
+                .ok_or_else(|| MapError::new(path, "unknown field"))
+        })
+    }
+
+    /// Converts every field path to another naming convention.
+    pub fn convert_case(&self, case: Case) -> Query {
+        let mapped = map(self, &MapOptions::default(), &mut |path: &str| {
+            let segments = Path::from(path)
+                .segments()
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Key(key) => Segment::Key(convert(key, case)),
+                    _ => segment.clone(),
+                })
+                .collect();
+            Ok::<_, Infallible>(Path::new(segments).to_string())
+        });
+        match mapped {
+            Ok(query) => query,
+            Err(never) => match never {},
+        }
+    }
+}
+
+fn map<E, F>(query: &Query, options: &MapOptions, f: &mut F) -> Result<Query, E>
+where
+    F: FnMut(&str) -> Result<String, E>,
+{
+    Ok(match query {
+        Query::And(queries) => Query::And(map_all(queries, options, f)?),
+        Query::Or(queries) => Query::Or(map_all(queries, options, f)?),
+        Query::Not(query) => Query::Not(Box::new(map(query, options, f)?)),
+        Query::Filter(infix, ident, value) => {
+            let value = match value {
+                Value::Identifier(path) if options.values => Value::Identifier(map_path(path, f)?),
+                _ => value.clone(),
+            };
+            Query::Filter(infix.clone(), map_value(ident, f)?, value)
+        }
+        Query::In(path, values) | Query::Out(path, values) => {
+            let values = values
+                .iter()
+                .map(|value| match value {
+                    Value::Identifier(p) if options.values => Ok(Value::Identifier(map_path(p, f)?)),
+                    _ => Ok(value.clone()),
+                })
+                .collect::<Result<_, E>>()?;
+            match query {
+                Query::In(..) => Query::In(map_path(path, f)?, values),
+                _ => Query::Out(map_path(path, f)?, values),
+            }
+        }
+        Query::Contains(path, value) => {
+            let value = match value {
+                Value::Identifier(p) if options.values => Value::Identifier(map_path(p, f)?),
+                _ => value.clone(),
+            };
+            Query::Contains(map_path(path, f)?, value)
+        }
+        Query::Sort(keys) => Query::Sort(
+            keys.iter()
+                .map(|(prefix, value)| Ok((prefix.clone(), map_value(value, f)?)))
+                .collect::<Result<_, E>>()?,
+        ),
+        Query::Select(fields) => Query::Select(map_paths(fields, f)?),
+        Query::Distinct(Some(path)) => Query::Distinct(Some(map_path(path, f)?)),
+        Query::Aggregate(keys, functions) => Query::Aggregate(
+            map_paths(keys, f)?,
+            functions
+                .iter()
+                .map(|(function, path)| Ok((*function, map_path(path, f)?)))
+                .collect::<Result<_, E>>()?,
+        ),
+        Query::Reduce(function, path) => Query::Reduce(*function, map_path(path, f)?),
+        Query::Exists(path) => Query::Exists(map_path(path, f)?),
+        Query::Missing(path) => Query::Missing(map_path(path, f)?),
+        Query::Search(fields, term) => Query::Search(map_paths(fields, f)?, term.clone()),
+        Query::Near(path, lat, lon, radius) => {
+            Query::Near(map_path(path, f)?, *lat, *lon, *radius)
+        }
+        Query::Limit(..)
+        | Query::Distinct(None)
+        | Query::First
+        | Query::One
+        | Query::Count
+        | Query::None => query.clone(),
+    })
+}
+
+fn map_all<E, F>(queries: &[Query], options: &MapOptions, f: &mut F) -> Result<Vec<Query>, E>
+where
+    F: FnMut(&str) -> Result<String, E>,
+{
+    queries.iter().map(|q| map(q, options, f)).collect()
+}
+
+fn map_paths<E, F>(paths: &[Path], f: &mut F) -> Result<Vec<Path>, E>
+where
+    F: FnMut(&str) -> Result<String, E>,
+{
+    paths.iter().map(|p| map_path(p, f)).collect()
+}
+
+fn map_path<E, F>(path: &Path, f: &mut F) -> Result<Path, E>
+where
+    F: FnMut(&str) -> Result<String, E>,
+{
+    Ok(f(&path.to_string())?.into())
+}
+
+fn map_value<E, F>(value: &Value, f: &mut F) -> Result<Value, E>
+where
+    F: FnMut(&str) -> Result<String, E>,
+{
+    match value {
+        Value::Identifier(path) => Ok(Value::Identifier(map_path(path, f)?)),
+        _ => Ok(value.clone()),
+    }
+}
+
+fn convert(segment: &str, case: Case) -> String {
+    let mut out = String::with_capacity(segment.len() + 4);
+    match case {
+        Case::Snake => {
+            for (i, c) in segment.chars().enumerate() {
+                if c.is_uppercase() {
+                    if i > 0 {
+                        out.push('_');
+                    }
+                    out.extend(c.to_lowercase());
+                } else {
+                    out.push(c);
+                }
+            }
+        }
+        Case::Camel => {
+            let mut upper = false;
+            for c in segment.chars() {
+                if c == '_' && !out.is_empty() {
+                    upper = true;
+                } else if upper {
+                    out.extend(c.to_uppercase());
+                    upper = false;
+                } else {
+                    out.push(c);
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Query;
+    use crate::parser::Parser;
+    use crate::transform::{Case, MapError, MapOptions};
+    use std::collections::HashMap;
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn nested() {
+        let query = parse(
+            "and(eq(userName,\"userName\"),or(gt(createdAt,1),exists(profile.phoneNumber)),\
+             sort(-createdAt),select(userName,profile.phoneNumber))",
+        );
+        assert_eq!(
+            query.convert_case(Case::Snake).to_rql(),
+            "and(eq(user_name,\"userName\"),or(gt(created_at,1),exists(profile.phone_number)),\
+             sort(-created_at),select(user_name,profile.phone_number))"
+        );
+        assert_eq!(
+            query.convert_case(Case::Snake).convert_case(Case::Camel),
+            query
+        );
+    }
+
+    #[test]
+    fn value_identifiers() {
+        let query = parse("gt(spentTotal,budgetTotal)");
+        let upper = |path: &str| Ok(path.to_uppercase());
+        assert_eq!(
+            query.map_identifiers(upper).unwrap().to_rql(),
+            "gt(SPENTTOTAL,budgetTotal)"
+        );
+        let options = MapOptions { values: true };
+        assert_eq!(
+            query
+                .map_identifiers_with(&options, upper)
+                .unwrap()
+                .to_rql(),
+            "gt(SPENTTOTAL,BUDGETTOTAL)"
+        );
+    }
+
+    #[test]
+    fn rename_fields() {
+        let fields: HashMap<String, String> = [("name", "full_name"), ("age", "age_years")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let query = parse("and(eq(name,\"a\"),aggregate(name,mean(age)))");
+        assert_eq!(
+            query.rename_fields(&fields).unwrap().to_rql(),
+            "and(eq(full_name,\"a\"),aggregate(full_name,mean(age_years)))"
+        );
+        let err = parse("or(eq(name,\"a\"),lt(password,1),lt(secret,1))")
+            .rename_fields(&fields)
+            .unwrap_err();
+        assert_eq!(err, MapError::new("password", "unknown field"));
+        assert_eq!(err.to_string(), "password: unknown field");
+    }
+}
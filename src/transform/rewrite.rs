@@ -0,0 +1,321 @@
+//! Rule-based rewriting: small local rules applied across the whole tree
+//! until none of them changes anything.
+
+use crate::ast::{Infix, Path, Query, Value};
+use std::fmt;
+
+/// A local rewrite. Returns the replacement for `query`, or `None` when the
+/// rule doesn't apply to it. Children have already been rewritten when a rule
+/// sees a node.
+///
+/// Closures taking a `&Query` are rules too.
+pub trait Rule {
+    fn apply(&self, query: &Query) -> Option<Query>;
+}
+
+impl<F> Rule for F
+where
+    F: Fn(&Query) -> Option<Query>,
+{
+    fn apply(&self, query: &Query) -> Option<Query> {
+        self(query)
+    }
+}
+
+/// The rules kept changing the query after [`Rewriter::max_passes`] passes,
+/// typically because two of them undo each other.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RewriteError {
+    pub passes: usize,
+    /// The query as it was after the last pass.
+    pub query: Query,
+}
+
+impl fmt::Display for RewriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "rewrite rules didn't settle after {} passes",
+            self.passes
+        )
+    }
+}
+
+impl std::error::Error for RewriteError {}
+
+/// Applies a set of rules to a query until it stops changing.
+///
+/// Each pass walks the tree bottom-up and, at every node, applies the first
+/// rule (in the order they were added) that changes it. Passes repeat until
+/// one changes nothing, up to a limit of 100 by default.
+///
+/// ```
+/// use rql_parser::ast::Infix;
+/// use rql_parser::parser::Parser;
+/// use rql_parser::transform::{ExpandField, Rewriter, SubstituteOperator};
+///
+/// let mut rewriter = Rewriter::new();
+/// rewriter
+///     .rule(ExpandField::new("speed", &["speed.max", "speed.avg"]))
+///     .rule(SubstituteOperator::new(Infix::Gt, Infix::Ge).field("age").value(|v| v.increment()));
+/// let query = Parser::new_from_string("and(gt(age,17),eq(speed,5))".to_owned())
+///     .parse_query()
+///     .unwrap();
+/// assert_eq!(
+///     rewriter.rewrite(&query).unwrap().to_rql(),
+///     "and(ge(age,18),or(eq(speed.max,5),eq(speed.avg,5)))"
+/// );
+/// ```
+pub struct Rewriter {
+    rules: Vec<Box<dyn Rule>>,
+    max_passes: usize,
+}
+
+impl Default for Rewriter {
+    fn default() -> Self {
+        Rewriter {
+            rules: vec![],
+            max_passes: 100,
+        }
+    }
+}
+
+impl Rewriter {
+    pub fn new() -> Self {
+        Rewriter::default()
+    }
+
+    /// Adds a rule, tried after the ones already added.
+    pub fn rule<R>(&mut self, rule: R) -> &mut Self
+    where
+        R: Rule + 'static,
+    {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Sets how many passes may change the query before giving up.
+    pub fn max_passes(&mut self, passes: usize) -> &mut Self {
+        self.max_passes = passes;
+        self
+    }
+
+    pub fn rewrite(&self, query: &Query) -> Result<Query, RewriteError> {
+        let mut query = query.clone();
+        for _ in 0..self.max_passes {
+            let mut changed = false;
+            query = self.pass(&query, &mut changed);
+            if !changed {
+                return Ok(query);
+            }
+        }
+        Err(RewriteError {
+            passes: self.max_passes,
+            query,
+        })
+    }
+
+    fn pass(&self, query: &Query, changed: &mut bool) -> Query {
+        let query = match query {
+            Query::And(queries) => {
+                Query::And(queries.iter().map(|q| self.pass(q, changed)).collect())
+            }
+            Query::Or(queries) => {
+                Query::Or(queries.iter().map(|q| self.pass(q, changed)).collect())
+            }
+            Query::Not(q) => Query::Not(Box::new(self.pass(q, changed))),
+            _ => query.clone(),
+        };
+        // 同じものを返したルールは適用しなかったものとみなす
+        for rule in &self.rules {
+            if let Some(rewritten) = rule.apply(&query).filter(|q| q != &query) {
+                *changed = true;
+                return rewritten;
+            }
+        }
+        query
+    }
+}
+
+impl fmt::Debug for Rewriter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rewriter")
+            .field("rules", &self.rules.len())
+            .field("max_passes", &self.max_passes)
+            .finish()
+    }
+}
+
+type ValueMap = Box<dyn Fn(&Value) -> Option<Value>>;
+
+/// Replaces one comparison operator by another, e.g. `gt(age,N)` by
+/// `ge(age,N+1)` for a backend without `>`.
+pub struct SubstituteOperator {
+    from: Infix,
+    to: Infix,
+    field: Option<Path>,
+    value: Option<ValueMap>,
+}
+
+impl SubstituteOperator {
+    /// Applies to every filter using `from`.
+    pub fn new(from: Infix, to: Infix) -> Self {
+        SubstituteOperator {
+            from,
+            to,
+            field: None,
+            value: None,
+        }
+    }
+
+    /// Restricts the rule to filters on `field`.
+    pub fn field(mut self, field: &str) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+
+    /// Adjusts the compared value. Filters whose value `f` returns `None` for
+    /// are left alone.
+    pub fn value<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Value) -> Option<Value> + 'static,
+    {
+        self.value = Some(Box::new(f));
+        self
+    }
+}
+
+impl Rule for SubstituteOperator {
+    fn apply(&self, query: &Query) -> Option<Query> {
+        match query {
+            Query::Filter(infix, ident @ Value::Identifier(path), value)
+                if infix == &self.from && self.field.as_ref().is_none_or(|f| f == path) =>
+            {
+                let value = match &self.value {
+                    Some(f) => f(value)?,
+                    None => value.clone(),
+                };
+                Some(Query::Filter(self.to.clone(), ident.clone(), value))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Expands a field into several, e.g. a legacy `speed` into `speed.max` and
+/// `speed.avg`: a filter, `in()`, `out()`, `contains()` or `exists()` on it
+/// becomes an `or()` over the new fields, and `missing()` an `and()`.
+pub struct ExpandField {
+    field: Path,
+    into: Vec<Path>,
+}
+
+impl ExpandField {
+    pub fn new(field: &str, into: &[&str]) -> Self {
+        ExpandField {
+            field: field.into(),
+            into: into.iter().map(|&s| s.into()).collect(),
+        }
+    }
+}
+
+impl Rule for ExpandField {
+    fn apply(&self, query: &Query) -> Option<Query> {
+        let each = |f: &dyn Fn(&Path) -> Query| self.into.iter().map(f).collect();
+        match query {
+            Query::Filter(infix, Value::Identifier(path), value) if path == &self.field => {
+                Some(Query::Or(each(&|p| {
+                    Query::Filter(infix.clone(), Value::Identifier(p.clone()), value.clone())
+                })))
+            }
+            Query::In(path, values) if path == &self.field => {
+                Some(Query::Or(each(&|p| Query::In(p.clone(), values.clone()))))
+            }
+            Query::Out(path, values) if path == &self.field => {
+                Some(Query::Or(each(&|p| Query::Out(p.clone(), values.clone()))))
+            }
+            Query::Contains(path, value) if path == &self.field => {
+                Some(Query::Or(each(&|p| Query::Contains(p.clone(), value.clone()))))
+            }
+            Query::Exists(path) if path == &self.field => {
+                Some(Query::Or(each(&|p| Query::Exists(p.clone()))))
+            }
+            Query::Missing(path) if path == &self.field => {
+                Some(Query::And(each(&|p| Query::Missing(p.clone()))))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    /// The next integer, for rules turning `gt(x,N)` into `ge(x,N+1)`. `None`
+    /// for anything but an integer literal below `i64::MAX`.
+    pub fn increment(&self) -> Option<Value> {
+        match self {
+            Value::IntegerLiteral(n) => n.checked_add(1).map(Value::IntegerLiteral),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::{Infix, Query};
+    use crate::parser::Parser;
+    use crate::transform::{ExpandField, Rewriter, SubstituteOperator};
+
+    fn parse(input: &str) -> Query {
+        Parser::new_from_string(input.to_owned())
+            .parse_query()
+            .unwrap()
+    }
+
+    #[test]
+    fn compose() {
+        let mut rewriter = Rewriter::new();
+        rewriter
+            .rule(ExpandField::new("speed", &["speed.max", "speed.avg"]))
+            .rule(
+                SubstituteOperator::new(Infix::Gt, Infix::Ge)
+                    .field("speed.max")
+                    .value(|v| v.increment()),
+            );
+        let query = parse("or(gt(speed,10),missing(speed),gt(age,3),gt(speed.max,1.5))");
+        assert_eq!(
+            rewriter.rewrite(&query).unwrap().to_rql(),
+            "or(or(ge(speed.max,11),gt(speed.avg,10)),and(missing(speed.max),missing(speed.avg)),gt(age,3),gt(speed.max,1.5))"
+        );
+        // 適用できるものが無ければそのまま
+        assert_eq!(
+            rewriter.rewrite(&parse("eq(a,1)")).unwrap(),
+            parse("eq(a,1)")
+        );
+    }
+
+    #[test]
+    fn ping_pong() {
+        let mut rewriter = Rewriter::new();
+        rewriter
+            .rule(SubstituteOperator::new(Infix::Lt, Infix::Gt))
+            .rule(SubstituteOperator::new(Infix::Gt, Infix::Lt))
+            .max_passes(10);
+        let err = rewriter
+            .rewrite(&parse("and(lt(a,1),eq(b,2))"))
+            .unwrap_err();
+        assert_eq!(err.passes, 10);
+        assert_eq!(err.query, parse("and(lt(a,1),eq(b,2))"));
+        assert_eq!(
+            err.to_string(),
+            "rewrite rules didn't settle after 10 passes"
+        );
+
+        // 自分自身を返すルールは変更とみなさない
+        let mut rewriter = Rewriter::new();
+        rewriter.rule(|q: &Query| Some(q.clone()));
+        assert_eq!(
+            rewriter.rewrite(&parse("eq(a,1)")).unwrap(),
+            parse("eq(a,1)")
+        );
+    }
+}
@@ -0,0 +1,23 @@
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use rql_parser::ast::Query;
+use rql_parser::convert::sql::Dialect;
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    // 変換できないクエリはエラーになるだけで、パニックしない
+    #[test]
+    fn converters_never_panic(bytes in proptest::collection::vec(any::<u8>(), 0..1024)) {
+        let mut u = Unstructured::new(&bytes);
+        let query = Query::arbitrary(&mut u).unwrap();
+        for dialect in [Dialect::Mysql, Dialect::Postgres, Dialect::Sqlite] {
+            let _ = query.to_sql(dialect);
+            let _ = query.to_sql_params(dialect);
+        }
+        let _ = query.to_mongo();
+        let _ = query.to_es();
+    }
+}
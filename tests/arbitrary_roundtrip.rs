@@ -0,0 +1,27 @@
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use rql_parser::ast::Query;
+use rql_parser::parser::{Parser, ParserOptions};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn to_rql_round_trips(bytes in proptest::collection::vec(any::<u8>(), 0..1024)) {
+        let mut u = Unstructured::new(&bytes);
+        let query = Query::arbitrary(&mut u).unwrap();
+        let rql = query.to_rql();
+        // 生成される $name は変数なので、変数として読む
+        let options = ParserOptions {
+            variables: true,
+            ..ParserOptions::default()
+        };
+        let parsed = Parser::new_from_string(rql.clone())
+            .with_options(options)
+            .parse_query();
+        prop_assert!(parsed.is_ok(), "{} failed: {:?}", rql, parsed);
+        prop_assert_eq!(parsed.unwrap(), query);
+    }
+}
@@ -0,0 +1,91 @@
+use rql_parser::ast::{Query, Value};
+use rql_parser::convert::Backend;
+use rql_parser::parser::Parser;
+
+// フィルタを S 式にする。missing() は既定の visit_missing で (not (exists ...)) になる
+struct SExpr {
+    filters: usize,
+}
+
+impl Backend for SExpr {
+    type Fragment = String;
+    type Output = String;
+    type Error = String;
+
+    fn visit_filter(&mut self, query: &Query) -> Result<String, String> {
+        self.filters += 1;
+        match query {
+            Query::Filter(infix, Value::Identifier(path), value) => {
+                Ok(format!("({} {} {})", infix.keyword(), path, value))
+            }
+            Query::Exists(field) => Ok(format!("(exists {})", field)),
+            _ => Err(format!("can't print {}", query.to_rql())),
+        }
+    }
+
+    fn combine_and(&mut self, parts: Vec<String>) -> Result<String, String> {
+        Ok(list("and", parts))
+    }
+
+    fn combine_or(&mut self, parts: Vec<String>) -> Result<String, String> {
+        Ok(list("or", parts))
+    }
+
+    fn negate(&mut self, part: String) -> Result<String, String> {
+        Ok(format!("(not {})", part))
+    }
+
+    fn finish(&mut self, part: String) -> Result<String, String> {
+        Ok(format!("{};{}", part, self.filters))
+    }
+}
+
+fn list(head: &str, parts: Vec<String>) -> String {
+    let mut items = vec![head.to_owned()];
+    items.extend(parts);
+    format!("({})", items.join(" "))
+}
+
+fn lower(input: &str) -> Result<String, String> {
+    Parser::from_str(input)
+        .parse_query()
+        .unwrap()
+        .lower(&mut SExpr { filters: 0 })
+}
+
+#[test]
+fn s_expressions() {
+    assert_eq!(
+        lower("and(eq(status,\"open\"),or(gt(priority,3),missing(owner)))").unwrap(),
+        "(and (eq status \"open\") (or (gt priority 3) (not (exists owner))));3"
+    );
+    assert_eq!(lower("or()").unwrap(), "(or);0");
+    assert_eq!(
+        Query::None.lower(&mut SExpr { filters: 0 }).unwrap(),
+        "(and);0"
+    );
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        lower("and(eq(a,1),sort(+a))").unwrap_err(),
+        "can't print sort(+a)"
+    );
+}
+
+// 組み込みの変換も同じ Backend で書かれている
+#[test]
+fn builtin() {
+    let query = Parser::from_str("and(eq(a,1),missing(b))")
+        .parse_query()
+        .unwrap();
+    assert_eq!(
+        query.to_mongo().unwrap(),
+        serde_json::json!({"$and": [{"a": {"$eq": 1}}, {"b": null}]})
+    );
+    assert_eq!(
+        query.to_sql(rql_parser::convert::sql::Dialect::Postgres).unwrap(),
+        r#""a" = 1 AND "b" IS NULL"#
+    );
+}
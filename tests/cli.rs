@@ -0,0 +1,904 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+
+fn rql() -> Command {
+    Command::cargo_bin("rql").unwrap()
+}
+
+#[test]
+fn query_argument() {
+    rql()
+        .arg("and(eq(a,1),gt(b,2))")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("out: And("));
+}
+
+#[test]
+fn query_from_stdin() {
+    rql()
+        .arg("-")
+        .write_stdin("eq(a,1)\n")
+        .assert()
+        .success()
+        .stdout("out: Filter(Eq, Identifier(\"a\"), IntegerLiteral(1))\n");
+    rql()
+        .write_stdin("eq(a,1)")
+        .assert()
+        .success()
+        .stdout("out: Filter(Eq, Identifier(\"a\"), IntegerLiteral(1))\n");
+}
+
+#[test]
+fn parse_error() {
+    rql()
+        .arg("and(eq(a,1),gt(b 2))")
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(
+            "error: expected ',', found '2' at position 17\n  \
+             |\n\
+             1 | and(eq(a,1),gt(b 2))\n  \
+             |                  ^\n",
+        );
+}
+
+#[test]
+fn parse_error_on_second_line() {
+    rql()
+        .write_stdin("and(\n  eq(a,1),\n  gt(b 2)\n)\n")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::ends_with("\n3 |   gt(b 2)\n  |        ^\n"));
+}
+
+#[test]
+fn usage_errors() {
+    rql()
+        .arg("--bogus")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unexpected argument '--bogus'"));
+    rql()
+        .args(["eq(a,1)", "-"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("Usage: rql"));
+    rql()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Usage: rql [OPTIONS] [QUERY]..."))
+        .stdout(predicate::str::contains("eval "));
+}
+
+const FORMAT_QUERY: &str = "and( eq(name,\"test\"), gt(speed.max,100) )";
+
+#[test]
+fn format_debug() {
+    rql()
+        .args(["--format", "debug", FORMAT_QUERY])
+        .assert()
+        .success()
+        .stdout(
+            "out: And([Filter(Eq, Identifier(\"name\"), StringLiteral(\"test\")), \
+             Filter(Gt, Identifier(\"speed.max\"), IntegerLiteral(100))])\n",
+        );
+}
+
+#[test]
+fn format_json() {
+    rql()
+        .args(["--format", "json", FORMAT_QUERY])
+        .assert()
+        .success()
+        .stdout(
+            "{\"And\":[{\"Filter\":[\"Eq\",{\"Identifier\":\"name\"},{\"StringLiteral\":\"test\"}]},\
+             {\"Filter\":[\"Gt\",{\"Identifier\":\"speed.max\"},{\"IntegerLiteral\":100}]}]}\n",
+        );
+    let output = rql()
+        .args(["--format=json", "--pretty", FORMAT_QUERY])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("{\n  \"And\": [\n"), "{}", stdout);
+}
+
+#[test]
+fn format_rql() {
+    rql()
+        .args(["--format", "rql", FORMAT_QUERY])
+        .assert()
+        .success()
+        .stdout("and(eq(name,\"test\"),gt(speed.max,100))\n");
+}
+
+#[test]
+fn format_errors() {
+    rql()
+        .args(["--format", "xml", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown format xml"));
+    rql()
+        .arg("--format")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("a value is required for '--format"));
+}
+
+#[test]
+fn tokens() {
+    // lexer.rs のテストと同じ入力
+    rql()
+        .args([
+            "--tokens",
+            r#"and(eq(foo,"test"),or(gt(bar.baz,100),ge(test,60.0))"#,
+        ])
+        .assert()
+        .success()
+        .stdout(
+            r#"0..3 And
+3..4 Lparen
+4..6 Eq
+6..7 Lparen
+7..10 Ident("foo")
+10..11 Comma
+11..17 Str("test")
+17..18 Rparen
+18..19 Comma
+19..21 Or
+21..22 Lparen
+22..24 Gt
+24..25 Lparen
+25..32 Ident("bar.baz")
+32..33 Comma
+33..36 Int("100")
+36..37 Rparen
+37..38 Comma
+38..40 Ge
+40..41 Lparen
+41..45 Ident("test")
+45..46 Comma
+46..50 Float("60.0")
+50..51 Rparen
+51..52 Rparen
+52..52 Eof
+"#,
+        );
+}
+
+#[test]
+fn tokens_illegal() {
+    rql()
+        .args(["--tokens", "eq(a,#)"])
+        .assert()
+        .success()
+        .stdout("0..2 Eq\n2..3 Lparen\n3..4 Ident(\"a\")\n4..5 Comma\n5..6 Illegal \"#\"\n6..7 Rparen\n7..7 Eof\n");
+}
+
+#[test]
+fn data_file() {
+    rql()
+        .args(["gt(age,30)", "--data", "tests/fixtures/people.json"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"address\":{\"country\":\"JP\"},\"age\":42,\"name\":\"alice\"}\n\
+             {\"address\":{\"country\":\"JP\"},\"age\":31,\"name\":\"carol\"}\n",
+        )
+        .stderr("2 of 4 documents matched\n");
+    rql()
+        .args([
+            "eq(address.country,\"FR\")",
+            "--data",
+            "tests/fixtures/people.json",
+        ])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr("0 of 4 documents matched\n");
+}
+
+#[test]
+fn data_sort() {
+    rql()
+        .args(["sort(+age)", "--data", "tests/fixtures/people.json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match("bob.*\n.*carol.*\n.*alice.*\n.*dave").unwrap());
+    rql()
+        .args(["and(sort(-age),limit(1,1))", "--data", "tests/fixtures/people.json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("carol").and(predicate::str::contains("alice").not()))
+        .stderr("4 of 4 documents matched\n");
+}
+
+#[test]
+fn data_one() {
+    rql()
+        .args(["and(eq(name,\"bob\"),one())", "--data", "tests/fixtures/people.json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("bob"));
+    rql()
+        .args(["and(eq(address.country,\"JP\"),one())", "--data", "tests/fixtures/people.json"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("error: one() expected exactly one document, found 2\n");
+}
+
+#[test]
+fn data_count() {
+    rql()
+        .args(["and(eq(address.country,\"JP\"),count())", "--data", "tests/fixtures/people.json"])
+        .assert()
+        .success()
+        .stdout("2\n")
+        .stderr("");
+}
+
+#[test]
+fn data_stdin() {
+    rql()
+        .args(["--data", "-", "eq(name,\"bob\")"])
+        .write_stdin(std::fs::read_to_string("tests/fixtures/people.json").unwrap())
+        .assert()
+        .success()
+        .stdout("{\"address\":{\"country\":\"US\"},\"age\":25,\"name\":\"bob\"}\n");
+    rql()
+        .args(["--data", "-"])
+        .write_stdin("[]")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("missing query"));
+}
+
+#[test]
+fn data_single_document() {
+    rql()
+        .args([
+            "eq(address.country,\"JP\")",
+            "--data",
+            "tests/fixtures/person.json",
+        ])
+        .assert()
+        .success()
+        .stdout("{\"address\":{\"country\":\"JP\"},\"age\":42,\"name\":\"alice\"}\n");
+    rql()
+        .args(["lt(age,18)", "--data", "tests/fixtures/person.json"])
+        .assert()
+        .code(1)
+        .stdout("");
+}
+
+#[test]
+fn data_errors() {
+    rql()
+        .args(["eq(a,1)", "--data", "tests/fixtures/missing.json"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::starts_with(
+            "error: can't read tests/fixtures/missing.json",
+        ));
+    rql()
+        .args(["eq(a,1)", "--data", "-"])
+        .write_stdin("{not json")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::starts_with("error: invalid JSON in -"));
+}
+
+#[test]
+fn ndjson() {
+    let log = std::fs::read_to_string("tests/fixtures/app.log.jsonl").unwrap();
+    rql()
+        .args(["--ndjson", "eq(level,\"error\")"])
+        .write_stdin(log.clone())
+        .assert()
+        .success()
+        .stdout(
+            "{\"level\":\"error\",\"msg\":\"timeout\",\"code\":504}\n\
+             {\"level\":\"error\",  \"msg\":\"disk full\"}\n",
+        )
+        .stderr("2 of 4 documents matched\n2 malformed lines\n");
+    rql()
+        .args(["--ndjson", "--strict", "eq(level,\"error\")"])
+        .write_stdin(log)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::ends_with("2 malformed lines\n"));
+    rql()
+        .args(["--ndjson", "--strict", "eq(level,\"info\")"])
+        .write_stdin("{\"level\":\"info\"}\n{\"level\":\"warn\"}")
+        .assert()
+        .success()
+        .stdout("{\"level\":\"info\"}\n")
+        .stderr("1 of 2 documents matched\n");
+}
+
+#[test]
+fn ndjson_usage() {
+    rql()
+        .args(["--ndjson", "-"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("can't both be read from stdin"));
+}
+
+#[test]
+fn csv() {
+    rql()
+        .args([
+            "--csv",
+            "tests/fixtures/scores.csv",
+            "and(gt(score,80),eq(country,\"JP\"))",
+        ])
+        .assert()
+        .success()
+        .stdout("name,score,country,active\nalice,85,JP,true\n")
+        .stderr("1 of 5 rows matched\n");
+    rql()
+        .args(["--csv", "tests/fixtures/scores.csv", "gt(score,80.0)"])
+        .args(["--output", "json"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"active\":true,\"country\":\"JP\",\"name\":\"alice\",\"score\":85}\n\
+             {\"active\":false,\"country\":\"US\",\"name\":\"bob\",\"score\":92}\n\
+             {\"active\":true,\"country\":\"JP\",\"name\":\"carol, jr.\",\"score\":81.5}\n\
+             {\"name\":\"erin\",\"score\":99}\n",
+        );
+}
+
+#[test]
+fn csv_quoted_and_missing_cells() {
+    rql()
+        .args(["--csv", "tests/fixtures/scores.csv", "eq(active,true)"])
+        .assert()
+        .success()
+        .stdout(
+            "name,score,country,active\nalice,85,JP,true\n\"carol, jr.\",81.5,JP,true\ndave,70,JP,true\n",
+        );
+    rql()
+        .args(["--csv", "tests/fixtures/scores.csv", "eq(email,\"x\")"])
+        .assert()
+        .success()
+        .stdout("name,score,country,active\n")
+        .stderr("0 of 5 rows matched\n");
+}
+
+#[test]
+fn csv_no_header() {
+    rql()
+        .args(["--no-header", "--csv", "-", "and(ge($2,85),eq($3,\"JP\"))"])
+        .write_stdin(std::fs::read_to_string("tests/fixtures/scores_no_header.csv").unwrap())
+        .assert()
+        .success()
+        .stdout("alice,85,JP\n")
+        .stderr("1 of 3 rows matched\n");
+}
+
+const EMIT_QUERY: &str = "and(eq(status,\"open\"),or(gt(priority,3),le(due.days,1.5)))";
+
+#[test]
+fn emit_sql() {
+    rql()
+        .args(["--emit", "sql", EMIT_QUERY])
+        .assert()
+        .success()
+        .stdout("\"status\" = 'open' AND (\"priority\" > 3 OR \"due\".\"days\" <= 1.5)\n");
+    rql()
+        .args(["--emit", "sql", "--dialect", "mysql", EMIT_QUERY])
+        .assert()
+        .success()
+        .stdout("`status` = 'open' AND (`priority` > 3 OR `due`.`days` <= 1.5)\n");
+    rql()
+        .args(["--emit", "sql", "--params", EMIT_QUERY])
+        .assert()
+        .success()
+        .stdout(
+            "\"status\" = $1 AND (\"priority\" > $2 OR \"due\".\"days\" <= $3)\n[\"open\",3,1.5]\n",
+        );
+}
+
+#[test]
+fn emit_mongo() {
+    rql()
+        .args(["--emit", "mongo", EMIT_QUERY])
+        .assert()
+        .success()
+        .stdout(
+            "{\"$and\":[{\"status\":{\"$eq\":\"open\"}},\
+             {\"$or\":[{\"priority\":{\"$gt\":3}},{\"due.days\":{\"$lte\":1.5}}]}]}\n",
+        );
+}
+
+#[test]
+fn emit_es() {
+    rql()
+        .args(["--emit", "es", EMIT_QUERY])
+        .assert()
+        .success()
+        .stdout(
+            "{\"bool\":{\"filter\":[{\"term\":{\"status\":\"open\"}},\
+             {\"bool\":{\"minimum_should_match\":1,\"should\":[{\"range\":{\"priority\":{\"gt\":3}}},\
+             {\"range\":{\"due.days\":{\"lte\":1.5}}}]}}]}}\n",
+        );
+}
+
+#[test]
+fn emit_dot() {
+    rql()
+        .args(["--emit", "dot", "or(eq(a,1),lt(b,2))"])
+        .assert()
+        .success()
+        .stdout(
+            "digraph query {\n  n0 [shape=box, label=\"or\"];\n  \
+             n1 [shape=ellipse, label=\"a = 1\"];\n  n0 -> n1;\n  \
+             n2 [shape=ellipse, label=\"b < 2\"];\n  n0 -> n2;\n}\n",
+        );
+}
+
+#[test]
+fn emit_errors() {
+    rql()
+        .args(["--emit", "sql", "--dialect", "oracle", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown SQL dialect oracle"));
+    rql()
+        .args(["--emit", "graphql", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown backend graphql"));
+}
+
+#[test]
+fn query_file() {
+    rql()
+        .args(["-f", "tests/fixtures/query.rql", "--format", "rql"])
+        .assert()
+        .success()
+        .stdout("and(eq(status,\"open\"),gt(priority,3))\n");
+    rql()
+        .args(["-f", "tests/fixtures/query.rql", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "-f can't be combined with a query",
+        ));
+}
+
+#[test]
+fn check() {
+    rql()
+        .args(["--check", "-f", "tests/fixtures/queries.rql"])
+        .assert()
+        .code(1)
+        .stdout(
+            "line 2: OK\n\
+             line 3: OK\n\
+             error: line 5: expected a comparison operator, found end of input at position 18\n  \
+             |\n\
+             5 | or(eq(a,1) eq(b,2)\n  \
+             |                   ^\n\
+             error: line 7: expected a value, found ')' at position 7\n  \
+             |\n\
+             7 | lt(age,)\n  \
+             |        ^\n\
+             line 8: OK\n",
+        );
+    rql()
+        .arg("--check")
+        .write_stdin("eq(a,1)\n\n# comment\ngt(b,2)\n")
+        .assert()
+        .success()
+        .stdout("line 1: OK\nline 4: OK\n");
+}
+
+#[test]
+fn fmt() {
+    rql()
+        .args(["fmt", "and( eq(a,1) ,or(eq(b,2),eq(c,3)) )"])
+        .assert()
+        .success()
+        .stdout("and(eq(a,1),or(eq(b,2),eq(c,3)))\n");
+    rql()
+        .args(["fmt", "--pretty", "and( eq(a,1) ,or(eq(b,2),eq(c,3)) )"])
+        .assert()
+        .success()
+        .stdout("and(\n  eq(a,1),\n  or(\n    eq(b,2),\n    eq(c,3)\n  )\n)\n");
+}
+
+#[test]
+fn fmt_check() {
+    rql()
+        .args(["fmt", "--check", "and(eq(a,1),eq(b,2))"])
+        .assert()
+        .success()
+        .stdout("");
+    rql()
+        .args(["fmt", "--check", "and( eq(a,1), eq(b,2) )"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("and(eq(a,1),eq(b,2))"));
+    rql()
+        .args([
+            "fmt",
+            "--check",
+            "--pretty",
+            "-f",
+            "tests/fixtures/query.rql",
+        ])
+        .assert()
+        .success();
+    rql()
+        .args(["fmt", "--check", "-f", "tests/fixtures/query.rql"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn multiple_queries() {
+    rql()
+        .args(["--format", "rql", r#"eq(status,"open")"#])
+        .assert()
+        .success()
+        .stdout("eq(status,\"open\")\n");
+    rql()
+        .args(["--format", "rql", r#"eq(status,"open")"#, "gt(priority,3)"])
+        .assert()
+        .success()
+        .stdout("and(eq(status,\"open\"),gt(priority,3))\n");
+    rql()
+        .args([
+            "--format",
+            "rql",
+            "eq(a,1)",
+            "eq(b,2)",
+            "or(eq(c,3),eq(d,4))",
+        ])
+        .assert()
+        .success()
+        .stdout("and(eq(a,1),eq(b,2),or(eq(c,3),eq(d,4)))\n");
+    rql()
+        .args(["--or", "--format", "rql", "eq(a,1)", "eq(b,2)"])
+        .assert()
+        .success()
+        .stdout("or(eq(a,1),eq(b,2))\n");
+}
+
+#[test]
+fn multiple_queries_error() {
+    rql().args(["eq(a,1)", "eq(b 2)"]).assert().code(1).stderr(
+        "error: argument 2: expected ',', found '2' at position 5\n  |\n1 | eq(b 2)\n  |      ^\n",
+    );
+    rql()
+        .args(["--tokens", "eq(a,1)", "eq(b,2)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--tokens takes a single query"));
+}
+
+#[test]
+fn count() {
+    rql()
+        .args([
+            "--count",
+            "--data",
+            "tests/fixtures/people.json",
+            r#"eq(address.country,"JP")"#,
+        ])
+        .assert()
+        .success()
+        .stdout("2\n")
+        .stderr("");
+    rql()
+        .args(["--count", "--ndjson", r#"eq(level,"error")"#])
+        .write_stdin(std::fs::read_to_string("tests/fixtures/app.log.jsonl").unwrap())
+        .assert()
+        .success()
+        .stdout("2\n")
+        .stderr("2 malformed lines\n");
+    rql()
+        .args([
+            "--count",
+            "--csv",
+            "tests/fixtures/scores.csv",
+            r#"eq(country,"JP")"#,
+        ])
+        .assert()
+        .success()
+        .stdout("3\n");
+}
+
+#[test]
+fn fail_empty() {
+    rql()
+        .args([
+            "--fail-empty",
+            "--data",
+            "tests/fixtures/people.json",
+            "gt(age,40)",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("alice"));
+    rql()
+        .args([
+            "--fail-empty",
+            "--count",
+            "--data",
+            "tests/fixtures/people.json",
+            "gt(age,100)",
+        ])
+        .assert()
+        .code(1)
+        .stdout("0\n");
+    rql()
+        .args(["--fail-empty", "--ndjson", r#"eq(level,"fatal")"#])
+        .write_stdin("{\"level\":\"info\"}\n")
+        .assert()
+        .code(1)
+        .stdout("");
+    rql()
+        .args([
+            "--fail-empty",
+            "--csv",
+            "tests/fixtures/scores.csv",
+            r#"eq(country,"FR")"#,
+        ])
+        .assert()
+        .code(1);
+    rql()
+        .args(["--count", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("need --data, --ndjson or --csv"));
+}
+
+#[test]
+fn no_color() {
+    // 標準エラーが端末でなければ色は付かない
+    rql()
+        .args(["--no-color", "eq(a 1)"])
+        .env("NO_COLOR", "1")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("\x1b[").not());
+}
+
+#[test]
+fn explain() {
+    rql()
+        .args([
+            "--explain",
+            "--data",
+            "tests/fixtures/person.json",
+            "and(eq(name,\"alice\"),lt(age,18))",
+        ])
+        .assert()
+        .code(1)
+        .stdout(
+            "and: no match (decided by #2)\n  \
+             eq(name,\"alice\"): match (name = \"alice\")\n  \
+             lt(age,18): no match (age = 42)\n",
+        );
+    rql()
+        .args([
+            "--explain",
+            "--format",
+            "json",
+            "--data",
+            "tests/fixtures/people.json",
+            "gt(age,30)",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"matched\":true").count(2));
+    rql()
+        .args(["--explain", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--explain needs --data"));
+}
+
+#[test]
+fn stats() {
+    rql()
+        .args([
+            "--data",
+            "tests/fixtures/events.json",
+            "--stats",
+            r#"eq(type,"purchase")"#,
+        ])
+        .assert()
+        .success()
+        .stdout("matched  4\ntotal    8\npercent  50.0%\n");
+    rql()
+        .args([
+            "--data",
+            "tests/fixtures/events.json",
+            "--stats",
+            "--stats-field",
+            "amount",
+            r#"eq(type,"purchase")"#,
+        ])
+        .assert()
+        .success()
+        .stdout(
+            "matched  4\ntotal    8\npercent  50.0%\nfield    amount\n\
+             min      35.5\nmax      120\nmean     78.5\n",
+        );
+    // 集計関数のフィールドを使う
+    rql()
+        .args([
+            "--data",
+            "tests/fixtures/events.json",
+            "--stats",
+            "--format",
+            "json",
+            r#"and(ne(type,"view"),sum(amount))"#,
+        ])
+        .assert()
+        .success()
+        .stdout(
+            r#"{"field":"amount","matched":5,"max":120,"mean":53.875,"min":-20,"percent":62.5,"total":8}"#
+                .to_owned()
+                + "\n",
+        );
+    rql()
+        .args([
+            "--data",
+            "tests/fixtures/events.json",
+            "--stats",
+            "--stats-field",
+            "amount",
+            "--fail-empty",
+            r#"eq(type,"signup")"#,
+        ])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("percent  0.0%\nfield    amount\nmin      -\n"));
+    rql()
+        .args(["--stats", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--stats needs --data"));
+}
+
+#[test]
+fn parse_subcommand() {
+    rql()
+        .args(["parse", "--format", "rql", FORMAT_QUERY])
+        .assert()
+        .success()
+        .stdout("and(eq(name,\"test\"),gt(speed.max,100))\n");
+    rql()
+        .args(["parse", "eq(a,1)", "eq(b,2)", "--or", "--format", "rql"])
+        .assert()
+        .success()
+        .stdout("or(eq(a,1),eq(b,2))\n");
+}
+
+#[test]
+fn eval_subcommand() {
+    rql()
+        .args(["eval", "--data", "tests/fixtures/people.json", "--count", "ge(age,30)"])
+        .assert()
+        .success()
+        .stdout("2\n");
+    rql()
+        .args(["eval", "--ndjson", r#"eq(level,"error")"#, "--count"])
+        .write_stdin("{\"level\":\"error\"}\n{\"level\":\"info\"}\n")
+        .assert()
+        .success()
+        .stdout("1\n");
+    rql()
+        .args(["eval", "--csv", "tests/fixtures/scores.csv", "--count", "eq(country,\"JP\")"])
+        .assert()
+        .success()
+        .stdout("3\n");
+}
+
+#[test]
+fn eval_usage_errors() {
+    // 入力は1つだけ必須
+    rql()
+        .args(["eval", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("the following required arguments were not provided"));
+    rql()
+        .args(["eval", "--data", "tests/fixtures/people.json", "--ndjson", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with"));
+    rql()
+        .args(["eval", "--ndjson", "--stats", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--stats needs --data"));
+    rql()
+        .args([
+            "eval",
+            "--data",
+            "tests/fixtures/people.json",
+            "--stats-field",
+            "age",
+            "eq(a,1)",
+        ])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--stats"));
+    rql()
+        .args(["eval", "--data", "tests/fixtures/people.json", "--no-header", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--no-header and --output need --csv"));
+}
+
+#[test]
+fn fmt_subcommand_errors() {
+    rql()
+        .args(["fmt", "--data", "tests/fixtures/people.json", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unexpected argument '--data'"));
+}
+
+#[test]
+fn tokens_subcommand() {
+    rql()
+        .args(["tokens", "eq(a,1)"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("0..2 Eq\n2..3 Lparen\n"));
+    rql()
+        .args(["tokens", "eq(a,1)", "eq(b,2)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unexpected argument 'eq(b,2)'"));
+}
+
+#[test]
+fn emit_subcommand() {
+    rql()
+        .args(["emit", "sql", "--dialect", "mysql", "--params", "eq(status,\"open\")"])
+        .assert()
+        .success()
+        .stdout("`status` = ?\n[\"open\"]\n");
+    rql()
+        .args(["emit", "mongo", "gt(a,1)"])
+        .assert()
+        .success()
+        .stdout("{\"a\":{\"$gt\":1}}\n");
+    rql()
+        .args(["emit", "graphql", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown backend graphql"));
+    rql()
+        .args(["emit", "es", "--params", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("--params needs the sql backend"));
+    rql()
+        .args(["emit", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unknown backend eq(a,1)"));
+}
+
+#[test]
+fn repl_subcommand_errors() {
+    rql()
+        .args(["repl", "eq(a,1)"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("unexpected argument 'eq(a,1)'"));
+}
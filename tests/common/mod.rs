@@ -0,0 +1,94 @@
+// 性質テストで共有する proptest の戦略。テストごとに使うものが違う
+#![allow(dead_code)]
+
+use proptest::prelude::*;
+use rql_parser::ast::{Infix, Path, Query, Value};
+use serde_json::json;
+
+pub const COMPARISONS: &[Infix] = &[
+    Infix::Eq,
+    Infix::NotEq,
+    Infix::Lt,
+    Infix::Le,
+    Infix::Gt,
+    Infix::Ge,
+];
+
+// 型の違う値が少しずつ。整数と小数は同じ値を比べられるように近くに置く
+pub fn json_value() -> impl Strategy<Value = serde_json::Value> {
+    let scalar = prop_oneof![
+        Just(serde_json::Value::Null),
+        any::<bool>().prop_map(|b| json!(b)),
+        (-3i64..3).prop_map(|i| json!(i)),
+        (-3i64..3).prop_map(|i| json!(i as f64 + 0.5)),
+        prop_oneof![Just("x"), Just("y"), Just("xy")].prop_map(|s| json!(s)),
+    ];
+    // contains() のために小さな配列も混ぜる
+    prop_oneof![
+        5 => scalar.clone(),
+        1 => prop::collection::vec(scalar, 0..3).prop_map(serde_json::Value::Array),
+    ]
+}
+
+// json_value と比べる値。識別子は a と c.d のフィールドか、無いフィールドの x
+pub fn literal() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        any::<bool>().prop_map(Value::Boolean),
+        (-3i64..3).prop_map(Value::IntegerLiteral),
+        (-3i64..3).prop_map(|i| Value::FloatLiteral(i as f64 + 0.5)),
+        prop_oneof![Just("x"), Just("y")].prop_map(|s| Value::StringLiteral(s.to_owned())),
+        prop_oneof![Just("x"), Just("a"), Just("c.d")].prop_map(|p| Value::Identifier(p.into())),
+    ]
+}
+
+pub fn one_of(paths: &'static [&'static str]) -> impl Strategy<Value = String> {
+    prop::sample::select(paths).prop_map(str::to_owned)
+}
+
+pub fn filter(
+    infixes: &'static [Infix],
+    path: impl Strategy<Value = String>,
+    literal: impl Strategy<Value = Value>,
+) -> impl Strategy<Value = Query> {
+    (prop::sample::select(infixes), path, literal).prop_map(|(infix, path, literal)| {
+        Query::Filter(infix, Value::Identifier(path.as_str().into()), literal)
+    })
+}
+
+pub fn exists(path: impl Strategy<Value = String>) -> impl Strategy<Value = Query> {
+    path.prop_flat_map(|path| {
+        let path = Path::from(path);
+        prop_oneof![Just(Query::Exists(path.clone())), Just(Query::Missing(path))]
+    })
+}
+
+// in()、out() と contains()
+pub fn membership(
+    path: impl Strategy<Value = String>,
+    literal: impl Strategy<Value = Value>,
+) -> impl Strategy<Value = Query> {
+    (path, prop::collection::vec(literal, 1..4), 0..3).prop_map(|(path, mut values, kind)| {
+        let path = Path::from(path);
+        match kind {
+            0 => Query::In(path, values),
+            1 => Query::Out(path, values),
+            _ => Query::Contains(path, values.remove(0)),
+        }
+    })
+}
+
+// 葉を and()、or() と not() で入れ子にする
+pub fn tree(
+    leaf: impl Strategy<Value = Query> + 'static,
+    depth: u32,
+    size: u32,
+    width: usize,
+) -> impl Strategy<Value = Query> {
+    leaf.prop_recursive(depth, size, width as u32, move |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..width).prop_map(Query::And),
+            prop::collection::vec(inner.clone(), 0..width).prop_map(Query::Or),
+            inner.prop_map(|q| Query::Not(Box::new(q))),
+        ]
+    })
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{json_value, literal, COMPARISONS};
+use proptest::prelude::*;
+use rql_parser::ast::{Infix, Query};
+use serde_json::json;
+
+fn query() -> impl Strategy<Value = Query> {
+    let leaf = prop_oneof![
+        8 => common::filter(COMPARISONS, common::one_of(&["a", "b", "c.d", "missing"]), literal()),
+        2 => common::exists(common::one_of(&["a", "c.d", "missing"])),
+        2 => common::membership(common::one_of(&["a", "b", "c.d", "missing"]), literal()),
+        1 => Just(Query::None),
+        1 => Just(Query::Limit(1, 0)),
+    ];
+    common::tree(leaf, 4, 32, 4)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn compiled_matches_like_query(
+        query in query(),
+        a in json_value(),
+        b in json_value(),
+        d in json_value(),
+    ) {
+        let doc = json!({"a": a, "b": b, "c": {"d": d}});
+        prop_assert_eq!(query.compile().matches(&doc), query.matches(&doc));
+    }
+}
+
+// 数字のキー、配列、ワイルドカードを含む深いドキュメント
+fn nested_value() -> impl Strategy<Value = serde_json::Value> {
+    json_value().prop_recursive(5, 64, 4, |inner| {
+        let key = prop_oneof![Just("a"), Just("b"), Just("0"), Just("1")];
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(serde_json::Value::Array),
+            prop::collection::btree_map(key.prop_map(str::to_owned), inner, 0..4)
+                .prop_map(|map| json!(map)),
+        ]
+    })
+}
+
+fn nested_path() -> impl Strategy<Value = String> {
+    let segment = prop_oneof![Just("a"), Just("b"), Just("0"), Just("1"), Just("*")];
+    prop::collection::vec(segment, 1..5).prop_map(|segments| segments.join("."))
+}
+
+fn nested_query() -> impl Strategy<Value = Query> {
+    const INFIXES: &[Infix] = &[Infix::Eq, Infix::NotEq, Infix::Gt, Infix::Le];
+    let leaf = prop_oneof![
+        3 => common::filter(INFIXES, nested_path(), literal()),
+        1 => common::exists(nested_path()),
+    ];
+    // 同じパスを何度も使うように、葉を組み合わせる
+    prop::collection::vec(leaf, 1..6).prop_flat_map(|leaves| {
+        prop_oneof![
+            Just(Query::And(leaves.clone())),
+            Just(Query::Or(leaves.clone())),
+            Just(Query::And(vec![Query::Or(leaves.clone()), Query::And(leaves)])),
+        ]
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn compiled_paths_match_path_walk(query in nested_query(), doc in nested_value()) {
+        prop_assert_eq!(query.compile().matches(&doc), query.matches(&doc));
+    }
+}
@@ -0,0 +1,80 @@
+use rql_parser::parser::Parser;
+use serde_json::json;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// counting が立っているスレッドの確保だけを数える
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.with(Cell::get) {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations(f: impl FnOnce() -> bool) -> (bool, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    COUNTING.with(|c| c.set(true));
+    let result = f();
+    COUNTING.with(|c| c.set(false));
+    (result, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn matches_without_allocating() {
+    let docs = [
+        json!({
+            "name": "Straße",
+            "tags": ["Red", ["blue", "GREEN"]],
+            "items": [{"price": 3}, {"price": 12.5}],
+            "location": {"lat": 35.68, "lon": 139.76},
+            "meta": {"note": "Café au lait"},
+        }),
+        json!({
+            "name": "kyoto",
+            "tags": [],
+            "items": [{"price": 1}],
+            "location": [139.76, 35.68],
+        }),
+        json!({"name": ["a", "b"], "tags": {"x": 1}, "items": null}),
+    ];
+    let inputs = [
+        "eq(tags,\"Red\")",
+        "ne(tags,1)",
+        "gt(items.*.price,10)",
+        "ieq(name,\"STRASSE\")",
+        "icontains(name,\"ross\")",
+        "istartswith(name,\"KY\")",
+        "and(exists(location),missing(deleted))",
+        "near(location,35.68,139.76,100)",
+        "search((tags,name),\"green red\")",
+        "search(\"au LAIT\")",
+        "or(eq(name,\"kyoto\"),lt(items.0.price,5),eq(name,\"a\"))",
+    ];
+    for input in &inputs {
+        let query = Parser::new_from_string(input.to_string()).parse_query().unwrap();
+        let compiled = query.compile();
+        for doc in &docs {
+            let (matched, count) = allocations(|| compiled.matches(doc));
+            assert_eq!(matched, query.matches(doc), "{} {}", input, doc);
+            assert_eq!(count, 0, "{} allocated on {}", input, doc);
+        }
+    }
+}
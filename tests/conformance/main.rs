@@ -0,0 +1,99 @@
+// How far the parser is from the RQL spec, over the examples in cases.txt.
+// Run with `cargo test --test conformance -- --nocapture` to see the report.
+use rql_parser::parser::Parser;
+
+struct Case {
+    line: usize,
+    input: String,
+    expected: String,
+    // ! の付いていないもの
+    conforms: bool,
+}
+
+fn cases() -> Vec<Case> {
+    let table = include_str!("cases.txt");
+    let mut cases = vec![];
+    for (i, line) in table.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (conforms, line) = match line.strip_prefix("! ") {
+            Some(line) => (false, line),
+            None => (true, line),
+        };
+        let line = match line.strip_prefix('[') {
+            Some(rest) => {
+                let (feature, rest) = rest.split_once("] ").expect("unclosed [feature]");
+                if !enabled(feature) {
+                    continue;
+                }
+                rest
+            }
+            None => line,
+        };
+        let (input, expected) = line
+            .split_once(" => ")
+            .unwrap_or_else(|| panic!("line {}: expected `input => expected`", i + 1));
+        cases.push(Case {
+            line: i + 1,
+            input: input.to_owned(),
+            expected: expected.to_owned(),
+            conforms,
+        });
+    }
+    cases
+}
+
+fn enabled(feature: &str) -> bool {
+    match feature {
+        "chrono" => cfg!(feature = "chrono"),
+        "decimal" => cfg!(feature = "decimal"),
+        _ => panic!("unknown feature {}", feature),
+    }
+}
+
+fn parse(input: &str) -> Result<String, String> {
+    Parser::from_str(input)
+        .parse_query()
+        .map(|query| query.to_rql())
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn conformance() {
+    let cases = cases();
+    let mut passed = 0;
+    let mut gaps = vec![];
+    let mut problems = vec![];
+    for case in &cases {
+        let actual = parse(&case.input);
+        let ok = actual.as_deref() == Ok(case.expected.as_str());
+        if ok {
+            passed += 1;
+        } else {
+            let got = actual.unwrap_or_else(|e| format!("error: {}", e));
+            gaps.push(format!("  {} => {} (got {})", case.input, case.expected, got));
+        }
+        match (case.conforms, ok) {
+            (true, false) => problems.push(format!(
+                "line {}: {} no longer parses to {}",
+                case.line, case.input, case.expected
+            )),
+            (false, true) => problems.push(format!(
+                "line {}: {} now conforms; remove its `!`",
+                case.line, case.input
+            )),
+            _ => {}
+        }
+    }
+    println!(
+        "RQL conformance: {}/{} cases ({:.1}%)",
+        passed,
+        cases.len(),
+        passed as f64 * 100.0 / cases.len() as f64
+    );
+    println!("not conforming:\n{}", gaps.join("\n"));
+    assert!(cases.len() >= 60);
+    assert!(problems.is_empty(), "\n{}", problems.join("\n"));
+}
@@ -0,0 +1,44 @@
+// Inputs that used to panic or overflow the stack. Parsing must return Ok or Err.
+use rql_parser::lexer::Lexer;
+use rql_parser::parser::Parser;
+use rql_parser::token::Token;
+
+fn crashers() -> Vec<String> {
+    vec![
+        // A NUL char desynchronized the byte position from the char iterator,
+        // so the next slice landed inside a multi-byte char.
+        "\u{0}é".to_owned(),
+        "\"a\u{0}\"é".to_owned(),
+        "eq(\u{0}漢字,1)".to_owned(),
+        "eq(a,\"\u{0}\")ü".to_owned(),
+        // Unterminated strings and identifiers at the end of the input.
+        "eq(a,\"é".to_owned(),
+        "eq(é".to_owned(),
+        "1.".to_owned(),
+        "eq(a,1.é)".to_owned(),
+        // Deep nesting overflowed the stack.
+        "and(".repeat(100_000),
+        "or(".repeat(50_000) + &")".repeat(50_000),
+        "and(or(".repeat(30_000) + "eq(a,1)",
+    ]
+}
+
+#[test]
+fn lexer_reaches_eof() {
+    for input in crashers() {
+        let len = input.len();
+        let mut lexer = Lexer::new(input);
+        let mut count = 0;
+        while lexer.next_token() != Token::Eof {
+            count += 1;
+            assert!(count <= len, "lexer did not reach Eof");
+        }
+    }
+}
+
+#[test]
+fn parser_returns() {
+    for input in crashers() {
+        let _ = Parser::new_from_string(input).parse_query();
+    }
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use proptest::prelude::*;
+use rql_parser::ast::{Implication, Infix, Query, Value};
+use serde_json::json;
+
+// 含意を調べやすいように、境界が近い数値と前方一致する文字列に絞る
+fn literal() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        4 => (-2i64..3).prop_map(Value::IntegerLiteral),
+        2 => prop_oneof![Just(0.5), Just(1.0), Just(1.5)].prop_map(Value::FloatLiteral),
+        2 => prop_oneof![Just("x"), Just("xy")].prop_map(|s| Value::StringLiteral(s.to_owned())),
+        1 => any::<bool>().prop_map(Value::Boolean),
+    ]
+}
+
+fn query() -> impl Strategy<Value = Query> {
+    const INFIXES: &[Infix] = &[
+        Infix::Eq,
+        Infix::NotEq,
+        Infix::Lt,
+        Infix::Le,
+        Infix::Gt,
+        Infix::Ge,
+        Infix::StartsWith,
+    ];
+    let leaf = prop_oneof![
+        8 => common::filter(INFIXES, common::one_of(&["a", "b"]), literal()),
+        2 => common::exists(common::one_of(&["a", "b"])),
+        2 => common::membership(common::one_of(&["a", "b"]), literal()),
+    ];
+    common::tree(leaf, 3, 12, 3)
+}
+
+// a と b の値のすべての組み合わせ。None はフィールドが無いことを表す
+fn documents() -> Vec<serde_json::Value> {
+    let values = values();
+    let mut documents = vec![];
+    for a in &values {
+        for b in &values {
+            let mut doc = serde_json::Map::new();
+            if let Some(a) = a {
+                doc.insert("a".to_owned(), a.clone());
+            }
+            if let Some(b) = b {
+                doc.insert("b".to_owned(), b.clone());
+            }
+            documents.push(serde_json::Value::Object(doc));
+        }
+    }
+    documents
+}
+
+fn values() -> Vec<Option<serde_json::Value>> {
+    let mut values = vec![None, Some(serde_json::Value::Null)];
+    values.extend((-3i64..4).map(|i| Some(json!(i))));
+    values.extend([-0.5, 0.5, 1.0, 1.5, 2.5].iter().map(|f| Some(json!(f))));
+    values.extend(["x", "y", "xy", "yx"].iter().map(|s| Some(json!(s))));
+    values.extend([true, false].iter().map(|b| Some(json!(b))));
+    values
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn implied_has_no_counterexample(q1 in query(), q2 in query()) {
+        let implication = q1.implies(&q2);
+        if implication == Implication::Implied {
+            for doc in documents() {
+                prop_assert!(
+                    !q1.matches(&doc) || q2.matches(&doc),
+                    "{} => {} fails on {}", q1.to_rql(), q2.to_rql(), doc
+                );
+            }
+        }
+        // 自分自身は常に含意する
+        prop_assert_ne!(q1.implies(&q1), Implication::NotImplied);
+    }
+
+    #[test]
+    fn unsatisfiable_matches_nothing(query in query()) {
+        if query.is_unsatisfiable() {
+            for doc in documents() {
+                prop_assert!(!query.matches(&doc), "{} matches {}", query.to_rql(), doc);
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "macros")]
+
+use rql_parser::ast::Query;
+use rql_parser::parser::Parser;
+use rql_parser::{rql, Filterable};
+
+#[derive(Filterable)]
+struct Order {
+    status: String,
+    total: i64,
+}
+
+#[test]
+fn reexported() {
+    let query: Query = rql!("and(eq(status,\"open\"),gt(total,{}))", 100);
+    let parsed = Parser::new_from_string("and(eq(status,\"open\"),gt(total,100))".to_owned())
+        .parse_query()
+        .unwrap();
+    assert_eq!(query, parsed);
+
+    let order = Order {
+        status: "open".to_owned(),
+        total: 120,
+    };
+    assert!(query.matches_struct(&order));
+    assert!(!rql!("lt(total,100)").matches_struct(&order));
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::{json_value, literal};
+use proptest::prelude::*;
+use rql_parser::ast::{Infix, Query};
+use serde_json::{json, Map};
+
+fn query() -> impl Strategy<Value = Query> {
+    const INFIXES: &[Infix] = &[Infix::Eq, Infix::NotEq, Infix::Lt, Infix::Ge];
+    let leaf = prop_oneof![
+        8 => common::filter(INFIXES, common::one_of(&["a", "b", "c.d", "missing"]), literal()),
+        2 => common::exists(common::one_of(&["a", "c.d", "missing"])),
+        2 => common::membership(common::one_of(&["a", "b", "c.d", "missing"]), literal()),
+        1 => Just(Query::Limit(1, 0)),
+    ];
+    common::tree(leaf, 4, 32, 4)
+}
+
+// 束縛するパスの組み合わせ。"c" と "c.d" は同時に束縛しない
+fn bindings() -> impl Strategy<Value = Map<String, serde_json::Value>> {
+    let paths = prop_oneof![
+        Just(vec![]),
+        Just(vec!["a"]),
+        Just(vec!["b", "c.d"]),
+        Just(vec!["a", "c"]),
+        Just(vec!["missing"]),
+    ];
+    (paths, json_value(), json_value(), json_value()).prop_map(|(paths, x, y, z)| {
+        let values = vec![x, y, z];
+        paths
+            .into_iter()
+            .zip(values)
+            .map(|(path, value)| match path {
+                "c" => ("c".to_owned(), json!({ "d": value })),
+                _ => (path.to_owned(), value),
+            })
+            .collect()
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn partial_eval_matches_like_query(
+        query in query(),
+        bindings in bindings(),
+        a in json_value(),
+        b in json_value(),
+        d in json_value(),
+    ) {
+        // 束縛と矛盾しないドキュメントを作る
+        let mut doc = json!({"a": a, "b": b, "c": {"d": d}});
+        for (path, value) in &bindings {
+            match path.split_once('.') {
+                Some((outer, inner)) => doc[outer][inner] = value.clone(),
+                None => doc[path.as_str()] = value.clone(),
+            }
+        }
+        let residual = query.partial_eval(&bindings);
+        prop_assert_eq!(residual.matches(&doc), query.matches(&doc), "residual {}", residual.to_rql());
+    }
+}
@@ -0,0 +1,42 @@
+use rql_parser::ast::Query;
+use rql_parser::parser::Parser;
+use std::fs;
+
+// 過去の版が書いたクエリ。保存されたものが読めなくならないよう、
+// ここの fixture は書き換えずに足すだけにする
+fn check(file: &str) {
+    let path = format!("tests/fixtures/wire/{}", file);
+    let cases: Vec<serde_json::Value> =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    for case in &cases {
+        let (rql, wire) = (case["rql"].as_str().unwrap(), &case["wire"]);
+        let query = Query::from_wire_json(wire).unwrap_or_else(|e| panic!("{}: {}", rql, e));
+        assert_eq!(query.to_rql(), rql, "{}", file);
+        // 読んだものを書けば元に戻る
+        assert_eq!(&query.to_wire_json(), wire, "{}", rql);
+
+        // 空のクエリや inf のように、RQL では書けないものもある
+        if let Ok(parsed) = Parser::from_str(rql).parse_query() {
+            let decoded = Query::from_wire_json(&parsed.to_wire_json()).unwrap();
+            assert_eq!(decoded, parsed, "{}", rql);
+        }
+    }
+}
+
+#[test]
+fn v1() {
+    check("v1/queries.json");
+    check("v1/values.json");
+}
+
+#[cfg(feature = "decimal")]
+#[test]
+fn v1_decimal() {
+    check("v1/decimal.json");
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn v1_dates() {
+    check("v1/dates.json");
+}